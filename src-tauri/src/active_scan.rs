@@ -0,0 +1,112 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+};
+
+use crate::{labels::ColorLabel, settings::SortBy, LoadGalleryResponse};
+
+/// The gallery load in progress, so overlapping `load_gallery` calls resolve
+/// the same way every time: a request for the folder already loading joins
+/// it, and any other request cancels it and starts once it has stopped. The
+/// newest request always wins; the ones it replaced return cancelled.
+#[derive(Default)]
+pub(crate) struct ActiveScan {
+    current: Mutex<Option<Arc<Scan>>>,
+}
+
+pub(crate) struct Scan {
+    folder: PathBuf,
+    thumbnail_size: u32,
+    sort_by: SortBy,
+    color_labels: Vec<ColorLabel>,
+    pub(crate) cancel_requested: Arc<AtomicBool>,
+    outcome: Mutex<Option<Result<LoadGalleryResponse, String>>>,
+    finished: Condvar,
+}
+
+pub(crate) enum Claim {
+    /// Run this scan once `superseded`, already cancelled, has finished.
+    Start {
+        scan: Arc<Scan>,
+        superseded: Option<Arc<Scan>>,
+    },
+    /// Wait for this identical scan and share its result.
+    Join(Arc<Scan>),
+}
+
+impl ActiveScan {
+    pub(crate) fn claim(
+        &self,
+        folder: &Path,
+        thumbnail_size: u32,
+        sort_by: SortBy,
+        color_labels: &[ColorLabel],
+    ) -> Claim {
+        let mut current = self.current.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(scan) = current.as_ref() {
+            if scan.folder == folder
+                && scan.thumbnail_size == thumbnail_size
+                && scan.sort_by == sort_by
+                && scan.color_labels == color_labels
+                && !scan.cancel_requested.load(Ordering::Relaxed)
+            {
+                return Claim::Join(scan.clone());
+            }
+        }
+        let scan = Arc::new(Scan {
+            folder: folder.to_path_buf(),
+            thumbnail_size,
+            sort_by,
+            color_labels: color_labels.to_vec(),
+            cancel_requested: Arc::default(),
+            outcome: Mutex::new(None),
+            finished: Condvar::new(),
+        });
+        let superseded = current.replace(scan.clone());
+        if let Some(superseded) = &superseded {
+            superseded.cancel_requested.store(true, Ordering::Relaxed);
+        }
+        Claim::Start { scan, superseded }
+    }
+
+    pub(crate) fn cancel(&self) {
+        let current = self.current.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(scan) = current.as_ref() {
+            scan.cancel_requested.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Hands the result to anyone who joined and frees the slot, unless a
+    /// newer scan has taken it already.
+    pub(crate) fn finish(&self, scan: &Arc<Scan>, outcome: &Result<LoadGalleryResponse, String>) {
+        let mut current = self.current.lock().unwrap_or_else(|err| err.into_inner());
+        if current
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, scan))
+        {
+            *current = None;
+        }
+        drop(current);
+        *scan.outcome.lock().unwrap_or_else(|err| err.into_inner()) = Some(outcome.clone());
+        scan.finished.notify_all();
+    }
+}
+
+impl Scan {
+    /// Blocks until the scan has finished.
+    pub(crate) fn wait(&self) -> Result<LoadGalleryResponse, String> {
+        let mut outcome = self.outcome.lock().unwrap_or_else(|err| err.into_inner());
+        loop {
+            if let Some(outcome) = outcome.as_ref() {
+                return outcome.clone();
+            }
+            outcome = self
+                .finished
+                .wait(outcome)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+    }
+}
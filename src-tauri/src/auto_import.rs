@@ -0,0 +1,263 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    thread,
+    time::Duration,
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+use thumbnailer_core::{
+    cache_path, content_hash, extended_path, last_modified_unix, PendingThumbnail, Scanner,
+    ThumbnailCache,
+};
+
+use crate::{
+    device_import, device_pixels, file_ops::numbered_target, now_unix, open_cache_db, protocol,
+    resolve_data_dir, settings::Settings, AppState,
+};
+
+/// How often the source folder is checked for new images.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Files changed more recently than this may still be being written.
+const SETTLE_SECONDS: i64 = 10;
+
+/// Copies new images from a folder that comes and goes, such as a memory
+/// card's mount point, into a library folder.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct AutoImport {
+    /// Watched for new images; `None` turns auto-import off.
+    pub(crate) source: Option<String>,
+    /// Images land in `YYYY/YYYY-MM-DD` folders under it, by capture date.
+    pub(crate) destination: Option<String>,
+}
+
+impl AutoImport {
+    /// Turns auto-import off when one folder is inside the other, since
+    /// every import would then be found again as a new image.
+    pub(crate) fn normalized(self) -> Self {
+        let clean = |folder: Option<String>| {
+            folder
+                .map(|folder| folder.trim().to_string())
+                .filter(|folder| !folder.is_empty())
+        };
+        let (source, destination) = (clean(self.source), clean(self.destination));
+        if let (Some(source_folder), Some(destination_folder)) = (&source, &destination) {
+            let source_key = PathBuf::from(cache_path(Path::new(source_folder)));
+            let destination_key = PathBuf::from(cache_path(Path::new(destination_folder)));
+            if source_key.starts_with(&destination_key) || destination_key.starts_with(&source_key)
+            {
+                log::warn!(
+                    "Turning off auto-import: {} and {} are nested",
+                    source_folder,
+                    destination_folder
+                );
+                return Self {
+                    source: None,
+                    destination,
+                };
+            }
+        }
+        Self {
+            source,
+            destination,
+        }
+    }
+}
+
+/// The `auto-import-item` event, sent as each image lands.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AutoImported {
+    source: String,
+    path: String,
+    /// Thumbnail URL, unless generating it failed.
+    thumbnail: Option<String>,
+}
+
+/// Starts the thread that watches `auto_import.source`. It reads the
+/// settings on every pass, so changing them takes effect without a restart.
+///
+/// Every image in the source that wasn't imported before is copied, so a
+/// card's existing photos come over the first time it is inserted.
+/// Imported files are remembered by path, size and modified time; the same
+/// card inserted again copies only what is new, yet a reformatted card
+/// reusing `IMG_0001.JPG` is still imported. A name already taken in the
+/// destination gets a number, as DCIM names repeat once a camera's counter
+/// rolls over.
+pub(crate) fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    let spawned = thread::Builder::new()
+        .name("auto-import".to_string())
+        .spawn(move || {
+            let mut imported = HashSet::new();
+            loop {
+                thread::sleep(POLL_INTERVAL);
+                let state = app.state::<AppState>();
+                let settings = state.settings.get();
+                let (Some(source), Some(destination)) = (
+                    &settings.auto_import.source,
+                    &settings.auto_import.destination,
+                ) else {
+                    continue;
+                };
+                let (source, destination) = (PathBuf::from(source), PathBuf::from(destination));
+                if state.generation_paused.load(Ordering::Relaxed)
+                    || !extended_path(&source).is_dir()
+                    || !extended_path(&destination).is_dir()
+                {
+                    continue;
+                }
+                if let Err(err) = import_new(&app, &source, &destination, &settings, &mut imported)
+                {
+                    log::warn!("Failed to auto-import from {}: {}", source.display(), err);
+                }
+            }
+        });
+    if let Err(err) = spawned {
+        log::warn!("Failed to start auto-import: {}", err);
+    }
+}
+
+/// `seen` holds files found imported already this session, so each pass
+/// only consults the database for files it hasn't met.
+fn import_new(
+    app: &tauri::AppHandle,
+    source: &Path,
+    destination: &Path,
+    settings: &Settings,
+    seen: &mut HashSet<(PathBuf, i64, i64)>,
+) -> Result<(), String> {
+    let mut scan = settings.scan.clone();
+    scan.recursive_scan = true;
+    let settled_before = now_unix() - SETTLE_SECONDS;
+    let candidates: Vec<(PathBuf, i64, i64)> = scan
+        .scan(source)?
+        .into_iter()
+        // Imported copies are never imported again.
+        .filter(|path| !path.starts_with(destination))
+        .filter_map(|path| {
+            let size_bytes = fs::metadata(extended_path(&path)).ok()?.len() as i64;
+            let modified_unix = last_modified_unix(&path).ok()?;
+            Some((path, size_bytes, modified_unix))
+        })
+        .filter(|(_, _, modified_unix)| *modified_unix <= settled_before)
+        .filter(|file| !seen.contains(file))
+        .collect();
+    if candidates.is_empty() {
+        return Ok(());
+    }
+
+    let data_dir = resolve_data_dir(app)?;
+    let mut connection = open_cache_db(&data_dir)?;
+    let thumbnail_size = match app.get_webview_window("main") {
+        Some(window) => device_pixels(settings.thumbnail_size, None, &window),
+        None => settings.thumbnail_size,
+    };
+    let generator = settings.generator(thumbnail_size);
+    let mut generated_any = false;
+    for file in candidates {
+        if was_imported(&connection, &file)? {
+            seen.insert(file);
+            continue;
+        }
+        let (path, _, modified_unix) = &file;
+        let target = match import_file(path, *modified_unix, destination) {
+            Ok(value) => value,
+            Err(err) => {
+                // Retried on the next pass; the card may have been pulled.
+                log::warn!("Failed to auto-import {}: {}", path.display(), err);
+                continue;
+            }
+        };
+        record(&connection, &file, &target)?;
+
+        let thumbnail = PendingThumbnail::for_path(&target)
+            .and_then(|pending| pending.generate(&generator))
+            .and_then(|generated| {
+                connection.store(std::slice::from_ref(&generated))?;
+                Ok(protocol::thumbnail_url(
+                    &generated.cache_key,
+                    &content_hash(&generated.blob),
+                ))
+            })
+            .map_err(|err| log::warn!("Failed to generate thumbnail for import: {}", err))
+            .ok();
+        generated_any |= thumbnail.is_some();
+        let payload = AutoImported {
+            source: path.to_string_lossy().to_string(),
+            path: target.to_string_lossy().to_string(),
+            thumbnail,
+        };
+        if let Err(err) = app.emit("auto-import-item", &payload) {
+            log::warn!("Failed to emit auto-import-item: {}", err);
+        }
+        seen.insert(file);
+    }
+    if generated_any {
+        connection.prune(settings.cache_max_bytes)?;
+    }
+    Ok(())
+}
+
+fn import_file(source: &Path, modified_unix: i64, destination: &Path) -> Result<PathBuf, String> {
+    let name = source
+        .file_name()
+        .ok_or_else(|| format!("{} has no file name.", source.display()))?;
+    let folder = device_import::dated_folder(destination, device_import::captured_unix(source));
+    let mut target = folder.join(name);
+    if extended_path(&target).exists() {
+        target = numbered_target(&target);
+    }
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| format!("{} has no file name.", target.display()))?;
+    device_import::import_into(&folder, &name, Some(modified_unix), |partial| {
+        fs::copy(extended_path(source), extended_path(partial))
+            .map(|_| ())
+            .map_err(|err| format!("Failed to copy {}: {err}", source.display()))
+    })
+}
+
+fn was_imported(
+    connection: &Connection,
+    (path, size_bytes, modified_unix): &(PathBuf, i64, i64),
+) -> Result<bool, String> {
+    connection
+        .query_row(
+            "SELECT 1 FROM auto_imports
+             WHERE source_path = ?1 AND size_bytes = ?2 AND modified_unix = ?3",
+            params![path.to_string_lossy(), size_bytes, modified_unix],
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|found| found.is_some())
+        .map_err(|err| format!("Failed to read auto-import history: {err}"))
+}
+
+fn record(
+    connection: &Connection,
+    (path, size_bytes, modified_unix): &(PathBuf, i64, i64),
+    target: &Path,
+) -> Result<(), String> {
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO auto_imports
+               (source_path, size_bytes, modified_unix, target_path, imported_unix)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                path.to_string_lossy(),
+                size_bytes,
+                modified_unix,
+                target.to_string_lossy(),
+                now_unix()
+            ],
+        )
+        .map(|_| ())
+        .map_err(|err| format!("Failed to record auto-import: {err}"))
+}
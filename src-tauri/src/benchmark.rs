@@ -0,0 +1,242 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::Instant,
+};
+
+use image::{codecs::jpeg::JpegEncoder, Rgb, RgbImage};
+use rayon::prelude::*;
+use serde::Serialize;
+use thumbnailer_core::{Generator, Scanner};
+
+use crate::{
+    settings::{self, OutputFormat, Settings},
+    AppState,
+};
+
+/// Images thumbnailed per run, sampled evenly from the folder.
+const SAMPLE_SIZE: usize = 24;
+/// Synthetic images stand in for a typical camera's output.
+const SYNTHETIC_IMAGES: usize = 12;
+const SYNTHETIC_WIDTH: u32 = 4000;
+const SYNTHETIC_HEIGHT: u32 = 3000;
+/// Followed by the process ID in the name of the synthetic workload's folder.
+pub(crate) const BENCHMARK_DIR_PREFIX: &str = "thumbnailer-benchmark-";
+const FORMATS: [OutputFormat; 3] = [OutputFormat::Png, OutputFormat::Jpeg, OutputFormat::Webp];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BenchmarkRun {
+    threads: usize,
+    format: OutputFormat,
+    total_ms: f64,
+    images_per_second: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BenchmarkReport {
+    cpu_count: usize,
+    sample_size: usize,
+    /// Whether generated images were used instead of the folder's.
+    synthetic: bool,
+    runs: Vec<BenchmarkRun>,
+    /// In the `concurrency` setting's terms, so 0 when one thread per core
+    /// is fastest.
+    recommended_concurrency: usize,
+    recommended_format: OutputFormat,
+    applied: bool,
+}
+
+/// Removes the synthetic workload however the benchmark ends.
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.0) {
+            log::warn!("Failed to remove benchmark images: {}", err);
+        }
+    }
+}
+
+/// Times thumbnail generation of a sample of `folder` (or of synthetic
+/// photos when none is given) at several thread counts, then each output
+/// format at the fastest count. With `apply`, the fastest configuration is
+/// saved like `set_settings` would, which drops the thumbnail cache if the
+/// format changes.
+#[tauri::command]
+pub(crate) async fn run_benchmark(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    folder: Option<String>,
+    apply: Option<bool>,
+) -> Result<BenchmarkReport, String> {
+    if let Some(folder) = &folder {
+        state.path_scope.check(Path::new(folder))?;
+    }
+    let current = state.settings.get();
+    let benchmarked = current.clone();
+    let mut report = tauri::async_runtime::spawn_blocking(move || {
+        run_benchmark_blocking(&benchmarked, folder.map(PathBuf::from))
+    })
+    .await
+    .map_err(|err| format!("Failed to join benchmark task: {err}"))??;
+
+    if apply.unwrap_or(false) {
+        let mut tuned = current;
+        tuned.concurrency = report.recommended_concurrency;
+        tuned.thumbnail_format = report.recommended_format;
+        settings::set_settings(app, state, tuned).await?;
+        report.applied = true;
+    }
+    Ok(report)
+}
+
+fn run_benchmark_blocking(
+    settings: &Settings,
+    folder: Option<PathBuf>,
+) -> Result<BenchmarkReport, String> {
+    let cpu_count = thread::available_parallelism().map_or(1, |count| count.get());
+    let (paths, temp_dir) = match folder {
+        Some(folder) => (sample(&folder, settings)?, None),
+        None => {
+            let temp_dir = synthetic_workload()?;
+            (fs_paths(&temp_dir.0)?, Some(temp_dir))
+        }
+    };
+    let synthetic = temp_dir.is_some();
+
+    // Warms the file cache and drops images that can't be thumbnailed, so
+    // every timed run does the same work.
+    let generator = settings.generator(settings.thumbnail_size).image;
+    let paths: Vec<PathBuf> = settings.install(|| {
+        paths
+            .into_par_iter()
+            .filter(|path| generator.generate(path).is_ok())
+            .collect()
+    });
+    if paths.is_empty() {
+        return Err("None of the sampled images could be thumbnailed.".to_string());
+    }
+
+    let mut thread_counts: Vec<usize> = (0..)
+        .map(|power| 1 << power)
+        .take_while(|&threads| threads < cpu_count)
+        .collect();
+    thread_counts.push(cpu_count);
+
+    let mut runs: Vec<BenchmarkRun> = thread_counts
+        .into_iter()
+        .map(|threads| time_run(settings, &paths, threads, settings.thumbnail_format))
+        .collect();
+    let fastest_threads = fastest(&runs).threads;
+    for format in FORMATS {
+        if format != settings.thumbnail_format {
+            runs.push(time_run(settings, &paths, fastest_threads, format));
+        }
+    }
+    let best = fastest(&runs);
+
+    Ok(BenchmarkReport {
+        cpu_count,
+        sample_size: paths.len(),
+        synthetic,
+        recommended_concurrency: if best.threads == cpu_count {
+            0
+        } else {
+            best.threads
+        },
+        recommended_format: best.format,
+        runs,
+        applied: false,
+    })
+}
+
+fn time_run(
+    settings: &Settings,
+    paths: &[PathBuf],
+    threads: usize,
+    format: OutputFormat,
+) -> BenchmarkRun {
+    let mut candidate = settings.clone();
+    candidate.concurrency = threads;
+    candidate.thumbnail_format = format;
+    // The `image` path only, since OS thumbnail APIs cache their results
+    // and would speed up every run after the first.
+    let generator = candidate.generator(candidate.thumbnail_size).image;
+    let started = Instant::now();
+    candidate.install(|| {
+        paths.par_iter().for_each(|path| {
+            if let Err(err) = generator.generate(path) {
+                log::warn!("Benchmark image failed: {}", err);
+            }
+        })
+    });
+    let elapsed = started.elapsed().as_secs_f64();
+    BenchmarkRun {
+        threads,
+        format,
+        total_ms: elapsed * 1000.0,
+        images_per_second: paths.len() as f64 / elapsed.max(f64::EPSILON),
+    }
+}
+
+fn fastest(runs: &[BenchmarkRun]) -> &BenchmarkRun {
+    runs.iter()
+        .max_by(|a, b| a.images_per_second.total_cmp(&b.images_per_second))
+        .expect("at least one benchmark run")
+}
+
+/// Evenly spaced images from the folder, so one burst of similar shots
+/// doesn't stand in for all of it.
+fn sample(folder: &Path, settings: &Settings) -> Result<Vec<PathBuf>, String> {
+    let mut image_paths = settings.scan.scan(folder)?;
+    image_paths.sort_unstable();
+    let step = (image_paths.len() / SAMPLE_SIZE).max(1);
+    Ok(image_paths
+        .into_iter()
+        .step_by(step)
+        .take(SAMPLE_SIZE)
+        .collect())
+}
+
+/// JPEGs with gradients and fine noise, which compress and decode about as
+/// slowly as real photos.
+fn synthetic_workload() -> Result<TempDir, String> {
+    let dir =
+        TempDir(std::env::temp_dir().join(format!("{BENCHMARK_DIR_PREFIX}{}", std::process::id())));
+    fs::create_dir_all(&dir.0)
+        .map_err(|err| format!("Failed to create benchmark folder: {err}"))?;
+    (0..SYNTHETIC_IMAGES)
+        .into_par_iter()
+        .try_for_each(|index| {
+            let seed = index as u32 * 7919;
+            let image = RgbImage::from_fn(SYNTHETIC_WIDTH, SYNTHETIC_HEIGHT, |x, y| {
+                let noise = (x.wrapping_mul(73_856_093) ^ y.wrapping_mul(19_349_663) ^ seed) % 32;
+                Rgb([
+                    ((x * 255 / SYNTHETIC_WIDTH) + noise).min(255) as u8,
+                    ((y * 255 / SYNTHETIC_HEIGHT) + noise).min(255) as u8,
+                    (((x + y) / 32 + seed) % 256) as u8,
+                ])
+            });
+            let path = dir.0.join(format!("synthetic-{index}.jpg"));
+            let mut file = fs::File::create(&path)
+                .map_err(|err| format!("Failed to create {}: {err}", path.display()))?;
+            JpegEncoder::new_with_quality(&mut file, 90)
+                .encode_image(&image)
+                .map_err(|err| format!("Failed to encode {}: {err}", path.display()))
+        })?;
+    Ok(dir)
+}
+
+fn fs_paths(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    fs::read_dir(dir)
+        .map_err(|err| format!("Failed to read benchmark folder: {err}"))?
+        .map(|entry| {
+            entry
+                .map(|entry| entry.path())
+                .map_err(|err| format!("Failed to read benchmark folder: {err}"))
+        })
+        .collect()
+}
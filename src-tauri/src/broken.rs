@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, Rgb, RgbImage};
+use rusqlite::{params, Connection, OptionalExtension};
+use thumbnailer_core::{cache_path, encode_image, GeneratedThumbnail};
+
+use crate::{open_cache_db, resolve_data_dir, settings::Settings, AppState};
+
+const PLACEHOLDER_BACKGROUND: [u8; 3] = [236, 236, 236];
+const PLACEHOLDER_MARK: [u8; 3] = [200, 64, 64];
+
+/// A file that failed to decode, with its modified time and the error.
+pub(crate) type DecodeFailure = (PathBuf, i64, String);
+
+/// The "broken file" thumbnail: a red cross on light gray, encoded like
+/// real thumbnails so it is cached and served the same way.
+pub(crate) fn placeholder(settings: &Settings, size: u32) -> Result<Vec<u8>, String> {
+    let size = size.max(1);
+    let (inset, stroke) = (size / 3, (size / 24).max(1));
+    let image = RgbImage::from_fn(size, size, |x, y| {
+        let inside = (inset..size - inset).contains(&x) && (inset..size - inset).contains(&y);
+        let on_diagonal = x.abs_diff(y) <= stroke || (x + y).abs_diff(size - 1) <= stroke;
+        Rgb(if inside && on_diagonal {
+            PLACEHOLDER_MARK
+        } else {
+            PLACEHOLDER_BACKGROUND
+        })
+    });
+    encode_image(
+        &DynamicImage::ImageRgb8(image),
+        settings.thumbnail_format,
+        settings.thumbnail_quality,
+    )
+    .map_err(|err| format!("Failed to encode placeholder thumbnail: {err}"))
+}
+
+/// A cache entry showing `placeholder` for the failed file, which stays
+/// until the file changes.
+pub(crate) fn placeholder_thumbnail(
+    (path, modified_unix, _): &DecodeFailure,
+    cache_key: String,
+    settings: &Settings,
+    size: u32,
+    blob: Vec<u8>,
+) -> GeneratedThumbnail {
+    GeneratedThumbnail {
+        cache_key,
+        source_path: cache_path(path),
+        display_path: path.to_string_lossy().to_string(),
+        modified_unix: *modified_unix,
+        pixel_size: size,
+        blob,
+        mime: settings.thumbnail_format.mime_type().to_string(),
+    }
+}
+
+pub(crate) fn record(connection: &Connection, failures: &[DecodeFailure]) -> Result<(), String> {
+    for (path, modified_unix, error) in failures {
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO decode_failures (path, modified_unix, error)
+                 VALUES (?1, ?2, ?3)",
+                params![cache_path(path), modified_unix, error],
+            )
+            .map_err(|err| format!("Failed to record decode failure: {err}"))?;
+    }
+    Ok(())
+}
+
+/// Why the file as of `modified_unix` failed to decode, if it did.
+pub(crate) fn cached(
+    connection: &Connection,
+    path: &Path,
+    modified_unix: i64,
+) -> Result<Option<String>, String> {
+    connection
+        .query_row(
+            "SELECT error FROM decode_failures WHERE path = ?1 AND modified_unix = ?2",
+            params![cache_path(path), modified_unix],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read decode failure: {err}"))
+}
+
+pub(crate) fn relocate(
+    connection: &Connection,
+    source: &Path,
+    target: &Path,
+) -> Result<(), String> {
+    connection
+        .execute(
+            "UPDATE OR REPLACE decode_failures SET path = ?1 WHERE path = ?2",
+            params![cache_path(target), cache_path(source)],
+        )
+        .map(|_| ())
+        .map_err(|err| format!("Failed to move decode failure: {err}"))
+}
+
+/// The subset of `paths` that failed to decode as they are now, in their
+/// given order: the "broken files" filter for auditing an archive.
+#[tauri::command]
+pub(crate) async fn filter_broken(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    paths: Vec<String>,
+) -> Result<Vec<String>, String> {
+    state.path_scope.check_all(&paths)?;
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        let mut broken = Vec::new();
+        for path in paths {
+            let Ok(modified_unix) = thumbnailer_core::last_modified_unix(Path::new(&path)) else {
+                continue;
+            };
+            if cached(&connection, Path::new(&path), modified_unix)?.is_some() {
+                broken.push(path);
+            }
+        }
+        Ok(broken)
+    })
+    .await
+    .map_err(|err| format!("Failed to join broken file filter task: {err}"))?
+}
@@ -0,0 +1,182 @@
+use std::path::Path;
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use thumbnailer_core::{extended_path, ThumbnailCache};
+
+use crate::{open_cache_db, resolve_data_dir, settings::MAX_THUMBNAIL_SIZE, SCHEMA_VERSION};
+
+/// Raw RGBA size of the largest thumbnail the settings allow. No encoded
+/// thumbnail should come close.
+const OVERSIZED_BLOB_BYTES: i64 = MAX_THUMBNAIL_SIZE as i64 * MAX_THUMBNAIL_SIZE as i64 * 4;
+
+/// Library and undo rows pointing at a parent row that is gone.
+const ORPHANED_LIBRARY_ROWS: [&str; 3] = [
+    "image_tags WHERE tag_id NOT IN (SELECT id FROM tags)",
+    "album_images WHERE album_id NOT IN (SELECT id FROM albums)",
+    "undo_entries WHERE operation_id NOT IN (SELECT id FROM undo_operations)",
+];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CacheHealthReport {
+    schema_version: i64,
+    expected_schema_version: i64,
+    /// Problems found by SQLite's `integrity_check`; empty when it passes.
+    integrity_errors: Vec<String>,
+    thumbnail_count: i64,
+    thumbnail_bytes: i64,
+    /// Thumbnails of files that were deleted. Files whose folder is missing
+    /// too are left alone, since that is usually an unplugged drive.
+    orphaned_thumbnails: usize,
+    orphaned_library_rows: usize,
+    oversized_thumbnails: usize,
+    /// Thumbnails stored with a MIME type no image decoder recognizes.
+    unreadable_thumbnails: usize,
+}
+
+/// The fixes `fix_cache_health` can apply, one per problem the report lists.
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum CacheFix {
+    RemoveOrphans,
+    RemoveOversized,
+    RemoveUnreadable,
+    /// Drops every thumbnail, which is the way out of a failed integrity
+    /// check since they are all regenerated on demand.
+    ClearThumbnails,
+    /// Reclaims the space freed by the other fixes.
+    Vacuum,
+}
+
+/// Thumbnail rows needing attention, by cache key.
+#[derive(Default)]
+struct Findings {
+    orphaned: Vec<String>,
+    oversized: Vec<String>,
+    unreadable: Vec<String>,
+}
+
+#[tauri::command]
+pub(crate) async fn check_cache_health(app: tauri::AppHandle) -> Result<CacheHealthReport, String> {
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || report(&open_cache_db(&data_dir)?))
+        .await
+        .map_err(|err| format!("Failed to join cache health task: {err}"))?
+}
+
+/// Applies the chosen `fixes` and returns the report from afterwards.
+#[tauri::command]
+pub(crate) async fn fix_cache_health(
+    app: tauri::AppHandle,
+    fixes: Vec<CacheFix>,
+) -> Result<CacheHealthReport, String> {
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        let findings = find(&connection)?;
+        let remove = |cache_keys: &[String]| {
+            cache_keys
+                .iter()
+                .try_for_each(|cache_key| connection.remove(cache_key))
+        };
+        if fixes.contains(&CacheFix::RemoveOrphans) {
+            remove(&findings.orphaned)?;
+            for rows in ORPHANED_LIBRARY_ROWS {
+                connection
+                    .execute(&format!("DELETE FROM {rows}"), [])
+                    .map_err(|err| format!("Failed to remove orphaned rows: {err}"))?;
+            }
+        }
+        if fixes.contains(&CacheFix::RemoveOversized) {
+            remove(&findings.oversized)?;
+        }
+        if fixes.contains(&CacheFix::RemoveUnreadable) {
+            remove(&findings.unreadable)?;
+        }
+        if fixes.contains(&CacheFix::ClearThumbnails) {
+            connection.clear()?;
+        }
+        if fixes.contains(&CacheFix::Vacuum) {
+            connection
+                .execute_batch("VACUUM")
+                .map_err(|err| format!("Failed to vacuum cache database: {err}"))?;
+        }
+        report(&connection)
+    })
+    .await
+    .map_err(|err| format!("Failed to join cache repair task: {err}"))?
+}
+
+fn report(connection: &Connection) -> Result<CacheHealthReport, String> {
+    let health_error = |err: rusqlite::Error| format!("Failed to check cache health: {err}");
+    let schema_version = connection
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(health_error)?;
+    let mut statement = connection
+        .prepare("PRAGMA integrity_check")
+        .map_err(health_error)?;
+    let integrity_errors = statement
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(health_error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(health_error)?
+        .into_iter()
+        .filter(|message| message != "ok")
+        .collect();
+    let (thumbnail_count, thumbnail_bytes) = connection.stats()?;
+    let findings = find(connection)?;
+    let mut orphaned_library_rows = 0;
+    for rows in ORPHANED_LIBRARY_ROWS {
+        orphaned_library_rows += connection
+            .query_row(&format!("SELECT COUNT(*) FROM {rows}"), [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .map_err(health_error)? as usize;
+    }
+    Ok(CacheHealthReport {
+        schema_version,
+        expected_schema_version: SCHEMA_VERSION,
+        integrity_errors,
+        thumbnail_count,
+        thumbnail_bytes,
+        orphaned_thumbnails: findings.orphaned.len(),
+        orphaned_library_rows,
+        oversized_thumbnails: findings.oversized.len(),
+        unreadable_thumbnails: findings.unreadable.len(),
+    })
+}
+
+fn find(connection: &Connection) -> Result<Findings, String> {
+    let read_error = |err: rusqlite::Error| format!("Failed to read thumbnail cache: {err}");
+    let mut statement = connection
+        .prepare("SELECT cache_key, source_path, LENGTH(thumbnail_blob), mime_type FROM thumbnails")
+        .map_err(read_error)?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })
+        .map_err(read_error)?;
+
+    let mut findings = Findings::default();
+    for row in rows {
+        let (cache_key, source_path, blob_bytes, mime_type) = row.map_err(read_error)?;
+        let source = Path::new(&source_path);
+        let folder_present = source
+            .parent()
+            .is_some_and(|folder| extended_path(folder).is_dir());
+        if folder_present && !extended_path(source).exists() {
+            findings.orphaned.push(cache_key);
+        } else if blob_bytes > OVERSIZED_BLOB_BYTES {
+            findings.oversized.push(cache_key);
+        } else if image::ImageFormat::from_mime_type(&mime_type).is_none() {
+            findings.unreadable.push(cache_key);
+        }
+    }
+    Ok(findings)
+}
@@ -0,0 +1,136 @@
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use thumbnailer_core::{cache_key_for_path, cache_path};
+
+use crate::{open_cache_db, places, resolve_data_dir, AppState};
+
+/// Sets the caption or note shown with an image; blank text removes it.
+/// Captions are the user's own and live only in the app's database.
+#[tauri::command]
+pub(crate) async fn set_caption(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+    text: String,
+) -> Result<(), String> {
+    state.path_scope.check(Path::new(&path))?;
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut connection = open_cache_db(&data_dir)?;
+        store(&mut connection, Path::new(&path), text.trim())
+    })
+    .await
+    .map_err(|err| format!("Failed to join caption task: {err}"))?
+}
+
+/// Paths of images whose caption contains every word of `query`, ignoring
+/// case; words match as prefixes, so "birth" finds "birthday".
+#[tauri::command]
+pub(crate) async fn search_captions(
+    app: tauri::AppHandle,
+    query: String,
+) -> Result<Vec<String>, String> {
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let Some(terms) = places::match_query(&query) else {
+            return Ok(Vec::new());
+        };
+        let connection = open_cache_db(&data_dir)?;
+        let mut statement = connection
+            .prepare("SELECT path FROM caption_search WHERE caption_search MATCH ?1 ORDER BY path")
+            .map_err(|err| format!("Failed to search captions: {err}"))?;
+        let paths = statement
+            .query_map(params![terms], |row| row.get(0))
+            .and_then(|rows| rows.collect::<Result<Vec<String>, _>>())
+            .map_err(|err| format!("Failed to search captions: {err}"));
+        paths
+    })
+    .await
+    .map_err(|err| format!("Failed to join caption search task: {err}"))?
+}
+
+/// The caption of the image with thumbnail cache key `cache_key`.
+pub(crate) fn cached(connection: &Connection, cache_key: &str) -> Result<Option<String>, String> {
+    connection
+        .query_row(
+            "SELECT caption FROM image_captions WHERE cache_key = ?1",
+            params![cache_key],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read caption: {err}"))
+}
+
+pub(crate) fn relocate(
+    connection: &Connection,
+    source: &Path,
+    target: &Path,
+) -> Result<(), String> {
+    let (source_key, target_key) = (cache_key_for_path(source), cache_key_for_path(target));
+    let target_path = cache_path(target);
+    connection
+        .execute(
+            "UPDATE OR REPLACE image_captions SET cache_key = ?1, path = ?2 WHERE cache_key = ?3",
+            params![target_key, target_path, source_key],
+        )
+        .and_then(|_| {
+            connection.execute(
+                "DELETE FROM caption_search WHERE cache_key = ?1",
+                params![target_key],
+            )
+        })
+        .and_then(|_| {
+            connection.execute(
+                "UPDATE caption_search SET cache_key = ?1, path = ?2 WHERE cache_key = ?3",
+                params![target_key, target_path, source_key],
+            )
+        })
+        .map(|_| ())
+        .map_err(|err| format!("Failed to move caption: {err}"))
+}
+
+/// Rebuilds the search index from the captions, after they were replaced
+/// wholesale by a library restore.
+pub(crate) fn reindex(connection: &Connection) -> Result<(), String> {
+    connection
+        .execute_batch(
+            "DELETE FROM caption_search;
+             INSERT INTO caption_search (cache_key, path, caption)
+               SELECT cache_key, path, caption FROM image_captions;",
+        )
+        .map_err(|err| format!("Failed to index captions: {err}"))
+}
+
+fn store(connection: &mut Connection, path: &Path, caption: &str) -> Result<(), String> {
+    let (cache_key, path) = (cache_key_for_path(path), cache_path(path));
+    let transaction = connection
+        .transaction()
+        .map_err(|err| format!("Failed to write caption: {err}"))?;
+    transaction
+        .execute(
+            "DELETE FROM image_captions WHERE cache_key = ?1",
+            params![cache_key],
+        )
+        .and_then(|_| {
+            transaction.execute(
+                "DELETE FROM caption_search WHERE cache_key = ?1",
+                params![cache_key],
+            )
+        })
+        .and_then(|_| {
+            if caption.is_empty() {
+                return Ok(0);
+            }
+            transaction.execute(
+                "INSERT INTO image_captions (cache_key, path, caption) VALUES (?1, ?2, ?3)",
+                params![cache_key, path, caption],
+            )?;
+            transaction.execute(
+                "INSERT INTO caption_search (cache_key, path, caption) VALUES (?1, ?2, ?3)",
+                params![cache_key, path, caption],
+            )
+        })
+        .and_then(|_| transaction.commit())
+        .map_err(|err| format!("Failed to write caption: {err}"))
+}
@@ -0,0 +1,71 @@
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use thumbnailer_core::cache_path;
+
+use crate::{
+    exif_info,
+    share_guard::{self, ShareGuard},
+};
+
+/// The EXIF capture time, remembered per modification time so each file's
+/// header is parsed once. Images without one are remembered too. `read` is
+/// false for online-only files, which only report a time already known.
+pub(crate) fn for_item(
+    connection: &Connection,
+    share_guard: &ShareGuard,
+    path: &Path,
+    modified_unix: i64,
+    read: bool,
+) -> Result<Option<i64>, String> {
+    if let Some(captured_unix) = cached(connection, path, modified_unix)? {
+        return Ok(captured_unix);
+    }
+    if !read {
+        return Ok(None);
+    }
+    let exif_path = PathBuf::from(path);
+    let captured_unix = share_guard.run_once(path, share_guard::METADATA_TIMEOUT, move || {
+        exif_info::read_exif(&exif_path)
+            .as_ref()
+            .and_then(exif_info::capture_time_unix)
+    })?;
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO capture_dates (path, modified_unix, captured_unix)
+             VALUES (?1, ?2, ?3)",
+            params![cache_path(path), modified_unix, captured_unix],
+        )
+        .map_err(|err| format!("Failed to store capture date: {err}"))?;
+    Ok(captured_unix)
+}
+
+/// `Some(None)` for an image known to have no capture time.
+pub(crate) fn cached(
+    connection: &Connection,
+    path: &Path,
+    modified_unix: i64,
+) -> Result<Option<Option<i64>>, String> {
+    connection
+        .query_row(
+            "SELECT captured_unix FROM capture_dates WHERE path = ?1 AND modified_unix = ?2",
+            params![cache_path(path), modified_unix],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read capture date: {err}"))
+}
+
+pub(crate) fn relocate(
+    connection: &Connection,
+    source: &Path,
+    target: &Path,
+) -> Result<(), String> {
+    connection
+        .execute(
+            "UPDATE OR REPLACE capture_dates SET path = ?1 WHERE path = ?2",
+            params![cache_path(target), cache_path(source)],
+        )
+        .map(|_| ())
+        .map_err(|err| format!("Failed to move capture date: {err}"))
+}
@@ -0,0 +1,179 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use rayon::prelude::*;
+use thumbnailer_core::{GeneratedThumbnail, Scanner, ThumbnailCache};
+
+use crate::{open_cache_db, prepare_single_image, settings};
+
+/// Must match `identifier` in tauri.conf.json so the CLI warms the same cache
+/// the app reads.
+const APP_IDENTIFIER: &str = "com.nehima.thumbnailer";
+
+/// Thumbnails are written to the cache this many at a time.
+const STORE_BATCH: usize = 64;
+
+const USAGE: &str = "usage: thumbnailer --generate <folder> [--size <pixels>] [--recursive]";
+
+/// Passed by the D-Bus activation file to run the freedesktop thumbnailer
+/// service instead of the app.
+#[cfg(target_os = "linux")]
+const DBUS_SERVICE_FLAG: &str = "--dbus-service";
+
+struct GenerateOptions {
+    folder: PathBuf,
+    size: Option<u32>,
+    recursive: bool,
+}
+
+/// Runs `--generate` (or, on Linux, `--dbus-service`) without opening a
+/// window. Returns `None` when the arguments don't ask for it, so the app
+/// starts normally.
+pub(crate) fn run(args: &[String]) -> Option<i32> {
+    #[cfg(target_os = "linux")]
+    if args.iter().any(|arg| arg == DBUS_SERVICE_FLAG) {
+        let result = data_dir().and_then(|data_dir| crate::dbus_thumbnailer::serve(&data_dir));
+        return Some(exit_code(result));
+    }
+    if !args.iter().any(|arg| arg == "--generate") {
+        return None;
+    }
+    Some(exit_code(
+        parse(args).and_then(|options| generate(&options)),
+    ))
+}
+
+fn exit_code(result: Result<(), String>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("error: {err}");
+            1
+        }
+    }
+}
+
+/// The app's data directory, resolved without a running app.
+fn data_dir() -> Result<PathBuf, String> {
+    let data_dir = dirs::data_dir()
+        .ok_or_else(|| "Failed to resolve app data path.".to_string())?
+        .join(APP_IDENTIFIER);
+    fs::create_dir_all(&data_dir)
+        .map_err(|err| format!("Failed to create app data directory: {err}"))?;
+    Ok(data_dir)
+}
+
+fn parse(args: &[String]) -> Result<GenerateOptions, String> {
+    let mut folder = None;
+    let mut size = None;
+    let mut recursive = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--generate" => {
+                folder = Some(PathBuf::from(args.next().ok_or(USAGE)?));
+            }
+            "--size" => {
+                let value = args.next().ok_or(USAGE)?;
+                let value = value
+                    .parse::<u32>()
+                    .map_err(|_| format!("Invalid --size {value}\n{USAGE}"))?;
+                size = Some(value.max(1));
+            }
+            "--recursive" => recursive = true,
+            other => return Err(format!("Unknown argument {other}\n{USAGE}")),
+        }
+    }
+    Ok(GenerateOptions {
+        folder: folder.ok_or(USAGE)?,
+        size,
+        recursive,
+    })
+}
+
+fn generate(options: &GenerateOptions) -> Result<(), String> {
+    if !options.folder.is_dir() {
+        return Err(format!(
+            "{} is not a valid directory.",
+            options.folder.display()
+        ));
+    }
+    let mut connection = open_cache_db(&data_dir()?)?;
+    let mut settings = settings::load(&connection)?;
+    settings.scan.recursive_scan = options.recursive;
+    let thumbnail_size = options.size.unwrap_or(settings.thumbnail_size);
+
+    let mut image_paths = settings.scan.scan(&options.folder)?;
+    settings.cloud_files.filter(&mut image_paths);
+    settings.raw_pairs.group(&mut image_paths);
+    image_paths.sort_unstable();
+    let total = image_paths.len();
+    println!("Found {total} image(s) in {}", options.folder.display());
+
+    let mut pending = Vec::new();
+    let mut failed = 0usize;
+    let mut online_only = 0usize;
+    for image_path in &image_paths {
+        match prepare_single_image(
+            &connection,
+            image_path,
+            thumbnail_size,
+            settings.cloud_files,
+        ) {
+            Ok((_, Some(pending_item), _)) => pending.push(pending_item),
+            Ok((item, None, _)) if item.cloud => online_only += 1,
+            Ok((_, None, _)) => {}
+            Err(err) => {
+                failed += 1;
+                eprintln!("skipped {}: {err}", image_path.display());
+            }
+        }
+    }
+    let already_cached = total - pending.len() - failed - online_only;
+    let to_generate = pending.len();
+    if online_only > 0 {
+        println!("Leaving {online_only} online-only file(s) undownloaded");
+    }
+    println!("{already_cached} already cached, generating {to_generate}");
+
+    let generator = settings.generator(thumbnail_size);
+    let completed = AtomicUsize::new(0);
+    let mut generated_count = 0usize;
+    settings.install(|| {
+        while !pending.is_empty() {
+            // Store as we go so an interrupted run keeps its work and memory
+            // stays bounded on large folders.
+            let batch: Vec<_> = pending.drain(..pending.len().min(STORE_BATCH)).collect();
+            let batch_len = batch.len();
+            let generated: Vec<GeneratedThumbnail> = batch
+                .into_par_iter()
+                .filter_map(|pending_item| {
+                    let name = pending_item.image_path.display().to_string();
+                    let result = pending_item.generate(&generator);
+                    let current = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                    match result {
+                        Ok(value) => {
+                            println!("[{current}/{to_generate}] {name}");
+                            Some(value)
+                        }
+                        Err(err) => {
+                            eprintln!("[{current}/{to_generate}] failed {name}: {err}");
+                            None
+                        }
+                    }
+                })
+                .collect();
+            failed += batch_len - generated.len();
+            generated_count += generated.len();
+            connection.store(&generated)?;
+            connection.prune(settings.cache_max_bytes)?;
+        }
+        Ok::<(), String>(())
+    })?;
+
+    println!("Done: {generated_count} generated, {already_cached} already cached, {failed} failed");
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// What galleries do with online-only files from OneDrive, Dropbox, iCloud
+/// and other sync clients, whose contents are downloaded as soon as they are
+/// read.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CloudFiles {
+    /// Leave them out of galleries entirely.
+    Skip,
+    /// List them as `cloud` items without generating thumbnails. Opening
+    /// one, or asking for its thumbnail, downloads just that file.
+    #[default]
+    Placeholder,
+    /// Treat them like local files, downloading each while scanning.
+    Download,
+}
+
+impl CloudFiles {
+    /// Drops placeholders from `paths` when they should be skipped.
+    pub(crate) fn filter(self, paths: &mut Vec<PathBuf>) {
+        if self == CloudFiles::Skip {
+            paths.retain(|path| !is_placeholder(path));
+        }
+    }
+
+    /// Whether a gallery should generate a thumbnail for `path`, which would
+    /// download it.
+    pub(crate) fn allows_generation(self, path: &Path) -> bool {
+        self == CloudFiles::Download || !is_placeholder(path)
+    }
+}
+
+// Win32 file attributes set by the cloud files API on dehydrated files.
+#[cfg(windows)]
+const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+#[cfg(windows)]
+const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x4_0000;
+#[cfg(windows)]
+const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x40_0000;
+
+/// `st_flags` bit for files whose contents are held by a file provider.
+#[cfg(target_os = "macos")]
+const SF_DATALESS: u32 = 0x4000_0000;
+
+/// Whether `path` is an online-only stub. Only reads file attributes, which
+/// never triggers a download.
+pub(crate) fn is_placeholder(path: &Path) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+
+        let Ok(metadata) = std::fs::metadata(thumbnailer_core::extended_path(path)) else {
+            return false;
+        };
+        metadata.file_attributes()
+            & (FILE_ATTRIBUTE_OFFLINE
+                | FILE_ATTRIBUTE_RECALL_ON_OPEN
+                | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS)
+            != 0
+    }
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::macos::fs::MetadataExt;
+
+        std::fs::metadata(path)
+            .map(|metadata| metadata.st_flags() & SF_DATALESS != 0)
+            .unwrap_or(false)
+    }
+    // Sync clients on Linux keep full local copies or expose FUSE mounts
+    // with no common marker.
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+        let _ = path;
+        false
+    }
+}
@@ -0,0 +1,202 @@
+use std::path::Path;
+
+use image::{imageops::FilterType, GrayImage, Rgb, RgbImage};
+use serde::Serialize;
+use thumbnailer_core::{decode_image, encode_image, OutputFormat};
+
+use crate::{data_url_for_blob, export::validate_source, settings::Settings, AppState};
+
+/// Both images are compared at this size, which also evens out differences
+/// in resolution and recompression noise.
+const COMPARE_SIZE: u32 = 512;
+/// Largest offset, in compared pixels, tried when lining the images up.
+const MAX_SHIFT: i32 = 3;
+/// Channel difference below which pixels count as the same.
+const DIFF_THRESHOLD: u8 = 24;
+const SSIM_WINDOW: u32 = 8;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImageComparison {
+    /// Size the images were compared at.
+    width: u32,
+    height: u32,
+    /// Share of pixels that differ noticeably, from 0 to 100.
+    diff_percent: f64,
+    /// Structural similarity from -1 to 1, where 1 means identical.
+    ssim: f64,
+    /// PNG data URL that is black where the images match and brightens to
+    /// yellow where they differ most.
+    heat_map: Option<String>,
+}
+
+/// Compares two images, typically near-duplicates, after scaling the second
+/// to the first and lining them up to within a few pixels, so crops by a
+/// hair and resaves at another size still compare as close.
+#[tauri::command]
+pub(crate) async fn compare_images(
+    state: tauri::State<'_, AppState>,
+    path_a: String,
+    path_b: String,
+    heat_map: Option<bool>,
+) -> Result<ImageComparison, String> {
+    state.path_scope.check(Path::new(&path_a))?;
+    state.path_scope.check(Path::new(&path_b))?;
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        compare_images_blocking(
+            Path::new(&path_a),
+            Path::new(&path_b),
+            heat_map.unwrap_or(false),
+            &settings,
+        )
+    })
+    .await
+    .map_err(|err| format!("Failed to join compare task: {err}"))?
+}
+
+fn compare_images_blocking(
+    path_a: &Path,
+    path_b: &Path,
+    heat_map: bool,
+    settings: &Settings,
+) -> Result<ImageComparison, String> {
+    let image_a = load_for_comparison(path_a, settings)?;
+    let (width, height) = image_a.dimensions();
+    let image_b = image::imageops::resize(
+        &load_for_comparison(path_b, settings)?,
+        width,
+        height,
+        FilterType::Triangle,
+    );
+    let (shift_x, shift_y) = best_shift(&luma(&image_a), &luma(&image_b));
+
+    // Only the part both images cover once shifted is compared.
+    let overlap_width = width - shift_x.unsigned_abs();
+    let overlap_height = height - shift_y.unsigned_abs();
+    let (a_x, b_x) = (shift_x.max(0) as u32, (-shift_x).max(0) as u32);
+    let (a_y, b_y) = (shift_y.max(0) as u32, (-shift_y).max(0) as u32);
+    let crop_a =
+        image::imageops::crop_imm(&image_a, a_x, a_y, overlap_width, overlap_height).to_image();
+    let crop_b =
+        image::imageops::crop_imm(&image_b, b_x, b_y, overlap_width, overlap_height).to_image();
+
+    let differences = GrayImage::from_fn(overlap_width, overlap_height, |x, y| {
+        let (a, b) = (crop_a.get_pixel(x, y).0, crop_b.get_pixel(x, y).0);
+        let difference = (0..3).map(|channel| a[channel].abs_diff(b[channel])).max();
+        image::Luma([difference.unwrap_or(0)])
+    });
+    let differing = differences
+        .pixels()
+        .filter(|pixel| pixel.0[0] >= DIFF_THRESHOLD)
+        .count();
+    let total = (overlap_width * overlap_height).max(1) as f64;
+
+    let heat_map = if heat_map {
+        let heat = RgbImage::from_fn(overlap_width, overlap_height, |x, y| {
+            let amplified = (u32::from(differences.get_pixel(x, y).0[0]) * 4).min(255);
+            let red = (amplified * 2).min(255) as u8;
+            let green = (amplified * 2).saturating_sub(255) as u8;
+            Rgb([red, green, 0])
+        });
+        let png = encode_image(&heat.into(), OutputFormat::Png, 0)
+            .map_err(|err| format!("Failed to encode heat map: {err}"))?;
+        Some(data_url_for_blob(&png, OutputFormat::Png.mime_type()))
+    } else {
+        None
+    };
+
+    Ok(ImageComparison {
+        width: overlap_width,
+        height: overlap_height,
+        diff_percent: differing as f64 * 100.0 / total,
+        ssim: ssim(&luma(&crop_a), &luma(&crop_b)),
+        heat_map,
+    })
+}
+
+/// The full image is only held while it is scaled down.
+fn load_for_comparison(path: &Path, settings: &Settings) -> Result<RgbImage, String> {
+    validate_source(path, settings)?;
+    decode_image(path, &settings.decode_limits, |image| {
+        image.thumbnail(COMPARE_SIZE, COMPARE_SIZE).to_rgb8()
+    })
+}
+
+fn luma(image: &RgbImage) -> GrayImage {
+    image::imageops::grayscale(image)
+}
+
+/// Offset of `b` against `a` with the smallest mean difference over the
+/// area they share.
+fn best_shift(a: &GrayImage, b: &GrayImage) -> (i32, i32) {
+    let (width, height) = (a.width() as i32, a.height() as i32);
+    let mut best = ((0, 0), f64::MAX);
+    for shift_y in -MAX_SHIFT..=MAX_SHIFT {
+        for shift_x in -MAX_SHIFT..=MAX_SHIFT {
+            let (overlap_width, overlap_height) = (width - shift_x.abs(), height - shift_y.abs());
+            if overlap_width <= 0 || overlap_height <= 0 {
+                continue;
+            }
+            let mut sum = 0u64;
+            for y in 0..overlap_height {
+                for x in 0..overlap_width {
+                    let pixel_a =
+                        a.get_pixel((x + shift_x.max(0)) as u32, (y + shift_y.max(0)) as u32);
+                    let pixel_b = b.get_pixel(
+                        (x + (-shift_x).max(0)) as u32,
+                        (y + (-shift_y).max(0)) as u32,
+                    );
+                    sum += u64::from(pixel_a.0[0].abs_diff(pixel_b.0[0]));
+                }
+            }
+            let mean = sum as f64 / (overlap_width * overlap_height) as f64;
+            if mean < best.1 {
+                best = ((shift_x, shift_y), mean);
+            }
+        }
+    }
+    best.0
+}
+
+/// Mean SSIM over non-overlapping windows of equally sized images.
+fn ssim(a: &GrayImage, b: &GrayImage) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let (width, height) = a.dimensions();
+    let mut total = 0.0;
+    let mut windows = 0;
+    for top in (0..height).step_by(SSIM_WINDOW as usize) {
+        for left in (0..width).step_by(SSIM_WINDOW as usize) {
+            let pixels: Vec<(f64, f64)> = (top..(top + SSIM_WINDOW).min(height))
+                .flat_map(|y| {
+                    (left..(left + SSIM_WINDOW).min(width)).map(move |x| {
+                        (
+                            f64::from(a.get_pixel(x, y).0[0]),
+                            f64::from(b.get_pixel(x, y).0[0]),
+                        )
+                    })
+                })
+                .collect();
+            let count = pixels.len() as f64;
+            let mean_a = pixels.iter().map(|(a, _)| a).sum::<f64>() / count;
+            let mean_b = pixels.iter().map(|(_, b)| b).sum::<f64>() / count;
+            let (mut variance_a, mut variance_b, mut covariance) = (0.0, 0.0, 0.0);
+            for (a, b) in &pixels {
+                variance_a += (a - mean_a) * (a - mean_a);
+                variance_b += (b - mean_b) * (b - mean_b);
+                covariance += (a - mean_a) * (b - mean_b);
+            }
+            variance_a /= count;
+            variance_b /= count;
+            covariance /= count;
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (variance_a + variance_b + C2));
+            windows += 1;
+        }
+    }
+    if windows == 0 {
+        return 1.0;
+    }
+    total / f64::from(windows)
+}
@@ -0,0 +1,190 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    file_ops::{self, ConflictPolicy, FileOperationSummary},
+    open_cache_db, preview_cache, resolve_data_dir, AppState,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum CullFlag {
+    Pick,
+    Reject,
+}
+
+/// What `finish_compare` does with the rejected candidates.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub(crate) enum RejectAction {
+    /// Sends them to the trash.
+    Delete,
+    /// Moves them into `destination`, numbering names already taken there.
+    Move { destination: String },
+}
+
+/// The candidates being compared side by side and how each was flagged.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CompareSession {
+    paths: Vec<String>,
+    /// Unflagged candidates are left out.
+    flags: HashMap<String, CullFlag>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CompareOutcome {
+    picks: Vec<String>,
+    rejects: Vec<String>,
+    /// Set when a `RejectAction` was applied.
+    applied: Option<FileOperationSummary>,
+}
+
+/// The one compare session open at a time.
+#[derive(Default)]
+pub(crate) struct Culling {
+    session: Mutex<Option<CompareSession>>,
+}
+
+impl Culling {
+    fn with_session<T>(
+        &self,
+        update: impl FnOnce(&mut CompareSession) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut session = self.session.lock().unwrap_or_else(|err| err.into_inner());
+        let session = session
+            .as_mut()
+            .ok_or_else(|| "No compare session is open.".to_string())?;
+        update(session)
+    }
+}
+
+/// Opens a compare session over `paths`, replacing any open one, and
+/// decodes the candidates at the viewer's `max_dimension` so flipping
+/// between them is instant. Only as many as the preview cache holds are
+/// preloaded; the rest load when shown.
+#[tauri::command]
+pub(crate) async fn start_compare(
+    state: tauri::State<'_, AppState>,
+    paths: Vec<String>,
+    max_dimension: Option<u32>,
+) -> Result<CompareSession, String> {
+    state.path_scope.check_all(&paths)?;
+    if paths.is_empty() {
+        return Err("Nothing to compare.".to_string());
+    }
+    let session = CompareSession {
+        paths: paths.clone(),
+        flags: HashMap::new(),
+    };
+    *state
+        .culling
+        .session
+        .lock()
+        .unwrap_or_else(|err| err.into_inner()) = Some(session.clone());
+
+    let cache = state.preview_cache.clone();
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        preview_cache::preload(&cache, paths, max_dimension, &settings)
+    })
+    .await
+    .map_err(|err| format!("Failed to join compare preload task: {err}"))?;
+    Ok(session)
+}
+
+/// Flags a candidate as a pick or a reject, or clears its flag with `None`.
+#[tauri::command]
+pub(crate) fn set_compare_flag(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    flag: Option<CullFlag>,
+) -> Result<CompareSession, String> {
+    state.culling.with_session(|session| {
+        if !session.paths.contains(&path) {
+            return Err(format!("{path} is not being compared."));
+        }
+        match flag {
+            Some(flag) => session.flags.insert(path, flag),
+            None => session.flags.remove(&path),
+        };
+        Ok(session.clone())
+    })
+}
+
+/// The open compare session, if any, such as after the window reloads.
+#[tauri::command]
+pub(crate) fn get_compare_session(state: tauri::State<'_, AppState>) -> Option<CompareSession> {
+    state
+        .culling
+        .session
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone()
+}
+
+/// Closes the compare session, applying `reject_action` to the rejects as
+/// one undoable operation. Without it, the files are left alone and the
+/// frontend gets the picks and rejects to act on itself.
+#[tauri::command]
+pub(crate) async fn finish_compare(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    reject_action: Option<RejectAction>,
+) -> Result<CompareOutcome, String> {
+    if let Some(RejectAction::Move { destination }) = &reject_action {
+        state.path_scope.check(Path::new(destination))?;
+    }
+    let session = state
+        .culling
+        .session
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .take()
+        .ok_or_else(|| "No compare session is open.".to_string())?;
+    let flagged = |wanted: CullFlag| -> Vec<String> {
+        session
+            .paths
+            .iter()
+            .filter(|path| session.flags.get(*path) == Some(&wanted))
+            .cloned()
+            .collect()
+    };
+    let (picks, rejects) = (flagged(CullFlag::Pick), flagged(CullFlag::Reject));
+    let Some(action) = reject_action.filter(|_| !rejects.is_empty()) else {
+        return Ok(CompareOutcome {
+            picks,
+            rejects,
+            applied: None,
+        });
+    };
+
+    let data_dir = resolve_data_dir(&app)?;
+    let paths = rejects.clone();
+    let applied = tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        match action {
+            RejectAction::Delete => file_ops::trash_paths(&connection, paths),
+            RejectAction::Move { destination } => file_ops::move_paths(
+                &connection,
+                paths,
+                &PathBuf::from(destination),
+                ConflictPolicy::AutoNumber,
+            ),
+        }
+    })
+    .await
+    .map_err(|err| format!("Failed to join compare task: {err}"))??;
+    state.selection.follow(&applied);
+    Ok(CompareOutcome {
+        picks,
+        rejects,
+        applied: Some(applied),
+    })
+}
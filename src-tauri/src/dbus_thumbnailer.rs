@@ -0,0 +1,325 @@
+use std::{
+    collections::HashSet,
+    fs::{self, DirBuilder, OpenOptions},
+    io::BufWriter,
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use image::DynamicImage;
+use md5::{Digest, Md5};
+use rusqlite::Connection;
+use tauri::Url;
+use thumbnailer_core::{decode_image, PendingThumbnail, ThumbnailCache};
+use zbus::{blocking::connection, fdo, interface, object_server::SignalEmitter};
+
+use crate::{open_cache_db, settings, settings::Settings};
+
+const BUS_NAME: &str = "org.freedesktop.thumbnails.Thumbnailer1";
+const OBJECT_PATH: &str = "/org/freedesktop/thumbnails/Thumbnailer1";
+/// The service exits after this long without requests; D-Bus activation
+/// starts it again when a file manager next asks.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Thumbnail spec flavors and the largest edge each allows.
+const FLAVORS: [(&str, u32); 4] = [
+    ("normal", 128),
+    ("large", 256),
+    ("x-large", 512),
+    ("xx-large", 1024),
+];
+const SCHEDULERS: [&str; 1] = ["default"];
+
+// Error codes defined by the thumbnail management D-Bus specification.
+const ERROR_UNSUPPORTED: i32 = 0;
+const ERROR_INVALID_DATA: i32 = 2;
+const ERROR_SAVE_FAILED: i32 = 4;
+
+struct Job {
+    handle: u32,
+    uris: Vec<String>,
+    flavor: &'static str,
+    size: u32,
+}
+
+struct Thumbnailer {
+    jobs: mpsc::Sender<Job>,
+    next_handle: AtomicU32,
+    dequeued: Arc<Mutex<HashSet<u32>>>,
+    mime_types: Vec<String>,
+}
+
+#[interface(name = "org.freedesktop.thumbnails.Thumbnailer1")]
+impl Thumbnailer {
+    /// MIME hints aren't needed since support is decided by extension, but
+    /// they must pair up with `uris` as the spec requires.
+    fn queue(
+        &self,
+        uris: Vec<String>,
+        mime_types: Vec<String>,
+        flavor: String,
+        scheduler: String,
+        handle_to_unqueue: u32,
+    ) -> fdo::Result<u32> {
+        if uris.len() != mime_types.len() {
+            return Err(fdo::Error::InvalidArgs(
+                "uris and mime_types must have the same length.".to_string(),
+            ));
+        }
+        let (flavor, size) = FLAVORS
+            .into_iter()
+            .find(|(name, _)| *name == flavor)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("Unsupported flavor {flavor}")))?;
+        if !scheduler.is_empty() && !SCHEDULERS.contains(&scheduler.as_str()) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "Unsupported scheduler {scheduler}"
+            )));
+        }
+        if handle_to_unqueue != 0 {
+            self.dequeue(handle_to_unqueue);
+        }
+        let handle = self.next_handle.fetch_add(1, Ordering::Relaxed);
+        self.jobs
+            .send(Job {
+                handle,
+                uris,
+                flavor,
+                size,
+            })
+            .map_err(|_| fdo::Error::Failed("The thumbnailer is shutting down.".to_string()))?;
+        Ok(handle)
+    }
+
+    fn dequeue(&self, handle: u32) {
+        if let Ok(mut dequeued) = self.dequeued.lock() {
+            dequeued.insert(handle);
+        }
+    }
+
+    /// Parallel lists: each URI scheme pairs with the MIME type at the same
+    /// index.
+    fn get_supported(&self) -> (Vec<String>, Vec<String>) {
+        let schemes = vec!["file".to_string(); self.mime_types.len()];
+        (schemes, self.mime_types.clone())
+    }
+
+    fn get_schedulers(&self) -> Vec<String> {
+        SCHEDULERS.map(String::from).to_vec()
+    }
+
+    fn get_flavors(&self) -> Vec<String> {
+        FLAVORS.map(|(name, _)| name.to_string()).to_vec()
+    }
+
+    #[zbus(signal)]
+    async fn started(emitter: &SignalEmitter<'_>, handle: u32) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn ready(emitter: &SignalEmitter<'_>, handle: u32, uris: &[String]) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn error(
+        emitter: &SignalEmitter<'_>,
+        handle: u32,
+        failed_uris: &[String],
+        error_code: i32,
+        message: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn finished(emitter: &SignalEmitter<'_>, handle: u32) -> zbus::Result<()>;
+}
+
+/// Serves `org.freedesktop.thumbnails.Thumbnailer1` on the session bus so
+/// file managers can delegate thumbnailing to us. Thumbnails come from the
+/// app's cache when possible and are written to the shared freedesktop
+/// thumbnail directory. Returns once idle for `IDLE_TIMEOUT`.
+pub(crate) fn serve(data_dir: &Path) -> Result<(), String> {
+    let mut cache = open_cache_db(data_dir)?;
+    let settings = settings::load(&cache)?;
+    let thumbnail_dir = dirs::cache_dir()
+        .ok_or_else(|| "Failed to resolve the user cache directory.".to_string())?
+        .join("thumbnails");
+
+    let (sender, jobs) = mpsc::channel();
+    let dequeued = Arc::new(Mutex::new(HashSet::new()));
+    let service = Thumbnailer {
+        jobs: sender,
+        next_handle: AtomicU32::new(1),
+        dequeued: dequeued.clone(),
+        mime_types: supported_mime_types(&settings),
+    };
+    let connection = connection::Builder::session()
+        .and_then(|builder| builder.name(BUS_NAME))
+        .and_then(|builder| builder.serve_at(OBJECT_PATH, service))
+        .and_then(|builder| builder.build())
+        .map_err(|err| format!("Failed to start D-Bus thumbnailer service: {err}"))?;
+    let emitter = SignalEmitter::new(connection.inner(), OBJECT_PATH)
+        .map_err(|err| format!("Failed to create D-Bus signal emitter: {err}"))?;
+
+    while let Ok(job) = jobs.recv_timeout(IDLE_TIMEOUT) {
+        emit(Thumbnailer::started(&emitter, job.handle));
+        for uri in &job.uris {
+            let cancelled = dequeued
+                .lock()
+                .map(|dequeued| dequeued.contains(&job.handle))
+                .unwrap_or(false);
+            if cancelled {
+                break;
+            }
+            let uris = std::slice::from_ref(uri);
+            match thumbnail_uri(&mut cache, &settings, &thumbnail_dir, uri, &job) {
+                Ok(()) => emit(Thumbnailer::ready(&emitter, job.handle, uris)),
+                Err((code, message)) => emit(Thumbnailer::error(
+                    &emitter, job.handle, uris, code, &message,
+                )),
+            }
+        }
+        if let Ok(mut dequeued) = dequeued.lock() {
+            dequeued.remove(&job.handle);
+        }
+        emit(Thumbnailer::finished(&emitter, job.handle));
+    }
+    Ok(())
+}
+
+fn emit(signal: impl std::future::Future<Output = zbus::Result<()>>) {
+    if let Err(err) = zbus::block_on(signal) {
+        log::warn!("Failed to emit D-Bus thumbnailer signal: {}", err);
+    }
+}
+
+fn supported_mime_types(settings: &Settings) -> Vec<String> {
+    let mut mime_types: Vec<String> = Vec::new();
+    for extension in &settings.scan.extensions {
+        if let Some(format) = image::ImageFormat::from_extension(extension) {
+            let mime_type = format.to_mime_type().to_string();
+            if !mime_types.contains(&mime_type) {
+                mime_types.push(mime_type);
+            }
+        }
+    }
+    mime_types
+}
+
+/// Flavors no larger than the gallery thumbnail are scaled from the cached
+/// one (generating and caching it first if needed), so the app and the file
+/// manager share one decode. Larger flavors are rendered from the source.
+fn thumbnail_uri(
+    cache: &mut Connection,
+    settings: &Settings,
+    thumbnail_dir: &Path,
+    uri: &str,
+    job: &Job,
+) -> Result<(), (i32, String)> {
+    let path = Url::parse(uri)
+        .ok()
+        .filter(|url| url.scheme() == "file")
+        .and_then(|url| url.to_file_path().ok())
+        .ok_or_else(|| (ERROR_UNSUPPORTED, format!("{uri} is not a local file.")))?;
+    if !settings.scan.is_supported_image(&path) {
+        return Err((
+            ERROR_UNSUPPORTED,
+            format!("Unsupported image format: {}", path.display()),
+        ));
+    }
+    let pending = PendingThumbnail::for_path(&path).map_err(|err| (ERROR_INVALID_DATA, err))?;
+    let modified_unix = pending.modified_unix;
+    let flavor_dir = thumbnail_dir.join(job.flavor);
+    let save = |image: DynamicImage| {
+        let image = if image.width() > job.size || image.height() > job.size {
+            image.thumbnail(job.size, job.size)
+        } else {
+            image
+        };
+        write_thumbnail(&flavor_dir, uri, modified_unix, &image)
+            .map_err(|err| (ERROR_SAVE_FAILED, err))
+    };
+
+    if job.size <= settings.thumbnail_size {
+        let cached = cache
+            .get(&pending.cache_key, modified_unix, job.size)
+            .unwrap_or_else(|err| {
+                log::warn!("{}", err);
+                None
+            });
+        let blob = match cached {
+            Some((blob, _)) => blob,
+            None => {
+                let generated = pending
+                    .generate(&settings.generator(settings.thumbnail_size))
+                    .map_err(|err| (ERROR_INVALID_DATA, err))?;
+                let stored = cache
+                    .store(std::slice::from_ref(&generated))
+                    .and_then(|()| cache.prune(settings.cache_max_bytes));
+                if let Err(err) = stored {
+                    log::warn!("{}", err);
+                }
+                generated.blob
+            }
+        };
+        let image = image::load_from_memory(&blob).map_err(|err| {
+            (
+                ERROR_INVALID_DATA,
+                format!("Failed to decode thumbnail: {err}"),
+            )
+        })?;
+        save(image)
+    } else {
+        // Saved inside so the full-size image is only held while it counts
+        // against the decode budget.
+        decode_image(&path, &settings.decode_limits, save)
+            .map_err(|err| (ERROR_INVALID_DATA, err))?
+    }
+}
+
+/// Writes `<md5 of uri>.png` with the `Thumb::URI` and `Thumb::MTime` keys
+/// the spec uses to detect stale thumbnails. The file is renamed into place
+/// so readers never see a partial PNG.
+fn write_thumbnail(
+    flavor_dir: &Path,
+    uri: &str,
+    modified_unix: i64,
+    image: &DynamicImage,
+) -> Result<(), String> {
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(flavor_dir)
+        .map_err(|err| format!("Failed to create {}: {err}", flavor_dir.display()))?;
+    let file_name = format!("{:x}.png", Md5::digest(uri.as_bytes()));
+    let target = flavor_dir.join(&file_name);
+    let temp: PathBuf = flavor_dir.join(format!("{file_name}.{}.tmp", std::process::id()));
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&temp)
+        .map_err(|err| format!("Failed to create {}: {err}", temp.display()))?;
+    let rgba = image.to_rgba8();
+    let mut encoder = png::Encoder::new(BufWriter::new(file), rgba.width(), rgba.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let written = encoder
+        .add_text_chunk("Thumb::URI".to_string(), uri.to_string())
+        .and_then(|()| {
+            encoder.add_text_chunk("Thumb::MTime".to_string(), modified_unix.to_string())
+        })
+        .and_then(|()| encoder.write_header())
+        .and_then(|mut writer| {
+            writer.write_image_data(&rgba)?;
+            writer.finish()
+        });
+    if let Err(err) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(format!("Failed to write thumbnail for {uri}: {err}"));
+    }
+    fs::rename(&temp, &target).map_err(|err| format!("Failed to save {}: {err}", target.display()))
+}
@@ -0,0 +1,501 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    path::{Component, Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use chrono::{Local, TimeZone};
+use serde::Serialize;
+use tauri::Emitter;
+use thumbnailer_core::{extended_path, last_modified_unix};
+
+use crate::{
+    data_url_for_blob, exif_info,
+    file_ops::{failure, success, FileOperationSummary},
+    now_unix,
+    settings::Settings,
+    video::external_tool,
+    AppState,
+};
+
+const MOUNT_PREFIX: &str = "mount:";
+const CAMERA_PREFIX: &str = "camera:";
+
+/// A connected camera or phone.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Device {
+    /// Passed back to the other device commands.
+    id: String,
+    name: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeviceImage {
+    /// Passed back to `import_from_device`.
+    id: String,
+    name: String,
+    /// Folder on the device, such as `/DCIM/100CANON`.
+    folder: String,
+    size_bytes: Option<u64>,
+    modified_unix: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DeviceListing {
+    images: Vec<DeviceImage>,
+    /// Data URLs keyed by image id, for the images that carry a preview.
+    thumbnails: HashMap<String, String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportProgress {
+    current: usize,
+    total: usize,
+    name: String,
+}
+
+/// How a device is reached. Desktops that mount phones and cameras
+/// themselves (GNOME's gvfs) hold on to them, so those are read through
+/// the mount; anything else goes through `gphoto2`, which speaks both PTP
+/// and MTP and has to be installed separately.
+enum Source {
+    Mount(PathBuf),
+    Camera { port: String },
+}
+
+impl Source {
+    fn from_id(id: &str) -> Result<Source, String> {
+        if let Some(path) = id.strip_prefix(MOUNT_PREFIX) {
+            let path = PathBuf::from(path);
+            if !mounted_devices().iter().any(|(mount, _)| *mount == path) {
+                return Err(format!("{} is no longer connected.", path.display()));
+            }
+            Ok(Source::Mount(path))
+        } else if let Some(port) = id.strip_prefix(CAMERA_PREFIX) {
+            Ok(Source::Camera {
+                port: port.to_string(),
+            })
+        } else {
+            Err(format!("{id:?} is not a device."))
+        }
+    }
+}
+
+/// Cameras and phones connected over USB, as MTP or PTP devices.
+#[tauri::command]
+pub(crate) async fn list_devices() -> Result<Vec<Device>, String> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let mounted = mounted_devices().into_iter().map(|(path, name)| Device {
+            id: format!("{MOUNT_PREFIX}{}", path.to_string_lossy()),
+            name,
+        });
+        let cameras = gphoto2_cameras().into_iter().map(|(port, name)| Device {
+            id: format!("{CAMERA_PREFIX}{port}"),
+            name,
+        });
+        Ok(mounted.chain(cameras).collect())
+    })
+    .await
+    .map_err(|err| format!("Failed to join device listing task: {err}"))?
+}
+
+/// The images in the device's `DCIM` folders, with the small previews the
+/// camera stored for them. Nothing is decoded: over USB, reading every
+/// image in full would take minutes.
+#[tauri::command]
+pub(crate) async fn list_device_images(
+    state: tauri::State<'_, AppState>,
+    device: String,
+) -> Result<DeviceListing, String> {
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || match Source::from_id(&device)? {
+        Source::Mount(root) => {
+            let images = mounted_images(&root, &settings);
+            let thumbnails = images
+                .iter()
+                .filter_map(|image| {
+                    let exif = exif_info::read_exif(Path::new(&image.id))?;
+                    let thumbnail = exif_info::embedded_thumbnail(&exif)?;
+                    Some((image.id.clone(), data_url_for_blob(thumbnail, "image/jpeg")))
+                })
+                .collect();
+            Ok(DeviceListing { images, thumbnails })
+        }
+        Source::Camera { port } => {
+            let images = gphoto2_images(&port, &settings)?;
+            let thumbnails = gphoto2_thumbnails(&port, &images);
+            Ok(DeviceListing { images, thumbnails })
+        }
+    })
+    .await
+    .map_err(|err| format!("Failed to join device listing task: {err}"))?
+}
+
+/// Copies the chosen images into `YYYY/YYYY-MM-DD` folders under
+/// `destination` by capture date, emitting `device-import-progress` as each
+/// one lands. Existing files are never overwritten; those images are
+/// reported as failures. Nothing is deleted from the device.
+#[tauri::command]
+pub(crate) async fn import_from_device(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    device: String,
+    images: Vec<String>,
+    destination: String,
+) -> Result<FileOperationSummary, String> {
+    state.path_scope.check(Path::new(&destination))?;
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        let destination = PathBuf::from(destination);
+        if !destination.is_dir() {
+            return Err(format!(
+                "{} is not a valid directory.",
+                destination.display()
+            ));
+        }
+        let source = Source::from_id(&device)?;
+        let listed: HashMap<String, DeviceImage> = match &source {
+            Source::Mount(_) => HashMap::new(),
+            Source::Camera { port } => gphoto2_images(port, &settings)?
+                .into_iter()
+                .map(|image| (image.id.clone(), image))
+                .collect(),
+        };
+        let total = images.len();
+        let mut results = Vec::with_capacity(total);
+        for (index, id) in images.into_iter().enumerate() {
+            let result = match &source {
+                Source::Mount(root) => import_mounted(root, &id, &destination),
+                Source::Camera { port } => match listed.get(&id) {
+                    Some(image) => import_camera(port, image, &destination),
+                    None => Err("The image is no longer on the device.".to_string()),
+                },
+            };
+            let name = match &source {
+                Source::Mount(_) => Path::new(&id)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string()),
+                Source::Camera { .. } => listed.get(&id).map(|image| image.name.clone()),
+            };
+            let progress = ImportProgress {
+                current: index + 1,
+                total,
+                name: name.unwrap_or_else(|| "image".to_string()),
+            };
+            if let Err(err) = app.emit("device-import-progress", &progress) {
+                log::warn!("Failed to emit device import progress: {}", err);
+            }
+            results.push(match result {
+                Ok(target) => success(id, Some(target)),
+                Err(err) => failure(id, err),
+            });
+        }
+        Ok(FileOperationSummary::from_results(results))
+    })
+    .await
+    .map_err(|err| format!("Failed to join device import task: {err}"))?
+}
+
+/// The EXIF capture time, or the modified time for images without one.
+pub(crate) fn captured_unix(path: &Path) -> i64 {
+    exif_info::read_exif(path)
+        .as_ref()
+        .and_then(exif_info::capture_time_unix)
+        .or_else(|| last_modified_unix(path).ok())
+        .unwrap_or_else(now_unix)
+}
+
+/// `YYYY/YYYY-MM-DD` under `destination`, in local time.
+pub(crate) fn dated_folder(destination: &Path, unix: i64) -> PathBuf {
+    match Local.timestamp_opt(unix, 0).single() {
+        Some(date) => destination
+            .join(date.format("%Y").to_string())
+            .join(date.format("%Y-%m-%d").to_string()),
+        None => destination.to_path_buf(),
+    }
+}
+
+/// Writes through `write` into a `.part` file beside the target, renamed
+/// once complete, so an unplugged device never leaves a truncated image
+/// that looks imported.
+pub(crate) fn import_into(
+    folder: &Path,
+    name: &str,
+    modified_unix: Option<i64>,
+    write: impl FnOnce(&Path) -> Result<(), String>,
+) -> Result<PathBuf, String> {
+    let target = folder.join(name);
+    if extended_path(&target).exists() {
+        return Err(format!("{} already exists.", target.display()));
+    }
+    fs::create_dir_all(extended_path(folder))
+        .map_err(|err| format!("Failed to create {}: {err}", folder.display()))?;
+    let partial = folder.join(format!("{name}.part"));
+    if let Err(err) = write(&partial) {
+        let _ = fs::remove_file(extended_path(&partial));
+        return Err(err);
+    }
+    if let Some(modified_unix) = modified_unix.and_then(|unix| u64::try_from(unix).ok()) {
+        let modified = UNIX_EPOCH + Duration::from_secs(modified_unix);
+        let set = File::options()
+            .write(true)
+            .open(extended_path(&partial))
+            .and_then(|file| file.set_modified(modified));
+        if let Err(err) = set {
+            log::warn!(
+                "Failed to set modified time of {}: {}",
+                target.display(),
+                err
+            );
+        }
+    }
+    fs::rename(extended_path(&partial), extended_path(&target))
+        .map_err(|err| format!("Failed to move into {}: {err}", target.display()))?;
+    Ok(target)
+}
+
+/// Names like `mtp:host=Google_Pixel_7_1A2B3C` and
+/// `gphoto2:host=Canon_Inc._Canon_Digital_Camera` under `$XDG_RUNTIME_DIR/gvfs`.
+#[cfg(target_os = "linux")]
+fn mounted_devices() -> Vec<(PathBuf, String)> {
+    let Some(gvfs) = std::env::var_os("XDG_RUNTIME_DIR").map(|dir| Path::new(&dir).join("gvfs"))
+    else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(&gvfs) else {
+        return Vec::new();
+    };
+    let mut devices: Vec<(PathBuf, String)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let mount = entry.file_name().to_string_lossy().to_string();
+            let host = mount
+                .strip_prefix("mtp:host=")
+                .or_else(|| mount.strip_prefix("gphoto2:host="))?;
+            Some((entry.path(), host.replace('_', " ")))
+        })
+        .collect();
+    devices.sort();
+    devices
+}
+
+#[cfg(not(target_os = "linux"))]
+fn mounted_devices() -> Vec<(PathBuf, String)> {
+    Vec::new()
+}
+
+/// Phones put `DCIM` inside a storage folder (`Internal shared storage`)
+/// and cameras at the root or inside one, so both levels are searched.
+fn mounted_images(root: &Path, settings: &Settings) -> Vec<DeviceImage> {
+    let storages = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path());
+    let mut pending: Vec<PathBuf> = std::iter::once(root.to_path_buf())
+        .chain(storages)
+        .map(|folder| folder.join("DCIM"))
+        .filter(|folder| folder.is_dir())
+        .collect();
+    let mut images = Vec::new();
+    while let Some(folder) = pending.pop() {
+        let entries = match fs::read_dir(&folder) {
+            Ok(value) => value,
+            Err(err) => {
+                log::warn!("Failed to read {}: {}", folder.display(), err);
+                continue;
+            }
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                pending.push(path);
+            } else if settings.scan.is_supported_image(&path) {
+                let metadata = entry.metadata().ok();
+                images.push(DeviceImage {
+                    id: path.to_string_lossy().to_string(),
+                    name: entry.file_name().to_string_lossy().to_string(),
+                    folder: folder
+                        .strip_prefix(root)
+                        .map(|relative| format!("/{}", relative.to_string_lossy()))
+                        .unwrap_or_default(),
+                    size_bytes: metadata.map(|metadata| metadata.len()),
+                    modified_unix: last_modified_unix(&path).ok(),
+                });
+            }
+        }
+    }
+    images.sort_by(|left, right| left.id.cmp(&right.id));
+    images
+}
+
+fn import_mounted(root: &Path, id: &str, destination: &Path) -> Result<PathBuf, String> {
+    let source = PathBuf::from(id);
+    let escapes = source
+        .components()
+        .any(|component| component == Component::ParentDir);
+    if escapes || !source.starts_with(root) {
+        return Err(format!("{id} is not on the device."));
+    }
+    let name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| "Path has no file name.".to_string())?;
+    let modified_unix = last_modified_unix(&source).ok();
+    let folder = dated_folder(destination, captured_unix(&source));
+    import_into(&folder, &name, modified_unix, |partial| {
+        fs::copy(extended_path(&source), extended_path(partial))
+            .map(|_| ())
+            .map_err(|err| format!("Failed to copy {id}: {err}"))
+    })
+}
+
+/// Ports and models from `gphoto2 --auto-detect`, which prints a header,
+/// a dashed rule, and then one `<model>  <port>` line per camera.
+fn gphoto2_cameras() -> Vec<(String, String)> {
+    let output = match external_tool("gphoto2").arg("--auto-detect").output() {
+        Ok(value) if value.status.success() => value.stdout,
+        Ok(value) => {
+            log::debug!("gphoto2 failed: {}", String::from_utf8_lossy(&value.stderr));
+            return Vec::new();
+        }
+        Err(err) => {
+            log::debug!("Failed to run gphoto2: {}", err);
+            return Vec::new();
+        }
+    };
+    String::from_utf8_lossy(&output)
+        .lines()
+        .skip_while(|line| !line.starts_with("---"))
+        .skip(1)
+        .filter_map(|line| {
+            let (model, port) = line.trim_end().rsplit_once(char::is_whitespace)?;
+            Some((port.to_string(), model.trim().to_string()))
+        })
+        .filter(|(port, model)| !port.is_empty() && !model.is_empty())
+        .collect()
+}
+
+fn gphoto2(port: &str, args: &[&str]) -> Result<String, String> {
+    let output = external_tool("gphoto2")
+        .args(["--port", port])
+        .args(args)
+        .output()
+        .map_err(|err| format!("Failed to run gphoto2: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "gphoto2 failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parses `gphoto2 --list-files`, which prints each folder as
+/// `There are 2 files in folder '/store_00010001/DCIM/100CANON':` followed
+/// by `#1     IMG_0001.JPG   rd  5201 KB 5760x3840 image/jpeg 1500000000`
+/// lines. The numbers count across folders and are what `--get-file` takes.
+fn gphoto2_images(port: &str, settings: &Settings) -> Result<Vec<DeviceImage>, String> {
+    let listing = gphoto2(port, &["--list-files"])?;
+    let mut folder = String::new();
+    let mut images = Vec::new();
+    for line in listing.lines() {
+        if let Some(start) = line.find("in folder '") {
+            let rest = &line[start + "in folder '".len()..];
+            folder = rest[..rest.rfind('\'').unwrap_or(rest.len())].to_string();
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let Some(number) = fields.next().and_then(|field| field.strip_prefix('#')) else {
+            continue;
+        };
+        let Some(name) = fields.next() else {
+            continue;
+        };
+        let in_dcim = folder
+            .split('/')
+            .any(|part| part.eq_ignore_ascii_case("DCIM"));
+        if !in_dcim || !settings.scan.is_supported_image(Path::new(name)) {
+            continue;
+        }
+        let fields: Vec<&str> = fields.collect();
+        let size_bytes = fields
+            .iter()
+            .position(|field| *field == "KB")
+            .and_then(|index| index.checked_sub(1))
+            .and_then(|index| fields[index].parse::<u64>().ok())
+            .map(|kilobytes| kilobytes * 1024);
+        images.push(DeviceImage {
+            id: number.to_string(),
+            name: name.to_string(),
+            folder: folder.clone(),
+            size_bytes,
+            modified_unix: fields.last().and_then(|field| field.parse().ok()),
+        });
+    }
+    Ok(images)
+}
+
+/// Fetches every preview in one `gphoto2` run, since each run has to open
+/// the device again. They are saved under the image's own name, so two
+/// images with the same name in different folders share one.
+fn gphoto2_thumbnails(port: &str, images: &[DeviceImage]) -> HashMap<String, String> {
+    let staging = std::env::temp_dir().join(format!(
+        "thumbnailer-device-{}-{}",
+        std::process::id(),
+        now_unix()
+    ));
+    if let Err(err) = fs::create_dir_all(&staging) {
+        log::warn!("Failed to create {}: {}", staging.display(), err);
+        return HashMap::new();
+    }
+    let pattern = staging.join("%f.%C");
+    let fetched = gphoto2(
+        port,
+        &[
+            "--get-all-thumbnails",
+            "--force-overwrite",
+            "--filename",
+            &pattern.to_string_lossy(),
+        ],
+    );
+    if let Err(err) = fetched {
+        log::warn!("Failed to read device thumbnails: {}", err);
+    }
+    let thumbnails = images
+        .iter()
+        .filter_map(|image| {
+            let blob = fs::read(staging.join(&image.name)).ok()?;
+            Some((image.id.clone(), data_url_for_blob(&blob, "image/jpeg")))
+        })
+        .collect();
+    if let Err(err) = fs::remove_dir_all(&staging) {
+        log::warn!("Failed to remove {}: {}", staging.display(), err);
+    }
+    thumbnails
+}
+
+/// Dated by the file time the camera reports; reading the EXIF date would
+/// mean downloading the image first.
+fn import_camera(port: &str, image: &DeviceImage, destination: &Path) -> Result<PathBuf, String> {
+    let folder = dated_folder(destination, image.modified_unix.unwrap_or_else(now_unix));
+    import_into(&folder, &image.name, image.modified_unix, |partial| {
+        gphoto2(
+            port,
+            &[
+                "--get-file",
+                &image.id,
+                "--force-overwrite",
+                "--filename",
+                // `%` starts a gphoto2 filename pattern.
+                &partial.to_string_lossy().replace('%', "%%"),
+            ],
+        )
+        .map(|_| ())
+    })
+}
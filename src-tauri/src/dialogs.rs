@@ -0,0 +1,110 @@
+use tauri::Manager;
+use tauri_plugin_dialog::{DialogExt, FilePath};
+
+use crate::{
+    resolve_data_dir,
+    settings::{self, Settings},
+    AppState,
+};
+
+/// Asks for a folder, to browse or to move, copy, export or import into, and
+/// adds it to the path scope. `None` when the user cancels.
+#[tauri::command]
+pub(crate) async fn pick_folder(
+    app: tauri::AppHandle,
+    title: Option<String>,
+) -> Result<Option<String>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut dialog = app.dialog().file();
+        if let Some(title) = title {
+            dialog = dialog.set_title(title);
+        }
+        allow_picked(&app, dialog.blocking_pick_folder())
+    })
+    .await
+    .map_err(|err| format!("Failed to join folder picker task: {err}"))?
+}
+
+/// Asks for a file to read, such as a library backup or another app's
+/// database, and adds it to the path scope. `None` when the user cancels.
+#[tauri::command]
+pub(crate) async fn pick_file(
+    app: tauri::AppHandle,
+    title: Option<String>,
+) -> Result<Option<String>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut dialog = app.dialog().file();
+        if let Some(title) = title {
+            dialog = dialog.set_title(title);
+        }
+        allow_picked(&app, dialog.blocking_pick_file())
+    })
+    .await
+    .map_err(|err| format!("Failed to join file picker task: {err}"))?
+}
+
+/// Asks where to save an export or backup, suggesting `file_name`, and adds
+/// the chosen file to the path scope. `None` when the user cancels.
+#[tauri::command]
+pub(crate) async fn pick_save_path(
+    app: tauri::AppHandle,
+    title: Option<String>,
+    file_name: Option<String>,
+) -> Result<Option<String>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut dialog = app.dialog().file();
+        if let Some(title) = title {
+            dialog = dialog.set_title(title);
+        }
+        if let Some(file_name) = file_name {
+            dialog = dialog.set_file_name(file_name);
+        }
+        allow_picked(&app, dialog.blocking_save_file())
+    })
+    .await
+    .map_err(|err| format!("Failed to join save picker task: {err}"))?
+}
+
+/// Asks for an application for `open_with` to launch and adds it to the
+/// editors. Returns the settings, unchanged when the user cancels.
+#[tauri::command]
+pub(crate) async fn pick_editor(
+    app: tauri::AppHandle,
+    title: Option<String>,
+) -> Result<Settings, String> {
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut dialog = app.dialog().file();
+        if let Some(title) = title {
+            dialog = dialog.set_title(title);
+        }
+        let state = app.state::<AppState>();
+        let Some(picked) = dialog.blocking_pick_file() else {
+            return Ok(state.settings.get());
+        };
+        let editor = picked
+            .into_path()
+            .map_err(|err| format!("Failed to read the picked path: {err}"))?;
+        settings::add_editor(
+            &data_dir,
+            &state.settings,
+            editor.to_string_lossy().to_string(),
+        )
+    })
+    .await
+    .map_err(|err| format!("Failed to join editor picker task: {err}"))?
+}
+
+fn allow_picked(
+    app: &tauri::AppHandle,
+    picked: Option<FilePath>,
+) -> Result<Option<String>, String> {
+    let Some(picked) = picked else {
+        return Ok(None);
+    };
+    let path = picked
+        .into_path()
+        .map_err(|err| format!("Failed to read the picked path: {err}"))?;
+    app.state::<AppState>().path_scope.allow(&path);
+    Ok(Some(path.to_string_lossy().to_string()))
+}
@@ -0,0 +1,115 @@
+use std::{io, path::Path};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tauri::Emitter;
+use thumbnailer_core::{cache_path, extended_path, last_modified_unix};
+
+use crate::share_guard::{self, ShareGuard};
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ItemDimensions {
+    path: String,
+    width: u32,
+    height: u32,
+}
+
+/// The `item-dimensions` event, sent with each progress update during a
+/// gallery load ahead of the thumbnails, so the grid can be laid out once.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ItemDimensionsBatch {
+    items: Vec<ItemDimensions>,
+}
+
+/// The image's pixel size, remembered per modification time so only new or
+/// changed files have their header read. `probe` is false for online-only
+/// files, which only report a size already known.
+pub(crate) fn for_item(
+    connection: &Connection,
+    share_guard: &ShareGuard,
+    path: &Path,
+    probe: bool,
+) -> Result<Option<ItemDimensions>, String> {
+    let modified_unix = last_modified_unix(path)?;
+    let cached = connection
+        .query_row(
+            "SELECT width, height FROM image_dimensions
+             WHERE path = ?1 AND modified_unix = ?2",
+            params![cache_path(path), modified_unix],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read image dimensions: {err}"))?;
+    let (width, height) = match cached {
+        Some(value) => value,
+        None if probe => {
+            let io_path = extended_path(path).into_owned();
+            let dimensions = share_guard.run(path, share_guard::METADATA_TIMEOUT, move || {
+                image::ImageReader::open(&io_path)?
+                    .with_guessed_format()?
+                    .into_dimensions()
+                    .map_err(io::Error::other)
+            })?;
+            connection
+                .execute(
+                    "INSERT OR REPLACE INTO image_dimensions (path, modified_unix, width, height)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![cache_path(path), modified_unix, dimensions.0, dimensions.1],
+                )
+                .map_err(|err| format!("Failed to store image dimensions: {err}"))?;
+            dimensions
+        }
+        None => return Ok(None),
+    };
+    Ok(Some(ItemDimensions {
+        path: path.to_string_lossy().to_string(),
+        width,
+        height,
+    }))
+}
+
+/// The pixel size recorded for this version of the file, without reading
+/// it.
+pub(crate) fn cached(
+    connection: &Connection,
+    path: &Path,
+    modified_unix: i64,
+) -> Result<Option<(u32, u32)>, String> {
+    connection
+        .query_row(
+            "SELECT width, height FROM image_dimensions WHERE path = ?1 AND modified_unix = ?2",
+            params![cache_path(path), modified_unix],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read image dimensions: {err}"))
+}
+
+pub(crate) fn relocate(
+    connection: &Connection,
+    source: &Path,
+    target: &Path,
+) -> Result<(), String> {
+    connection
+        .execute(
+            "UPDATE OR REPLACE image_dimensions SET path = ?1 WHERE path = ?2",
+            params![cache_path(target), cache_path(source)],
+        )
+        .map(|_| ())
+        .map_err(|err| format!("Failed to move image dimensions: {err}"))
+}
+
+/// Sends the dimensions gathered since the last call, if any.
+pub(crate) fn emit(app: &tauri::AppHandle, items: &mut Vec<ItemDimensions>) {
+    if items.is_empty() {
+        return;
+    }
+    let batch = ItemDimensionsBatch {
+        items: std::mem::take(items),
+    };
+    if let Err(err) = app.emit("item-dimensions", &batch) {
+        log::warn!("Failed to emit item dimensions: {}", err);
+    }
+}
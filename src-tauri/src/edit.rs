@@ -0,0 +1,538 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, Cursor, Read},
+    path::{Path, PathBuf},
+};
+
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgba, RgbaImage};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+use thumbnailer_core::{cache_path, decode_image, encode_image, extended_path};
+
+use crate::{
+    data_url_for_blob, export::validate_source, load_thumbnail_blocking, now_unix, open_cache_db,
+    resolve_data_dir, settings::OutputFormat, video::external_tool, AppState,
+};
+
+/// Folder under the app data directory holding originals replaced by edits.
+const BACKUP_DIR: &str = "edit-backups";
+/// Straightening beyond this is a rotation, not a fix.
+const MAX_STRAIGHTEN_DEGREES: f32 = 45.0;
+
+/// One step of an edit, applied in order.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub(crate) enum EditOperation {
+    /// In pixels of the image as it stands after the steps before.
+    #[serde(rename_all = "camelCase")]
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// Clockwise; negative turns go counterclockwise.
+    #[serde(rename_all = "camelCase")]
+    Rotate {
+        quarter_turns: i32,
+    },
+    FlipHorizontal,
+    FlipVertical,
+    /// Rotates clockwise by a small angle and crops to the largest
+    /// rectangle of the same shape, so no empty corners show.
+    Straighten {
+        degrees: f32,
+    },
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum EditTarget {
+    /// Replace the original, keeping a copy of it under the app data
+    /// directory for `restore_original`.
+    #[default]
+    Overwrite,
+    /// Save beside the original as `name (edited).ext`.
+    Copy,
+}
+
+/// The `image-edited` event, also returned by `edit_image`.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EditedImage {
+    source: String,
+    /// The original's path when overwritten, otherwise the new copy.
+    path: String,
+    /// Where the file as it was before its first edit is kept.
+    backup_path: Option<String>,
+    thumbnail: String,
+}
+
+/// Crops, rotates, flips or straightens an image without leaving the app,
+/// regenerates its thumbnail and emits `image-edited`. The result is saved
+/// in the original's format; metadata such as EXIF is not carried over,
+/// except by a lossless JPEG crop.
+#[tauri::command]
+pub(crate) async fn edit_image(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+    operations: Vec<EditOperation>,
+    target: Option<EditTarget>,
+) -> Result<EditedImage, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let data_dir = resolve_data_dir(&app)?;
+    let settings = state.settings.get();
+    let edited = tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        move || {
+            let source = PathBuf::from(&path);
+            validate_source(&source, &settings)?;
+            if operations.is_empty() {
+                return Err("No edit operations given.".to_string());
+            }
+            let format = ImageFormat::from_path(&source)
+                .map_err(|_| format!("Unsupported image format: {}", source.display()))?;
+            let lossless = match (format, operations.as_slice()) {
+                (ImageFormat::Jpeg, [crop @ EditOperation::Crop { .. }]) => {
+                    lossless_jpeg_crop(&source, *crop)
+                }
+                _ => None,
+            };
+            let bytes = match lossless {
+                Some(bytes) => bytes,
+                None => decode_image(&source, &settings.decode_limits, |image| {
+                    let edited = operations
+                        .iter()
+                        .try_fold(image, |image, operation| apply(image, *operation))?;
+                    encode(&edited, format, settings.edit_jpeg_quality)
+                })??,
+            };
+
+            let (output, backup_path) = match target.unwrap_or_default() {
+                EditTarget::Overwrite => {
+                    let connection = open_cache_db(&data_dir)?;
+                    let backup_path = archive_original(&connection, &data_dir, &source)?;
+                    (source.clone(), Some(backup_path))
+                }
+                EditTarget::Copy => (edited_copy_path(&source), None),
+            };
+            write_replacing(&output, &bytes)?;
+
+            let state = app.state::<AppState>();
+            let (blob, mime_type) = load_thumbnail_blocking(
+                data_dir,
+                output.to_string_lossy().to_string(),
+                settings.thumbnail_size,
+                &settings,
+                &state.session_metrics,
+                &state.thumbnail_flights,
+            )?;
+            Ok(EditedImage {
+                source: path,
+                path: output.to_string_lossy().to_string(),
+                backup_path: backup_path.map(|backup| backup.to_string_lossy().to_string()),
+                thumbnail: data_url_for_blob(&blob, &mime_type),
+            })
+        }
+    })
+    .await
+    .map_err(|err| format!("Failed to join edit task: {err}"))??;
+    if let Err(err) = app.emit("image-edited", &edited) {
+        log::warn!("Failed to emit image edit: {}", err);
+    }
+    Ok(edited)
+}
+
+/// Puts back the file as it was before `edit_image` first overwrote it,
+/// undoing every edit since, and emits `image-edited`. The kept copy is
+/// removed, so a later edit archives the restored file afresh.
+#[tauri::command]
+pub(crate) async fn restore_original(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+) -> Result<EditedImage, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let data_dir = resolve_data_dir(&app)?;
+    let settings = state.settings.get();
+    let restored = tauri::async_runtime::spawn_blocking({
+        let app = app.clone();
+        move || -> Result<EditedImage, String> {
+            let target = PathBuf::from(&path);
+            let connection = open_cache_db(&data_dir)?;
+            let backup_path = archived_original(&connection, &target)?
+                .ok_or_else(|| format!("No original of {} is kept.", target.display()))?;
+            let bytes = fs::read(extended_path(&backup_path))
+                .map_err(|err| format!("Failed to read {}: {err}", backup_path.display()))?;
+            write_replacing(&target, &bytes)?;
+            connection
+                .execute(
+                    "DELETE FROM edit_originals WHERE path = ?1",
+                    params![cache_path(&target)],
+                )
+                .map_err(|err| format!("Failed to forget original: {err}"))?;
+            if let Err(err) = fs::remove_file(extended_path(&backup_path)) {
+                log::warn!("Failed to remove {}: {}", backup_path.display(), err);
+            }
+
+            let state = app.state::<AppState>();
+            let (blob, mime_type) = load_thumbnail_blocking(
+                data_dir,
+                path.clone(),
+                settings.thumbnail_size,
+                &settings,
+                &state.session_metrics,
+                &state.thumbnail_flights,
+            )?;
+            Ok(EditedImage {
+                source: path.clone(),
+                path,
+                backup_path: None,
+                thumbnail: data_url_for_blob(&blob, &mime_type),
+            })
+        }
+    })
+    .await
+    .map_err(|err| format!("Failed to join restore task: {err}"))??;
+    if let Err(err) = app.emit("image-edited", &restored) {
+        log::warn!("Failed to emit image edit: {}", err);
+    }
+    Ok(restored)
+}
+
+pub(crate) fn relocate(
+    connection: &Connection,
+    source: &Path,
+    target: &Path,
+) -> Result<(), String> {
+    connection
+        .execute(
+            "UPDATE OR REPLACE edit_originals SET path = ?1 WHERE path = ?2",
+            params![cache_path(target), cache_path(source)],
+        )
+        .map(|_| ())
+        .map_err(|err| format!("Failed to move kept original: {err}"))
+}
+
+/// Sends the kept original of an image being deleted to the trash along
+/// with it, keeping the row, so undoing the delete brings both back.
+/// Returns the copy's path for the undo journal.
+pub(crate) fn trash_original(
+    connection: &Connection,
+    path: &Path,
+) -> Result<Option<String>, String> {
+    let Some(backup_path) = archived_original(connection, path)? else {
+        return Ok(None);
+    };
+    trash::delete(&backup_path)
+        .map_err(|err| format!("Failed to move {} to trash: {err}", backup_path.display()))?;
+    Ok(Some(backup_path.to_string_lossy().to_string()))
+}
+
+fn apply(image: DynamicImage, operation: EditOperation) -> Result<DynamicImage, String> {
+    Ok(match operation {
+        EditOperation::Crop {
+            x,
+            y,
+            width,
+            height,
+        } => {
+            let (image_width, image_height) = image.dimensions();
+            let fits = width > 0
+                && height > 0
+                && x.checked_add(width)
+                    .is_some_and(|right| right <= image_width)
+                && y.checked_add(height)
+                    .is_some_and(|bottom| bottom <= image_height);
+            if !fits {
+                return Err(format!(
+                    "Crop {width}x{height} at {x},{y} is outside the {image_width}x{image_height} image."
+                ));
+            }
+            image.crop_imm(x, y, width, height)
+        }
+        EditOperation::Rotate { quarter_turns } => match quarter_turns.rem_euclid(4) {
+            1 => image.rotate90(),
+            2 => image.rotate180(),
+            3 => image.rotate270(),
+            _ => image,
+        },
+        EditOperation::FlipHorizontal => image.fliph(),
+        EditOperation::FlipVertical => image.flipv(),
+        EditOperation::Straighten { degrees } => {
+            if !degrees.is_finite() || degrees.abs() > MAX_STRAIGHTEN_DEGREES {
+                return Err(format!(
+                    "Straighten by {degrees} degrees is outside ±{MAX_STRAIGHTEN_DEGREES}."
+                ));
+            }
+            straighten(&image, degrees)
+        }
+    })
+}
+
+/// Rotation with bilinear sampling, cropped so every output pixel comes
+/// from inside the source.
+fn straighten(image: &DynamicImage, degrees: f32) -> DynamicImage {
+    if degrees == 0.0 {
+        return image.clone();
+    }
+    let source = image.to_rgba8();
+    let (width, height) = (source.width() as f32, source.height() as f32);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (sin_abs, cos_abs) = (sin.abs(), cos.abs());
+    // The largest `scale` for which the scaled-down rectangle, rotated back,
+    // still fits inside the original on both axes.
+    let scale = (width / (width * cos_abs + height * sin_abs))
+        .min(height / (width * sin_abs + height * cos_abs));
+    let output_width = ((width * scale).floor() as u32).max(1);
+    let output_height = ((height * scale).floor() as u32).max(1);
+    let (center_x, center_y) = (width / 2.0, height / 2.0);
+    let output = RgbaImage::from_fn(output_width, output_height, |x, y| {
+        let dx = x as f32 + 0.5 - output_width as f32 / 2.0;
+        let dy = y as f32 + 0.5 - output_height as f32 / 2.0;
+        // Each output pixel turned back counterclockwise onto the source.
+        let source_x = center_x + dx * cos + dy * sin - 0.5;
+        let source_y = center_y - dx * sin + dy * cos - 0.5;
+        sample_bilinear(&source, source_x, source_y)
+    });
+    DynamicImage::ImageRgba8(output)
+}
+
+fn sample_bilinear(image: &RgbaImage, x: f32, y: f32) -> Rgba<u8> {
+    let max_x = image.width() as f32 - 1.0;
+    let max_y = image.height() as f32 - 1.0;
+    let (x, y) = (x.clamp(0.0, max_x), y.clamp(0.0, max_y));
+    let (left, top) = (x.floor(), y.floor());
+    let (right, bottom) = ((left + 1.0).min(max_x), (top + 1.0).min(max_y));
+    let (fx, fy) = (x - left, y - top);
+    let pixel = |px: f32, py: f32| image.get_pixel(px as u32, py as u32).0;
+    let (top_left, top_right) = (pixel(left, top), pixel(right, top));
+    let (bottom_left, bottom_right) = (pixel(left, bottom), pixel(right, bottom));
+    let mut blended = [0u8; 4];
+    for channel in 0..4 {
+        let upper = top_left[channel] as f32 * (1.0 - fx) + top_right[channel] as f32 * fx;
+        let lower = bottom_left[channel] as f32 * (1.0 - fx) + bottom_right[channel] as f32 * fx;
+        blended[channel] = (upper * (1.0 - fy) + lower * fy).round() as u8;
+    }
+    Rgba(blended)
+}
+
+/// Crops a JPEG with `jpegtran`, moving the compressed blocks rather than
+/// decoding and recompressing them, and keeps its metadata. Only possible
+/// when the top-left corner falls on an MCU boundary, as trimming an edge
+/// usually does; `None` otherwise or without `jpegtran` on `PATH`, and the
+/// crop is re-encoded instead.
+fn lossless_jpeg_crop(source: &Path, crop: EditOperation) -> Option<Vec<u8>> {
+    let EditOperation::Crop {
+        x,
+        y,
+        width,
+        height,
+    } = crop
+    else {
+        return None;
+    };
+    let (mcu_width, mcu_height, image_width, image_height) = match jpeg_layout(source) {
+        Ok(value) => value,
+        Err(err) => {
+            log::debug!("No lossless crop for {}: {}", source.display(), err);
+            return None;
+        }
+    };
+    let fits = width > 0
+        && height > 0
+        && x.checked_add(width)
+            .is_some_and(|right| right <= image_width)
+        && y.checked_add(height)
+            .is_some_and(|bottom| bottom <= image_height);
+    if !fits || x % mcu_width != 0 || y % mcu_height != 0 {
+        return None;
+    }
+    let output = external_tool("jpegtran")
+        .args([
+            "-copy",
+            "all",
+            "-crop",
+            &format!("{width}x{height}+{x}+{y}"),
+        ])
+        .arg(extended_path(source).as_os_str())
+        .output();
+    match output {
+        Ok(output) if output.status.success() && !output.stdout.is_empty() => Some(output.stdout),
+        Ok(output) => {
+            log::warn!(
+                "Lossless crop of {} failed: {}",
+                source.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            None
+        }
+        Err(err) => {
+            log::debug!("No lossless crop for {}: {}", source.display(), err);
+            None
+        }
+    }
+}
+
+/// The MCU size and image size from a JPEG's frame header: each MCU spans
+/// eight pixels per unit of the largest sampling factor.
+fn jpeg_layout(path: &Path) -> Result<(u32, u32, u32, u32), String> {
+    let read_error = |err: std::io::Error| format!("Failed to read {}: {err}", path.display());
+    let not_jpeg = || format!("{} has no JPEG frame header.", path.display());
+    let mut reader = BufReader::new(File::open(extended_path(path)).map_err(read_error)?);
+    let mut read_bytes = |count: usize| -> Result<Vec<u8>, String> {
+        let mut bytes = vec![0; count];
+        reader.read_exact(&mut bytes).map_err(read_error)?;
+        Ok(bytes)
+    };
+    if read_bytes(2)? != [0xFF, 0xD8] {
+        return Err(not_jpeg());
+    }
+    loop {
+        let marker = read_bytes(2)?;
+        if marker[0] != 0xFF {
+            return Err(not_jpeg());
+        }
+        let length = u16::from_be_bytes([read_bytes(1)?[0], read_bytes(1)?[0]]) as usize;
+        let segment = read_bytes(length.saturating_sub(2))?;
+        // SOF0 to SOF15, apart from DHT, JPG and DAC, which share the range.
+        let is_frame =
+            (0xC0..=0xCF).contains(&marker[1]) && ![0xC4, 0xC8, 0xCC].contains(&marker[1]);
+        if marker[1] == 0xDA {
+            return Err(not_jpeg());
+        }
+        if !is_frame {
+            continue;
+        }
+        if segment.len() < 6 {
+            return Err(not_jpeg());
+        }
+        let height = u16::from_be_bytes([segment[1], segment[2]]) as u32;
+        let width = u16::from_be_bytes([segment[3], segment[4]]) as u32;
+        let components = segment[6..].chunks_exact(3).take(segment[5] as usize);
+        let (max_horizontal, max_vertical) = components.fold((1, 1), |(h, v), component| {
+            (h.max(component[1] >> 4), v.max(component[1] & 0x0F))
+        });
+        return Ok((
+            8 * max_horizontal as u32,
+            8 * max_vertical as u32,
+            width,
+            height,
+        ));
+    }
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat, jpeg_quality: u8) -> Result<Vec<u8>, String> {
+    // JPEGs are re-encoded at the configured quality rather than the
+    // crate's lower default.
+    if format == ImageFormat::Jpeg {
+        return encode_image(image, OutputFormat::Jpeg, jpeg_quality)
+            .map_err(|err| format!("Failed to encode edited image: {err}"));
+    }
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), format)
+        .map_err(|err| format!("Failed to encode edited image: {err}"))?;
+    Ok(bytes)
+}
+
+/// The kept copy of `source` from before its first edit, made now if there
+/// is none yet, so later edits never replace the true original.
+fn archive_original(
+    connection: &Connection,
+    data_dir: &Path,
+    source: &Path,
+) -> Result<PathBuf, String> {
+    if let Some(backup_path) = archived_original(connection, source)? {
+        return Ok(backup_path);
+    }
+    let backup_path = back_up(data_dir, source)?;
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO edit_originals (path, backup_path, archived_unix)
+             VALUES (?1, ?2, ?3)",
+            params![
+                cache_path(source),
+                backup_path.to_string_lossy(),
+                now_unix()
+            ],
+        )
+        .map_err(|err| format!("Failed to record original: {err}"))?;
+    Ok(backup_path)
+}
+
+/// Copies whose file has gone missing from the backup folder don't count.
+fn archived_original(connection: &Connection, source: &Path) -> Result<Option<PathBuf>, String> {
+    let backup_path: Option<String> = connection
+        .query_row(
+            "SELECT backup_path FROM edit_originals WHERE path = ?1",
+            params![cache_path(source)],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read kept original: {err}"))?;
+    Ok(backup_path
+        .map(PathBuf::from)
+        .filter(|backup_path| extended_path(backup_path).is_file()))
+}
+
+/// Copies the original into the backup folder under a name that records
+/// when it was replaced.
+fn back_up(data_dir: &Path, source: &Path) -> Result<PathBuf, String> {
+    let backup_dir = data_dir.join(BACKUP_DIR);
+    fs::create_dir_all(&backup_dir)
+        .map_err(|err| format!("Failed to create {}: {err}", backup_dir.display()))?;
+    let stem = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "image".to_string());
+    let extension = source
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let backup_path = unused_path(&backup_dir, &format!("{stem}-{}", now_unix()), &extension);
+    fs::copy(extended_path(source), &backup_path)
+        .map_err(|err| format!("Failed to back up {}: {err}", source.display()))?;
+    Ok(backup_path)
+}
+
+fn edited_copy_path(source: &Path) -> PathBuf {
+    let folder = source.parent().unwrap_or_else(|| Path::new(""));
+    let stem = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "image".to_string());
+    let extension = source
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    unused_path(folder, &format!("{stem} (edited)"), &extension)
+}
+
+/// `folder/stem.ext`, or `stem 2.ext`, `stem 3.ext` and so on when taken.
+fn unused_path(folder: &Path, stem: &str, extension: &str) -> PathBuf {
+    let mut candidate = folder.join(format!("{stem}{extension}"));
+    let mut counter = 2;
+    while extended_path(&candidate).exists() {
+        candidate = folder.join(format!("{stem} {counter}{extension}"));
+        counter += 1;
+    }
+    candidate
+}
+
+/// Writes beside `path` first and renames over it, so a failed write never
+/// leaves a truncated file behind.
+pub(crate) fn write_replacing(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("{} has no file name.", path.display()))?;
+    let temp_path = path.with_file_name(format!(".{}.edit-tmp", file_name.to_string_lossy()));
+    fs::write(extended_path(&temp_path), bytes)
+        .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+    fs::rename(extended_path(&temp_path), extended_path(path)).map_err(|err| {
+        let _ = fs::remove_file(extended_path(&temp_path));
+        format!("Failed to write {}: {err}", path.display())
+    })
+}
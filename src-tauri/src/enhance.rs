@@ -0,0 +1,61 @@
+use image::{DynamicImage, Rgba};
+
+/// Share of pixels at each end of a channel's histogram treated as outliers,
+/// so a few specular highlights or dead pixels don't pin the stretch.
+const CLIP_FRACTION: f64 = 0.005;
+/// Channels spanning fewer levels than this are left alone; stretching a
+/// near-flat one, like a clear sky, only amplifies noise.
+const MIN_SPAN: u8 = 32;
+
+/// Auto levels: stretches each color channel so its darkest and brightest
+/// values reach black and white. Doing so per channel also neutralizes an
+/// overall color cast, which covers contrast and white balance in one pass.
+pub(crate) fn auto_enhance(image: DynamicImage) -> DynamicImage {
+    let mut pixels = image.to_rgba8();
+    let mut histograms = [[0u64; 256]; 3];
+    for Rgba(pixel) in pixels.pixels() {
+        for channel in 0..3 {
+            histograms[channel][pixel[channel] as usize] += 1;
+        }
+    }
+    let total = u64::from(pixels.width()) * u64::from(pixels.height());
+    let clipped = (total as f64 * CLIP_FRACTION) as u64;
+    let tables = histograms.map(|histogram| stretch_table(&histogram, clipped));
+    for Rgba(pixel) in pixels.pixels_mut() {
+        for channel in 0..3 {
+            pixel[channel] = tables[channel][pixel[channel] as usize];
+        }
+    }
+    DynamicImage::ImageRgba8(pixels)
+}
+
+/// Maps the levels between both clipped ends of `histogram` onto 0-255.
+fn stretch_table(histogram: &[u64; 256], clipped: u64) -> [u8; 256] {
+    let mut identity = [0u8; 256];
+    for (level, value) in identity.iter_mut().enumerate() {
+        *value = level as u8;
+    }
+    let low = first_unclipped(histogram, clipped, 0..256).unwrap_or(0);
+    let high = first_unclipped(histogram, clipped, (0..256).rev()).unwrap_or(255);
+    if high <= low || high - low < usize::from(MIN_SPAN) {
+        return identity;
+    }
+    let span = (high - low) as f32;
+    identity.map(|level| {
+        let stretched = (f32::from(level) - low as f32) / span * 255.0;
+        stretched.round().clamp(0.0, 255.0) as u8
+    })
+}
+
+/// The first of `levels` reached after more than `clipped` pixels.
+fn first_unclipped(
+    histogram: &[u64; 256],
+    clipped: u64,
+    mut levels: impl Iterator<Item = usize>,
+) -> Option<usize> {
+    let mut seen = 0;
+    levels.find(|&level| {
+        seen += histogram[level];
+        seen > clipped
+    })
+}
@@ -0,0 +1,114 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use chrono::{FixedOffset, Local, NaiveDate, TimeZone};
+use exif::{Context, Exif, In, Tag, Value};
+use thumbnailer_core::extended_path;
+
+/// Microsoft's `Rating` tag (0-5 stars), written by Windows Explorer and most
+/// DAM tools alongside the XMP rating.
+const RATING_TAG: Tag = Tag(Context::Tiff, 0x4746);
+
+pub(crate) fn read_exif(path: &Path) -> Option<Exif> {
+    let file = File::open(extended_path(path)).ok()?;
+    let mut reader = BufReader::new(file);
+    exif::Reader::new()
+        .read_from_container(&mut reader)
+        .map_err(|err| log::debug!("No EXIF data in {}: {}", path.display(), err))
+        .ok()
+}
+
+/// Capture time as a unix timestamp, from `DateTimeOriginal` (falling back to
+/// `DateTimeDigitized`). Without an `OffsetTime*` tag the camera's clock is
+/// assumed to be in the local time zone.
+pub(crate) fn capture_time_unix(exif: &Exif) -> Option<i64> {
+    [
+        (Tag::DateTimeOriginal, Tag::OffsetTimeOriginal),
+        (Tag::DateTimeDigitized, Tag::OffsetTimeDigitized),
+    ]
+    .into_iter()
+    .find_map(|(date_tag, offset_tag)| {
+        let mut date_time = exif::DateTime::from_ascii(ascii_value(exif, date_tag)?).ok()?;
+        if let Some(offset) = ascii_value(exif, offset_tag) {
+            let _ = date_time.parse_offset(offset);
+        }
+        date_time_to_unix(&date_time)
+    })
+}
+
+/// Latitude and longitude in degrees, negative to the south and west.
+pub(crate) fn gps_position(exif: &Exif) -> Option<(f64, f64)> {
+    let coordinate = |tag, ref_tag, negative: u8| {
+        let Value::Rational(parts) = &exif.get_field(tag, In::PRIMARY)?.value else {
+            return None;
+        };
+        let degrees = parts
+            .iter()
+            .zip([1.0, 60.0, 3600.0])
+            .map(|(part, divisor)| part.to_f64() / divisor)
+            .sum::<f64>();
+        let sign = match ascii_value(exif, ref_tag)?.first() {
+            Some(&reference) if reference == negative => -1.0,
+            _ => 1.0,
+        };
+        Some(sign * degrees).filter(|value| value.is_finite())
+    };
+    let latitude = coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S')?;
+    let longitude = coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W')?;
+    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude))
+        .then_some((latitude, longitude))
+}
+
+/// "Make Model", without repeating the make when the model already has it.
+pub(crate) fn camera(exif: &Exif) -> Option<String> {
+    let text = |tag| {
+        ascii_value(exif, tag)
+            .map(|value| String::from_utf8_lossy(value).trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    match (text(Tag::Make), text(Tag::Model)) {
+        (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{make} {model}")),
+        (make, model) => model.or(make),
+    }
+}
+
+pub(crate) fn rating(exif: &Exif) -> Option<u32> {
+    exif.get_field(RATING_TAG, In::PRIMARY)?
+        .value
+        .get_uint(0)
+        .filter(|stars| *stars <= 5)
+}
+
+/// The small JPEG preview most cameras embed in the EXIF thumbnail IFD.
+pub(crate) fn embedded_thumbnail(exif: &Exif) -> Option<&[u8]> {
+    let offset = exif
+        .get_field(Tag::JPEGInterchangeFormat, In::THUMBNAIL)?
+        .value
+        .get_uint(0)? as usize;
+    let length = exif
+        .get_field(Tag::JPEGInterchangeFormatLength, In::THUMBNAIL)?
+        .value
+        .get_uint(0)? as usize;
+    let thumbnail = exif.buf().get(offset..offset.checked_add(length)?)?;
+    thumbnail.starts_with(&[0xFF, 0xD8]).then_some(thumbnail)
+}
+
+fn ascii_value(exif: &Exif, tag: Tag) -> Option<&[u8]> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values.first().map(Vec::as_slice),
+        _ => None,
+    }
+}
+
+fn date_time_to_unix(value: &exif::DateTime) -> Option<i64> {
+    let naive = NaiveDate::from_ymd_opt(value.year.into(), value.month.into(), value.day.into())?
+        .and_hms_opt(value.hour.into(), value.minute.into(), value.second.into())?;
+    let timestamp = match value.offset {
+        Some(minutes) => FixedOffset::east_opt(i32::from(minutes) * 60)?
+            .from_local_datetime(&naive)
+            .single()?
+            .timestamp(),
+        None => Local.from_local_datetime(&naive).earliest()?.timestamp(),
+    };
+    Some(timestamp)
+}
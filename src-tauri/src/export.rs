@@ -0,0 +1,428 @@
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use image::{imageops::FilterType, GenericImageView};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+use thumbnailer_core::{decode_image, encode_image, extended_path};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    enhance::auto_enhance,
+    file_ops::{failure, success, FileOperationSummary},
+    settings::{OutputFormat, Settings},
+    watermark::{Watermark, WatermarkOptions},
+    AppState, FULL_IMAGE_JPEG_QUALITY,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportOptions {
+    /// The selection when left out.
+    paths: Option<Vec<String>>,
+    destination: String,
+    /// Name of an `ExportPreset` from the settings, supplying whichever of
+    /// the fields below are left out.
+    preset: Option<String>,
+    /// Longest side of the exported copies; smaller images are not upscaled.
+    max_dimension: Option<u32>,
+    /// Required unless a preset is given.
+    format: Option<OutputFormat>,
+    quality: Option<u8>,
+    watermark: Option<WatermarkOptions>,
+    /// Stretch levels and contrast and correct the white balance; see
+    /// `enhance::auto_enhance`.
+    #[serde(default)]
+    auto_enhance: bool,
+}
+
+/// Named export settings for common sharing flows, kept in the settings so
+/// users can adjust them or add their own.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportPreset {
+    pub(crate) name: String,
+    /// `None` keeps the full size.
+    max_dimension: Option<u32>,
+    format: OutputFormat,
+    quality: Option<u8>,
+    #[serde(default)]
+    auto_enhance: bool,
+}
+
+impl ExportPreset {
+    pub(crate) fn defaults() -> Vec<ExportPreset> {
+        vec![
+            ExportPreset {
+                name: "Email".to_string(),
+                max_dimension: Some(1600),
+                format: OutputFormat::Jpeg,
+                quality: Some(80),
+                auto_enhance: false,
+            },
+            ExportPreset {
+                name: "Web".to_string(),
+                max_dimension: Some(2048),
+                format: OutputFormat::Webp,
+                quality: None,
+                auto_enhance: false,
+            },
+            ExportPreset {
+                name: "Full".to_string(),
+                max_dimension: None,
+                format: OutputFormat::Jpeg,
+                quality: Some(95),
+                auto_enhance: false,
+            },
+        ]
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct ZipOptions {
+    /// Re-encode into this format; without it (and without `max_dimension`
+    /// or `auto_enhance`) the originals are packaged untouched.
+    format: Option<OutputFormat>,
+    max_dimension: Option<u32>,
+    quality: Option<u8>,
+    auto_enhance: bool,
+}
+
+/// Re-encoded copies are produced this many at a time, so a large selection
+/// never holds every encoded image in memory at once.
+const ZIP_ENCODE_BATCH: usize = 16;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportProgress {
+    current: usize,
+    total: usize,
+    name: String,
+}
+
+/// Re-encodes the selected images into `destination`, emitting
+/// `export-progress` as each one finishes. Existing files are never
+/// overwritten; those images are reported as failures.
+#[tauri::command]
+pub(crate) async fn export_images(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    mut options: ExportOptions,
+) -> Result<FileOperationSummary, String> {
+    let paths = state.selection.or_selected(options.paths.take())?;
+    state.path_scope.check_all(&paths)?;
+    state.path_scope.check(Path::new(&options.destination))?;
+    for file in options.watermark.iter().flat_map(WatermarkOptions::files) {
+        state.path_scope.check(file)?;
+    }
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        export_images_blocking(&app, &settings, &paths, options)
+    })
+    .await
+    .map_err(|err| format!("Failed to join export task: {err}"))?
+}
+
+fn export_images_blocking(
+    app: &tauri::AppHandle,
+    settings: &Settings,
+    paths: &[String],
+    options: ExportOptions,
+) -> Result<FileOperationSummary, String> {
+    let destination = PathBuf::from(&options.destination);
+    if !destination.is_dir() {
+        return Err(format!("{} is not a directory.", destination.display()));
+    }
+
+    let preset = match options.preset.as_deref() {
+        Some(name) => Some(
+            settings
+                .export_presets
+                .iter()
+                .find(|preset| preset.name.eq_ignore_ascii_case(name))
+                .ok_or_else(|| format!("No export preset named {name:?}."))?,
+        ),
+        None => None,
+    };
+    let format = options
+        .format
+        .or(preset.map(|preset| preset.format))
+        .ok_or_else(|| "An export format or preset is required.".to_string())?;
+    let watermark = options
+        .watermark
+        .as_ref()
+        .map(Watermark::prepare)
+        .transpose()?;
+    let render = RenderOptions {
+        max_dimension: options
+            .max_dimension
+            .or(preset.and_then(|preset| preset.max_dimension)),
+        format,
+        quality: options.quality.or(preset.and_then(|preset| preset.quality)),
+        auto_enhance: options.auto_enhance || preset.is_some_and(|preset| preset.auto_enhance),
+        watermark: watermark.as_ref(),
+    };
+
+    let total = paths.len();
+    let completed = AtomicUsize::new(0);
+    let results = settings.install(|| {
+        paths
+            .par_iter()
+            .map(|path| {
+                let result = match export_image(Path::new(path), &destination, &render, settings) {
+                    Ok(output_path) => success(path.clone(), Some(output_path)),
+                    Err(err) => failure(path.clone(), err),
+                };
+                let current = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                emit_progress(app, current, total, Path::new(path));
+                result
+            })
+            .collect()
+    });
+    Ok(FileOperationSummary::from_results(results))
+}
+
+fn export_image(
+    source: &Path,
+    destination: &Path,
+    render: &RenderOptions,
+    settings: &Settings,
+) -> Result<PathBuf, String> {
+    validate_source(source, settings)?;
+    let target = destination.join(converted_file_name(source, render.format)?);
+    let bytes = render_copy(source, render, settings)?;
+
+    // `create_new` also guards against two sources with the same stem
+    // racing for one output name.
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&target)
+        .map_err(|err| format!("Failed to create {}: {err}", target.display()))?;
+    file.write_all(&bytes)
+        .map_err(|err| format!("Failed to write {}: {err}", target.display()))?;
+    Ok(target)
+}
+
+/// Packages `paths`, or the selection, into a new ZIP at `destination`,
+/// emitting `export-progress` per entry. Images that can't be read are
+/// skipped and reported; an existing archive is never overwritten.
+#[tauri::command]
+pub(crate) async fn export_zip(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    paths: Option<Vec<String>>,
+    dest: String,
+    options: Option<ZipOptions>,
+) -> Result<FileOperationSummary, String> {
+    let paths = state.selection.or_selected(paths)?;
+    state.path_scope.check_all(&paths)?;
+    state.path_scope.check(Path::new(&dest))?;
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        let destination = PathBuf::from(dest);
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&destination)
+            .map_err(|err| format!("Failed to create {}: {err}", destination.display()))?;
+        let result =
+            export_zip_blocking(&app, &settings, file, &paths, &options.unwrap_or_default());
+        if result.is_err() {
+            let _ = fs::remove_file(&destination);
+        }
+        result
+    })
+    .await
+    .map_err(|err| format!("Failed to join ZIP export task: {err}"))?
+}
+
+fn export_zip_blocking(
+    app: &tauri::AppHandle,
+    settings: &Settings,
+    file: File,
+    paths: &[String],
+    options: &ZipOptions,
+) -> Result<FileOperationSummary, String> {
+    let reencode =
+        options.format.is_some() || options.max_dimension.is_some() || options.auto_enhance;
+    let format = options.format.unwrap_or(OutputFormat::Jpeg);
+    // Image data is already compressed; deflating it again only costs time.
+    let entry_options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(true);
+
+    let mut writer = ZipWriter::new(file);
+    let mut entry_names = HashSet::new();
+    let mut results = Vec::with_capacity(paths.len());
+    let mut current = 0;
+    for batch in paths.chunks(ZIP_ENCODE_BATCH) {
+        let copies: Vec<Option<Result<Vec<u8>, String>>> = if reencode {
+            settings.install(|| {
+                batch
+                    .par_iter()
+                    .map(|path| {
+                        let source = Path::new(path);
+                        Some(validate_source(source, settings).and_then(|()| {
+                            render_copy(
+                                source,
+                                &RenderOptions {
+                                    max_dimension: options.max_dimension,
+                                    format,
+                                    quality: options.quality,
+                                    auto_enhance: options.auto_enhance,
+                                    watermark: None,
+                                },
+                                settings,
+                            )
+                        }))
+                    })
+                    .collect()
+            })
+        } else {
+            batch.iter().map(|_| None).collect()
+        };
+
+        for (path, copy) in batch.iter().zip(copies) {
+            let source = Path::new(path);
+            let entry = match copy {
+                Some(rendered) => rendered.and_then(|bytes| {
+                    let name = converted_file_name(source, format)?;
+                    Ok((name, Some(bytes)))
+                }),
+                None => validate_source(source, settings).and_then(|()| {
+                    let name = source
+                        .file_name()
+                        .ok_or_else(|| format!("{} has no file name.", source.display()))?;
+                    Ok((name.to_os_string(), None))
+                }),
+            };
+            let result = entry.and_then(|(name, bytes)| {
+                let name = unique_entry_name(&mut entry_names, &name);
+                writer
+                    .start_file(name.as_str(), entry_options)
+                    .map_err(|err| format!("Failed to add {name} to archive: {err}"))?;
+                match bytes {
+                    Some(bytes) => writer.write_all(&bytes),
+                    None => File::open(extended_path(source))
+                        .and_then(|mut file| io::copy(&mut file, &mut writer).map(|_| ())),
+                }
+                .map_err(|err| format!("Failed to write {name} to archive: {err}"))
+            });
+            results.push(match result {
+                Ok(()) => success(path.clone(), None),
+                Err(err) => failure(path.clone(), err),
+            });
+            current += 1;
+            emit_progress(app, current, paths.len(), source);
+        }
+    }
+    writer
+        .finish()
+        .map_err(|err| format!("Failed to finish archive: {err}"))?;
+    Ok(FileOperationSummary::from_results(results))
+}
+
+pub(crate) fn validate_source(source: &Path, settings: &Settings) -> Result<(), String> {
+    if !extended_path(source).is_file() {
+        return Err(format!("{} is not a file.", source.display()));
+    }
+    if !settings.scan.is_supported_image(source) {
+        return Err(format!("Unsupported image format: {}", source.display()));
+    }
+    Ok(())
+}
+
+struct RenderOptions<'a> {
+    max_dimension: Option<u32>,
+    format: OutputFormat,
+    quality: Option<u8>,
+    auto_enhance: bool,
+    watermark: Option<&'a Watermark>,
+}
+
+/// Decodes `source`, downscales it to `max_dimension`, enhances it, stamps
+/// the watermark and encodes it as `format`.
+fn render_copy(
+    source: &Path,
+    options: &RenderOptions,
+    settings: &Settings,
+) -> Result<Vec<u8>, String> {
+    decode_image(source, &settings.decode_limits, |image| {
+        let (width, height) = image.dimensions();
+        let image = match options.max_dimension.map(|value| value.max(1)) {
+            Some(max_dimension) if width > max_dimension || height > max_dimension => {
+                image.resize(max_dimension, max_dimension, FilterType::Lanczos3)
+            }
+            _ => image,
+        };
+        // After downscaling, so the histograms come from fewer pixels.
+        let image = if options.auto_enhance {
+            auto_enhance(image)
+        } else {
+            image
+        };
+        let image = match options.watermark {
+            Some(watermark) => watermark.apply(image),
+            None => image,
+        };
+        let quality = options
+            .quality
+            .unwrap_or(FULL_IMAGE_JPEG_QUALITY)
+            .clamp(1, 100);
+        encode_image(&image, options.format, quality)
+            .map_err(|err| format!("Failed to encode image {}: {err}", source.display()))
+    })?
+}
+
+fn converted_file_name(source: &Path, format: OutputFormat) -> Result<OsString, String> {
+    let mut file_name = source
+        .file_stem()
+        .ok_or_else(|| format!("{} has no file name.", source.display()))?
+        .to_os_string();
+    file_name.push(".");
+    file_name.push(format.extension());
+    Ok(file_name)
+}
+
+/// Archive entry names must be unique; later duplicates become `name (2).jpg`.
+fn unique_entry_name(taken: &mut HashSet<String>, file_name: &OsString) -> String {
+    let file_name = Path::new(file_name);
+    let stem = file_name
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = file_name
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let mut candidate = format!("{stem}{extension}");
+    let mut counter = 2;
+    while !taken.insert(candidate.to_lowercase()) {
+        candidate = format!("{stem} ({counter}){extension}");
+        counter += 1;
+    }
+    candidate
+}
+
+pub(crate) fn emit_progress(app: &tauri::AppHandle, current: usize, total: usize, path: &Path) {
+    let progress = ExportProgress {
+        current,
+        total,
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "image".to_string()),
+    };
+    if let Err(err) = app.emit("export-progress", &progress) {
+        log::warn!("Failed to emit export progress: {}", err);
+    }
+}
@@ -0,0 +1,396 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
+};
+
+use image::{imageops::FilterType, DynamicImage, GenericImageView};
+use rusqlite::{params, Connection, OptionalExtension};
+use rustface::{Detector, ImageData, Model};
+use serde::Serialize;
+use tauri::Manager;
+use thumbnailer_core::{cache_path, decode_image, encode_image, extended_path};
+
+use crate::{
+    open_cache_db, resolve_data_dir, settings::Settings, video, AppState, PAUSE_POLL_INTERVAL,
+};
+
+/// SeetaFace frontal face model bundled under the app's resources.
+const MODEL_RESOURCE: &str = "models/seeta_fd_frontal_v1.0.bin";
+/// Images are searched at this size; faces smaller than the model's
+/// 20 pixel window at this scale are missed, which suits browsing.
+const DETECTION_MAX_DIMENSION: u32 = 1024;
+/// Detections scoring below this are dropped; higher means fewer false
+/// positives and more missed faces.
+const SCORE_THRESHOLD: f64 = 2.0;
+/// Space kept around a face in its crop, as a share of the face's size.
+const FACE_CROP_MARGIN: f32 = 0.4;
+
+static MODEL: OnceLock<Option<Model>> = OnceLock::new();
+
+/// A face found in an image, in the decoded image's pixels.
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FaceRegion {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Looks for faces in the open folder's images on one background thread,
+/// when the settings opt in. Like the subfolder prefetch, any gallery load
+/// stops it, and it waits while generation is paused.
+#[derive(Default)]
+pub(crate) struct FaceScanner {
+    cancel_current: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+impl FaceScanner {
+    pub(crate) fn cancel(&self) {
+        if let Some(cancel) = self
+            .cancel_current
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+        {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn start(
+        &self,
+        app: &tauri::AppHandle,
+        paths: Vec<PathBuf>,
+        settings: Settings,
+        generation_paused: Arc<AtomicBool>,
+    ) {
+        self.cancel();
+        if !settings.detect_faces {
+            return;
+        }
+        let data_dir = match resolve_data_dir(app) {
+            Ok(value) => value,
+            Err(err) => {
+                log::warn!("Failed to start face detection: {}", err);
+                return;
+            }
+        };
+        let resource_dir = app.path().resource_dir();
+        let cancel = Arc::new(AtomicBool::new(false));
+        *self
+            .cancel_current
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = Some(cancel.clone());
+        let spawned = thread::Builder::new()
+            .name("face-detection".to_string())
+            .spawn(move || {
+                let model = MODEL.get_or_init(|| {
+                    let model = resource_dir
+                        .map_err(|err| format!("Failed to resolve resource path: {err}"))
+                        .and_then(|resource_dir| load_model(&resource_dir));
+                    match model {
+                        Ok(value) => Some(value),
+                        Err(err) => {
+                            log::warn!("Face detection is unavailable: {}", err);
+                            None
+                        }
+                    }
+                });
+                let Some(model) = model else {
+                    return;
+                };
+                let should_stop = || {
+                    while generation_paused.load(Ordering::Relaxed)
+                        && !cancel.load(Ordering::Relaxed)
+                    {
+                        thread::sleep(PAUSE_POLL_INTERVAL);
+                    }
+                    cancel.load(Ordering::Relaxed)
+                };
+                if let Err(err) = scan(&data_dir, model, &paths, &settings, should_stop) {
+                    log::warn!("Failed to detect faces: {}", err);
+                }
+            });
+        if let Err(err) = spawned {
+            log::warn!("Failed to start face detection: {}", err);
+        }
+    }
+}
+
+fn load_model(resource_dir: &Path) -> Result<Model, String> {
+    let path = resource_dir.join(MODEL_RESOURCE);
+    let bytes =
+        fs::read(&path).map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+    rustface::read_model(bytes.as_slice())
+        .map_err(|err| format!("Failed to load {}: {err}", path.display()))
+}
+
+fn scan(
+    data_dir: &Path,
+    model: &Model,
+    paths: &[PathBuf],
+    settings: &Settings,
+    should_stop: impl Fn() -> bool,
+) -> Result<(), String> {
+    let connection = open_cache_db(data_dir)?;
+    let mut detector = rustface::create_detector_with_model(model.clone());
+    detector.set_min_face_size(20);
+    detector.set_score_thresh(SCORE_THRESHOLD);
+    detector.set_pyramid_scale_factor(0.8);
+    detector.set_slide_window_step(4, 4);
+    for path in paths {
+        if should_stop() {
+            return Ok(());
+        }
+        if video::is_video(path) || !settings.cloud_files.allows_generation(path) {
+            continue;
+        }
+        let modified_unix = match modified_unix(path) {
+            Ok(value) => value,
+            Err(err) => {
+                log::debug!("Skipping face detection: {}", err);
+                continue;
+            }
+        };
+        let scanned: Option<i64> = connection
+            .query_row(
+                "SELECT 1 FROM face_scans WHERE path = ?1 AND modified_unix = ?2",
+                params![cache_path(path), modified_unix],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|err| format!("Failed to read face scan: {err}"))?;
+        if scanned.is_some() {
+            continue;
+        }
+        match decode_image(path, &settings.decode_limits, |image| {
+            detect(detector.as_mut(), &image)
+        }) {
+            Ok(regions) => store(&connection, path, modified_unix, &regions)?,
+            Err(err) => log::debug!("Skipping face detection: {}", err),
+        }
+    }
+    Ok(())
+}
+
+fn modified_unix(path: &Path) -> Result<i64, String> {
+    let modified = fs::metadata(extended_path(path))
+        .and_then(|metadata| metadata.modified())
+        .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+    Ok(modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default())
+}
+
+/// Searches a grayscale copy no larger than `DETECTION_MAX_DIMENSION`, and
+/// scales what it finds back to the image's own size.
+fn detect(detector: &mut dyn Detector, image: &DynamicImage) -> Vec<FaceRegion> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let small = image
+        .resize(
+            DETECTION_MAX_DIMENSION,
+            DETECTION_MAX_DIMENSION,
+            FilterType::Triangle,
+        )
+        .to_luma8();
+    let scale = width as f32 / small.width() as f32;
+    detector
+        .detect(&ImageData::new(
+            small.as_raw(),
+            small.width(),
+            small.height(),
+        ))
+        .iter()
+        .filter_map(|face| {
+            let bbox = face.bbox();
+            let x = (bbox.x().max(0) as f32 * scale) as u32;
+            let y = (bbox.y().max(0) as f32 * scale) as u32;
+            if x >= width || y >= height {
+                return None;
+            }
+            Some(FaceRegion {
+                x,
+                y,
+                width: ((bbox.width() as f32 * scale) as u32).min(width - x),
+                height: ((bbox.height() as f32 * scale) as u32).min(height - y),
+            })
+        })
+        .collect()
+}
+
+fn store(
+    connection: &Connection,
+    path: &Path,
+    modified_unix: i64,
+    regions: &[FaceRegion],
+) -> Result<(), String> {
+    let path = cache_path(path);
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO face_scans (path, modified_unix, face_count)
+             VALUES (?1, ?2, ?3)",
+            params![path, modified_unix, regions.len() as i64],
+        )
+        .and_then(|_| connection.execute("DELETE FROM face_regions WHERE path = ?1", params![path]))
+        .map_err(|err| format!("Failed to store face scan: {err}"))?;
+    for region in regions {
+        connection
+            .execute(
+                "INSERT INTO face_regions (path, x, y, width, height) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![path, region.x, region.y, region.width, region.height],
+            )
+            .map_err(|err| format!("Failed to store face region: {err}"))?;
+    }
+    Ok(())
+}
+
+fn regions(connection: &Connection, path: &Path) -> Result<Vec<FaceRegion>, String> {
+    let mut statement = connection
+        .prepare("SELECT x, y, width, height FROM face_regions WHERE path = ?1 ORDER BY rowid")
+        .map_err(|err| format!("Failed to read face regions: {err}"))?;
+    let regions = statement
+        .query_map(params![cache_path(path)], |row| {
+            Ok(FaceRegion {
+                x: row.get(0)?,
+                y: row.get(1)?,
+                width: row.get(2)?,
+                height: row.get(3)?,
+            })
+        })
+        .and_then(|rows| rows.collect::<Result<Vec<FaceRegion>, _>>())
+        .map_err(|err| format!("Failed to read face regions: {err}"));
+    regions
+}
+
+pub(crate) fn relocate(
+    connection: &Connection,
+    source: &Path,
+    target: &Path,
+) -> Result<(), String> {
+    let (source, target) = (cache_path(source), cache_path(target));
+    connection
+        .execute(
+            "UPDATE OR REPLACE face_scans SET path = ?1 WHERE path = ?2",
+            params![target, source],
+        )
+        .and_then(|_| {
+            connection.execute(
+                "UPDATE face_regions SET path = ?1 WHERE path = ?2",
+                params![target, source],
+            )
+        })
+        .map(|_| ())
+        .map_err(|err| format!("Failed to move face regions: {err}"))
+}
+
+/// The subset of `paths` in which the background pass found at least one
+/// face, in their given order: the "photos with people" filter. Images not
+/// scanned yet are left out.
+#[tauri::command]
+pub(crate) async fn filter_with_faces(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    paths: Vec<String>,
+) -> Result<Vec<String>, String> {
+    state.path_scope.check_all(&paths)?;
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        let mut statement = connection
+            .prepare("SELECT face_count FROM face_scans WHERE path = ?1")
+            .map_err(|err| format!("Failed to read face scans: {err}"))?;
+        let mut with_faces = Vec::new();
+        for path in paths {
+            let face_count: Option<i64> = statement
+                .query_row(params![cache_path(Path::new(&path))], |row| row.get(0))
+                .optional()
+                .map_err(|err| format!("Failed to read face scan: {err}"))?;
+            if face_count.unwrap_or(0) > 0 {
+                with_faces.push(path);
+            }
+        }
+        Ok(with_faces)
+    })
+    .await
+    .map_err(|err| format!("Failed to join face filter task: {err}"))?
+}
+
+/// The faces found in `path`, empty until the background pass reaches it.
+#[tauri::command]
+pub(crate) async fn get_face_regions(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+) -> Result<Vec<FaceRegion>, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        regions(&open_cache_db(&data_dir)?, Path::new(&path))
+    })
+    .await
+    .map_err(|err| format!("Failed to join face regions task: {err}"))?
+}
+
+/// A square thumbnail of face `index` of `path`, with some margin, encoded
+/// like gallery thumbnails and returned as a raw binary IPC payload.
+#[tauri::command]
+pub(crate) async fn load_face_thumbnail(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+    index: usize,
+    thumbnail_size: Option<u32>,
+) -> Result<tauri::ipc::Response, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let data_dir = resolve_data_dir(&app)?;
+    let settings = state.settings.get();
+    let thumbnail_size = thumbnail_size.unwrap_or(settings.thumbnail_size).max(1);
+    let bytes = tauri::async_runtime::spawn_blocking(move || {
+        let path = PathBuf::from(path);
+        let region = regions(&open_cache_db(&data_dir)?, &path)?
+            .get(index)
+            .copied()
+            .ok_or_else(|| format!("{} has no face {index}.", path.display()))?;
+        decode_image(&path, &settings.decode_limits, |image| {
+            let (x, y, side) = face_crop(region, image.dimensions());
+            let face = image.crop_imm(x, y, side, side).resize_exact(
+                thumbnail_size,
+                thumbnail_size,
+                FilterType::Lanczos3,
+            );
+            encode_image(&face, settings.thumbnail_format, settings.thumbnail_quality)
+                .map_err(|err| format!("Failed to encode face thumbnail: {err}"))
+        })?
+    })
+    .await
+    .map_err(|err| format!("Failed to join face thumbnail task: {err}"))??;
+    Ok(tauri::ipc::Response::new(bytes))
+}
+
+/// A square around `region` grown by `FACE_CROP_MARGIN` on each side,
+/// shifted and shrunk as needed to stay inside the image.
+fn face_crop(region: FaceRegion, (width, height): (u32, u32)) -> (u32, u32, u32) {
+    let face_side = region.width.max(region.height) as f32;
+    let side = ((face_side * (1.0 + 2.0 * FACE_CROP_MARGIN)) as u32)
+        .min(width)
+        .min(height)
+        .max(1);
+    let center_x = region.x + region.width / 2;
+    let center_y = region.y + region.height / 2;
+    let x = center_x
+        .saturating_sub(side / 2)
+        .min(width.saturating_sub(side));
+    let y = center_y
+        .saturating_sub(side / 2)
+        .min(height.saturating_sub(side));
+    (x, y, side)
+}
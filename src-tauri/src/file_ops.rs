@@ -0,0 +1,835 @@
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use thumbnailer_core::{cache_key_for_path, cache_path, extended_path, last_modified_unix};
+
+use crate::{
+    broken, captions, capture_dates, dimensions, edit, exif_info, faces, library, now_unix,
+    open_cache_db, places, resolve_data_dir, verify, video, AppState,
+};
+
+const OPERATION_COPY: &str = "copy";
+const OPERATION_DELETE: &str = "delete";
+const OPERATION_MOVE: &str = "move";
+const OPERATION_RENAME: &str = "rename";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FileOperationResult {
+    path: String,
+    new_path: Option<String>,
+    error: Option<String>,
+    /// How a name already taken in the destination was dealt with.
+    conflict: Option<ConflictOutcome>,
+    /// Another file the operation sent to the trash, journaled so undo
+    /// brings it back: the one `ConflictPolicy::Overwrite` replaced, or a
+    /// deleted image's kept original.
+    #[serde(skip)]
+    replaced: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FileOperationSummary {
+    succeeded: usize,
+    /// Left alone under `ConflictPolicy::Skip`; not counted as succeeded.
+    skipped: usize,
+    failed: usize,
+    results: Vec<FileOperationResult>,
+}
+
+impl FileOperationSummary {
+    pub(crate) fn from_results(results: Vec<FileOperationResult>) -> Self {
+        let skipped = results
+            .iter()
+            .filter(|result| result.conflict == Some(ConflictOutcome::Skipped))
+            .count();
+        let succeeded = results
+            .iter()
+            .filter(|result| result.error.is_none())
+            .count()
+            - skipped;
+        Self {
+            succeeded,
+            skipped,
+            failed: results.len() - succeeded - skipped,
+            results,
+        }
+    }
+
+    /// The files the operation went through with, and where each one ended
+    /// up if it wasn't deleted.
+    pub(crate) fn done(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.results
+            .iter()
+            .filter(|result| {
+                result.error.is_none() && result.conflict != Some(ConflictOutcome::Skipped)
+            })
+            .map(|result| (result.path.as_str(), result.new_path.as_deref()))
+    }
+}
+
+/// What a move or copy does when the destination already has a file by
+/// the same name.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum ConflictPolicy {
+    /// Leave both files alone.
+    Skip,
+    /// Send the file in the destination to the trash, then replace it.
+    Overwrite,
+    /// Use the first free `name (2).jpg`, `name (3).jpg`, ...
+    AutoNumber,
+    /// Fail the file with `ConflictOutcome::Unresolved`, so the user can be
+    /// asked and the batch rerun for those files with their choice.
+    #[default]
+    Prompt,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum ConflictOutcome {
+    Skipped,
+    Overwritten,
+    Renamed,
+    Unresolved,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UndoSummary {
+    kind: String,
+    restored: usize,
+    failed: usize,
+    results: Vec<FileOperationResult>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TrashedItem {
+    /// Where the file was deleted from, and where it is restored to.
+    path: String,
+    name: String,
+    deleted_unix: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MtimeSyncResult {
+    path: String,
+    previous_modified_unix: Option<i64>,
+    capture_unix: Option<i64>,
+    changed: bool,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MtimeSyncSummary {
+    dry_run: bool,
+    changed: usize,
+    unchanged: usize,
+    failed: usize,
+    results: Vec<MtimeSyncResult>,
+}
+
+struct JournalEntry {
+    rowid: i64,
+    source_path: String,
+    target_path: Option<String>,
+}
+
+/// Without `paths`, deletes the selection; the same goes for moving and
+/// copying.
+#[tauri::command]
+pub(crate) async fn delete_files(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    paths: Option<Vec<String>>,
+) -> Result<FileOperationSummary, String> {
+    let paths = state.selection.or_selected(paths)?;
+    state.path_scope.check_all(&paths)?;
+    let data_dir = resolve_data_dir(&app)?;
+    let summary = tauri::async_runtime::spawn_blocking(move || {
+        trash_paths(&open_cache_db(&data_dir)?, paths)
+    })
+    .await
+    .map_err(|err| format!("Failed to join delete task: {err}"))??;
+    state.selection.follow(&summary);
+    Ok(summary)
+}
+
+/// Sends `paths` to the trash as one undoable operation.
+pub(crate) fn trash_paths(
+    connection: &Connection,
+    paths: Vec<String>,
+) -> Result<FileOperationSummary, String> {
+    let results = paths
+        .into_iter()
+        .map(|path| match trash::delete(&path) {
+            Ok(()) => {
+                let mut result = success(path, None);
+                match edit::trash_original(connection, Path::new(&result.path)) {
+                    Ok(original) => result.replaced = original,
+                    Err(err) => log::warn!("{}", err),
+                }
+                result
+            }
+            Err(err) => failure(path, format!("Failed to move to trash: {err}")),
+        })
+        .collect();
+    record_operation(connection, OPERATION_DELETE, results)
+}
+
+#[tauri::command]
+pub(crate) async fn move_files(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    paths: Option<Vec<String>>,
+    destination: String,
+    conflict: Option<ConflictPolicy>,
+) -> Result<FileOperationSummary, String> {
+    let paths = state.selection.or_selected(paths)?;
+    state.path_scope.check_all(&paths)?;
+    state.path_scope.check(Path::new(&destination))?;
+    let data_dir = resolve_data_dir(&app)?;
+    let summary = tauri::async_runtime::spawn_blocking(move || {
+        move_paths(
+            &open_cache_db(&data_dir)?,
+            paths,
+            Path::new(&destination),
+            conflict.unwrap_or_default(),
+        )
+    })
+    .await
+    .map_err(|err| format!("Failed to join move task: {err}"))??;
+    state.selection.follow(&summary);
+    Ok(summary)
+}
+
+/// Moves `paths` into `destination` as one undoable operation, carrying
+/// their thumbnails and library entries along.
+pub(crate) fn move_paths(
+    connection: &Connection,
+    paths: Vec<String>,
+    destination: &Path,
+    policy: ConflictPolicy,
+) -> Result<FileOperationSummary, String> {
+    let results = transfer(paths, destination, policy, |source, target| {
+        relocate(connection, source, target)
+    })?;
+    record_operation(connection, OPERATION_MOVE, results)
+}
+
+/// Copies files into `destination`. The copies start without thumbnails,
+/// ratings or tags; undoing sends them to the trash.
+#[tauri::command]
+pub(crate) async fn copy_files(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    paths: Option<Vec<String>>,
+    destination: String,
+    conflict: Option<ConflictPolicy>,
+) -> Result<FileOperationSummary, String> {
+    let paths = state.selection.or_selected(paths)?;
+    state.path_scope.check_all(&paths)?;
+    state.path_scope.check(Path::new(&destination))?;
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        let results = transfer(
+            paths,
+            Path::new(&destination),
+            conflict.unwrap_or_default(),
+            |source, target| {
+                if !source.is_file() {
+                    return Err(format!("{} is not a file.", source.display()));
+                }
+                fs::copy(extended_path(source), extended_path(target))
+                    .map(|_| ())
+                    .map_err(|err| format!("Failed to copy {}: {err}", source.display()))
+            },
+        )?;
+        record_operation(&connection, OPERATION_COPY, results)
+    })
+    .await
+    .map_err(|err| format!("Failed to join copy task: {err}"))?
+}
+
+#[tauri::command]
+pub(crate) async fn rename_file(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+    new_name: String,
+) -> Result<FileOperationSummary, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let data_dir = resolve_data_dir(&app)?;
+    let summary = tauri::async_runtime::spawn_blocking(move || {
+        let trimmed = new_name.trim();
+        if trimmed.is_empty() || trimmed.contains(['/', '\\']) {
+            return Err(format!("{new_name:?} is not a valid file name."));
+        }
+        let source = PathBuf::from(&path);
+        let target = source.with_file_name(trimmed);
+        let connection = open_cache_db(&data_dir)?;
+        let result = match relocate(&connection, &source, &target) {
+            Ok(()) => success(path, Some(target)),
+            Err(err) => failure(path, err),
+        };
+        record_operation(&connection, OPERATION_RENAME, vec![result])
+    })
+    .await
+    .map_err(|err| format!("Failed to join rename task: {err}"))??;
+    state.selection.follow(&summary);
+    Ok(summary)
+}
+
+/// Reverts the most recent delete, move, copy, or rename that has not been
+/// undone yet. Returns `None` when the journal is empty.
+#[tauri::command]
+pub(crate) async fn undo_last_operation(
+    app: tauri::AppHandle,
+) -> Result<Option<UndoSummary>, String> {
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        undo_last_operation_blocking(&connection)
+    })
+    .await
+    .map_err(|err| format!("Failed to join undo task: {err}"))?
+}
+
+/// Images in the trash, newest deletion first, whether or not this app
+/// deleted them. macOS offers no way to enumerate the trash, so this fails
+/// there; `restore_from_trash` still works for paths the app knows.
+#[tauri::command]
+pub(crate) async fn list_trashed_items(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<TrashedItem>, String> {
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut items: Vec<TrashedItem> = trashed_items()?
+            .into_iter()
+            .filter(|item| settings.scan.is_supported_image(&item.original_path()))
+            .map(|item| TrashedItem {
+                path: item.original_path().to_string_lossy().to_string(),
+                name: item.name.to_string_lossy().to_string(),
+                deleted_unix: item.time_deleted,
+            })
+            .collect();
+        items.sort_by_key(|item| std::cmp::Reverse(item.deleted_unix));
+        Ok(items)
+    })
+    .await
+    .map_err(|err| format!("Failed to join trash listing task: {err}"))?
+}
+
+/// Puts the most recently trashed file at each of `paths` back where it
+/// was. Like every other file operation, only paths in folders opened this
+/// session can be restored.
+#[tauri::command]
+pub(crate) async fn restore_from_trash(
+    state: tauri::State<'_, AppState>,
+    paths: Vec<String>,
+) -> Result<FileOperationSummary, String> {
+    state.path_scope.check_all(&paths)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let results = paths
+            .into_iter()
+            .map(|path| match restore_trashed(Path::new(&path), None, None) {
+                Ok(()) => success(path, None),
+                Err(err) => failure(path, err),
+            })
+            .collect();
+        Ok(FileOperationSummary::from_results(results))
+    })
+    .await
+    .map_err(|err| format!("Failed to join restore task: {err}"))?
+}
+
+/// Sets each file's modified time to its EXIF capture date. With `dry_run`
+/// the report is produced without touching any file.
+#[tauri::command]
+pub(crate) async fn sync_mtime_from_exif(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    paths: Vec<String>,
+    dry_run: Option<bool>,
+) -> Result<MtimeSyncSummary, String> {
+    state.path_scope.check_all(&paths)?;
+    let data_dir = resolve_data_dir(&app)?;
+    let dry_run = dry_run.unwrap_or(false);
+    tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        let results: Vec<MtimeSyncResult> = paths
+            .into_iter()
+            .map(|path| sync_mtime_from_exif_single(&connection, path, dry_run))
+            .collect();
+        let failed = results
+            .iter()
+            .filter(|result| result.error.is_some())
+            .count();
+        let changed = results.iter().filter(|result| result.changed).count();
+        Ok(MtimeSyncSummary {
+            dry_run,
+            changed,
+            unchanged: results.len() - changed - failed,
+            failed,
+            results,
+        })
+    })
+    .await
+    .map_err(|err| format!("Failed to join timestamp sync task: {err}"))?
+}
+
+fn sync_mtime_from_exif_single(
+    connection: &Connection,
+    path: String,
+    dry_run: bool,
+) -> MtimeSyncResult {
+    let image_path = PathBuf::from(&path);
+    let mut result = MtimeSyncResult {
+        path,
+        previous_modified_unix: None,
+        capture_unix: None,
+        changed: false,
+        error: None,
+    };
+    let previous = match last_modified_unix(&image_path) {
+        Ok(value) => value,
+        Err(err) => {
+            result.error = Some(err);
+            return result;
+        }
+    };
+    result.previous_modified_unix = Some(previous);
+
+    let Some(capture) = exif_info::read_exif(&image_path)
+        .as_ref()
+        .and_then(exif_info::capture_time_unix)
+    else {
+        result.error = Some("No EXIF capture date.".to_string());
+        return result;
+    };
+    result.capture_unix = Some(capture);
+    if capture == previous {
+        return result;
+    }
+    let Ok(capture_secs) = u64::try_from(capture) else {
+        result.error = Some("EXIF capture date is before 1970.".to_string());
+        return result;
+    };
+
+    if !dry_run {
+        let updated = fs::File::options()
+            .write(true)
+            .open(extended_path(&image_path))
+            .and_then(|file| file.set_modified(UNIX_EPOCH + Duration::from_secs(capture_secs)));
+        if let Err(err) = updated {
+            result.error = Some(format!("Failed to set modified time: {err}"));
+            return result;
+        }
+        // The image itself did not change, so keep its cached thumbnail valid.
+        if let Err(err) = connection.execute(
+            "UPDATE thumbnails SET source_modified_unix = ?1
+             WHERE cache_key = ?2 AND source_modified_unix = ?3",
+            params![capture, cache_key_for_path(&image_path), previous],
+        ) {
+            log::warn!(
+                "Failed to update cache entry for {}: {}",
+                image_path.display(),
+                err
+            );
+        }
+    }
+    result.changed = true;
+    result
+}
+
+fn undo_last_operation_blocking(connection: &Connection) -> Result<Option<UndoSummary>, String> {
+    let operation: Option<(i64, String, i64)> = connection
+        .query_row(
+            "SELECT id, kind, created_unix
+             FROM undo_operations
+             WHERE undone = 0
+             ORDER BY id DESC
+             LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read undo journal: {err}"))?;
+    let Some((operation_id, kind, created_unix)) = operation else {
+        return Ok(None);
+    };
+
+    let mut statement = connection
+        .prepare(
+            "SELECT rowid, source_path, target_path
+             FROM undo_entries
+             WHERE operation_id = ?1
+             ORDER BY rowid",
+        )
+        .map_err(|err| format!("Failed to read undo journal: {err}"))?;
+    // In journal order, so a moved file leaves the name it took before the
+    // file it replaced comes back from the trash.
+    let entries = statement
+        .query_map(params![operation_id], |row| {
+            Ok(JournalEntry {
+                rowid: row.get(0)?,
+                source_path: row.get(1)?,
+                target_path: row.get(2)?,
+            })
+        })
+        .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| format!("Failed to read undo journal: {err}"))?;
+    // Undoing an overwriting copy trashes the copy before the file it
+    // replaced is restored, and both went in under the same path.
+    let trashed_before = trashed_items().ok().map(|items| {
+        items
+            .into_iter()
+            .map(|item| item.id)
+            .collect::<HashSet<_>>()
+    });
+
+    let results: Vec<FileOperationResult> = entries
+        .into_iter()
+        .map(|entry| {
+            let original = PathBuf::from(&entry.source_path);
+            let outcome = match entry.target_path.as_deref() {
+                Some(target) if kind == OPERATION_COPY => trash::delete(target)
+                    .map_err(|err| format!("Failed to move {target} to trash: {err}")),
+                Some(target) if kind != OPERATION_DELETE => {
+                    relocate(connection, Path::new(target), &original)
+                }
+                _ => restore_trashed(&original, Some(created_unix), trashed_before.as_ref()),
+            };
+            if outcome.is_ok() {
+                if let Err(err) = connection.execute(
+                    "DELETE FROM undo_entries WHERE rowid = ?1",
+                    params![entry.rowid],
+                ) {
+                    log::warn!("Failed to update undo journal: {}", err);
+                }
+            }
+            match outcome {
+                Ok(()) => success(entry.source_path, None),
+                Err(err) => failure(entry.source_path, err),
+            }
+        })
+        .collect();
+
+    // Entries that failed stay journaled, so undoing again retries them.
+    if results.iter().all(|result| result.error.is_none()) {
+        connection
+            .execute(
+                "UPDATE undo_operations SET undone = 1 WHERE id = ?1",
+                params![operation_id],
+            )
+            .map_err(|err| format!("Failed to update undo journal: {err}"))?;
+    }
+
+    let restored = results
+        .iter()
+        .filter(|result| result.error.is_none())
+        .count();
+    Ok(Some(UndoSummary {
+        kind,
+        restored,
+        failed: results.len() - restored,
+        results,
+    }))
+}
+
+/// Journals the successful part of a batch as a single undoable operation.
+fn record_operation(
+    connection: &Connection,
+    kind: &str,
+    results: Vec<FileOperationResult>,
+) -> Result<FileOperationSummary, String> {
+    let summary = FileOperationSummary::from_results(results);
+    if summary.succeeded > 0 {
+        connection
+            .execute(
+                "INSERT INTO undo_operations (kind, created_unix) VALUES (?1, ?2)",
+                params![kind, now_unix()],
+            )
+            .map_err(|err| format!("Failed to write undo journal: {err}"))?;
+        let operation_id = connection.last_insert_rowid();
+        for result in summary.results.iter().filter(|result| {
+            result.error.is_none() && result.conflict != Some(ConflictOutcome::Skipped)
+        }) {
+            connection
+                .execute(
+                    "INSERT INTO undo_entries (operation_id, source_path, target_path)
+                     VALUES (?1, ?2, ?3)",
+                    params![operation_id, result.path, result.new_path],
+                )
+                .map_err(|err| format!("Failed to write undo journal: {err}"))?;
+            // Without a target, undo restores it from the trash.
+            if let Some(replaced) = &result.replaced {
+                connection
+                    .execute(
+                        "INSERT INTO undo_entries (operation_id, source_path, target_path)
+                         VALUES (?1, ?2, NULL)",
+                        params![operation_id, replaced],
+                    )
+                    .map_err(|err| format!("Failed to write undo journal: {err}"))?;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Runs `op` from each path to its place in `destination`, settling name
+/// conflicts by `policy` first.
+fn transfer(
+    paths: Vec<String>,
+    destination: &Path,
+    policy: ConflictPolicy,
+    op: impl Fn(&Path, &Path) -> Result<(), String>,
+) -> Result<Vec<FileOperationResult>, String> {
+    if !destination.is_dir() {
+        return Err(format!(
+            "{} is not a valid directory.",
+            destination.display()
+        ));
+    }
+    let results = paths
+        .into_iter()
+        .map(|path| {
+            let source = PathBuf::from(&path);
+            let Some(file_name) = source.file_name() else {
+                return failure(path, "Path has no file name.".to_string());
+            };
+            let mut target = destination.join(file_name);
+            let conflict = if extended_path(&target).exists() {
+                match policy {
+                    ConflictPolicy::Skip => {
+                        return with_conflict(success(path, None), ConflictOutcome::Skipped);
+                    }
+                    ConflictPolicy::Prompt => {
+                        let error = format!("{} already exists.", target.display());
+                        return with_conflict(failure(path, error), ConflictOutcome::Unresolved);
+                    }
+                    // Overwriting a file with itself would only trash it.
+                    ConflictPolicy::Overwrite if cache_path(&target) == cache_path(&source) => {
+                        return failure(path, "The file is already in that folder.".to_string());
+                    }
+                    ConflictPolicy::Overwrite => {
+                        if let Err(err) = trash::delete(&target) {
+                            let error =
+                                format!("Failed to move {} to trash: {err}", target.display());
+                            return failure(path, error);
+                        }
+                        Some(ConflictOutcome::Overwritten)
+                    }
+                    ConflictPolicy::AutoNumber => {
+                        target = numbered_target(&target);
+                        Some(ConflictOutcome::Renamed)
+                    }
+                }
+            } else {
+                None
+            };
+            let overwritten = conflict == Some(ConflictOutcome::Overwritten);
+            let result = match op(&source, &target) {
+                Ok(()) => FileOperationResult {
+                    replaced: overwritten.then(|| target.to_string_lossy().to_string()),
+                    ..success(path, Some(target))
+                },
+                Err(err) => {
+                    if overwritten {
+                        if let Err(err) = restore_trashed(&target, None, None) {
+                            log::warn!("Failed to put back overwritten file: {}", err);
+                        }
+                    }
+                    failure(path, err)
+                }
+            };
+            FileOperationResult { conflict, ..result }
+        })
+        .collect();
+    Ok(results)
+}
+
+/// `name (2).jpg` beside `taken`, or the first higher number that's free.
+pub(crate) fn numbered_target(taken: &Path) -> PathBuf {
+    let stem = taken
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = taken
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (2..)
+        .map(|counter| taken.with_file_name(format!("{stem} ({counter}){extension}")))
+        .find(|candidate| !extended_path(candidate).exists())
+        .unwrap_or_else(|| taken.to_path_buf())
+}
+
+fn with_conflict(result: FileOperationResult, outcome: ConflictOutcome) -> FileOperationResult {
+    FileOperationResult {
+        conflict: Some(outcome),
+        ..result
+    }
+}
+
+/// Moves `source` to `target` without overwriting, falling back to copy and
+/// delete across volumes, and carries the cached thumbnail along.
+fn relocate(connection: &Connection, source: &Path, target: &Path) -> Result<(), String> {
+    let (io_source, io_target) = (extended_path(source), extended_path(target));
+    if !io_source.exists() {
+        return Err(format!("{} does not exist.", source.display()));
+    }
+    if io_target.exists() {
+        return Err(format!("{} already exists.", target.display()));
+    }
+    if fs::rename(&io_source, &io_target).is_err() {
+        if !io_source.is_file() {
+            return Err(format!(
+                "Failed to move {} to {}.",
+                source.display(),
+                target.display()
+            ));
+        }
+        fs::copy(&io_source, &io_target)
+            .map_err(|err| format!("Failed to copy {}: {err}", source.display()))?;
+        fs::remove_file(&io_source)
+            .map_err(|err| format!("Failed to remove {}: {err}", source.display()))?;
+    }
+
+    if let Err(err) = connection.execute(
+        "UPDATE OR REPLACE thumbnails
+         SET cache_key = ?1, source_path = ?2, display_path = ?3
+         WHERE cache_key = ?4",
+        params![
+            cache_key_for_path(target),
+            cache_path(target),
+            target.to_string_lossy(),
+            cache_key_for_path(source)
+        ],
+    ) {
+        log::warn!(
+            "Failed to move cache entry for {}: {}",
+            source.display(),
+            err
+        );
+    }
+    if let Err(err) = library::relocate(connection, source, target) {
+        log::warn!("{}", err);
+    }
+    if let Err(err) = verify::relocate(connection, source, target) {
+        log::warn!("{}", err);
+    }
+    if let Err(err) = dimensions::relocate(connection, source, target) {
+        log::warn!("{}", err);
+    }
+    if let Err(err) = capture_dates::relocate(connection, source, target) {
+        log::warn!("{}", err);
+    }
+    if let Err(err) = video::relocate(connection, source, target) {
+        log::warn!("{}", err);
+    }
+    if let Err(err) = places::relocate(connection, source, target) {
+        log::warn!("{}", err);
+    }
+    if let Err(err) = faces::relocate(connection, source, target) {
+        log::warn!("{}", err);
+    }
+    if let Err(err) = captions::relocate(connection, source, target) {
+        log::warn!("{}", err);
+    }
+    if let Err(err) = broken::relocate(connection, source, target) {
+        log::warn!("{}", err);
+    }
+    if let Err(err) = edit::relocate(connection, source, target) {
+        log::warn!("{}", err);
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+fn trashed_items() -> Result<Vec<trash::TrashItem>, String> {
+    trash::os_limited::list().map_err(|err| format!("Failed to list the trash: {err}"))
+}
+
+#[cfg(target_os = "macos")]
+fn trashed_items() -> Result<Vec<trash::TrashItem>, String> {
+    Err("The trash can't be browsed on macOS.".to_string())
+}
+
+/// Several generations of the same path may be in the trash; the newest
+/// one is restored, and with `deleted_after_unix` only if it was deleted
+/// since. With `among`, only items with those ids are candidates.
+#[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+fn restore_trashed(
+    original: &Path,
+    deleted_after_unix: Option<i64>,
+    among: Option<&HashSet<OsString>>,
+) -> Result<(), String> {
+    let item = trashed_items()?
+        .into_iter()
+        .filter(|item| {
+            item.original_path() == original
+                && deleted_after_unix.map_or(true, |after| item.time_deleted >= after - 1)
+                && among.map_or(true, |ids| ids.contains(&item.id))
+        })
+        .max_by_key(|item| item.time_deleted)
+        .ok_or_else(|| format!("{} is no longer in the trash.", original.display()))?;
+    trash::os_limited::restore_all([item])
+        .map_err(|err| format!("Failed to restore {}: {err}", original.display()))
+}
+
+#[cfg(target_os = "macos")]
+fn restore_trashed(
+    original: &Path,
+    _deleted_after_unix: Option<i64>,
+    _among: Option<&HashSet<OsString>>,
+) -> Result<(), String> {
+    // macOS has no API to enumerate the trash; Finder keeps the file name
+    // unless it collides with an item already there, so an item trashed
+    // later under the same name is renamed and not picked up here.
+    let file_name = original
+        .file_name()
+        .ok_or_else(|| format!("{} has no file name.", original.display()))?;
+    let home = std::env::var_os("HOME").ok_or_else(|| "HOME is not set.".to_string())?;
+    let trashed = Path::new(&home).join(".Trash").join(file_name);
+    if !trashed.exists() {
+        return Err(format!("{} is no longer in the trash.", original.display()));
+    }
+    if original.exists() {
+        return Err(format!("{} already exists.", original.display()));
+    }
+    fs::rename(&trashed, original)
+        .map_err(|err| format!("Failed to restore {}: {err}", original.display()))
+}
+
+pub(crate) fn success(path: String, new_path: Option<PathBuf>) -> FileOperationResult {
+    FileOperationResult {
+        path,
+        new_path: new_path.map(|value| value.to_string_lossy().to_string()),
+        error: None,
+        conflict: None,
+        replaced: None,
+    }
+}
+
+pub(crate) fn failure(path: String, error: String) -> FileOperationResult {
+    FileOperationResult {
+        path,
+        new_path: None,
+        error: Some(error),
+        conflict: None,
+        replaced: None,
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use image::{imageops, DynamicImage, RgbaImage};
+use thumbnailer_core::{
+    cache_key_for_path, cache_path, content_hash, decode_image, extended_path, last_modified_unix,
+    GeneratedThumbnail, Generator, Scanner, ThumbnailCache,
+};
+
+use crate::{open_cache_db, protocol, resolve_data_dir, settings::Settings, AppState};
+
+/// File names (compared case-insensitively) used as a folder's cover as-is.
+const COVER_FILE_NAMES: [&str; 6] = [
+    "folder.jpg",
+    "folder.jpeg",
+    "folder.png",
+    "cover.jpg",
+    "cover.jpeg",
+    "cover.png",
+];
+
+/// Images tiled into a generated cover, two per row.
+const COLLAGE_IMAGES: usize = 4;
+
+/// Thumbnail URL for a folder tile: its `folder.jpg`/`cover.png` if there is
+/// one, otherwise a 2×2 collage of its first images. `None` when the folder
+/// has no images of its own.
+///
+/// Covers are cached under the folder's path. A detected cover file stays
+/// valid until that file changes; a collage until the folder's contents do.
+#[tauri::command]
+pub(crate) async fn get_folder_cover(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+    thumbnail_size: Option<u32>,
+) -> Result<Option<String>, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let data_dir = resolve_data_dir(&app)?;
+    let settings = state.settings.get();
+    let thumbnail_size = thumbnail_size.unwrap_or(settings.thumbnail_size);
+    tauri::async_runtime::spawn_blocking(move || {
+        folder_cover_blocking(&data_dir, PathBuf::from(path), thumbnail_size, &settings)
+    })
+    .await
+    .map_err(|err| format!("Failed to join folder cover task: {err}"))?
+}
+
+pub(crate) fn folder_cover_blocking(
+    data_dir: &Path,
+    folder: PathBuf,
+    thumbnail_size: u32,
+    settings: &Settings,
+) -> Result<Option<String>, String> {
+    if !extended_path(&folder).is_dir() {
+        return Err(format!("{} is not a valid directory.", folder.display()));
+    }
+    let cover_file = find_cover_file(&folder);
+    let modified_unix = last_modified_unix(cover_file.as_deref().unwrap_or(&folder))?;
+    let cache_key = cache_key_for_path(&folder);
+    let mut connection = open_cache_db(data_dir)?;
+    if let Some(hash) = connection.content_hash(&cache_key, modified_unix, thumbnail_size)? {
+        return Ok(Some(protocol::thumbnail_url(&cache_key, &hash)));
+    }
+
+    let generator = settings.generator(thumbnail_size);
+    let (blob, mime) = match cover_file {
+        Some(cover_file) => generator.generate(&cover_file)?,
+        None => {
+            let Some(collage) = collage(&folder, thumbnail_size, settings)? else {
+                return Ok(None);
+            };
+            generator
+                .image
+                .thumbnail_image(&DynamicImage::ImageRgba8(collage), &folder)?
+        }
+    };
+    let hash = content_hash(&blob);
+    connection.store(&[GeneratedThumbnail {
+        cache_key: cache_key.clone(),
+        source_path: cache_path(&folder),
+        display_path: folder.to_string_lossy().to_string(),
+        modified_unix,
+        pixel_size: thumbnail_size,
+        blob,
+        mime,
+    }])?;
+    connection.prune(settings.cache_max_bytes)?;
+    Ok(Some(protocol::thumbnail_url(&cache_key, &hash)))
+}
+
+fn find_cover_file(folder: &Path) -> Option<PathBuf> {
+    let entries = fs::read_dir(extended_path(folder)).ok()?;
+    entries.filter_map(Result::ok).find_map(|entry| {
+        let name = entry.file_name().to_string_lossy().to_ascii_lowercase();
+        let path = folder.join(entry.file_name());
+        (COVER_FILE_NAMES.contains(&name.as_str()) && extended_path(&path).is_file())
+            .then_some(path)
+    })
+}
+
+/// Crops the folder's first images (by name, not descending into
+/// subfolders) into square cells. Images that fail to decode are skipped.
+fn collage(folder: &Path, size: u32, settings: &Settings) -> Result<Option<RgbaImage>, String> {
+    let mut scan = settings.scan.clone();
+    scan.recursive_scan = false;
+    let mut image_paths = scan.scan(folder)?;
+    image_paths.sort_unstable();
+
+    let cell = (size / 2).max(1);
+    let tiles: Vec<RgbaImage> = image_paths
+        .iter()
+        .filter_map(|path| {
+            match decode_image(path, &settings.decode_limits, |image| {
+                image.resize_to_fill(cell, cell, imageops::FilterType::Triangle)
+            }) {
+                Ok(image) => Some(image),
+                Err(err) => {
+                    log::warn!("Skipping {} in folder cover: {}", path.display(), err);
+                    None
+                }
+            }
+        })
+        .take(COLLAGE_IMAGES)
+        .map(|image| image.to_rgba8())
+        .collect();
+    if tiles.is_empty() {
+        return Ok(None);
+    }
+
+    let mut collage = RgbaImage::new(cell * 2, cell * 2);
+    for (index, tile) in tiles.iter().enumerate() {
+        let (column, row) = (index as u32 % 2, index as u32 / 2);
+        imageops::overlay(
+            &mut collage,
+            tile,
+            i64::from(column * cell),
+            i64::from(row * cell),
+        );
+    }
+    Ok(Some(collage))
+}
@@ -0,0 +1,181 @@
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rayon::prelude::*;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thumbnailer_core::{cache_path, extended_path, last_modified_unix, Scanner};
+
+use crate::{exif_info, open_cache_db, resolve_data_dir, settings::Settings, AppState};
+
+/// How many of the biggest files the summary lists.
+const LARGEST_FILES: usize = 10;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LargeFile {
+    path: String,
+    size_bytes: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FolderStats {
+    image_count: usize,
+    total_bytes: u64,
+    /// Image count per lowercase file extension.
+    formats: BTreeMap<String, usize>,
+    /// Capture dates from EXIF, or modified times where there are none.
+    earliest_unix: Option<i64>,
+    latest_unix: Option<i64>,
+    largest_files: Vec<LargeFile>,
+}
+
+struct FileInfo {
+    path: PathBuf,
+    size_bytes: u64,
+    modified_unix: i64,
+}
+
+/// Summarizes the images under `path` (honoring the scan settings) for a
+/// folder header. The walk and file sizes are read every time; the summary
+/// itself, which needs every file's EXIF for its date range, is cached while
+/// no image was added, removed or changed.
+#[tauri::command]
+pub(crate) async fn get_folder_stats(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+) -> Result<FolderStats, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let data_dir = resolve_data_dir(&app)?;
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        folder_stats_blocking(&connection, &settings, PathBuf::from(path))
+    })
+    .await
+    .map_err(|err| format!("Failed to join folder stats task: {err}"))?
+}
+
+fn folder_stats_blocking(
+    connection: &Connection,
+    settings: &Settings,
+    folder: PathBuf,
+) -> Result<FolderStats, String> {
+    if !extended_path(&folder).is_dir() {
+        return Err(format!("{} is not a valid directory.", folder.display()));
+    }
+    let mut image_paths = settings.scan.scan(&folder)?;
+    image_paths.sort_unstable();
+    let files: Vec<FileInfo> = settings.install(|| {
+        image_paths
+            .into_par_iter()
+            .filter_map(|path| match file_info(path) {
+                Ok(info) => Some(info),
+                Err(err) => {
+                    log::warn!("Skipping image in folder stats: {}", err);
+                    None
+                }
+            })
+            .collect()
+    });
+
+    let fingerprint = fingerprint(&files, settings)?;
+    let cached: Option<String> = connection
+        .query_row(
+            "SELECT stats FROM folder_stats WHERE path = ?1 AND fingerprint = ?2",
+            params![cache_path(&folder), fingerprint],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read folder stats: {err}"))?;
+    if let Some(stats) = cached.and_then(|stats| serde_json::from_str(&stats).ok()) {
+        return Ok(stats);
+    }
+
+    let stats = summarize(&files, settings);
+    let encoded = serde_json::to_string(&stats)
+        .map_err(|err| format!("Failed to serialize folder stats: {err}"))?;
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO folder_stats (path, fingerprint, stats) VALUES (?1, ?2, ?3)",
+            params![cache_path(&folder), fingerprint, encoded],
+        )
+        .map_err(|err| format!("Failed to write folder stats: {err}"))?;
+    Ok(stats)
+}
+
+fn file_info(path: PathBuf) -> Result<FileInfo, String> {
+    let size_bytes = fs::metadata(extended_path(&path))
+        .map_err(|err| format!("Failed to read metadata for {}: {err}", path.display()))?
+        .len();
+    let modified_unix = last_modified_unix(&path)?;
+    Ok(FileInfo {
+        path,
+        size_bytes,
+        modified_unix,
+    })
+}
+
+/// Changes whenever an image is added, removed, resized or touched, or the
+/// scan settings change what counts as one.
+fn fingerprint(files: &[FileInfo], settings: &Settings) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    let scan = serde_json::to_string(&settings.scan)
+        .map_err(|err| format!("Failed to serialize scan settings: {err}"))?;
+    hasher.update(scan.as_bytes());
+    for file in files {
+        hasher.update(file.path.to_string_lossy().as_bytes());
+        hasher.update(file.size_bytes.to_le_bytes());
+        hasher.update(file.modified_unix.to_le_bytes());
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn summarize(files: &[FileInfo], settings: &Settings) -> FolderStats {
+    let dates: Vec<i64> = settings.install(|| {
+        files
+            .par_iter()
+            .map(|file| {
+                exif_info::read_exif(&file.path)
+                    .as_ref()
+                    .and_then(exif_info::capture_time_unix)
+                    .unwrap_or(file.modified_unix)
+            })
+            .collect()
+    });
+
+    let mut formats = BTreeMap::new();
+    for file in files {
+        let extension = file
+            .path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        *formats.entry(extension).or_insert(0) += 1;
+    }
+    let mut by_size: Vec<&FileInfo> = files.iter().collect();
+    by_size.sort_unstable_by_key(|file| Reverse(file.size_bytes));
+
+    FolderStats {
+        image_count: files.len(),
+        total_bytes: files.iter().map(|file| file.size_bytes).sum(),
+        formats,
+        earliest_unix: dates.iter().copied().min(),
+        latest_unix: dates.iter().copied().max(),
+        largest_files: by_size
+            .into_iter()
+            .take(LARGEST_FILES)
+            .map(|file| LargeFile {
+                path: file.path.to_string_lossy().to_string(),
+                size_bytes: file.size_bytes,
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde::Serialize;
+
+use crate::{
+    dimensions, open_cache_db, resolve_data_dir, AppState, GalleryItem, LoadGalleryResponse,
+};
+
+/// Loaded galleries kept for `get_items_range`; older ones are dropped.
+const MAX_SESSIONS: usize = 4;
+
+/// Finished gallery loads, so a frontend that virtualizes its grid can ask
+/// for the items on screen instead of holding the whole folder. Each is a
+/// snapshot, except that images the watchers refresh are updated in place.
+#[derive(Default)]
+pub(crate) struct GallerySessions {
+    next_id: AtomicU64,
+    sessions: Mutex<VecDeque<(u64, Arc<LoadGalleryResponse>)>>,
+}
+
+impl GallerySessions {
+    /// Keeps `response` and returns the id it can be fetched by.
+    pub(crate) fn insert(&self, response: &LoadGalleryResponse) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut sessions = self.sessions.lock().unwrap_or_else(|err| err.into_inner());
+        if sessions.len() == MAX_SESSIONS {
+            sessions.pop_front();
+        }
+        sessions.push_back((id, Arc::new(response.clone())));
+        id
+    }
+
+    /// Points every session listing `path` at its regenerated thumbnail.
+    pub(crate) fn refresh(&self, path: &str, modified_unix: i64, thumbnail: String) {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|err| err.into_inner());
+        for (_, response) in sessions.iter_mut() {
+            if !response.items.iter().any(|item| item.path == path) {
+                continue;
+            }
+            let response = Arc::make_mut(response);
+            for item in response.items.iter_mut().filter(|item| item.path == path) {
+                item.modified_unix = modified_unix;
+            }
+            response
+                .thumbnails
+                .insert(path.to_string(), thumbnail.clone());
+        }
+    }
+
+    fn get(&self, id: u64) -> Option<Arc<LoadGalleryResponse>> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .find(|(session_id, _)| *session_id == id)
+            .map(|(_, response)| response.clone())
+    }
+}
+
+/// A gallery item with everything the grid shows for it.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RangeItem {
+    #[serde(flatten)]
+    item: GalleryItem,
+    /// Absent for online-only files and images that failed to generate.
+    thumbnail: Option<String>,
+    /// Known once the image's header has been read.
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ItemsRange {
+    start: usize,
+    /// Items in the whole gallery.
+    total: usize,
+    items: Vec<RangeItem>,
+}
+
+/// Cuts a gallery response down to its first `window_size` items, for frontends
+/// that fetch the rest through `get_items_range`.
+pub(crate) fn windowed(
+    mut response: LoadGalleryResponse,
+    window_size: Option<usize>,
+) -> LoadGalleryResponse {
+    let Some(window_size) = window_size else {
+        return response;
+    };
+    response.items.truncate(window_size);
+    let kept: HashSet<&str> = response
+        .items
+        .iter()
+        .map(|item| item.path.as_str())
+        .collect();
+    response
+        .thumbnails
+        .retain(|path, _| kept.contains(path.as_str()));
+    response
+}
+
+/// Up to `count` items from `start` of the gallery `load_gallery` returned
+/// as `session_id`, in its order.
+#[tauri::command]
+pub(crate) async fn get_items_range(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    session_id: u64,
+    start: usize,
+    count: usize,
+) -> Result<ItemsRange, String> {
+    let response = state
+        .gallery_sessions
+        .get(session_id)
+        .ok_or_else(|| "The gallery is no longer loaded; load it again.".to_string())?;
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        let total = response.items.len();
+        let start = start.min(total);
+        let end = start.saturating_add(count).min(total);
+        let items = response.items[start..end]
+            .iter()
+            .map(|item| {
+                let size =
+                    dimensions::cached(&connection, Path::new(&item.path), item.modified_unix)?;
+                Ok(RangeItem {
+                    item: item.clone(),
+                    thumbnail: response.thumbnails.get(&item.path).cloned(),
+                    width: size.map(|(width, _)| width),
+                    height: size.map(|(_, height)| height),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(ItemsRange {
+            start,
+            total,
+            items,
+        })
+    })
+    .await
+    .map_err(|err| format!("Failed to join item range task: {err}"))?
+}
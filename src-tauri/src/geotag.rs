@@ -0,0 +1,220 @@
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use exif::{experimental::Writer, Context, Field, In, Rational, Tag, Value};
+use thumbnailer_core::extended_path;
+
+use crate::{
+    edit::write_replacing,
+    exif_info,
+    file_ops::{failure, success, FileOperationSummary},
+    xmp, AppState,
+};
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_EXIF_NAMESPACE: &str = "http://ns.adobe.com/exif/1.0/";
+/// Properties replaced in an existing sidecar.
+const XMP_GPS_PROPERTIES: &[&str] = &["exif:GPSVersionID", "exif:GPSLatitude", "exif:GPSLongitude"];
+/// Seconds are stored in ten-thousandths, about 3 mm at the equator.
+const SECONDS_DENOMINATOR: u32 = 10_000;
+
+/// Writes a GPS position into each file, so photos from cameras without GPS
+/// show up on maps. JPEGs get it in their EXIF; other formats, and JPEGs
+/// whose maker notes would break if their EXIF moved, get an XMP sidecar
+/// (`name.xmp`) instead, reported as the result's `newPath`.
+#[tauri::command]
+pub(crate) async fn set_geotag(
+    state: tauri::State<'_, AppState>,
+    paths: Vec<String>,
+    latitude: f64,
+    longitude: f64,
+) -> Result<FileOperationSummary, String> {
+    state.path_scope.check_all(&paths)?;
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return Err(format!("{latitude}, {longitude} is not a valid position."));
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let results = paths
+            .into_iter()
+            .map(|path| match geotag(Path::new(&path), latitude, longitude) {
+                Ok(sidecar) => success(path, sidecar),
+                Err(err) => failure(path, err),
+            })
+            .collect();
+        Ok(FileOperationSummary::from_results(results))
+    })
+    .await
+    .map_err(|err| format!("Failed to join geotag task: {err}"))?
+}
+
+/// Returns the sidecar written, if the file itself was left alone.
+fn geotag(path: &Path, latitude: f64, longitude: f64) -> Result<Option<PathBuf>, String> {
+    if !extended_path(path).is_file() {
+        return Err(format!("{} is not a file.", path.display()));
+    }
+    let is_jpeg = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_some_and(|extension| extension == "jpg" || extension == "jpeg");
+    if is_jpeg {
+        let bytes = fs::read(extended_path(path))
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        if let Some(tagged) = tag_jpeg(&bytes, latitude, longitude)? {
+            write_replacing(path, &tagged)?;
+            return Ok(None);
+        }
+    }
+    let sidecar = xmp::sidecar_path(path);
+    let existing = xmp::read_sidecar(path)?;
+    let xmp = tag_xmp(existing.as_deref(), latitude, longitude)?;
+    write_replacing(&sidecar, xmp.as_bytes())?;
+    Ok(Some(sidecar))
+}
+
+/// The JPEG with its EXIF rewritten around new GPS fields, or `None` when
+/// it carries a maker note, whose internal offsets would no longer match.
+fn tag_jpeg(bytes: &[u8], latitude: f64, longitude: f64) -> Result<Option<Vec<u8>>, String> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return Err("Not a JPEG file.".to_string());
+    }
+    // Segments up to the image data, as (start, end) including the marker.
+    let mut segments = Vec::new();
+    let mut position = 2;
+    while position + 4 <= bytes.len() && bytes[position] == 0xFF && bytes[position + 1] != 0xDA {
+        let length = u16::from_be_bytes([bytes[position + 2], bytes[position + 3]]) as usize;
+        let end = position + 2 + length;
+        if length < 2 || end > bytes.len() {
+            return Err("Truncated JPEG header.".to_string());
+        }
+        segments.push((position, end));
+        position = end;
+    }
+    let exif_segment = segments.iter().copied().find(|&(start, end)| {
+        bytes[start + 1] == 0xE1 && bytes[start + 4..end].starts_with(EXIF_HEADER)
+    });
+
+    let existing = match exif_segment {
+        Some((start, end)) => Some(
+            exif::Reader::new()
+                .read_raw(bytes[start + 4 + EXIF_HEADER.len()..end].to_vec())
+                .map_err(|err| format!("Failed to read EXIF: {err}"))?,
+        ),
+        None => None,
+    };
+    if let Some(exif) = &existing {
+        if exif.get_field(Tag::MakerNote, In::PRIMARY).is_some() {
+            return Ok(None);
+        }
+    }
+    let mut fields: Vec<Field> = existing
+        .iter()
+        .flat_map(|exif| exif.fields())
+        .filter(|field| field.tag.context() != Context::Gps)
+        .cloned()
+        .collect();
+    fields.extend(gps_fields(latitude, longitude));
+
+    let mut writer = Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+    if let Some(thumbnail) = existing.as_ref().and_then(exif_info::embedded_thumbnail) {
+        writer.set_jpeg(thumbnail, In::THUMBNAIL);
+    }
+    let little_endian = existing.as_ref().is_some_and(|exif| exif.little_endian());
+    let mut tiff = Cursor::new(Vec::new());
+    writer
+        .write(&mut tiff, little_endian)
+        .map_err(|err| format!("Failed to write EXIF: {err}"))?;
+    let tiff = tiff.into_inner();
+    let segment_length = u16::try_from(2 + EXIF_HEADER.len() + tiff.len())
+        .map_err(|_| "EXIF data is too large for a JPEG header.".to_string())?;
+
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend_from_slice(&segment_length.to_be_bytes());
+    segment.extend_from_slice(EXIF_HEADER);
+    segment.extend_from_slice(&tiff);
+    // Replace the old EXIF in place, or add it after a JFIF header.
+    let (insert_at, resume_at) = match exif_segment {
+        Some((start, end)) => (start, end),
+        None => match segments.first() {
+            Some(&(start, end)) if bytes[start + 1] == 0xE0 => (end, end),
+            _ => (2, 2),
+        },
+    };
+    let mut tagged = Vec::with_capacity(bytes.len() + segment.len());
+    tagged.extend_from_slice(&bytes[..insert_at]);
+    tagged.extend_from_slice(&segment);
+    tagged.extend_from_slice(&bytes[resume_at..]);
+    Ok(Some(tagged))
+}
+
+fn gps_fields(latitude: f64, longitude: f64) -> Vec<Field> {
+    let field = |tag, value| Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value,
+    };
+    let reference = |value: f64, positive: &[u8], negative: &[u8]| {
+        Value::Ascii(vec![if value < 0.0 { negative } else { positive }.to_vec()])
+    };
+    vec![
+        field(Tag::GPSVersionID, Value::Byte(vec![2, 3, 0, 0])),
+        field(Tag::GPSLatitudeRef, reference(latitude, b"N", b"S")),
+        field(
+            Tag::GPSLatitude,
+            Value::Rational(degrees_minutes_seconds(latitude)),
+        ),
+        field(Tag::GPSLongitudeRef, reference(longitude, b"E", b"W")),
+        field(
+            Tag::GPSLongitude,
+            Value::Rational(degrees_minutes_seconds(longitude)),
+        ),
+    ]
+}
+
+fn degrees_minutes_seconds(value: f64) -> Vec<Rational> {
+    let value = value.abs();
+    let degrees = value.trunc();
+    let minutes = ((value - degrees) * 60.0).trunc();
+    let seconds = (value - degrees - minutes / 60.0) * 3600.0;
+    vec![
+        Rational::from((degrees as u32, 1)),
+        Rational::from((minutes as u32, 1)),
+        Rational::from((
+            (seconds * f64::from(SECONDS_DENOMINATOR)).round() as u32,
+            SECONDS_DENOMINATOR,
+        )),
+    ]
+}
+
+/// A sidecar with the position, written fresh or merged into `existing`:
+/// its GPS properties are replaced and everything else is kept.
+fn tag_xmp(existing: Option<&str>, latitude: f64, longitude: f64) -> Result<String, String> {
+    xmp::set_properties(
+        existing,
+        "exif",
+        XMP_EXIF_NAMESPACE,
+        XMP_GPS_PROPERTIES,
+        &[
+            ("exif:GPSVersionID", "2.3.0.0".to_string()),
+            ("exif:GPSLatitude", xmp_coordinate(latitude, 'N', 'S')),
+            ("exif:GPSLongitude", xmp_coordinate(longitude, 'E', 'W')),
+        ],
+    )
+}
+
+/// XMP's `DDD,MM.mmmmmmK` form of a coordinate.
+fn xmp_coordinate(value: f64, positive: char, negative: char) -> String {
+    let direction = if value < 0.0 { negative } else { positive };
+    let value = value.abs();
+    let degrees = value.trunc();
+    format!(
+        "{},{:.6}{direction}",
+        degrees as u32,
+        (value - degrees) * 60.0
+    )
+}
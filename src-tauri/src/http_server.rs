@@ -0,0 +1,338 @@
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+};
+
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use serde::Serialize;
+use thumbnailer_core::extended_path;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+use crate::{resolve_data_dir, settings::SettingsStore, AppState, DB_FILE_NAME};
+
+const WORKER_COUNT: usize = 4;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HttpServerInfo {
+    port: u16,
+    token: String,
+    base_url: String,
+}
+
+pub(crate) struct HttpServerHandle {
+    server: Arc<Server>,
+    info: HttpServerInfo,
+}
+
+impl Drop for HttpServerHandle {
+    fn drop(&mut self) {
+        for _ in 0..WORKER_COUNT {
+            self.server.unblock();
+        }
+    }
+}
+
+/// Starts (or returns the already running) loopback server. Every request
+/// must carry the token, either as `?token=` or as a bearer token.
+#[tauri::command]
+pub(crate) fn start_http_server(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<HttpServerInfo, String> {
+    let mut running = state
+        .http_server
+        .lock()
+        .map_err(|_| "HTTP server state is unavailable.".to_string())?;
+    if let Some(handle) = running.as_ref() {
+        return Ok(handle.info.clone());
+    }
+
+    let data_dir = resolve_data_dir(&app)?;
+    let server =
+        Server::http("127.0.0.1:0").map_err(|err| format!("Failed to start HTTP server: {err}"))?;
+    let port = server
+        .server_addr()
+        .to_ip()
+        .map(|addr| addr.port())
+        .ok_or_else(|| "HTTP server is not bound to a TCP port.".to_string())?;
+    let info = HttpServerInfo {
+        port,
+        token: random_token(),
+        base_url: format!("http://127.0.0.1:{port}"),
+    };
+
+    let server = Arc::new(server);
+    let db_path = Arc::new(data_dir.join(DB_FILE_NAME));
+    for _ in 0..WORKER_COUNT {
+        let server = server.clone();
+        let db_path = db_path.clone();
+        let token = info.token.clone();
+        let settings = state.settings.clone();
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                handle_request(request, &db_path, &token, &settings);
+            }
+        });
+    }
+
+    *running = Some(HttpServerHandle {
+        server,
+        info: info.clone(),
+    });
+    Ok(info)
+}
+
+#[tauri::command]
+pub(crate) fn stop_http_server(state: tauri::State<'_, AppState>) {
+    if let Ok(mut running) = state.http_server.lock() {
+        running.take();
+    }
+}
+
+fn handle_request(request: Request, db_path: &Path, token: &str, settings: &SettingsStore) {
+    let result = route_request(&request, db_path, token, settings);
+    let response = match result {
+        Ok(value) => value,
+        Err((status, message)) => text_response(status, &message),
+    };
+    if let Err(err) = request.respond(response) {
+        log::debug!("Failed to send HTTP response: {}", err);
+    }
+}
+
+type BodyResponse = Response<Box<dyn Read + Send>>;
+
+fn route_request(
+    request: &Request,
+    db_path: &Path,
+    token: &str,
+    settings: &SettingsStore,
+) -> Result<BodyResponse, (StatusCode, String)> {
+    if *request.method() == Method::Options {
+        return Ok(text_response(StatusCode(204), ""));
+    }
+    if !matches!(request.method(), Method::Get | Method::Head) {
+        return Err((StatusCode(405), "Method not allowed.".to_string()));
+    }
+
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    let query_token = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="));
+    let header_token =
+        header_value(request, "Authorization").and_then(|value| value.strip_prefix("Bearer "));
+    if query_token.or(header_token) != Some(token) {
+        return Err((StatusCode(401), "Missing or invalid token.".to_string()));
+    }
+
+    let connection = Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|err| {
+        (
+            StatusCode(500),
+            format!("Failed to open cache database: {err}"),
+        )
+    })?;
+
+    let mut segments = path.trim_start_matches('/').splitn(2, '/');
+    match (segments.next(), segments.next()) {
+        (Some("thumb"), Some(key)) => thumbnail_response(&connection, key),
+        (Some("image"), Some(key)) => {
+            image_response(&connection, key, header_value(request, "Range"), settings)
+        }
+        _ => Err((StatusCode(404), "Not found.".to_string())),
+    }
+}
+
+fn thumbnail_response(
+    connection: &Connection,
+    cache_key: &str,
+) -> Result<BodyResponse, (StatusCode, String)> {
+    let (blob, mime_type): (Vec<u8>, String) = connection
+        .query_row(
+            "SELECT thumbnail_blob, mime_type FROM thumbnails WHERE cache_key = ?1",
+            params![cache_key],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|err| {
+            (
+                StatusCode(500),
+                format!("Failed to read cache entry: {err}"),
+            )
+        })?
+        .ok_or_else(|| (StatusCode(404), "Thumbnail not cached.".to_string()))?;
+    let length = blob.len();
+    Ok(body_response(
+        StatusCode(200),
+        Box::new(Cursor::new(blob)),
+        length,
+        &mime_type,
+    ))
+}
+
+fn image_response(
+    connection: &Connection,
+    cache_key: &str,
+    range: Option<&str>,
+    settings: &SettingsStore,
+) -> Result<BodyResponse, (StatusCode, String)> {
+    let source_path: String = connection
+        .query_row(
+            "SELECT source_path FROM thumbnails WHERE cache_key = ?1",
+            params![cache_key],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| {
+            (
+                StatusCode(500),
+                format!("Failed to read cache entry: {err}"),
+            )
+        })?
+        .ok_or_else(|| (StatusCode(404), "Image not known to the cache.".to_string()))?;
+    let image_path = PathBuf::from(source_path);
+    let mime_type = settings
+        .get()
+        .scan
+        .mime_type_for_path(&image_path)
+        .ok_or_else(|| (StatusCode(415), "Unsupported image format.".to_string()))?;
+    let mut file = File::open(extended_path(&image_path))
+        .map_err(|err| (StatusCode(404), format!("Failed to open image: {err}")))?;
+    let file_length = file
+        .metadata()
+        .map_err(|err| {
+            (
+                StatusCode(500),
+                format!("Failed to read image metadata: {err}"),
+            )
+        })?
+        .len();
+
+    let (start, end) = match range.map(|value| parse_byte_range(value, file_length)) {
+        Some(ByteRange::Span(start, end)) => (start, end),
+        Some(ByteRange::Unsatisfiable) => {
+            let mut response = text_response(StatusCode(416), "Range not satisfiable.");
+            response.add_header(header("Content-Range", &format!("bytes */{file_length}")));
+            return Ok(response);
+        }
+        Some(ByteRange::Whole) | None => {
+            let mut response = body_response(
+                StatusCode(200),
+                Box::new(file),
+                file_length as usize,
+                mime_type,
+            );
+            response.add_header(header("Accept-Ranges", "bytes"));
+            return Ok(response);
+        }
+    };
+    file.seek(SeekFrom::Start(start))
+        .map_err(|err| (StatusCode(500), format!("Failed to seek image: {err}")))?;
+    let length = end - start + 1;
+    let mut response = body_response(
+        StatusCode(206),
+        Box::new(file.take(length)),
+        length as usize,
+        mime_type,
+    );
+    response.add_header(header("Accept-Ranges", "bytes"));
+    response.add_header(header(
+        "Content-Range",
+        &format!("bytes {start}-{end}/{file_length}"),
+    ));
+    Ok(response)
+}
+
+enum ByteRange {
+    /// Malformed or multi-range requests, which are served whole.
+    Whole,
+    /// An inclusive byte span within the file.
+    Span(u64, u64),
+    /// Well formed but starting past the end of the file.
+    Unsatisfiable,
+}
+
+/// Parses a single `bytes=start-end` range, including the suffix and
+/// open-ended forms.
+fn parse_byte_range(value: &str, file_length: u64) -> ByteRange {
+    let Some((start, end)) = value
+        .trim()
+        .strip_prefix("bytes=")
+        .filter(|spec| !spec.contains(','))
+        .and_then(|spec| spec.split_once('-'))
+    else {
+        return ByteRange::Whole;
+    };
+    let parse = |value: &str| value.parse::<u64>().ok();
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => match parse(suffix) {
+            Some(0) => return ByteRange::Unsatisfiable,
+            Some(suffix) => (file_length.saturating_sub(suffix), u64::MAX),
+            None => return ByteRange::Whole,
+        },
+        (start, "") => match parse(start) {
+            Some(start) => (start, u64::MAX),
+            None => return ByteRange::Whole,
+        },
+        (start, end) => match (parse(start), parse(end)) {
+            (Some(start), Some(end)) if start <= end => (start, end),
+            _ => return ByteRange::Whole,
+        },
+    };
+    if start >= file_length {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Span(start, end.min(file_length - 1))
+}
+
+fn body_response(
+    status: StatusCode,
+    body: Box<dyn Read + Send>,
+    length: usize,
+    mime_type: &str,
+) -> BodyResponse {
+    Response::new(
+        status,
+        vec![
+            header("Content-Type", mime_type),
+            header("Cache-Control", "private, max-age=3600"),
+            header("Access-Control-Allow-Origin", "*"),
+            header("Access-Control-Allow-Headers", "Authorization, Range"),
+        ],
+        body,
+        Some(length),
+        None,
+    )
+}
+
+fn text_response(status: StatusCode, message: &str) -> BodyResponse {
+    let bytes = message.as_bytes().to_vec();
+    let length = bytes.len();
+    body_response(status, Box::new(Cursor::new(bytes)), length, "text/plain")
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("static header is valid")
+}
+
+fn header_value<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+/// 32 bytes from the OS RNG, as hex.
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).expect("the OS random number generator is available");
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -0,0 +1,200 @@
+use std::{collections::HashMap, path::Path};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    edit::write_replacing,
+    file_ops::{failure, success, FileOperationSummary},
+    open_cache_db, resolve_data_dir, xmp, AppState, LoadGalleryResponse,
+};
+
+const XMP_NAMESPACE: &str = "http://ns.adobe.com/xap/1.0/";
+const XMP_LABEL: &str = "xmp:Label";
+
+/// Lightroom's color labels, kept apart from star ratings since many
+/// photographers use the two for different stages of culling.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ColorLabel {
+    Red,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl ColorLabel {
+    const ALL: [ColorLabel; 5] = [
+        ColorLabel::Red,
+        ColorLabel::Yellow,
+        ColorLabel::Green,
+        ColorLabel::Blue,
+        ColorLabel::Purple,
+    ];
+
+    /// As Lightroom writes it in `xmp:Label`, and as it is stored here.
+    fn name(self) -> &'static str {
+        match self {
+            ColorLabel::Red => "Red",
+            ColorLabel::Yellow => "Yellow",
+            ColorLabel::Green => "Green",
+            ColorLabel::Blue => "Blue",
+            ColorLabel::Purple => "Purple",
+        }
+    }
+
+    /// Other labels, such as the custom names Lightroom's label sets
+    /// allow, aren't color labels and read as none.
+    fn from_name(name: &str) -> Option<ColorLabel> {
+        ColorLabel::ALL
+            .into_iter()
+            .find(|label| label.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// Color labels for `paths`, keyed by path; unlabeled images are left out.
+/// An image's XMP sidecar, when it has one, is the authority, so labels set
+/// in Lightroom or darktable show up here.
+#[tauri::command]
+pub(crate) async fn get_color_labels(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    paths: Vec<String>,
+) -> Result<HashMap<String, ColorLabel>, String> {
+    state.path_scope.check_all(&paths)?;
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        let mut labels = HashMap::new();
+        for path in paths {
+            if let Some(label) = label_for(&connection, Path::new(&path))? {
+                labels.insert(path, label);
+            }
+        }
+        Ok(labels)
+    })
+    .await
+    .map_err(|err| format!("Failed to join color label task: {err}"))?
+}
+
+/// Labels each of `paths`, or of the selection, or clears their label with
+/// `None`. The label is also written to the image's XMP sidecar, created if
+/// needed, which is reported as the result's `newPath`; a cleared label
+/// creates none.
+#[tauri::command]
+pub(crate) async fn set_color_label(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    paths: Option<Vec<String>>,
+    label: Option<ColorLabel>,
+) -> Result<FileOperationSummary, String> {
+    let paths = state.selection.or_selected(paths)?;
+    state.path_scope.check_all(&paths)?;
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        let results = paths
+            .into_iter()
+            .map(|path| {
+                let labeled = store(&connection, &path, label)
+                    .and_then(|()| write_sidecar(Path::new(&path), label));
+                match labeled {
+                    Ok(sidecar) => success(path, sidecar),
+                    Err(err) => failure(path, err),
+                }
+            })
+            .collect();
+        Ok(FileOperationSummary::from_results(results))
+    })
+    .await
+    .map_err(|err| format!("Failed to join color label task: {err}"))?
+}
+
+/// Keeps only the gallery items carrying one of `wanted`.
+pub(crate) fn filter(
+    connection: &Connection,
+    response: &mut LoadGalleryResponse,
+    wanted: &[ColorLabel],
+) -> Result<(), String> {
+    let mut kept = Vec::with_capacity(response.items.len());
+    for item in std::mem::take(&mut response.items) {
+        let label = label_for(connection, Path::new(&item.path))?;
+        if label.is_some_and(|label| wanted.contains(&label)) {
+            kept.push(item);
+        }
+    }
+    response
+        .thumbnails
+        .retain(|path, _| kept.iter().any(|item| item.path == *path));
+    response.total_items = kept.len();
+    response.items = kept;
+    Ok(())
+}
+
+/// The sidecar's label if there is a sidecar, brought into the database
+/// when it differs; the stored label otherwise.
+fn label_for(connection: &Connection, path: &Path) -> Result<Option<ColorLabel>, String> {
+    let stored: Option<String> = connection
+        .query_row(
+            "SELECT label FROM image_labels WHERE path = ?1",
+            params![path.to_string_lossy()],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read color label: {err}"))?;
+    let stored = stored.as_deref().and_then(ColorLabel::from_name);
+    let sidecar = match xmp::read_sidecar(path) {
+        Ok(Some(value)) => value,
+        Ok(None) => return Ok(stored),
+        Err(err) => {
+            log::debug!("{}", err);
+            return Ok(stored);
+        }
+    };
+    let label = xmp::property(&sidecar, XMP_LABEL)
+        .as_deref()
+        .and_then(ColorLabel::from_name);
+    if label != stored {
+        store(connection, &path.to_string_lossy(), label)?;
+    }
+    Ok(label)
+}
+
+fn store(connection: &Connection, path: &str, label: Option<ColorLabel>) -> Result<(), String> {
+    let written = match label {
+        Some(label) => connection.execute(
+            "INSERT OR REPLACE INTO image_labels (path, label) VALUES (?1, ?2)",
+            params![path, label.name()],
+        ),
+        None => connection.execute("DELETE FROM image_labels WHERE path = ?1", params![path]),
+    };
+    written
+        .map(|_| ())
+        .map_err(|err| format!("Failed to write color label: {err}"))
+}
+
+/// Returns the sidecar written, if any.
+fn write_sidecar(
+    path: &Path,
+    label: Option<ColorLabel>,
+) -> Result<Option<std::path::PathBuf>, String> {
+    let existing = xmp::read_sidecar(path)?;
+    if existing.is_none() && label.is_none() {
+        return Ok(None);
+    }
+    let properties: Vec<(&str, String)> = label
+        .map(|label| (XMP_LABEL, label.name().to_string()))
+        .into_iter()
+        .collect();
+    let merged = xmp::set_properties(
+        existing.as_deref(),
+        "xmp",
+        XMP_NAMESPACE,
+        &[XMP_LABEL],
+        &properties,
+    )?;
+    let sidecar = xmp::sidecar_path(path);
+    write_replacing(&sidecar, merged.as_bytes())?;
+    Ok(Some(sidecar))
+}
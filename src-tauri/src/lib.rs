@@ -1,40 +1,199 @@
 use std::{
-    collections::HashMap,
-    env,
+    collections::{HashMap, HashSet},
     fs,
-    io::Cursor,
+    io::{Cursor, Read},
     path::{Path, PathBuf},
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        mpsc, Arc, Mutex, OnceLock,
     },
+    thread,
     time::{Duration, Instant, UNIX_EPOCH},
 };
 
 use base64::Engine;
-use image::{codecs::png::PngEncoder, ColorType, GenericImageView, ImageEncoder};
+use image::{
+    codecs::{jpeg::JpegEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    ColorType, DynamicImage, ImageEncoder,
+};
+use memmap2::Mmap;
 use rayon::prelude::*;
-use rusqlite::{params, Connection, OptionalExtension};
-use serde::Serialize;
-use sha2::{Digest, Sha256};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
 use tauri::{Emitter, Manager};
+use tauri_plugin_deep_link::DeepLinkExt;
+use thumbnailer_core::{
+    cache_key_for_path, content_hash, decode_image, extended_path, last_modified_unix, retry_io,
+    timings::{self, StageTiming},
+    GeneratedThumbnail, PendingThumbnail, Scanner, ThumbnailCache,
+};
+
+mod active_scan;
+mod auto_import;
+mod benchmark;
+mod broken;
+mod cache_health;
+mod capture_dates;
+mod captions;
+mod cli;
+mod cloud_files;
+mod compare;
+mod culling;
+mod device_import;
+#[cfg(target_os = "linux")]
+mod dbus_thumbnailer;
+mod dialogs;
+mod dimensions;
+mod edit;
+mod enhance;
+mod exif_info;
+mod export;
+mod faces;
+mod file_ops;
+mod folder_cover;
+mod folder_stats;
+mod gallery_sessions;
+mod geotag;
+mod http_server;
+mod labels;
+mod library;
+mod library_backup;
+mod library_import;
+mod logging;
+mod manifest;
+mod motion;
+mod open_request;
+mod os_recents;
+mod os_thumbnail;
+mod path_scope;
+mod pdf;
+mod places;
+mod prefetch;
+mod preview_cache;
+mod protocol;
+mod provisional;
+mod ratings;
+mod raw_pairs;
+mod recent_folders;
+mod recovery;
+mod selection;
+mod session_metrics;
+mod settings;
+mod share_guard;
+mod shell;
+mod single_flight;
+#[cfg(desktop)]
+mod shortcut;
+mod tiles;
+mod tray;
+mod verify;
+mod video;
+mod viewer;
+mod volume;
+mod watcher;
+mod watermark;
+mod xmp;
 
 const DB_FILE_NAME: &str = "thumbnail_cache.sqlite";
+/// Stored as the database's `user_version`; bump it when `init_schema`
+/// changes shape.
+const SCHEMA_VERSION: i64 = 17;
 const PROGRESS_EMIT_INTERVAL: Duration = Duration::from_millis(100);
+/// How often paused thumbnail generation checks whether it may resume.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Generated thumbnails stored per transaction during a gallery load.
+const WRITE_BATCH_SIZE: usize = 64;
+const DEFAULT_FULL_IMAGE_MAX_DIMENSION: u32 = 4096;
+const FULL_IMAGE_JPEG_QUALITY: u8 = 90;
+/// Originals at least this big are mapped rather than read into memory when
+/// they are streamed, and kept out of the preview cache.
+const MMAP_MIN_BYTES: u64 = 64 * 1024 * 1024;
+/// Size of each piece `stream_full_image` sends.
+const FULL_IMAGE_CHUNK_BYTES: usize = 4 * 1024 * 1024;
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct GalleryItem {
     name: String,
     path: String,
+    /// Online-only file listed without a thumbnail; see `CloudFiles`.
+    cloud: bool,
+    modified_unix: i64,
+    /// `None` in offline galleries, listed from the cache alone.
+    size_bytes: Option<u64>,
+    /// From EXIF; `None` for images without a capture time.
+    captured_unix: Option<i64>,
+    /// Live Photo or motion photo; see `motion::get_motion_clip`.
+    has_motion: bool,
+    /// Set for video files, once probed.
+    video: Option<video::VideoInfo>,
+    /// The other half of a RAW+JPEG pair listed as this one item; see
+    /// `RawPairs`.
+    pair_path: Option<String>,
+    /// Why the file couldn't be decoded, in which case its thumbnail is a
+    /// "broken file" placeholder.
+    error: Option<String>,
+    /// The user's note on the image; see `captions::set_caption`.
+    caption: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct LoadGalleryResponse {
     items: Vec<GalleryItem>,
     thumbnails: HashMap<String, String>,
     cancelled: bool,
+    /// Set when the folder's share stopped responding partway through, or
+    /// when it is offline.
+    error: Option<String>,
+    /// The folder's drive is gone and only cached thumbnails are listed.
+    offline: bool,
+    /// Items in the whole gallery, which a `window_size` may not all return.
+    total_items: usize,
+    /// For `get_items_range`; `None` until the load has finished.
+    session_id: Option<u64>,
+    /// Set when the load was cancelled before every item had its
+    /// thumbnail; `load_gallery_resume` carries on from it.
+    resume_token: Option<ResumeToken>,
+}
+
+/// Where a cancelled gallery load stopped. The frontend keeps it as is.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResumeToken {
+    folder: String,
+    thumbnail_size: u32,
+    sort_by: settings::SortBy,
+    color_labels: Vec<labels::ColorLabel>,
+    /// Files still to list or generate, RAW+JPEG halves included, in scan
+    /// order.
+    remaining: Vec<String>,
+    /// Items finished before the ones remaining, out of `total`.
+    position: usize,
+    total: usize,
+}
+
+/// One gallery load, fresh or resumed.
+struct GalleryRequest {
+    folder_path: String,
+    /// In device pixels.
+    thumbnail_size: u32,
+    sort_by: settings::SortBy,
+    color_labels: Vec<labels::ColorLabel>,
+    resume: Option<ResumeToken>,
+}
+
+/// Where the time of the last gallery load went, for attaching to reports
+/// of slowness.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScanTimings {
+    folder: String,
+    image_count: usize,
+    /// Wall time of the whole load.
+    total_ms: f64,
+    stages: Vec<StageTiming>,
 }
 
 #[derive(Serialize)]
@@ -45,114 +204,463 @@ struct ThumbnailProgress {
     name: String,
 }
 
-struct PendingThumbnail {
-    image_path: PathBuf,
-    cache_key: String,
-    modified_unix: i64,
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GalleryRemoved {
+    path: String,
 }
 
-struct GeneratedThumbnail {
-    cache_key: String,
-    source_path: String,
-    modified_unix: i64,
-    blob: Vec<u8>,
-    mime: String,
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FullImagePlaceholder {
+    path: String,
+    image: String,
 }
 
+/// A full image payload: re-encoded or modest originals in memory, large
+/// originals mapped straight from disk.
+enum FullImageBytes {
+    Owned(Arc<Vec<u8>>),
+    Mapped(Mmap),
+}
+
+impl FullImageBytes {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Self::Owned(bytes) => bytes,
+            Self::Mapped(map) => map,
+        }
+    }
+
+    /// Copies a mapping, so callers that need a `Vec` ask for unmapped bytes.
+    fn into_vec(self) -> Vec<u8> {
+        match self {
+            Self::Owned(bytes) => Arc::unwrap_or_clone(bytes),
+            Self::Mapped(map) => map.to_vec(),
+        }
+    }
+}
+
+/// In-progress `load_thumbnail` work, shared by requests for the same one.
+type ThumbnailFlights = single_flight::SingleFlight<Result<(Vec<u8>, String), String>>;
+
 #[derive(Default)]
 struct AppState {
-    cancel_requested: Arc<AtomicBool>,
+    active_scan: active_scan::ActiveScan,
+    edit_watchers: watcher::EditWatchers,
+    gallery_watcher: watcher::GalleryWatcher,
+    http_server: Mutex<Option<http_server::HttpServerHandle>>,
+    tile_pyramid: Arc<tiles::TilePyramidCache>,
+    preview_cache: Arc<preview_cache::PreviewCache>,
+    settings: Arc<settings::SettingsStore>,
+    viewer_windows: viewer::ViewerWindows,
+    pending_open: Mutex<Option<open_request::OpenRequest>>,
+    startup_options: Mutex<open_request::StartupOptions>,
+    generation_paused: Arc<AtomicBool>,
+    volume_monitor: volume::VolumeMonitor,
+    path_scope: path_scope::PathScope,
+    last_scan_timings: Mutex<Option<ScanTimings>>,
+    session_metrics: Arc<session_metrics::SessionMetrics>,
+    thumbnail_flights: Arc<ThumbnailFlights>,
+    prefetcher: prefetch::Prefetcher,
+    face_scanner: faces::FaceScanner,
+    gallery_sessions: Arc<gallery_sessions::GallerySessions>,
+    culling: culling::Culling,
+    selection: selection::Selection,
+    abnormal_exit: Mutex<Option<recovery::SessionMarker>>,
 }
 
+/// Returns the image as a raw binary IPC payload, which the webview receives
+/// as an `ArrayBuffer` without any base64 round trip. Images larger than
+/// `max_dimension` (or in formats webviews can't display) are downscaled and
+/// re-encoded; the untouched file is only sent when `original` is set.
+///
+/// With `progressive` set, a `full-image-placeholder` event carrying the
+/// cached thumbnail (or the embedded EXIF preview) is emitted first, so the
+/// viewer has something to show while the full payload is prepared.
 #[tauri::command]
-fn get_initial_folder() -> Option<String> {
-    env::args()
-        .skip(1)
-        .find(|arg| Path::new(arg).is_dir())
-        .map(|arg| arg.to_string())
+async fn load_full_image(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+    max_dimension: Option<u32>,
+    original: Option<bool>,
+    progressive: Option<bool>,
+) -> Result<tauri::ipc::Response, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let max_dimension = if original.unwrap_or(false) {
+        None
+    } else {
+        Some(max_dimension.unwrap_or(DEFAULT_FULL_IMAGE_MAX_DIMENSION).max(1))
+    };
+    let image_bytes =
+        full_image_bytes(app, &state, path, max_dimension, progressive, false).await?;
+    Ok(tauri::ipc::Response::new(image_bytes.into_vec()))
 }
 
+/// `load_full_image` for very large images: the payload arrives through
+/// `on_chunk` in pieces, so a mapped original is never copied whole. Returns
+/// the total size once every piece is sent.
 #[tauri::command]
-async fn load_full_image(path: String) -> Result<String, String> {
-    tauri::async_runtime::spawn_blocking(move || load_full_image_blocking(path))
-        .await
-        .map_err(|err| format!("Failed to join full image task: {err}"))?
+async fn stream_full_image(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+    max_dimension: Option<u32>,
+    original: Option<bool>,
+    on_chunk: tauri::ipc::Channel,
+) -> Result<usize, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let max_dimension = if original.unwrap_or(false) {
+        None
+    } else {
+        Some(max_dimension.unwrap_or(DEFAULT_FULL_IMAGE_MAX_DIMENSION).max(1))
+    };
+    let image_bytes = full_image_bytes(app, &state, path, max_dimension, None, true).await?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let bytes = image_bytes.as_slice();
+        for chunk in bytes.chunks(FULL_IMAGE_CHUNK_BYTES) {
+            on_chunk
+                .send(tauri::ipc::InvokeResponseBody::Raw(chunk.to_vec()))
+                .map_err(|err| format!("Failed to send image chunk: {err}"))?;
+        }
+        Ok(bytes.len())
+    })
+    .await
+    .map_err(|err| format!("Failed to join image stream task: {err}"))?
 }
 
+async fn full_image_bytes(
+    app: tauri::AppHandle,
+    state: &AppState,
+    path: String,
+    max_dimension: Option<u32>,
+    progressive: Option<bool>,
+    map_large: bool,
+) -> Result<FullImageBytes, String> {
+    let data_dir = resolve_data_dir(&app)?;
+    let preview_cache = state.preview_cache.clone();
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        let image_path = PathBuf::from(&path);
+        if let Some(bytes) = preview_cache.get(&image_path, max_dimension) {
+            return Ok(FullImageBytes::Owned(bytes));
+        }
+        if progressive.unwrap_or(false) {
+            emit_full_image_placeholder(&app, &data_dir, &image_path);
+        }
+        let bytes = load_full_image_blocking(path, max_dimension, &settings, map_large)?;
+        match &bytes {
+            FullImageBytes::Owned(bytes) if (bytes.len() as u64) < MMAP_MIN_BYTES => {
+                preview_cache.insert(image_path, max_dimension, bytes.clone());
+            }
+            _ => {}
+        }
+        Ok(bytes)
+    })
+    .await
+    .map_err(|err| format!("Failed to join full image task: {err}"))?
+}
+
+/// Returns the encoded thumbnail as a raw binary IPC payload. Sizes are in
+/// CSS pixels, scaled like `load_gallery`'s.
 #[tauri::command]
 async fn load_thumbnail(
     app: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    state: tauri::State<'_, AppState>,
     path: String,
-    thumbnail_size: u32,
-) -> Result<String, String> {
-    let data_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|err| format!("Failed to resolve app data path: {err}"))?;
-    fs::create_dir_all(&data_dir)
-        .map_err(|err| format!("Failed to create app data directory: {err}"))?;
+    thumbnail_size: Option<u32>,
+    device_pixel_ratio: Option<f64>,
+) -> Result<tauri::ipc::Response, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let data_dir = resolve_data_dir(&app)?;
+    let settings = state.settings.get();
+    let thumbnail_size = device_pixels(
+        thumbnail_size.unwrap_or(settings.thumbnail_size),
+        device_pixel_ratio,
+        &window,
+    );
+    let metrics = state.session_metrics.clone();
+    let flights = state.thumbnail_flights.clone();
 
-    tauri::async_runtime::spawn_blocking(move || {
-        load_thumbnail_blocking(data_dir, path, thumbnail_size)
+    let (thumbnail_blob, _mime_type) = tauri::async_runtime::spawn_blocking(move || {
+        load_thumbnail_blocking(data_dir, path, thumbnail_size, &settings, &metrics, &flights)
     })
     .await
-    .map_err(|err| format!("Failed to join thumbnail task: {err}"))?
+    .map_err(|err| format!("Failed to join thumbnail task: {err}"))??;
+    Ok(tauri::ipc::Response::new(thumbnail_blob))
 }
 
+/// `thumbnail_size` is in CSS pixels. Thumbnails are made at that size
+/// times `device_pixel_ratio`, or the window's scale factor when it isn't
+/// given, so grids stay sharp on HiDPI screens.
+///
+/// With `window_size`, only that many items come back; the rest are fetched
+/// with `get_items_range` as they scroll into view. With `color_labels`,
+/// only images carrying one of them are listed. `recursive` overrides the
+/// setting for this load, as `--recursive` does for the first one.
 #[tauri::command]
+// Each argument is a named field of the IPC call; grouping them would
+// change the call's shape for every caller.
+#[allow(clippy::too_many_arguments)]
 async fn load_gallery(
     app: tauri::AppHandle,
+    window: tauri::WebviewWindow,
     state: tauri::State<'_, AppState>,
     folder_path: String,
-    thumbnail_size: u32,
+    thumbnail_size: Option<u32>,
+    sort_by: Option<settings::SortBy>,
+    device_pixel_ratio: Option<f64>,
+    window_size: Option<usize>,
+    color_labels: Option<Vec<labels::ColorLabel>>,
+    recursive: Option<bool>,
 ) -> Result<LoadGalleryResponse, String> {
-    let data_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|err| format!("Failed to resolve app data path: {err}"))?;
-    fs::create_dir_all(&data_dir)
-        .map_err(|err| format!("Failed to create app data directory: {err}"))?;
+    let mut settings = state.settings.get();
+    if let Some(recursive) = recursive {
+        settings.scan.recursive_scan = recursive;
+    }
+    let request = GalleryRequest {
+        thumbnail_size: device_pixels(
+            thumbnail_size.unwrap_or(settings.thumbnail_size),
+            device_pixel_ratio,
+            &window,
+        ),
+        sort_by: sort_by.unwrap_or(settings.sort_by),
+        color_labels: color_labels.unwrap_or_default(),
+        folder_path,
+        resume: None,
+    };
+    run_gallery_load(app, state, settings, request, window_size).await
+}
 
-    state.cancel_requested.store(false, Ordering::Relaxed);
-    let cancel_requested = state.cancel_requested.clone();
+/// Carries on a load that was cancelled, from the `resumeToken` it
+/// returned. Only the items that weren't finished come back: those listed
+/// without a thumbnail, now with one, and the ones never reached. A
+/// cancelled resume returns a token of its own.
+#[tauri::command]
+async fn load_gallery_resume(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    token: ResumeToken,
+) -> Result<LoadGalleryResponse, String> {
+    let settings = state.settings.get();
+    let request = GalleryRequest {
+        folder_path: token.folder.clone(),
+        thumbnail_size: token.thumbnail_size,
+        sort_by: token.sort_by,
+        color_labels: token.color_labels.clone(),
+        resume: Some(token),
+    };
+    run_gallery_load(app, state, settings, request, None).await
+}
+
+async fn run_gallery_load(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    settings: settings::Settings,
+    request: GalleryRequest,
+    window_size: Option<usize>,
+) -> Result<LoadGalleryResponse, String> {
+    let data_dir = resolve_data_dir(&app)?;
+    let thumbnail_size = request.thumbnail_size;
+    let folder = PathBuf::from(&request.folder_path);
+    recent_folders::check_scope(data_dir.clone(), &state.path_scope, &folder).await?;
+    if request.resume.is_none() {
+        state.selection.clear();
+    }
+    let claim = state.active_scan.claim(
+        &folder,
+        thumbnail_size,
+        request.sort_by,
+        &request.color_labels,
+    );
+    let scan = match claim {
+        active_scan::Claim::Join(scan) => {
+            return tauri::async_runtime::spawn_blocking(move || scan.wait())
+                .await
+                .map_err(|err| format!("Failed to join gallery task: {err}"))?
+                .map(|response| gallery_sessions::windowed(response, window_size));
+        }
+        active_scan::Claim::Start { scan, superseded } => {
+            if let Some(superseded) = superseded {
+                tauri::async_runtime::spawn_blocking(move || superseded.wait())
+                    .await
+                    .map_err(|err| format!("Failed to join gallery task: {err}"))?
+                    .ok();
+            }
+            scan
+        }
+    };
+    state.prefetcher.cancel();
+    state.face_scanner.cancel();
+    let cancel_requested = scan.cancel_requested.clone();
+    let generation_paused = state.generation_paused.clone();
     let app_handle = app.clone();
-    tauri::async_runtime::spawn_blocking(move || {
+    recovery::set_scanning_folder(&data_dir, Some(&folder));
+    let task_data_dir = data_dir.clone();
+    let filter_data_dir = data_dir.clone();
+    let prefetch_settings = settings.clone();
+    let face_settings = settings.clone();
+    let watch_settings = settings.clone();
+    let sessions = state.gallery_sessions.clone();
+    timings::reset();
+    let started = Instant::now();
+    let response = tauri::async_runtime::spawn_blocking(move || {
         load_gallery_blocking(
             app_handle,
             cancel_requested,
-            data_dir,
-            folder_path,
-            thumbnail_size,
+            generation_paused,
+            task_data_dir,
+            &request,
+            settings,
         )
+        .and_then(|mut response| {
+            sort_items(&mut response.items, request.sort_by);
+            if !request.color_labels.is_empty() {
+                let connection = open_cache_db(&filter_data_dir)?;
+                labels::filter(&connection, &mut response, &request.color_labels)?;
+            }
+            // A resumed load returns only part of the gallery.
+            if request.resume.is_none() {
+                response.session_id = Some(sessions.insert(&response));
+            }
+            Ok(response)
+        })
     })
     .await
-    .map_err(|err| format!("Failed to join gallery task: {err}"))?
+    .map_err(|err| format!("Failed to join gallery task: {err}"))
+    .and_then(|response| response);
+    state.active_scan.finish(&scan, &response);
+    recovery::set_scanning_folder(&data_dir, None);
+    if let Ok(response) = &response {
+        let scan_timings = ScanTimings {
+            folder: folder.to_string_lossy().to_string(),
+            image_count: response.items.len(),
+            total_ms: started.elapsed().as_secs_f64() * 1000.0,
+            stages: timings::snapshot(),
+        };
+        if let Ok(mut last) = state.last_scan_timings.lock() {
+            *last = Some(scan_timings);
+        }
+        if !response.cancelled && !response.offline {
+            state.prefetcher.start(
+                data_dir.clone(),
+                folder.clone(),
+                thumbnail_size,
+                prefetch_settings,
+                state.generation_paused.clone(),
+            );
+            let paths = response.items.iter().map(|item| PathBuf::from(&item.path));
+            state.face_scanner.start(
+                &app,
+                paths.collect(),
+                face_settings,
+                state.generation_paused.clone(),
+            );
+        }
+        if response.offline {
+            state.gallery_watcher.stop();
+        } else if let Err(err) = state.gallery_watcher.watch(
+            app.clone(),
+            data_dir.clone(),
+            &folder,
+            thumbnail_size,
+            watch_settings,
+        ) {
+            log::warn!("Failed to watch gallery folder: {}", err);
+        }
+        state.volume_monitor.watch(&app, folder, !response.offline);
+    }
+    tray::refresh(&app);
+    response.map(|response| gallery_sessions::windowed(response, window_size))
 }
 
 #[tauri::command]
 fn cancel_gallery_scan(state: tauri::State<'_, AppState>) {
-    state.cancel_requested.store(true, Ordering::Relaxed);
+    state.active_scan.cancel();
+}
+
+#[tauri::command]
+fn get_last_scan_timings(state: tauri::State<'_, AppState>) -> Option<ScanTimings> {
+    state.last_scan_timings.lock().ok()?.clone()
+}
+
+/// A size in CSS pixels as device pixels, within the thumbnail size limit.
+fn device_pixels(
+    size: u32,
+    device_pixel_ratio: Option<f64>,
+    window: &tauri::WebviewWindow,
+) -> u32 {
+    let ratio = device_pixel_ratio
+        .or_else(|| window.scale_factor().ok())
+        .filter(|ratio| ratio.is_finite() && *ratio > 0.0)
+        .unwrap_or(1.0);
+    ((f64::from(size) * ratio).round() as u32).clamp(1, settings::MAX_THUMBNAIL_SIZE)
+}
+
+fn resolve_data_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|err| format!("Failed to resolve app data path: {err}"))?;
+    fs::create_dir_all(&data_dir)
+        .map_err(|err| format!("Failed to create app data directory: {err}"))?;
+    Ok(data_dir)
 }
 
 fn load_gallery_blocking(
     app: tauri::AppHandle,
     cancel_requested: Arc<AtomicBool>,
+    generation_paused: Arc<AtomicBool>,
     data_dir: PathBuf,
-    folder_path: String,
-    thumbnail_size: u32,
+    request: &GalleryRequest,
+    settings: settings::Settings,
 ) -> Result<LoadGalleryResponse, String> {
-    let folder = PathBuf::from(folder_path);
-    if !folder.is_dir() {
+    let folder = PathBuf::from(&request.folder_path);
+    let thumbnail_size = request.thumbnail_size;
+    let metrics = app.state::<AppState>().session_metrics.clone();
+    let share_guard = share_guard::ShareGuard::default();
+    let folder_metadata = share_guard.run(&folder, share_guard::METADATA_TIMEOUT, {
+        let folder = folder.clone();
+        move || fs::metadata(extended_path(&folder))
+    });
+    let mut connection = open_cache_db(&data_dir)?;
+    let folder_metadata = match folder_metadata {
+        Ok(value) => value,
+        Err(err) => return volume::offline_gallery(&connection, &folder, &settings)?.ok_or(err),
+    };
+    if !folder_metadata.is_dir() {
         return Err(format!("{} is not a valid directory.", folder.display()));
     }
 
-    let db_path = data_dir.join(DB_FILE_NAME);
-    let mut connection =
-        Connection::open(db_path).map_err(|err| format!("Failed to open cache database: {err}"))?;
-    init_schema(&connection)?;
+    if let Err(err) = recent_folders::record(&connection, &folder) {
+        log::warn!("{}", err);
+    }
 
-    let mut image_paths = collect_supported_images(&folder)?;
+    let path_scope = &app.state::<AppState>().path_scope;
+    let mut image_paths = match &request.resume {
+        // Only files of this folder the scan would list, whatever the token
+        // says, and with links and `..` resolved the way commands see them.
+        Some(token) => token
+            .remaining
+            .iter()
+            .map(PathBuf::from)
+            .filter(|path| {
+                path.starts_with(&folder)
+                    && settings.scan.is_supported_image(path)
+                    && path_scope.check(path).is_ok()
+            })
+            .collect(),
+        None => settings.scan.scan(&folder)?,
+    };
+    settings.cloud_files.filter(&mut image_paths);
+    let raw_pairs = settings.raw_pairs.group(&mut image_paths);
     image_paths.sort_unstable();
+    let (position, total) = match &request.resume {
+        Some(token) => (token.position, token.total),
+        None => (0, image_paths.len()),
+    };
 
     let mut results = Vec::new();
     let mut pending = Vec::new();
@@ -160,22 +668,28 @@ fn load_gallery_blocking(
 
     let mut skipped_count = 0usize;
     let mut cancelled = false;
-    let total = image_paths.len();
+    let mut unprocessed = Vec::new();
     let mut last_progress_emit_at: Option<Instant> = None;
-    for (index, image_path) in image_paths.into_iter().enumerate() {
+    let mut item_dimensions = Vec::new();
+    let mut provisional_thumbnails = Vec::new();
+    let mut motion_pairs = motion::MotionPairs::default();
+    let mut image_paths = image_paths.into_iter().enumerate();
+    while let Some((index, image_path)) = image_paths.next() {
         if cancel_requested.load(Ordering::Relaxed) {
             cancelled = true;
+            unprocessed.push(image_path);
+            unprocessed.extend(image_paths.by_ref().map(|(_, path)| path));
             break;
         }
         let progress = ThumbnailProgress {
-            current: index + 1,
+            current: position + index + 1,
             total,
             name: image_path
                 .file_name()
                 .map(|name| name.to_string_lossy().to_string())
                 .unwrap_or_else(|| "image".to_string()),
         };
-        let is_last_item = index + 1 == total;
+        let is_last_item = position + index + 1 == total;
         let should_emit = is_last_item
             || last_progress_emit_at
                 .map(|timestamp| timestamp.elapsed() >= PROGRESS_EMIT_INTERVAL)
@@ -185,16 +699,87 @@ fn load_gallery_blocking(
             if let Err(err) = app.emit("thumbnail-progress", &progress) {
                 log::warn!("Failed to emit thumbnail progress: {}", err);
             }
+            dimensions::emit(&app, &mut item_dimensions);
+            provisional::emit(&app, &mut provisional_thumbnails);
         }
 
-        match prepare_single_image(&connection, &image_path) {
-            Ok((item, maybe_pending, maybe_thumbnail_data_url)) => {
-                if let Some(thumbnail_data_url) = maybe_thumbnail_data_url {
-                    thumbnails.insert(item.path.clone(), thumbnail_data_url);
+        // Once the share has answered for a file, the reads that follow are
+        // served from the client's attribute cache.
+        let reachable = share_guard.run(&image_path, share_guard::METADATA_TIMEOUT, {
+            let image_path = image_path.clone();
+            move || retry_io(|| fs::metadata(extended_path(&image_path)))
+        });
+        let prepared = reachable.and_then(|metadata| {
+            prepare_single_image(
+                &connection,
+                &image_path,
+                thumbnail_size,
+                settings.cloud_files,
+            )
+            .map(|prepared| (metadata.len(), prepared))
+        });
+        match prepared {
+            Ok((size_bytes, (mut item, maybe_pending, maybe_thumbnail_url))) => {
+                item.size_bytes = Some(size_bytes);
+                item.has_motion = motion_pairs.has_motion(&image_path);
+                item.pair_path = raw_pairs
+                    .get(&image_path)
+                    .map(|pair| pair.to_string_lossy().to_string());
+                let probe = settings.cloud_files.allows_generation(&image_path);
+                match capture_dates::for_item(
+                    &connection,
+                    &share_guard,
+                    &image_path,
+                    item.modified_unix,
+                    probe,
+                ) {
+                    Ok(captured_unix) => item.captured_unix = captured_unix,
+                    Err(err) => {
+                        log::debug!("No capture date for {}: {}", image_path.display(), err)
+                    }
+                }
+                if let Err(err) = places::for_item(
+                    &connection,
+                    &share_guard,
+                    &image_path,
+                    item.modified_unix,
+                    probe,
+                ) {
+                    log::debug!("No place for {}: {}", image_path.display(), err);
+                }
+                match video::info_for_item(
+                    &connection,
+                    &share_guard,
+                    &image_path,
+                    item.modified_unix,
+                    probe,
+                ) {
+                    Ok(info) => item.video = info,
+                    Err(err) => log::debug!("No video info for {}: {}", image_path.display(), err),
+                }
+                match dimensions::for_item(&connection, &share_guard, &image_path, probe) {
+                    Ok(Some(value)) => item_dimensions.push(value),
+                    Ok(None) => {}
+                    // Generation reports unreadable images.
+                    Err(err) => log::debug!("No dimensions for {}: {}", image_path.display(), err),
+                }
+                if let Some(thumbnail_url) = maybe_thumbnail_url {
+                    metrics.record_hit();
+                    thumbnails.insert(item.path.clone(), thumbnail_url);
                 }
                 results.push(item);
                 if let Some(pending_item) = maybe_pending {
-                    pending.push(pending_item);
+                    metrics.record_miss();
+                    match provisional::for_item(&share_guard, &image_path) {
+                        Ok(Some(value)) => provisional_thumbnails.push(value),
+                        Ok(None) => {}
+                        Err(err) => log::debug!(
+                            "No embedded thumbnail for {}: {}",
+                            image_path.display(),
+                            err
+                        ),
+                    }
+                    pending.push((size_bytes, pending_item));
                 }
             }
             Err(err) => {
@@ -207,317 +792,615 @@ fn load_gallery_blocking(
             }
         }
     }
+    dimensions::emit(&app, &mut item_dimensions);
+    provisional::emit(&app, &mut provisional_thumbnails);
 
+    // Smallest first, so the grid fills up with the cheap images while the
+    // huge ones are still decoding.
+    pending.sort_by_key(|(size_bytes, _)| *size_bytes);
+    let pending_paths: Vec<PathBuf> = pending
+        .iter()
+        .map(|(_, pending_item)| pending_item.image_path.clone())
+        .collect();
     if !cancelled && !pending.is_empty() {
-        let generated: Vec<GeneratedThumbnail> = pending
-            .into_par_iter()
-            .filter_map(|pending_item| {
-                if cancel_requested.load(Ordering::Relaxed) {
-                    return None;
-                }
-                match generate_pending_thumbnail(pending_item, thumbnail_size) {
-                    Ok(value) => Some(value),
-                    Err(err) => {
-                        log::warn!("Skipping generated thumbnail due to error: {}", err);
-                        None
-                    }
-                }
-            })
-            .collect();
+        let generator = settings.generator(thumbnail_size);
+        // Files deleted or moved away since the scan listed them.
+        let removed = Mutex::new(Vec::new());
+        let failures: Mutex<Vec<broken::DecodeFailure>> = Mutex::new(Vec::new());
+        let placeholder = OnceLock::new();
+        let decode_timeout = Duration::from_secs(settings.decode_timeout_seconds);
+        let decoders = share_guard::DecodePool::new(settings.concurrency);
+        // Thumbnails flow to a writer thread through a bounded channel, so
+        // only a few batches are ever held in memory: workers block once it
+        // is full until the writer catches up.
+        let (sender, receiver) = mpsc::sync_channel(WRITE_BATCH_SIZE * 2);
+        let written = thread::scope(|scope| {
+            let writer =
+                scope.spawn(|| write_generated(&mut connection, receiver, &mut thumbnails));
+            // Stops early only when the writer is gone after failing.
+            // Bridged rather than split into ranges, so workers take images
+            // in size order instead of each starting partway through.
+            let _ = settings.install(|| {
+                pending
+                    .into_iter()
+                    .map(|(_, pending_item)| pending_item)
+                    .par_bridge()
+                    .try_for_each_with(sender, |sender, pending_item| {
+                        while generation_paused.load(Ordering::Relaxed)
+                            && !cancel_requested.load(Ordering::Relaxed)
+                        {
+                            thread::sleep(PAUSE_POLL_INTERVAL);
+                        }
+                        if cancel_requested.load(Ordering::Relaxed) {
+                            return Ok(());
+                        }
+                        let image_path = pending_item.image_path.clone();
+                        let cache_key = pending_item.cache_key.clone();
+                        let modified_unix = pending_item.modified_unix;
+                        let generated =
+                            share_guard.run_decode(&decoders, &image_path, decode_timeout, move || {
+                                pending_item.generate(&generator)
+                            });
+                        let error = match generated {
+                            Ok(Some(Ok(value))) => {
+                                metrics.record_generated(value.blob.len());
+                                return sender.send((image_path, value)).map_err(|_| ());
+                            }
+                            Ok(Some(Err(_))) | Err(_) if share_guard.is_missing(&image_path) => {
+                                let payload = GalleryRemoved {
+                                    path: image_path.to_string_lossy().to_string(),
+                                };
+                                if let Err(err) = app.emit("gallery-removed", &payload) {
+                                    log::warn!("Failed to emit gallery removal: {}", err);
+                                }
+                                removed
+                                    .lock()
+                                    .unwrap_or_else(|err| err.into_inner())
+                                    .push(payload.path);
+                                return Ok(());
+                            }
+                            // The file was read but isn't a usable image.
+                            Ok(Some(Err(err))) => err,
+                            Ok(None) => {
+                                format!("Decode timed out after {}s.", decode_timeout.as_secs())
+                            }
+                            Err(err) => {
+                                metrics.record_failure();
+                                log::warn!("Skipping generated thumbnail due to error: {}", err);
+                                return Ok(());
+                            }
+                        };
+                        metrics.record_failure();
+                        log::warn!("Failed to decode {}: {}", image_path.display(), error);
+                        let failure = (image_path.clone(), modified_unix, error);
+                        let blob = placeholder
+                            .get_or_init(|| broken::placeholder(&settings, thumbnail_size));
+                        let sent = match blob {
+                            Ok(blob) => {
+                                let thumbnail = broken::placeholder_thumbnail(
+                                    &failure,
+                                    cache_key,
+                                    &settings,
+                                    thumbnail_size,
+                                    blob.clone(),
+                                );
+                                sender.send((image_path, thumbnail)).map_err(|_| ())
+                            }
+                            Err(err) => {
+                                log::warn!("{}", err);
+                                Ok(())
+                            }
+                        };
+                        failures
+                            .lock()
+                            .unwrap_or_else(|err| err.into_inner())
+                            .push(failure);
+                        sent
+                    })
+            });
+            writer
+                .join()
+                .unwrap_or_else(|_| Err("Thumbnail writer panicked.".to_string()))
+        })?;
 
         if cancel_requested.load(Ordering::Relaxed) {
             cancelled = true;
         }
 
-        if !generated.is_empty() {
-            let tx = connection
-                .transaction()
-                .map_err(|err| format!("Failed to start cache transaction: {err}"))?;
-            for entry in generated {
-                let data_url = data_url_for_blob(&entry.blob, &entry.mime);
-                thumbnails.insert(entry.source_path.clone(), data_url);
-                tx.execute(
-                    "INSERT INTO thumbnails (
-                       cache_key,
-                       source_path,
-                       source_modified_unix,
-                       thumbnail_blob,
-                       mime_type
-                     ) VALUES (?1, ?2, ?3, ?4, ?5)
-                     ON CONFLICT(cache_key) DO UPDATE SET
-                       source_modified_unix = excluded.source_modified_unix,
-                       thumbnail_blob = excluded.thumbnail_blob,
-                       mime_type = excluded.mime_type",
-                    params![
-                        entry.cache_key,
-                        entry.source_path,
-                        entry.modified_unix,
-                        entry.blob,
-                        entry.mime
-                    ],
-                )
-                .map_err(|err| format!("Failed to write cache entry: {err}"))?;
+        let removed = removed.into_inner().unwrap_or_else(|err| err.into_inner());
+        if !removed.is_empty() {
+            for path in &removed {
+                connection.remove(&cache_key_for_path(Path::new(path)))?;
+            }
+            results.retain(|item| !removed.contains(&item.path));
+        }
+
+        let failures = failures.into_inner().unwrap_or_else(|err| err.into_inner());
+        if !failures.is_empty() {
+            broken::record(&connection, &failures)?;
+            let errors: HashMap<String, &String> = failures
+                .iter()
+                .map(|(path, _, error)| (path.to_string_lossy().to_string(), error))
+                .collect();
+            for item in &mut results {
+                if let Some(error) = errors.get(&item.path) {
+                    item.error = Some(error.to_string());
+                }
             }
-            tx.commit()
-                .map_err(|err| format!("Failed to commit cache transaction: {err}"))?;
+        }
+
+        if written > 0 {
+            connection.prune(settings.cache_max_bytes)?;
         }
     }
 
     if skipped_count > 0 {
         log::warn!("Skipped {} image(s) while loading gallery", skipped_count);
     }
+    let resume_token = cancelled.then(|| {
+        let listed: HashSet<&str> = results.iter().map(|item| item.path.as_str()).collect();
+        let ungenerated = pending_paths.into_iter().filter(|path| {
+            let path = path.to_string_lossy();
+            listed.contains(path.as_ref()) && !thumbnails.contains_key(path.as_ref())
+        });
+        let remaining: Vec<PathBuf> = ungenerated.chain(unprocessed).collect();
+        ResumeToken {
+            folder: folder.to_string_lossy().to_string(),
+            thumbnail_size,
+            sort_by: request.sort_by,
+            color_labels: request.color_labels.clone(),
+            position: total.saturating_sub(remaining.len()),
+            total,
+            remaining: remaining
+                .iter()
+                .flat_map(|path| std::iter::once(path).chain(raw_pairs.get(path)))
+                .map(|path| path.to_string_lossy().to_string())
+                .collect(),
+        }
+    });
     Ok(LoadGalleryResponse {
+        total_items: results.len(),
         items: results,
         thumbnails,
         cancelled,
+        error: share_guard.error(&folder),
+        offline: false,
+        session_id: None,
+        resume_token,
     })
 }
 
-fn load_full_image_blocking(path: String) -> Result<String, String> {
+/// Stores thumbnails as they arrive, a batch per transaction, and lists each
+/// in the response. Returns how many were written.
+fn write_generated(
+    connection: &mut Connection,
+    receiver: mpsc::Receiver<(PathBuf, GeneratedThumbnail)>,
+    thumbnails: &mut HashMap<String, String>,
+) -> Result<usize, String> {
+    let mut batch = Vec::with_capacity(WRITE_BATCH_SIZE);
+    let mut written = 0;
+    for (image_path, generated) in receiver {
+        // `source_path` is the cache's spelling of the path, so the gallery's
+        // own path is what the response uses.
+        thumbnails.insert(
+            image_path.to_string_lossy().to_string(),
+            protocol::thumbnail_url(&generated.cache_key, &content_hash(&generated.blob)),
+        );
+        batch.push(generated);
+        if batch.len() == WRITE_BATCH_SIZE {
+            connection.store(&batch)?;
+            written += batch.len();
+            batch.clear();
+        }
+    }
+    if !batch.is_empty() {
+        connection.store(&batch)?;
+        written += batch.len();
+    }
+    Ok(written)
+}
+
+/// With `map_large` set, big originals are mapped instead of read, for
+/// callers that consume the bytes in place.
+fn load_full_image_blocking(
+    path: String,
+    max_dimension: Option<u32>,
+    settings: &settings::Settings,
+    map_large: bool,
+) -> Result<FullImageBytes, String> {
     let image_path = PathBuf::from(path);
-    if !image_path.is_file() {
+    let io_path = extended_path(&image_path);
+    if !io_path.is_file() {
         return Err(format!("{} is not a file.", image_path.display()));
     }
-
-    let image_bytes = fs::read(&image_path)
-        .map_err(|err| format!("Failed to read image {}: {err}", image_path.display()))?;
-    let mime_type = mime_type_for_path(&image_path)
+    let mime_type = settings
+        .scan
+        .mime_type_for_path(&image_path)
         .ok_or_else(|| format!("Unsupported image format: {}", image_path.display()))?;
-    let encoded = base64::engine::general_purpose::STANDARD.encode(image_bytes);
-    Ok(format!("data:{mime_type};base64,{encoded}"))
+
+    if let Some(max_dimension) = max_dimension {
+        let (width, height) = image::ImageReader::open(&io_path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(|err| format!("Failed to read image {}: {err}", image_path.display()))?
+            .into_dimensions()
+            .map_err(|err| format!("Failed to read image {}: {err}", image_path.display()))?;
+        let displayable = mime_type != "image/tiff";
+        if width > max_dimension || height > max_dimension || !displayable {
+            let encoded = decode_image(&image_path, &settings.decode_limits, |image| {
+                let resized = if width > max_dimension || height > max_dimension {
+                    image.resize(max_dimension, max_dimension, FilterType::Lanczos3)
+                } else {
+                    image
+                };
+                encode_for_display(&resized).map_err(|err| {
+                    format!("Failed to encode image {}: {err}", image_path.display())
+                })
+            })??;
+            return Ok(FullImageBytes::Owned(Arc::new(encoded)));
+        }
+    }
+
+    let read_error = |err: std::io::Error| {
+        format!("Failed to read image {}: {err}", image_path.display())
+    };
+    let mut file = retry_io(|| fs::File::open(&io_path)).map_err(read_error)?;
+    let len = file.metadata().map_err(read_error)?.len();
+    if map_large && len >= MMAP_MIN_BYTES {
+        // SAFETY: the mapping is read-only and the file is opened by a
+        // viewer, not written; a file truncated underneath it by another
+        // program is the usual memory-mapping caveat.
+        let map = unsafe { Mmap::map(&file) }.map_err(read_error)?;
+        return Ok(FullImageBytes::Mapped(map));
+    }
+    let mut bytes = Vec::with_capacity(len as usize);
+    file.read_to_end(&mut bytes).map_err(read_error)?;
+    Ok(FullImageBytes::Owned(Arc::new(bytes)))
+}
+
+fn emit_full_image_placeholder(app: &tauri::AppHandle, data_dir: &Path, image_path: &Path) {
+    let cached = open_cache_db(data_dir).ok().and_then(|connection| {
+        let modified_unix = last_modified_unix(image_path).ok()?;
+        connection
+            .get(&cache_key_for_path(image_path), modified_unix, 0)
+            .ok()
+            .flatten()
+    });
+    let placeholder = cached
+        .map(|(blob, mime_type)| data_url_for_blob(&blob, &mime_type))
+        .or_else(|| {
+            let exif = exif_info::read_exif(image_path)?;
+            let thumbnail = exif_info::embedded_thumbnail(&exif)?;
+            Some(data_url_for_blob(thumbnail, "image/jpeg"))
+        });
+    let Some(image) = placeholder else {
+        return;
+    };
+    let payload = FullImagePlaceholder {
+        path: image_path.to_string_lossy().to_string(),
+        image,
+    };
+    if let Err(err) = app.emit("full-image-placeholder", &payload) {
+        log::warn!("Failed to emit full image placeholder: {}", err);
+    }
+}
+
+/// JPEG for opaque images, lossless WebP when there is alpha to preserve.
+fn encode_for_display(image: &DynamicImage) -> Result<Vec<u8>, image::ImageError> {
+    let mut bytes = Vec::new();
+    let mut cursor = Cursor::new(&mut bytes);
+    if image.color().has_alpha() {
+        let rgba = image.to_rgba8();
+        WebPEncoder::new_lossless(&mut cursor).write_image(
+            &rgba,
+            rgba.width(),
+            rgba.height(),
+            ColorType::Rgba8.into(),
+        )?;
+    } else {
+        let rgb = image.to_rgb8();
+        JpegEncoder::new_with_quality(&mut cursor, FULL_IMAGE_JPEG_QUALITY).write_image(
+            &rgb,
+            rgb.width(),
+            rgb.height(),
+            ColorType::Rgb8.into(),
+        )?;
+    }
+    Ok(bytes)
 }
 
 fn load_thumbnail_blocking(
     data_dir: PathBuf,
     path: String,
     thumbnail_size: u32,
-) -> Result<String, String> {
+    settings: &settings::Settings,
+    metrics: &session_metrics::SessionMetrics,
+    flights: &ThumbnailFlights,
+) -> Result<(Vec<u8>, String), String> {
     let image_path = PathBuf::from(path);
-    if !image_path.is_file() {
+    if !extended_path(&image_path).is_file() {
         return Err(format!("{} is not a file.", image_path.display()));
     }
-    if !is_supported_image(&image_path) {
+    if !settings.scan.is_supported_image(&image_path) {
         return Err(format!("Unsupported image format: {}", image_path.display()));
     }
 
-    let db_path = data_dir.join(DB_FILE_NAME);
-    let connection =
-        Connection::open(db_path).map_err(|err| format!("Failed to open cache database: {err}"))?;
-    init_schema(&connection)?;
-
-    let modified_unix = last_modified_unix(&image_path)?;
-    let cache_key = cache_key_for_path(&image_path);
-    let cached: Option<(Vec<u8>, String)> = connection
-        .query_row(
-            "SELECT thumbnail_blob, mime_type
-             FROM thumbnails
-             WHERE cache_key = ?1 AND source_modified_unix = ?2",
-            params![cache_key, modified_unix],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )
-        .optional()
-        .map_err(|err| format!("Failed to read cache entry: {err}"))?;
-
-    let (thumbnail_blob, mime_type) = match cached {
-        Some((blob, mime)) => (blob, mime),
-        None => {
-            let (blob, mime) = generate_thumbnail_blob(&image_path, thumbnail_size)?;
-            connection
-                .execute(
-                    "INSERT INTO thumbnails (
-                       cache_key,
-                       source_path,
-                       source_modified_unix,
-                       thumbnail_blob,
-                       mime_type
-                     ) VALUES (?1, ?2, ?3, ?4, ?5)
-                     ON CONFLICT(cache_key) DO UPDATE SET
-                       source_modified_unix = excluded.source_modified_unix,
-                       thumbnail_blob = excluded.thumbnail_blob,
-                       mime_type = excluded.mime_type",
-                    params![
-                        cache_key,
-                        image_path.to_string_lossy().to_string(),
-                        modified_unix,
-                        blob,
-                        mime
-                    ],
-                )
-                .map_err(|err| format!("Failed to write cache entry: {err}"))?;
-            (blob, mime)
+    let pending = PendingThumbnail::for_path(&image_path)?;
+    // The grid can ask for a thumbnail again before the first request has
+    // made it; those wait for it rather than decoding the image twice.
+    let flight_key = format!(
+        "{}:{}:{thumbnail_size}",
+        pending.cache_key, pending.modified_unix
+    );
+    flights.run(&flight_key, move || {
+        let mut connection = open_cache_db(&data_dir)?;
+        let cached = connection.get(&pending.cache_key, pending.modified_unix, thumbnail_size)?;
+        if let Some(cached) = cached {
+            metrics.record_hit();
+            return Ok(cached);
         }
-    };
+        metrics.record_miss();
+        let generated = pending
+            .generate(&settings.generator(thumbnail_size))
+            .inspect_err(|_| metrics.record_failure())?;
+        metrics.record_generated(generated.blob.len());
+        connection.store(std::slice::from_ref(&generated))?;
+        connection.prune(settings.cache_max_bytes)?;
+        Ok((generated.blob, generated.mime))
+    })
+}
 
-    let encoded = base64::engine::general_purpose::STANDARD.encode(thumbnail_blob);
-    Ok(format!("data:{mime_type};base64,{encoded}"))
+/// Stable, so items with the same date stay in path order.
+fn sort_items(items: &mut [GalleryItem], sort_by: settings::SortBy) {
+    match sort_by {
+        settings::SortBy::Name => {}
+        settings::SortBy::Modified => items.sort_by_key(|item| item.modified_unix),
+        settings::SortBy::Captured => {
+            items.sort_by_key(|item| item.captured_unix.unwrap_or(item.modified_unix))
+        }
+        // Unknown sizes go last rather than first.
+        settings::SortBy::Size => {
+            items.sort_by_key(|item| (item.size_bytes.is_none(), item.size_bytes))
+        }
+    }
 }
 
+/// Cached thumbnails are used even for online-only files, which keep theirs
+/// after the sync client frees up space.
 fn prepare_single_image(
     connection: &Connection,
     image_path: &Path,
+    thumbnail_size: u32,
+    cloud_files: cloud_files::CloudFiles,
 ) -> Result<(GalleryItem, Option<PendingThumbnail>, Option<String>), String> {
-    let modified_unix = last_modified_unix(image_path)?;
-    let cache_key = cache_key_for_path(image_path);
-    let cached: Option<(Vec<u8>, String)> = connection
-        .query_row(
-            "SELECT thumbnail_blob, mime_type
-             FROM thumbnails
-             WHERE cache_key = ?1 AND source_modified_unix = ?2",
-            params![cache_key, modified_unix],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )
-        .optional()
-        .map_err(|err| format!("Failed to read cache entry: {err}"))?;
+    let pending = PendingThumbnail::for_path(image_path)?;
 
-    let item = GalleryItem {
+    let mut item = GalleryItem {
         name: image_path
             .file_name()
             .map(|name| name.to_string_lossy().to_string())
             .unwrap_or_else(|| "image".to_string()),
         path: image_path.to_string_lossy().to_string(),
+        cloud: false,
+        modified_unix: pending.modified_unix,
+        size_bytes: None,
+        captured_unix: None,
+        has_motion: false,
+        video: None,
+        pair_path: None,
+        error: broken::cached(connection, image_path, pending.modified_unix)?,
+        caption: captions::cached(connection, &pending.cache_key)?,
     };
 
-    if let Some((blob, mime_type)) = cached {
-        let data_url = data_url_for_blob(&blob, &mime_type);
-        return Ok((item, None, Some(data_url)));
+    if let Some(hash) =
+        connection.content_hash(&pending.cache_key, pending.modified_unix, thumbnail_size)?
+    {
+        let thumbnail_url = protocol::thumbnail_url(&pending.cache_key, &hash);
+        return Ok((item, None, Some(thumbnail_url)));
+    }
+    if !cloud_files.allows_generation(image_path) {
+        item.cloud = true;
+        return Ok((item, None, None));
     }
 
-    Ok((
-        item,
-        Some(PendingThumbnail {
-            image_path: image_path.to_path_buf(),
-            cache_key,
-            modified_unix,
-        }),
-        None,
-    ))
+    Ok((item, Some(pending), None))
 }
 
-fn generate_pending_thumbnail(
-    pending: PendingThumbnail,
-    thumbnail_size: u32,
-) -> Result<GeneratedThumbnail, String> {
-    let (blob, mime) = generate_thumbnail_blob(&pending.image_path, thumbnail_size)?;
-    Ok(GeneratedThumbnail {
-        cache_key: pending.cache_key,
-        source_path: pending.image_path.to_string_lossy().to_string(),
-        modified_unix: pending.modified_unix,
-        blob,
-        mime,
-    })
+/// The database is in write-ahead log mode, so thumbnail requests and other
+/// readers don't wait on a gallery load's writes.
+fn open_cache_db(data_dir: &Path) -> Result<Connection, String> {
+    let db_path = data_dir.join(DB_FILE_NAME);
+    let connection =
+        Connection::open(db_path).map_err(|err| format!("Failed to open cache database: {err}"))?;
+    connection
+        .query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))
+        .map_err(|err| format!("Failed to enable write-ahead logging: {err}"))?;
+    init_schema(&connection)?;
+    Ok(connection)
 }
 
+/// The thumbnail cache's own table comes from `thumbnailer_core`; the rest
+/// belong to the app.
 fn init_schema(connection: &Connection) -> Result<(), String> {
+    thumbnailer_core::cache::init_schema(connection)?;
     connection
         .execute_batch(
-            "CREATE TABLE IF NOT EXISTS thumbnails (
-               cache_key TEXT PRIMARY KEY,
+            "CREATE TABLE IF NOT EXISTS undo_operations (
+               id INTEGER PRIMARY KEY AUTOINCREMENT,
+               kind TEXT NOT NULL,
+               created_unix INTEGER NOT NULL,
+               undone INTEGER NOT NULL DEFAULT 0
+             );
+             CREATE TABLE IF NOT EXISTS undo_entries (
+               operation_id INTEGER NOT NULL,
                source_path TEXT NOT NULL,
+               target_path TEXT
+             );
+             CREATE INDEX IF NOT EXISTS undo_entries_operation
+               ON undo_entries (operation_id);
+             CREATE TABLE IF NOT EXISTS image_tiles (
+               cache_key TEXT NOT NULL,
                source_modified_unix INTEGER NOT NULL,
-               thumbnail_blob BLOB NOT NULL,
-               mime_type TEXT NOT NULL
+               level INTEGER NOT NULL,
+               tile_x INTEGER NOT NULL,
+               tile_y INTEGER NOT NULL,
+               tile_blob BLOB NOT NULL,
+               PRIMARY KEY (cache_key, level, tile_x, tile_y)
+             );
+             CREATE TABLE IF NOT EXISTS settings (
+               key TEXT PRIMARY KEY,
+               value TEXT NOT NULL
+             );
+             -- `trusted` is 0 for folders restored from a library backup
+             -- until the user opens them again.
+             CREATE TABLE IF NOT EXISTS recent_folders (
+               path TEXT PRIMARY KEY,
+               opened_unix INTEGER NOT NULL,
+               pinned INTEGER NOT NULL DEFAULT 0,
+               trusted INTEGER NOT NULL DEFAULT 1
+             );
+             CREATE TABLE IF NOT EXISTS image_ratings (
+               path TEXT PRIMARY KEY,
+               rating INTEGER NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS tags (
+               id INTEGER PRIMARY KEY AUTOINCREMENT,
+               name TEXT NOT NULL UNIQUE
+             );
+             CREATE TABLE IF NOT EXISTS image_tags (
+               path TEXT NOT NULL,
+               tag_id INTEGER NOT NULL,
+               PRIMARY KEY (path, tag_id)
+             );
+             CREATE TABLE IF NOT EXISTS albums (
+               id INTEGER PRIMARY KEY AUTOINCREMENT,
+               name TEXT NOT NULL UNIQUE
+             );
+             CREATE TABLE IF NOT EXISTS album_images (
+               album_id INTEGER NOT NULL,
+               path TEXT NOT NULL,
+               PRIMARY KEY (album_id, path)
+             );
+             CREATE TABLE IF NOT EXISTS checksums (
+               path TEXT PRIMARY KEY,
+               size_bytes INTEGER NOT NULL,
+               modified_unix INTEGER NOT NULL,
+               sha256 TEXT NOT NULL,
+               verified_unix INTEGER NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS folder_stats (
+               path TEXT PRIMARY KEY,
+               fingerprint TEXT NOT NULL,
+               stats TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS capture_dates (
+               path TEXT PRIMARY KEY,
+               modified_unix INTEGER NOT NULL,
+               captured_unix INTEGER
+             );
+             CREATE TABLE IF NOT EXISTS video_info (
+               path TEXT PRIMARY KEY,
+               modified_unix INTEGER NOT NULL,
+               duration_seconds REAL NOT NULL,
+               width INTEGER NOT NULL,
+               height INTEGER NOT NULL,
+               codec TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS image_places (
+               path TEXT PRIMARY KEY,
+               modified_unix INTEGER NOT NULL,
+               latitude REAL,
+               longitude REAL,
+               place TEXT
+             );
+             CREATE VIRTUAL TABLE IF NOT EXISTS place_search USING fts5(path UNINDEXED, place);
+             CREATE TABLE IF NOT EXISTS image_dimensions (
+               path TEXT PRIMARY KEY,
+               modified_unix INTEGER NOT NULL,
+               width INTEGER NOT NULL,
+               height INTEGER NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS face_scans (
+               path TEXT PRIMARY KEY,
+               modified_unix INTEGER NOT NULL,
+               face_count INTEGER NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS face_regions (
+               path TEXT NOT NULL,
+               x INTEGER NOT NULL,
+               y INTEGER NOT NULL,
+               width INTEGER NOT NULL,
+               height INTEGER NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS face_regions_path ON face_regions (path);
+             CREATE TABLE IF NOT EXISTS rating_scans (
+               path TEXT PRIMARY KEY,
+               modified_unix INTEGER NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS auto_imports (
+               source_path TEXT NOT NULL,
+               size_bytes INTEGER NOT NULL,
+               modified_unix INTEGER NOT NULL,
+               target_path TEXT NOT NULL,
+               imported_unix INTEGER NOT NULL,
+               PRIMARY KEY (source_path, size_bytes, modified_unix)
+             );
+             CREATE TABLE IF NOT EXISTS image_labels (
+               path TEXT PRIMARY KEY,
+               label TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS image_captions (
+               cache_key TEXT PRIMARY KEY,
+               path TEXT NOT NULL,
+               caption TEXT NOT NULL
+             );
+             CREATE VIRTUAL TABLE IF NOT EXISTS caption_search
+               USING fts5(cache_key UNINDEXED, path UNINDEXED, caption);
+             CREATE TABLE IF NOT EXISTS decode_failures (
+               path TEXT PRIMARY KEY,
+               modified_unix INTEGER NOT NULL,
+               error TEXT NOT NULL
+             );
+             -- The copy of an image kept from before its first overwriting
+             -- edit, by the image's cache_path. Later edits keep the row,
+             -- restore_original drops it with the copy, deleting the image
+             -- sends the copy to the trash with it, and moves and renames
+             -- carry it along.
+             CREATE TABLE IF NOT EXISTS edit_originals (
+               path TEXT PRIMARY KEY,
+               backup_path TEXT NOT NULL,
+               archived_unix INTEGER NOT NULL
              );",
         )
-        .map_err(|err| format!("Failed to initialize database schema: {err}"))
-}
-
-fn collect_supported_images(folder: &Path) -> Result<Vec<PathBuf>, String> {
-    let mut images = Vec::new();
-    let mut directories = vec![folder.to_path_buf()];
-
-    while let Some(current_dir) = directories.pop() {
-        let entries = match fs::read_dir(&current_dir) {
-            Ok(value) => value,
-            Err(err) => {
-                log::warn!(
-                    "Skipping unreadable directory while scanning ({}): {}",
-                    current_dir.display(),
-                    err
-                );
-                continue;
-            }
-        };
-
-        for entry in entries {
-            let entry = match entry {
-                Ok(value) => value,
-                Err(err) => {
-                    log::warn!("Skipping unreadable folder entry: {}", err);
-                    continue;
-                }
-            };
-            let path = entry.path();
-            if path.is_dir() {
-                directories.push(path);
-                continue;
-            }
-            if path.is_file() && is_supported_image(&path) {
-                images.push(path);
-            }
-        }
-    }
-
-    Ok(images)
-}
-
-fn is_supported_image(path: &Path) -> bool {
-    match path.extension().and_then(|ext| ext.to_str()) {
-        Some(ext) => matches!(
-            ext.to_ascii_lowercase().as_str(),
-            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" | "tif" | "tiff"
-        ),
-        None => false,
+        .map_err(|err| format!("Failed to initialize database schema: {err}"))?;
+    // Databases from before restored folders were told apart; their recent
+    // folders were all opened here.
+    let has_trusted = connection
+        .prepare("SELECT 1 FROM pragma_table_info('recent_folders') WHERE name = 'trusted'")
+        .and_then(|mut statement| statement.exists([]))
+        .map_err(|err| format!("Failed to read database schema: {err}"))?;
+    if !has_trusted {
+        connection
+            .execute_batch(
+                "ALTER TABLE recent_folders ADD COLUMN trusted INTEGER NOT NULL DEFAULT 1;",
+            )
+            .map_err(|err| format!("Failed to upgrade database schema: {err}"))?;
     }
-}
-
-fn mime_type_for_path(path: &Path) -> Option<&'static str> {
-    match path.extension().and_then(|ext| ext.to_str()) {
-        Some(ext) => match ext.to_ascii_lowercase().as_str() {
-            "jpg" | "jpeg" => Some("image/jpeg"),
-            "png" => Some("image/png"),
-            "gif" => Some("image/gif"),
-            "bmp" => Some("image/bmp"),
-            "webp" => Some("image/webp"),
-            "tif" | "tiff" => Some("image/tiff"),
-            _ => None,
-        },
-        None => None,
+    let version: i64 = connection
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|err| format!("Failed to read database schema version: {err}"))?;
+    if version == SCHEMA_VERSION {
+        return Ok(());
     }
+    connection
+        .pragma_update(None, "user_version", SCHEMA_VERSION)
+        .map_err(|err| format!("Failed to record database schema version: {err}"))
 }
 
-fn last_modified_unix(path: &Path) -> Result<i64, String> {
-    let metadata =
-        fs::metadata(path).map_err(|err| format!("Failed to read metadata for {}: {err}", path.display()))?;
-    let modified = metadata
-        .modified()
-        .map_err(|err| format!("Failed to read modified time for {}: {err}", path.display()))?;
-    let duration = modified
+fn now_unix() -> i64 {
+    std::time::SystemTime::now()
         .duration_since(UNIX_EPOCH)
-        .map_err(|err| format!("Invalid modified time for {}: {err}", path.display()))?;
-    Ok(duration.as_secs() as i64)
-}
-
-fn cache_key_for_path(path: &Path) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(path.to_string_lossy().as_bytes());
-    format!("{:x}", hasher.finalize())
-}
-
-fn generate_thumbnail_blob(path: &Path, thumbnail_size: u32) -> Result<(Vec<u8>, String), String> {
-    let image = image::open(path)
-        .map_err(|err| format!("Failed to open image {}: {err}", path.display()))?;
-    let thumbnail = image.thumbnail(thumbnail_size, thumbnail_size);
-    let rgba = thumbnail.to_rgba8();
-    let (width, height) = thumbnail.dimensions();
-    let mut png_bytes = Vec::new();
-    {
-        let mut cursor = Cursor::new(&mut png_bytes);
-        let encoder = PngEncoder::new(&mut cursor);
-        encoder
-            .write_image(&rgba, width, height, ColorType::Rgba8.into())
-            .map_err(|err| format!("Failed to encode thumbnail {}: {err}", path.display()))?;
-    }
-    Ok((png_bytes, "image/png".to_string()))
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
 }
 
 fn data_url_for_blob(blob: &[u8], mime_type: &str) -> String {
@@ -525,28 +1408,184 @@ fn data_url_for_blob(blob: &[u8], mime_type: &str) -> String {
     format!("data:{mime_type};base64,{encoded}")
 }
 
+/// Handles `--generate <folder> [--size <pixels>] [--recursive]`, which warms
+/// the thumbnail cache without opening a window. Returns the exit code, or
+/// `None` when the app should start normally.
+pub fn run_headless() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    cli::run(&args)
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
+    let builder = tauri::Builder::default();
+    // Must be the first plugin so a second launch exits before doing any work.
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+        open_request::forward_launch(app, args, cwd)
+    }));
+    builder
         .manage(AppState::default())
+        .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_dialog::init())
+        .register_asynchronous_uri_scheme_protocol(
+            protocol::THUMBNAIL_SCHEME,
+            |ctx, request, responder| {
+                protocol::handle_thumbnail_request(ctx.app_handle(), request, responder)
+            },
+        )
         .setup(|app| {
-            if cfg!(debug_assertions) {
-                app.handle().plugin(
-                    tauri_plugin_log::Builder::default()
-                        .level(log::LevelFilter::Info)
-                        .build(),
-                )?;
+            let loaded = resolve_data_dir(app.handle())
+                .and_then(|data_dir| open_cache_db(&data_dir))
+                .and_then(|connection| settings::load(&connection));
+            let log_level = loaded.as_ref().map(|value| value.log_level).unwrap_or_default();
+            if let Err(err) = logging::init(app.handle(), log_level) {
+                eprintln!("{}", err);
+            }
+            if let Err(err) = recovery::recover(app.handle()) {
+                log::warn!("Failed to recover from the previous session: {}", err);
+            }
+            places::init(app.handle());
+            match loaded {
+                Ok(value) => app.state::<AppState>().settings.replace(value),
+                Err(err) => log::warn!("Failed to load settings, using defaults: {}", err),
+            }
+            {
+                let state = app.state::<AppState>();
+                let cwd = std::env::current_dir().unwrap_or_default();
+                let args = std::env::args().skip(1);
+                *state
+                    .startup_options
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner()) =
+                    open_request::StartupOptions::parse(args, &cwd, &state.settings.get());
+            }
+            os_recents::warm(app.handle());
+            auto_import::start(app.handle());
+            // Installed builds register the scheme in the bundle; this covers
+            // development builds and AppImages.
+            #[cfg(any(windows, target_os = "linux"))]
+            if let Err(err) = app.deep_link().register_all() {
+                log::warn!("Failed to register deep link scheme: {}", err);
+            }
+            if let Err(err) = tray::create(app.handle()) {
+                log::warn!("{}", err);
             }
+            #[cfg(desktop)]
+            {
+                let shortcut = app.state::<AppState>().settings.get().global_shortcut;
+                if let Err(err) = shortcut::init(app.handle(), shortcut.as_deref()) {
+                    log::warn!("{}", err);
+                }
+            }
+            let handle = app.handle().clone();
+            app.deep_link().on_open_url(move |event| {
+                open_request::open_deep_links(&handle, &event.urls())
+            });
             Ok(())
         })
+        .on_window_event(|window, event| match event {
+            tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) => {
+                open_request::handle_drop(window, paths)
+            }
+            tauri::WindowEvent::Destroyed => {
+                window.state::<AppState>().viewer_windows.forget(window.label())
+            }
+            _ => {}
+        })
         .invoke_handler(tauri::generate_handler![
-            get_initial_folder,
+            open_request::get_initial_open_request,
+            open_request::get_startup_options,
+            dialogs::pick_folder,
+            dialogs::pick_file,
+            dialogs::pick_save_path,
+            dialogs::pick_editor,
             load_gallery,
+            load_gallery_resume,
             load_full_image,
+            stream_full_image,
             cancel_gallery_scan,
-            load_thumbnail
+            get_last_scan_timings,
+            logging::get_log_path,
+            session_metrics::get_session_metrics,
+            recovery::get_abnormal_exit,
+            load_thumbnail,
+            shell::reveal_in_file_manager,
+            shell::open_with,
+            shell::stop_watching_file,
+            shell::set_wallpaper,
+            file_ops::delete_files,
+            file_ops::move_files,
+            file_ops::copy_files,
+            file_ops::rename_file,
+            file_ops::undo_last_operation,
+            file_ops::list_trashed_items,
+            file_ops::restore_from_trash,
+            file_ops::sync_mtime_from_exif,
+            folder_cover::get_folder_cover,
+            folder_stats::get_folder_stats,
+            gallery_sessions::get_items_range,
+            http_server::start_http_server,
+            http_server::stop_http_server,
+            tiles::get_image_tile_info,
+            tiles::get_image_tiles,
+            tiles::load_image_region,
+            motion::get_motion_clip,
+            video::get_video_frame,
+            preview_cache::preload_images,
+            settings::get_settings,
+            settings::set_settings,
+            edit::edit_image,
+            edit::restore_original,
+            geotag::set_geotag,
+            places::search_places,
+            faces::filter_with_faces,
+            broken::filter_broken,
+            faces::get_face_regions,
+            faces::load_face_thumbnail,
+            ratings::get_ratings,
+            ratings::set_rating,
+            captions::set_caption,
+            captions::search_captions,
+            labels::get_color_labels,
+            labels::set_color_label,
+            export::export_images,
+            export::export_zip,
+            manifest::export_manifest,
+            verify::verify_folder,
+            compare::compare_images,
+            culling::start_compare,
+            culling::set_compare_flag,
+            culling::get_compare_session,
+            culling::finish_compare,
+            cache_health::check_cache_health,
+            cache_health::fix_cache_health,
+            benchmark::run_benchmark,
+            library_import::import_library,
+            library_backup::backup_library_db,
+            library_backup::restore_library_db,
+            device_import::list_devices,
+            device_import::list_device_images,
+            device_import::import_from_device,
+            pdf::export_pdf,
+            viewer::open_in_new_window,
+            viewer::get_viewer_image,
+            recent_folders::get_recent_folders,
+            recent_folders::set_folder_pinned,
+            selection::set_selection,
+            selection::get_selection_summary
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                recovery::end_session(app);
+            }
+            // Files opened from Finder arrive as an event rather than
+            // arguments.
+            #[cfg(target_os = "macos")]
+            if let tauri::RunEvent::Opened { urls } = event {
+                open_request::open_file_urls(app, &urls);
+            }
+        });
 }
@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Keeps a star rating unless the image already has one.
+pub(crate) fn add_rating(connection: &Connection, path: &str, rating: u32) -> Result<bool, String> {
+    connection
+        .execute(
+            "INSERT INTO image_ratings (path, rating) VALUES (?1, ?2)
+             ON CONFLICT(path) DO NOTHING",
+            params![path, rating.min(5)],
+        )
+        .map(|changed| changed > 0)
+        .map_err(|err| format!("Failed to write rating: {err}"))
+}
+
+pub(crate) fn add_tag(connection: &Connection, path: &str, tag: &str) -> Result<bool, String> {
+    let tag_id = named_id(connection, "tags", tag)?;
+    connection
+        .execute(
+            "INSERT OR IGNORE INTO image_tags (path, tag_id) VALUES (?1, ?2)",
+            params![path, tag_id],
+        )
+        .map(|changed| changed > 0)
+        .map_err(|err| format!("Failed to write tag: {err}"))
+}
+
+pub(crate) fn add_to_album(
+    connection: &Connection,
+    path: &str,
+    album: &str,
+) -> Result<bool, String> {
+    let album_id = named_id(connection, "albums", album)?;
+    connection
+        .execute(
+            "INSERT OR IGNORE INTO album_images (album_id, path) VALUES (?1, ?2)",
+            params![album_id, path],
+        )
+        .map(|changed| changed > 0)
+        .map_err(|err| format!("Failed to write album entry: {err}"))
+}
+
+/// Carries ratings, tags and album membership over to a moved or renamed
+/// file.
+pub(crate) fn relocate(
+    connection: &Connection,
+    source: &Path,
+    target: &Path,
+) -> Result<(), String> {
+    let (source, target) = (source.to_string_lossy(), target.to_string_lossy());
+    for table in [
+        "image_ratings",
+        "rating_scans",
+        "image_tags",
+        "album_images",
+        "image_labels",
+    ] {
+        connection
+            .execute(
+                &format!("UPDATE OR REPLACE {table} SET path = ?1 WHERE path = ?2"),
+                params![target, source],
+            )
+            .map_err(|err| format!("Failed to move library entries: {err}"))?;
+    }
+    Ok(())
+}
+
+/// Id of the tag or album called `name`, creating it if needed. `table` is
+/// one of our own table names, never user input.
+fn named_id(connection: &Connection, table: &str, name: &str) -> Result<i64, String> {
+    let existing = connection
+        .query_row(
+            &format!("SELECT id FROM {table} WHERE name = ?1"),
+            params![name],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to look up {name}: {err}"))?;
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+    connection
+        .execute(
+            &format!("INSERT INTO {table} (name) VALUES (?1)"),
+            params![name],
+        )
+        .map_err(|err| format!("Failed to create {name}: {err}"))?;
+    Ok(connection.last_insert_rowid())
+}
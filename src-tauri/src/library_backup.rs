@@ -0,0 +1,196 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::{captions, init_schema, open_cache_db, resolve_data_dir, AppState, SCHEMA_VERSION};
+
+/// The user's own organization work, with the columns copied. Everything
+/// else in the database, thumbnails included, can be rebuilt from the
+/// files. `edit_originals` is left out too: it points at backups of edited
+/// originals in this machine's data folder, which the file doesn't carry.
+const LIBRARY_TABLES: [(&str, &str); 8] = [
+    ("image_ratings", "path, rating"),
+    ("image_labels", "path, label"),
+    ("image_captions", "cache_key, path, caption"),
+    ("tags", "id, name"),
+    ("image_tags", "path, tag_id"),
+    ("albums", "id, name"),
+    ("album_images", "album_id, path"),
+    // Pinned folders are the app's favorites.
+    ("recent_folders", "path, opened_unix, pinned"),
+];
+/// Library tables that backups from older versions of the app lack.
+const LATER_TABLES: [&str; 2] = ["image_labels", "image_captions"];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LibraryCounts {
+    ratings: usize,
+    tags: usize,
+    albums: usize,
+    folders: usize,
+}
+
+/// Writes ratings, color labels, captions, tags, albums and recent and
+/// pinned folders to `destination`, replacing it. The backup is a database
+/// with the app's own schema and an empty thumbnail cache, so it stays small
+/// and can be restored on another machine.
+#[tauri::command]
+pub(crate) async fn backup_library_db(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    destination: String,
+) -> Result<LibraryCounts, String> {
+    state.path_scope.check(Path::new(&destination))?;
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let destination = PathBuf::from(destination);
+        let mut partial = destination.clone().into_os_string();
+        partial.push(".part");
+        let partial = PathBuf::from(partial);
+        if partial.exists() {
+            fs::remove_file(&partial)
+                .map_err(|err| format!("Failed to remove {}: {err}", partial.display()))?;
+        }
+        init_schema(
+            &Connection::open(&partial)
+                .map_err(|err| format!("Failed to create {}: {err}", partial.display()))?,
+        )?;
+
+        let connection = open_cache_db(&data_dir)?;
+        attach(&connection, &partial)?;
+        let copied = copy_tables(&connection, "main", "backup");
+        detach(&connection)?;
+        if let Err(err) = copied {
+            let _ = fs::remove_file(&partial);
+            return Err(err);
+        }
+        fs::rename(&partial, &destination)
+            .map_err(|err| format!("Failed to write {}: {err}", destination.display()))?;
+        counts(&connection, "main")
+    })
+    .await
+    .map_err(|err| format!("Failed to join library backup task: {err}"))?
+}
+
+/// Replaces ratings, color labels, captions, tags, albums and recent and
+/// pinned folders with those in a file written by `backup_library_db`.
+/// Nothing is merged; what was here before is gone. Edit originals are kept,
+/// since they belong to this machine.
+#[tauri::command]
+pub(crate) async fn restore_library_db(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    source: String,
+) -> Result<LibraryCounts, String> {
+    state.path_scope.check(Path::new(&source))?;
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let source = PathBuf::from(source);
+        if !source.is_file() {
+            return Err(format!("{} does not exist.", source.display()));
+        }
+        let connection = open_cache_db(&data_dir)?;
+        attach(&connection, &source)?;
+        let restored = check_backup(&connection)
+            .and_then(|()| copy_tables(&connection, "backup", "main"))
+            .and_then(|()| captions::reindex(&connection))
+            .and_then(|()| counts(&connection, "main"));
+        detach(&connection)?;
+        restored
+    })
+    .await
+    .map_err(|err| format!("Failed to join library restore task: {err}"))?
+}
+
+fn attach(connection: &Connection, path: &Path) -> Result<(), String> {
+    connection
+        .execute(
+            "ATTACH DATABASE ?1 AS backup",
+            params![path.to_string_lossy()],
+        )
+        .map(|_| ())
+        .map_err(|err| format!("Failed to open {}: {err}", path.display()))
+}
+
+/// Closes the attached file, which Windows won't let us rename while open.
+fn detach(connection: &Connection) -> Result<(), String> {
+    connection
+        .execute_batch("DETACH DATABASE backup")
+        .map_err(|err| format!("Failed to close library backup: {err}"))
+}
+
+/// Backups from newer versions of the app may lay the tables out
+/// differently, so they are refused rather than half restored.
+fn check_backup(connection: &Connection) -> Result<(), String> {
+    let version: i64 = connection
+        .query_row("PRAGMA backup.user_version", [], |row| row.get(0))
+        .map_err(|err| format!("Failed to read library backup: {err}"))?;
+    if version > SCHEMA_VERSION {
+        return Err("The backup was made by a newer version of the app.".to_string());
+    }
+    for (table, _) in LIBRARY_TABLES {
+        if !LATER_TABLES.contains(&table) && !has_table(connection, "backup", table)? {
+            return Err("Not a library backup.".to_string());
+        }
+    }
+    Ok(())
+}
+
+fn has_table(connection: &Connection, schema: &str, table: &str) -> Result<bool, String> {
+    connection
+        .query_row(
+            &format!(
+                "SELECT COUNT(*) FROM {schema}.sqlite_master WHERE type = 'table' AND name = ?1"
+            ),
+            params![table],
+            |row| row.get::<_, i64>(0),
+        )
+        .map(|present| present > 0)
+        .map_err(|err| format!("Failed to read library backup: {err}"))
+}
+
+/// Replaces the library tables in the `to` schema with those in `from`, in
+/// one transaction. A table `from` lacks is left empty. Copied folders come
+/// from a file rather than from the user picking them, so they aren't
+/// trusted with the path scope until they are picked again.
+fn copy_tables(connection: &Connection, from: &str, to: &str) -> Result<(), String> {
+    let mut batch = String::from("BEGIN;");
+    for (table, columns) in LIBRARY_TABLES {
+        batch.push_str(&format!("DELETE FROM {to}.{table};"));
+        if has_table(connection, from, table)? {
+            batch.push_str(&format!(
+                "INSERT INTO {to}.{table} ({columns}) SELECT {columns} FROM {from}.{table};"
+            ));
+        }
+    }
+    batch.push_str(&format!("UPDATE {to}.recent_folders SET trusted = 0;"));
+    batch.push_str("COMMIT;");
+    connection.execute_batch(&batch).map_err(|err| {
+        let _ = connection.execute_batch("ROLLBACK");
+        format!("Failed to copy library tables: {err}")
+    })
+}
+
+fn counts(connection: &Connection, schema: &str) -> Result<LibraryCounts, String> {
+    let count = |table: &str| {
+        connection
+            .query_row(
+                &format!("SELECT COUNT(*) FROM {schema}.{table}"),
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|count| count as usize)
+            .map_err(|err| format!("Failed to count {table}: {err}"))
+    };
+    Ok(LibraryCounts {
+        ratings: count("image_ratings")?,
+        tags: count("tags")?,
+        albums: count("albums")?,
+        folders: count("recent_folders")?,
+    })
+}
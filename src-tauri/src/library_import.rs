@@ -0,0 +1,294 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+
+use crate::{library, open_cache_db, resolve_data_dir, AppState};
+
+/// Root of digiKam's internal bookkeeping tags, which aren't user tags.
+const DIGIKAM_INTERNAL_TAGS: &str = "_Digikam_Internal_Tags_";
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LibraryKind {
+    Digikam,
+    Shotwell,
+}
+
+/// Rewrites paths recorded under `from` to live under `to`, for libraries
+/// whose photos have moved since.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PathRemap {
+    from: String,
+    to: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportSummary {
+    kind: LibraryKind,
+    /// Images found on disk.
+    matched: usize,
+    /// Images the database knows about that no longer exist.
+    missing: usize,
+    ratings: usize,
+    tags: usize,
+    albums: usize,
+}
+
+#[derive(Default)]
+struct LibraryEntry {
+    path: PathBuf,
+    rating: Option<u32>,
+    tags: Vec<String>,
+    albums: Vec<String>,
+}
+
+/// Imports ratings, tags and albums from a digiKam (`digikam4.db`) or
+/// Shotwell (`photo.db`) database, detected from its tables. Images are
+/// matched by path; ratings already set here are kept.
+/// `database` must be in the path scope, as `pick_file` leaves it.
+#[tauri::command]
+pub(crate) async fn import_library(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    database: String,
+    remap: Option<PathRemap>,
+) -> Result<ImportSummary, String> {
+    state.path_scope.check(Path::new(&database))?;
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let source = Connection::open_with_flags(
+            &database,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(|err| format!("Failed to open {database}: {err}"))?;
+        let kind = detect_kind(&source)?;
+        let entries = match kind {
+            LibraryKind::Digikam => read_digikam(&source)?,
+            LibraryKind::Shotwell => read_shotwell(&source)?,
+        };
+        let mut connection = open_cache_db(&data_dir)?;
+        apply(&mut connection, kind, entries, remap.as_ref())
+    })
+    .await
+    .map_err(|err| format!("Failed to join library import task: {err}"))?
+}
+
+fn detect_kind(source: &Connection) -> Result<LibraryKind, String> {
+    let has_table = |name: &str| {
+        source
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                [name],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|count| count > 0)
+            .map_err(|err| format!("Failed to inspect database: {err}"))
+    };
+    if has_table("AlbumRoots")? && has_table("Images")? {
+        Ok(LibraryKind::Digikam)
+    } else if has_table("PhotoTable")? {
+        Ok(LibraryKind::Shotwell)
+    } else {
+        Err("Not a digiKam or Shotwell database.".to_string())
+    }
+}
+
+/// digiKam albums are plain folders, so only ratings and tags are taken.
+/// Hierarchical tags are imported as `Parent/Child`.
+fn read_digikam(source: &Connection) -> Result<Vec<LibraryEntry>, String> {
+    let query_error = |err: rusqlite::Error| format!("Failed to read digiKam database: {err}");
+    let mut entries: HashMap<i64, LibraryEntry> = HashMap::new();
+    let mut statement = source
+        .prepare(
+            "SELECT i.id, r.specificPath, a.relativePath, i.name, ii.rating
+             FROM Images i
+             JOIN Albums a ON a.id = i.album
+             JOIN AlbumRoots r ON r.id = a.albumRoot
+             LEFT JOIN ImageInformation ii ON ii.imageid = i.id
+             WHERE i.status IN (1, 2)",
+        )
+        .map_err(query_error)?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, Option<i64>>(4)?,
+            ))
+        })
+        .map_err(query_error)?;
+    for row in rows {
+        let (id, root, relative, name, rating) = row.map_err(query_error)?;
+        let path = Path::new(&root)
+            .join(relative.trim_start_matches('/'))
+            .join(name);
+        entries.insert(
+            id,
+            LibraryEntry {
+                path,
+                // -1 means unrated; 0 is an explicit zero stars.
+                rating: rating.and_then(|rating| u32::try_from(rating).ok()),
+                ..Default::default()
+            },
+        );
+    }
+
+    let mut statement = source
+        .prepare(
+            "WITH RECURSIVE tag_path(id, path, internal) AS (
+               SELECT id, name, name = ?1 FROM Tags WHERE pid = 0
+               UNION ALL
+               SELECT t.id, tp.path || '/' || t.name, tp.internal
+               FROM Tags t JOIN tag_path tp ON t.pid = tp.id
+             )
+             SELECT it.imageid, tp.path
+             FROM ImageTags it JOIN tag_path tp ON tp.id = it.tagid
+             WHERE NOT tp.internal",
+        )
+        .map_err(query_error)?;
+    let rows = statement
+        .query_map([DIGIKAM_INTERNAL_TAGS], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(query_error)?;
+    for row in rows {
+        let (id, tag) = row.map_err(query_error)?;
+        if let Some(entry) = entries.get_mut(&id) {
+            entry.tags.push(tag);
+        }
+    }
+    Ok(entries.into_values().collect())
+}
+
+/// Shotwell events become albums. Tags list their photos as
+/// `thumb<hex id>` entries; videos are skipped.
+fn read_shotwell(source: &Connection) -> Result<Vec<LibraryEntry>, String> {
+    let query_error = |err: rusqlite::Error| format!("Failed to read Shotwell database: {err}");
+    let mut events: HashMap<i64, String> = HashMap::new();
+    let mut statement = source
+        .prepare("SELECT id, name FROM EventTable WHERE name IS NOT NULL AND name <> ''")
+        .map_err(query_error)?;
+    let rows = statement
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(query_error)?;
+    for row in rows {
+        let (id, name) = row.map_err(query_error)?;
+        events.insert(id, name);
+    }
+
+    let mut entries: HashMap<i64, LibraryEntry> = HashMap::new();
+    let mut statement = source
+        .prepare("SELECT id, filename, rating, event_id FROM PhotoTable")
+        .map_err(query_error)?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<i64>>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+            ))
+        })
+        .map_err(query_error)?;
+    for row in rows {
+        let (id, filename, rating, event_id) = row.map_err(query_error)?;
+        entries.insert(
+            id,
+            LibraryEntry {
+                path: PathBuf::from(filename),
+                // 0 is unrated and -1 rejected.
+                rating: rating
+                    .filter(|rating| (1..=5).contains(rating))
+                    .map(|rating| rating as u32),
+                albums: event_id
+                    .and_then(|event_id| events.get(&event_id).cloned())
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+        );
+    }
+
+    let mut statement = source
+        .prepare("SELECT name, photo_id_list FROM TagTable")
+        .map_err(query_error)?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })
+        .map_err(query_error)?;
+    for row in rows {
+        let (name, photo_ids) = row.map_err(query_error)?;
+        let tag = name.trim_start_matches('/').to_string();
+        if tag.is_empty() {
+            continue;
+        }
+        let photo_ids = photo_ids.unwrap_or_default();
+        for photo_id in photo_ids.split(',') {
+            let Some(id) = photo_id
+                .strip_prefix("thumb")
+                .and_then(|hex| i64::from_str_radix(hex, 16).ok())
+            else {
+                continue;
+            };
+            if let Some(entry) = entries.get_mut(&id) {
+                entry.tags.push(tag.clone());
+            }
+        }
+    }
+    Ok(entries.into_values().collect())
+}
+
+fn apply(
+    connection: &mut Connection,
+    kind: LibraryKind,
+    entries: Vec<LibraryEntry>,
+    remap: Option<&PathRemap>,
+) -> Result<ImportSummary, String> {
+    let mut summary = ImportSummary {
+        kind,
+        matched: 0,
+        missing: 0,
+        ratings: 0,
+        tags: 0,
+        albums: 0,
+    };
+    let tx = connection
+        .transaction()
+        .map_err(|err| format!("Failed to start import transaction: {err}"))?;
+    for entry in entries {
+        let path = match remap {
+            Some(remap) => match entry.path.strip_prefix(&remap.from) {
+                Ok(rest) => Path::new(&remap.to).join(rest),
+                Err(_) => entry.path,
+            },
+            None => entry.path,
+        };
+        if !path.is_file() {
+            summary.missing += 1;
+            continue;
+        }
+        summary.matched += 1;
+        let path = path.to_string_lossy();
+        if let Some(rating) = entry.rating {
+            summary.ratings += usize::from(library::add_rating(&tx, &path, rating)?);
+        }
+        for tag in &entry.tags {
+            summary.tags += usize::from(library::add_tag(&tx, &path, tag)?);
+        }
+        for album in &entry.albums {
+            summary.albums += usize::from(library::add_to_album(&tx, &path, album)?);
+        }
+    }
+    tx.commit()
+        .map_err(|err| format!("Failed to commit import transaction: {err}"))?;
+    Ok(summary)
+}
@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind};
+
+use crate::resolve_data_dir;
+
+const LOG_FILE_NAME: &str = "thumbnailer";
+/// Each file rotates at this size, and the newest few are kept, so logs stay
+/// small enough to attach to a bug report.
+const MAX_LOG_FILE_BYTES: u128 = 5 * 1024 * 1024;
+const KEPT_LOG_FILES: usize = 5;
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn filter(self) -> log::LevelFilter {
+        match self {
+            Self::Error => log::LevelFilter::Error,
+            Self::Warn => log::LevelFilter::Warn,
+            Self::Info => log::LevelFilter::Info,
+            Self::Debug => log::LevelFilter::Debug,
+            Self::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+/// Logs to a rotating file in the app data folder, and to stdout as well in
+/// development builds.
+pub(crate) fn init(app: &tauri::AppHandle, level: LogLevel) -> Result<(), String> {
+    let mut targets = vec![Target::new(TargetKind::Folder {
+        path: log_dir(app)?,
+        file_name: Some(LOG_FILE_NAME.to_string()),
+    })];
+    if cfg!(debug_assertions) {
+        targets.push(Target::new(TargetKind::Stdout));
+    }
+    // The logger passes everything through, so the level can change without
+    // a restart.
+    app.plugin(
+        tauri_plugin_log::Builder::new()
+            .clear_targets()
+            .targets(targets)
+            .level(log::LevelFilter::Trace)
+            .max_file_size(MAX_LOG_FILE_BYTES)
+            .rotation_strategy(RotationStrategy::KeepSome(KEPT_LOG_FILES))
+            .build(),
+    )
+    .map_err(|err| format!("Failed to initialize logging: {err}"))?;
+    apply(level);
+    Ok(())
+}
+
+pub(crate) fn apply(level: LogLevel) {
+    log::set_max_level(level.filter());
+}
+
+fn log_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(resolve_data_dir(app)?.join("logs"))
+}
+
+/// The current log file, for the support UI to reveal or attach.
+#[tauri::command]
+pub(crate) fn get_log_path(app: tauri::AppHandle) -> Result<String, String> {
+    let path = log_dir(&app)?.join(LOG_FILE_NAME).with_extension("log");
+    Ok(path.to_string_lossy().to_string())
+}
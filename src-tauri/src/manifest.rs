@@ -0,0 +1,151 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thumbnailer_core::{extended_path, Scanner};
+
+use crate::{exif_info, settings::Settings, AppState};
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ManifestFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestEntry {
+    path: String,
+    size_bytes: u64,
+    width: Option<u32>,
+    height: Option<u32>,
+    sha256: String,
+    /// RFC 3339 capture time from EXIF, in UTC.
+    captured_at: Option<String>,
+    camera: Option<String>,
+    rating: Option<u32>,
+}
+
+const CSV_HEADER: &str = "path,size_bytes,width,height,sha256,captured_at,camera,rating";
+
+/// Lists every image under `folder` (honoring the scan settings) as JSON or
+/// CSV. Files that can't be read are left out and logged.
+#[tauri::command]
+pub(crate) async fn export_manifest(
+    state: tauri::State<'_, AppState>,
+    folder: String,
+    format: ManifestFormat,
+) -> Result<String, String> {
+    state.path_scope.check(Path::new(&folder))?;
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        export_manifest_blocking(&settings, folder, format)
+    })
+    .await
+    .map_err(|err| format!("Failed to join manifest task: {err}"))?
+}
+
+fn export_manifest_blocking(
+    settings: &Settings,
+    folder: String,
+    format: ManifestFormat,
+) -> Result<String, String> {
+    let folder = PathBuf::from(folder);
+    if !folder.is_dir() {
+        return Err(format!("{} is not a valid directory.", folder.display()));
+    }
+    let mut image_paths = settings.scan.scan(&folder)?;
+    image_paths.sort_unstable();
+
+    let entries: Vec<ManifestEntry> = settings.install(|| {
+        image_paths
+            .par_iter()
+            .filter_map(|path| match manifest_entry(path) {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    log::warn!("Skipping image in manifest ({}): {}", path.display(), err);
+                    None
+                }
+            })
+            .collect()
+    });
+
+    match format {
+        ManifestFormat::Json => serde_json::to_string_pretty(&entries)
+            .map_err(|err| format!("Failed to serialize manifest: {err}")),
+        ManifestFormat::Csv => {
+            let mut csv = String::from(CSV_HEADER);
+            csv.push('\n');
+            for entry in &entries {
+                let fields = [
+                    csv_field(&entry.path),
+                    entry.size_bytes.to_string(),
+                    optional(entry.width),
+                    optional(entry.height),
+                    entry.sha256.clone(),
+                    entry.captured_at.clone().unwrap_or_default(),
+                    csv_field(entry.camera.as_deref().unwrap_or_default()),
+                    optional(entry.rating),
+                ];
+                csv.push_str(&fields.join(","));
+                csv.push('\n');
+            }
+            Ok(csv)
+        }
+    }
+}
+
+fn manifest_entry(path: &Path) -> Result<ManifestEntry, String> {
+    let io_path = extended_path(path);
+    let size_bytes = fs::metadata(&io_path)
+        .map_err(|err| format!("Failed to read metadata: {err}"))?
+        .len();
+    let sha256 = sha256_file(path)?;
+    let dimensions = image::ImageReader::open(&io_path)
+        .and_then(|reader| reader.with_guessed_format())
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok());
+    let exif = exif_info::read_exif(path);
+
+    Ok(ManifestEntry {
+        path: path.to_string_lossy().to_string(),
+        size_bytes,
+        width: dimensions.map(|(width, _)| width),
+        height: dimensions.map(|(_, height)| height),
+        sha256,
+        captured_at: exif
+            .as_ref()
+            .and_then(exif_info::capture_time_unix)
+            .and_then(|unix| chrono::DateTime::from_timestamp(unix, 0))
+            .map(|date_time| date_time.to_rfc3339()),
+        camera: exif.as_ref().and_then(exif_info::camera),
+        rating: exif.as_ref().and_then(exif_info::rating),
+    })
+}
+
+/// Hex SHA-256 of the file's contents.
+pub(crate) fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    let mut file =
+        File::open(extended_path(path)).map_err(|err| format!("Failed to open file: {err}"))?;
+    io::copy(&mut file, &mut hasher).map_err(|err| format!("Failed to hash file: {err}"))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn optional(value: Option<u32>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
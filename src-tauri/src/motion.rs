@@ -0,0 +1,153 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use thumbnailer_core::extended_path;
+
+use crate::AppState;
+
+/// Extensions of the still half of an iPhone Live Photo.
+const LIVE_PHOTO_STILLS: &[&str] = &["heic", "heif", "jpg", "jpeg"];
+/// How much of a motion photo is searched for its XMP packet, which cameras
+/// write near the start.
+const XMP_SEARCH_BYTES: u64 = 256 * 1024;
+
+/// Finds the moving half of each gallery image: the `.MOV` an iPhone saves
+/// beside a Live Photo's still, or the video an Android motion photo
+/// carries after its JPEG data. Each folder is listed once for its videos.
+#[derive(Default)]
+pub(crate) struct MotionPairs {
+    /// Lowercased file stem to the video, per folder.
+    videos_by_folder: HashMap<PathBuf, HashMap<String, PathBuf>>,
+}
+
+impl MotionPairs {
+    pub(crate) fn has_motion(&mut self, image_path: &Path) -> bool {
+        is_motion_photo_name(image_path) || self.live_photo_video(image_path).is_some()
+    }
+
+    fn live_photo_video(&mut self, image_path: &Path) -> Option<PathBuf> {
+        if !has_extension(image_path, LIVE_PHOTO_STILLS) {
+            return None;
+        }
+        let folder = image_path.parent()?;
+        let videos = self
+            .videos_by_folder
+            .entry(folder.to_path_buf())
+            .or_insert_with(|| list_videos(folder));
+        let stem = image_path.file_stem()?.to_string_lossy().to_lowercase();
+        videos.get(&stem).cloned()
+    }
+}
+
+fn list_videos(folder: &Path) -> HashMap<String, PathBuf> {
+    let entries = match fs::read_dir(extended_path(folder)) {
+        Ok(value) => value,
+        Err(err) => {
+            log::debug!("No Live Photo videos in {}: {}", folder.display(), err);
+            return HashMap::new();
+        }
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| folder.join(entry.file_name()))
+        .filter(|path| has_extension(path, &["mov"]))
+        .filter_map(|path| {
+            let stem = path.file_stem()?.to_string_lossy().to_lowercase();
+            Some((stem, path))
+        })
+        .collect()
+}
+
+/// Google's names for motion photos: `MVIMG_*.jpg` from older Pixel and
+/// Android cameras, `*.MP.jpg` from newer ones.
+fn is_motion_photo_name(path: &Path) -> bool {
+    if !has_extension(path, &["jpg", "jpeg"]) {
+        return false;
+    }
+    let Some(stem) = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+    else {
+        return false;
+    };
+    stem.starts_with("mvimg_") || stem.ends_with(".mp")
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_some_and(|extension| extensions.contains(&extension.as_str()))
+}
+
+/// Length of the video at the end of a motion photo, from the XMP written
+/// by the camera: `Item:Length` of the `MotionPhoto` container item in the
+/// current format, or `GCamera:MicroVideoOffset` in the older one.
+fn embedded_video_length(xmp_area: &[u8]) -> Option<u64> {
+    let text = String::from_utf8_lossy(xmp_area);
+    if let Some(start) = text.find("Item:Semantic=\"MotionPhoto\"") {
+        let item_end = text[start..]
+            .find("/>")
+            .map_or(text.len(), |end| start + end);
+        let item_start = text[..start].rfind('<').unwrap_or(0);
+        if let Some(length) = attribute(&text[item_start..item_end], "Item:Length") {
+            return Some(length);
+        }
+    }
+    attribute(&text, "GCamera:MicroVideoOffset")
+}
+
+fn attribute(text: &str, name: &str) -> Option<u64> {
+    let pattern = format!("{name}=\"");
+    let start = text.find(&pattern)? + pattern.len();
+    let end = text[start..].find('"')? + start;
+    text[start..end]
+        .trim()
+        .parse()
+        .ok()
+        .filter(|&value| value > 0)
+}
+
+fn read_clip(image_path: &Path) -> Result<Vec<u8>, String> {
+    let read_error =
+        |err: std::io::Error| format!("Failed to read {}: {err}", image_path.display());
+    if let Some(video) = MotionPairs::default().live_photo_video(image_path) {
+        return fs::read(extended_path(&video))
+            .map_err(|err| format!("Failed to read {}: {err}", video.display()));
+    }
+    if !is_motion_photo_name(image_path) {
+        return Err(format!("{} has no motion clip.", image_path.display()));
+    }
+    let mut file = File::open(extended_path(image_path)).map_err(read_error)?;
+    let file_bytes = file.metadata().map_err(read_error)?.len();
+    let mut xmp_area = Vec::new();
+    (&mut file)
+        .take(XMP_SEARCH_BYTES)
+        .read_to_end(&mut xmp_area)
+        .map_err(read_error)?;
+    let length = embedded_video_length(&xmp_area)
+        .filter(|&length| length < file_bytes)
+        .ok_or_else(|| format!("{} has no motion clip.", image_path.display()))?;
+    let mut clip = Vec::new();
+    file.seek(SeekFrom::Start(file_bytes - length))
+        .and_then(|_| file.read_to_end(&mut clip))
+        .map_err(read_error)?;
+    Ok(clip)
+}
+
+/// Returns the video of a Live Photo or motion photo as a raw binary IPC
+/// payload, for playback while the pointer rests on its tile.
+#[tauri::command]
+pub(crate) async fn get_motion_clip(
+    state: tauri::State<'_, AppState>,
+    path: String,
+) -> Result<tauri::ipc::Response, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let clip = tauri::async_runtime::spawn_blocking(move || read_clip(Path::new(&path)))
+        .await
+        .map_err(|err| format!("Failed to join motion clip task: {err}"))??;
+    Ok(tauri::ipc::Response::new(clip))
+}
@@ -0,0 +1,399 @@
+use std::{
+    iter::Peekable,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use tauri::{Emitter, Manager, Url};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+
+use crate::{
+    export::validate_source,
+    settings::{Settings, MAX_THUMBNAIL_SIZE},
+    viewer, AppState,
+};
+
+/// URL scheme registered for `thumbnailer://open?path=...` links.
+pub(crate) const DEEP_LINK_SCHEME: &str = "thumbnailer";
+
+/// Tells the main window to load `folder` and, optionally, preview `select`.
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OpenRequest {
+    pub(crate) folder: String,
+    pub(crate) select: Option<String>,
+    /// From the command line, over the settings for this load only.
+    pub(crate) thumbnail_size: Option<u32>,
+    pub(crate) recursive: Option<bool>,
+}
+
+/// The command line, as in
+/// `thumbnailer [--folder <folder>] [--select <image>] [--size <pixels>] [--recursive]`.
+/// A bare folder or image argument works as `--folder` or `--select`.
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StartupOptions {
+    /// Carries the size and recursion below too.
+    pub(crate) open: Option<OpenRequest>,
+    /// For the first gallery load, over the settings.
+    pub(crate) thumbnail_size: Option<u32>,
+    pub(crate) recursive: bool,
+}
+
+impl StartupOptions {
+    /// Relative paths are resolved against `cwd`. Arguments that don't make
+    /// sense are logged and ignored, since there is no terminal to report
+    /// them on.
+    pub(crate) fn parse<I>(args: I, cwd: &Path, settings: &Settings) -> Self
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Self::default();
+        let (mut folder, mut select, mut bare) = (None, None, Vec::new());
+        let mut args = args.into_iter().peekable();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--folder" => folder = flag_value(&mut args, &arg).map(|value| cwd.join(value)),
+                "--select" => select = flag_value(&mut args, &arg).map(|value| cwd.join(value)),
+                "--size" => {
+                    if let Some(value) = flag_value(&mut args, &arg) {
+                        match value.parse::<u32>() {
+                            Ok(size) => {
+                                options.thumbnail_size = Some(size.clamp(1, MAX_THUMBNAIL_SIZE))
+                            }
+                            Err(_) => {
+                                log::warn!("Ignoring --size {}: not a number of pixels", value)
+                            }
+                        }
+                    }
+                }
+                "--recursive" => options.recursive = true,
+                flag if flag.starts_with("--") => log::warn!("Ignoring unknown argument {}", flag),
+                _ => bare.push(cwd.join(arg)),
+            }
+        }
+
+        let folder = folder.filter(|folder| {
+            let is_dir = folder.is_dir();
+            if !is_dir {
+                log::warn!("Ignoring --folder {}: not a folder", folder.display());
+            }
+            is_dir
+        });
+        let select = select.filter(|image| match validate_source(image, settings) {
+            Ok(()) => true,
+            Err(err) => {
+                log::warn!("Ignoring --select {}: {}", image.display(), err);
+                false
+            }
+        });
+        options.open = match (folder, select) {
+            (Some(folder), select) => Some(OpenRequest {
+                folder: folder.to_string_lossy().to_string(),
+                select: select.map(|image| image.to_string_lossy().to_string()),
+                ..Default::default()
+            }),
+            (None, Some(image)) => request_for_image(image).ok(),
+            (None, None) => bare.into_iter().find_map(|path| from_path(path, settings)),
+        };
+        if let Some(open) = &mut options.open {
+            open.thumbnail_size = options.thumbnail_size;
+            open.recursive = options.recursive.then_some(true);
+        }
+        options
+    }
+}
+
+/// The argument after `flag`, unless it is another flag.
+fn flag_value<I>(args: &mut Peekable<I>, flag: &str) -> Option<String>
+where
+    I: Iterator<Item = String>,
+{
+    let value = args.next_if(|next| !next.starts_with("--"));
+    if value.is_none() {
+        log::warn!("Ignoring {} without a value", flag);
+    }
+    value
+}
+
+/// The startup options `run` parsed from the command line.
+#[tauri::command]
+pub(crate) fn get_startup_options(state: tauri::State<'_, AppState>) -> StartupOptions {
+    state
+        .startup_options
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone()
+}
+
+/// What the app was launched with: a folder to browse, or an image to view
+/// within its folder (as when opening an associated file). A deep link is
+/// confirmed first and arrives as an `open-request` instead.
+#[tauri::command]
+pub(crate) fn get_initial_open_request(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Option<OpenRequest> {
+    if let Some(request) = state
+        .pending_open
+        .lock()
+        .ok()
+        .and_then(|mut pending| pending.take())
+    {
+        return Some(request);
+    }
+    let startup = state
+        .startup_options
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .open
+        .clone();
+    if let Some(request) = startup {
+        allow(&app, &request);
+        return Some(request);
+    }
+    let settings = state.settings.get();
+    let urls = app.deep_link().get_current().ok().flatten()?;
+    let request = urls
+        .iter()
+        .find_map(|url| from_deep_link(url, &settings).ok())?;
+    confirm_deep_link(&app, request);
+    None
+}
+
+/// What to open for an argument list, as `StartupOptions::parse` reads it.
+pub(crate) fn from_args<I>(args: I, cwd: &Path, settings: &Settings) -> Option<OpenRequest>
+where
+    I: IntoIterator<Item = String>,
+{
+    StartupOptions::parse(args, cwd, settings).open
+}
+
+/// A folder to browse, or a supported image to view within its folder.
+fn from_path(path: PathBuf, settings: &Settings) -> Option<OpenRequest> {
+    if path.is_dir() {
+        Some(OpenRequest {
+            folder: path.to_string_lossy().to_string(),
+            ..Default::default()
+        })
+    } else if validate_source(&path, settings).is_ok() {
+        request_for_image(path).ok()
+    } else {
+        None
+    }
+}
+
+/// Allows the request's folder, which the user chose outside the webview,
+/// and sends it to the main window.
+pub(crate) fn send(app: &tauri::AppHandle, request: OpenRequest) {
+    allow(app, &request);
+    if let Err(err) = app.emit_to("main", "open-request", request) {
+        log::warn!("Failed to emit open request: {}", err);
+    }
+}
+
+fn allow(app: &tauri::AppHandle, request: &OpenRequest) {
+    app.state::<AppState>()
+        .path_scope
+        .allow(Path::new(&request.folder));
+}
+
+/// Called in the running instance when the app is launched again: opens
+/// whatever the new launch was given, with its `--size` and `--recursive`,
+/// and brings the main window forward.
+#[cfg(desktop)]
+pub(crate) fn forward_launch(app: &tauri::AppHandle, args: Vec<String>, cwd: String) {
+    let settings = app.state::<AppState>().settings.get();
+    if let Some(request) = from_args(args.into_iter().skip(1), Path::new(&cwd), &settings) {
+        send(app, request);
+    }
+    focus_main_window(app);
+}
+
+/// Handles deep links delivered while the app is running. On Windows and
+/// Linux these arrive through the single-instance plugin.
+pub(crate) fn open_deep_links(app: &tauri::AppHandle, urls: &[Url]) {
+    let settings = app.state::<AppState>().settings.get();
+    for url in urls {
+        match from_deep_link(url, &settings) {
+            Ok(request) => {
+                focus_main_window(app);
+                confirm_deep_link(app, request);
+                return;
+            }
+            Err(err) => log::warn!("Ignoring deep link {}: {}", url, err),
+        }
+    }
+}
+
+/// Handles files opened through a file association on macOS. The request is
+/// also kept for `get_initial_open_request`, since a cold launch delivers it
+/// before the frontend is listening.
+#[cfg(target_os = "macos")]
+pub(crate) fn open_file_urls(app: &tauri::AppHandle, urls: &[Url]) {
+    let state = app.state::<AppState>();
+    let paths = urls
+        .iter()
+        .filter_map(|url| url.to_file_path().ok())
+        .map(|path| path.to_string_lossy().to_string());
+    let Some(request) = from_args(paths, Path::new("/"), &state.settings.get()) else {
+        return;
+    };
+    if let Ok(mut pending) = state.pending_open.lock() {
+        *pending = Some(request.clone());
+    }
+    send(app, request);
+    focus_main_window(app);
+}
+
+/// Links can come from any web page, so the folder one names is only
+/// allowed and opened once the user agrees in a native prompt.
+fn confirm_deep_link(app: &tauri::AppHandle, request: OpenRequest) {
+    let target = request.select.as_deref().unwrap_or(&request.folder);
+    let handle = app.clone();
+    app.dialog()
+        .message(format!("A link asked to open {target}."))
+        .title("Open from link?")
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Open".to_string(),
+            "Cancel".to_string(),
+        ))
+        .show(move |confirmed| {
+            if confirmed {
+                send(&handle, request);
+            }
+        });
+}
+
+/// Parses `thumbnailer://open?path=<folder or image>`.
+fn from_deep_link(url: &Url, settings: &Settings) -> Result<OpenRequest, String> {
+    if url.scheme() != DEEP_LINK_SCHEME || url.host_str() != Some("open") {
+        return Err("Unsupported link; expected thumbnailer://open?path=...".to_string());
+    }
+    let path = url
+        .query_pairs()
+        .find(|(key, _)| key == "path")
+        .map(|(_, value)| PathBuf::from(value.as_ref()))
+        .ok_or_else(|| "The link has no path parameter.".to_string())?;
+    if !path.is_absolute() {
+        return Err(format!("{} is not an absolute path.", path.display()));
+    }
+    if path.is_dir() {
+        return Ok(OpenRequest {
+            folder: path.to_string_lossy().to_string(),
+            ..Default::default()
+        });
+    }
+    validate_source(&path, settings)?;
+    request_for_image(path)
+}
+
+pub(crate) fn focus_main_window(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        if let Err(err) = window.set_focus() {
+            log::warn!("Failed to focus main window: {}", err);
+        }
+    }
+}
+
+enum DropTarget {
+    Folder(PathBuf),
+    Images(Vec<PathBuf>),
+}
+
+/// Handles paths dropped onto the main window. A folder is loaded as the
+/// gallery; image files load their parent folder and preview the first one,
+/// with any further images opened in viewer windows.
+pub(crate) fn handle_drop(window: &tauri::Window, paths: &[PathBuf]) {
+    if window.label() != "main" {
+        return;
+    }
+    let state = window.state::<AppState>();
+    let request = match classify_drop(paths, &state.settings.get()) {
+        Ok(DropTarget::Folder(folder)) => OpenRequest {
+            folder: folder.to_string_lossy().to_string(),
+            ..Default::default()
+        },
+        Ok(DropTarget::Images(images)) => {
+            let mut images = images.into_iter();
+            let Some(first) = images.next() else {
+                return;
+            };
+            let extra: Vec<PathBuf> = images.collect();
+            if !extra.is_empty() {
+                // Their folders aren't opened, so each image is allowed on
+                // its own for the viewer to load it.
+                for image in &extra {
+                    state.path_scope.allow(image);
+                }
+                // Building windows inside a window event handler deadlocks
+                // on Windows, so do it off the event loop.
+                let app = window.app_handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    let viewers = &app.state::<AppState>().viewer_windows;
+                    for image in extra {
+                        if let Err(err) = viewer::open_viewer(&app, viewers, image) {
+                            log::warn!("Failed to open dropped image: {}", err);
+                        }
+                    }
+                });
+            }
+            match request_for_image(first) {
+                Ok(request) => request,
+                Err(err) => return reject_drop(window, err),
+            }
+        }
+        Err(err) => return reject_drop(window, err),
+    };
+    send(window.app_handle(), request);
+}
+
+/// An open request that shows `image` within its folder.
+pub(crate) fn request_for_image(image: PathBuf) -> Result<OpenRequest, String> {
+    let folder = image
+        .parent()
+        .ok_or_else(|| format!("{} has no parent directory.", image.display()))?;
+    Ok(OpenRequest {
+        folder: folder.to_string_lossy().to_string(),
+        select: Some(image.to_string_lossy().to_string()),
+        ..Default::default()
+    })
+}
+
+/// Folders win over files; of several folders only the first is used.
+/// Files that aren't supported images are ignored.
+fn classify_drop(paths: &[PathBuf], settings: &Settings) -> Result<DropTarget, String> {
+    let mut folders = paths.iter().filter(|path| path.is_dir());
+    if let Some(folder) = folders.next() {
+        let ignored = folders.count();
+        if ignored > 0 {
+            log::warn!("Ignoring {} additional dropped folder(s)", ignored);
+        }
+        return Ok(DropTarget::Folder(folder.clone()));
+    }
+
+    let images: Vec<PathBuf> = paths
+        .iter()
+        .filter(|path| match validate_source(path, settings) {
+            Ok(()) => true,
+            Err(err) => {
+                log::warn!("Ignoring dropped file: {}", err);
+                false
+            }
+        })
+        .cloned()
+        .collect();
+    if images.is_empty() {
+        return Err("None of the dropped items is a folder or supported image.".to_string());
+    }
+    Ok(DropTarget::Images(images))
+}
+
+fn reject_drop(window: &tauri::Window, message: String) {
+    if let Err(err) = window.emit_to(window.label(), "drop-rejected", message) {
+        log::warn!("Failed to emit drop rejection: {}", err);
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    thread,
+};
+
+use tauri::Manager;
+use thumbnailer_core::{extended_path, PendingThumbnail, ThumbnailCache};
+
+use crate::{
+    device_pixels, folder_cover, open_cache_db, recent_folders, resolve_data_dir,
+    settings::Settings, AppState, PAUSE_POLL_INTERVAL,
+};
+
+/// Recent images warmed, newest first.
+const MAX_RECENT_FILES: usize = 100;
+/// Folder covers warmed: the app's own recent folders, then those holding
+/// the recent images.
+const MAX_RECENT_FOLDERS: usize = 24;
+
+/// Generates thumbnails for the images the OS lists as recently used, and
+/// covers for their folders and the app's recent ones, on a background
+/// thread at startup, so the start screen's recents render from the cache.
+pub(crate) fn warm(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let settings = state.settings.get();
+    if !settings.warm_from_os_recents {
+        return;
+    }
+    let data_dir = match resolve_data_dir(app) {
+        Ok(value) => value,
+        Err(err) => {
+            log::warn!("Failed to warm the cache from recent files: {}", err);
+            return;
+        }
+    };
+    let thumbnail_size = match app.get_webview_window("main") {
+        Some(window) => device_pixels(settings.thumbnail_size, None, &window),
+        None => settings.thumbnail_size,
+    };
+    let generation_paused = state.generation_paused.clone();
+    let spawned = thread::Builder::new()
+        .name("recents-warmup".to_string())
+        .spawn(move || {
+            let wait_while_paused = || {
+                while generation_paused.load(Ordering::Relaxed) {
+                    thread::sleep(PAUSE_POLL_INTERVAL);
+                }
+            };
+            if let Err(err) = warm_blocking(&data_dir, thumbnail_size, &settings, wait_while_paused)
+            {
+                log::warn!("Failed to warm the cache from recent files: {}", err);
+            }
+        });
+    if let Err(err) = spawned {
+        log::warn!(
+            "Failed to start warming the cache from recent files: {}",
+            err
+        );
+    }
+}
+
+fn warm_blocking(
+    data_dir: &Path,
+    thumbnail_size: u32,
+    settings: &Settings,
+    wait_while_paused: impl Fn(),
+) -> Result<(), String> {
+    let mut connection = open_cache_db(data_dir)?;
+    let files: Vec<PathBuf> = recent_files()
+        .into_iter()
+        .filter(|path| {
+            settings.scan.is_supported_image(path)
+                && settings.cloud_files.allows_generation(path)
+                && extended_path(path).is_file()
+        })
+        .take(MAX_RECENT_FILES)
+        .collect();
+
+    let generator = settings.generator(thumbnail_size);
+    let mut generated_any = false;
+    for path in &files {
+        wait_while_paused();
+        let pending = match PendingThumbnail::for_path(path) {
+            Ok(value) => value,
+            Err(err) => {
+                log::debug!("Skipping recent file: {}", err);
+                continue;
+            }
+        };
+        if connection.contains(&pending.cache_key, pending.modified_unix, thumbnail_size)? {
+            continue;
+        }
+        match pending.generate(&generator) {
+            Ok(generated) => {
+                connection.store(std::slice::from_ref(&generated))?;
+                generated_any = true;
+            }
+            Err(err) => log::debug!("Skipping recent file: {}", err),
+        }
+    }
+
+    // Covers are requested at the grid's CSS size.
+    let mut seen = HashSet::new();
+    let folders: Vec<PathBuf> = recent_folders::list(&connection)?
+        .into_iter()
+        .map(|folder| PathBuf::from(folder.path))
+        .chain(
+            files
+                .iter()
+                .filter_map(|path| path.parent().map(Path::to_path_buf)),
+        )
+        .filter(|folder| seen.insert(folder.clone()))
+        .take(MAX_RECENT_FOLDERS)
+        .collect();
+    for folder in folders {
+        wait_while_paused();
+        if let Err(err) =
+            folder_cover::folder_cover_blocking(data_dir, folder, settings.thumbnail_size, settings)
+        {
+            log::debug!("Skipping recent folder cover: {}", err);
+        }
+    }
+    if generated_any {
+        connection.prune(settings.cache_max_bytes)?;
+    }
+    Ok(())
+}
+
+/// Files from the desktop's recently-used list, newest first.
+#[cfg(target_os = "linux")]
+fn recent_files() -> Vec<PathBuf> {
+    let Some(list) = dirs::data_dir().map(|dir| dir.join("recently-used.xbel")) else {
+        return Vec::new();
+    };
+    let xbel = match std::fs::read_to_string(&list) {
+        Ok(value) => value,
+        Err(err) => {
+            log::debug!("No recently-used list at {}: {}", list.display(), err);
+            return Vec::new();
+        }
+    };
+    // Each `<bookmark href="file:///..." modified="2024-...Z" ...>`; the
+    // timestamps are ISO 8601 in UTC, so they sort as text.
+    let mut bookmarks: Vec<(String, PathBuf)> = xbel
+        .split("<bookmark ")
+        .skip(1)
+        .filter_map(|bookmark| {
+            let tag = &bookmark[..bookmark.find('>')?];
+            let path = tauri::Url::parse(&xml_attribute(tag, "href")?.replace("&amp;", "&"))
+                .ok()?
+                .to_file_path()
+                .ok()?;
+            let modified = xml_attribute(tag, "modified").unwrap_or_default();
+            Some((modified, path))
+        })
+        .collect();
+    bookmarks.sort_by(|(left, _), (right, _)| right.cmp(left));
+    bookmarks.into_iter().map(|(_, path)| path).collect()
+}
+
+#[cfg(target_os = "linux")]
+fn xml_attribute(tag: &str, name: &str) -> Option<String> {
+    let start = tag.find(&format!(" {name}=\""))? + name.len() + 3;
+    let length = tag[start..].find('"')?;
+    Some(tag[start..start + length].to_string())
+}
+
+/// Targets of the shortcuts in the user's Recent folder, newest first.
+#[cfg(windows)]
+fn recent_files() -> Vec<PathBuf> {
+    use std::os::windows::ffi::OsStringExt;
+
+    use windows::{
+        core::{Interface, HSTRING},
+        Win32::{
+            System::Com::{
+                CoCreateInstance, CoInitializeEx, CoUninitialize, IPersistFile,
+                CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED, STGM_READ,
+            },
+            UI::Shell::{IShellLinkW, ShellLink},
+        },
+    };
+
+    let Some(recent) = dirs::data_dir().map(|dir| dir.join(r"Microsoft\Windows\Recent")) else {
+        return Vec::new();
+    };
+    let mut shortcuts: Vec<(std::time::SystemTime, PathBuf)> = match std::fs::read_dir(&recent) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("lnk"))
+            })
+            .filter_map(|path| {
+                let modified = std::fs::metadata(&path).and_then(|meta| meta.modified());
+                Some((modified.ok()?, path))
+            })
+            .collect(),
+        Err(err) => {
+            log::debug!("No Recent folder at {}: {}", recent.display(), err);
+            return Vec::new();
+        }
+    };
+    shortcuts.sort_by(|(left, _), (right, _)| right.cmp(left));
+
+    // SAFETY: COM is initialized on this thread for the duration of the
+    // calls, and every interface is released before it is uninitialized.
+    unsafe {
+        let initialized = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+        let targets = shortcuts
+            .into_iter()
+            .filter_map(|(_, shortcut)| {
+                let link: IShellLinkW =
+                    CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER).ok()?;
+                link.cast::<IPersistFile>()
+                    .ok()?
+                    .Load(&HSTRING::from(shortcut.as_os_str()), STGM_READ)
+                    .ok()?;
+                let mut target = [0u16; 32_768];
+                link.GetPath(&mut target, std::ptr::null_mut(), 0).ok()?;
+                let length = target.iter().position(|&unit| unit == 0)?;
+                (length > 0)
+                    .then(|| PathBuf::from(std::ffi::OsString::from_wide(&target[..length])))
+            })
+            .collect();
+        if initialized {
+            CoUninitialize();
+        }
+        targets
+    }
+}
+
+/// Images Spotlight saw opened in the last two weeks, newest first.
+#[cfg(target_os = "macos")]
+fn recent_files() -> Vec<PathBuf> {
+    let output = crate::video::external_tool("mdfind")
+        .args([
+            "-0",
+            "kMDItemLastUsedDate >= $time.today(-14) && kMDItemContentTypeTree == 'public.image'",
+        ])
+        .output();
+    let output = match output {
+        Ok(value) if value.status.success() => value.stdout,
+        Ok(value) => {
+            log::debug!("mdfind failed: {}", String::from_utf8_lossy(&value.stderr));
+            return Vec::new();
+        }
+        Err(err) => {
+            log::debug!("Failed to run mdfind: {}", err);
+            return Vec::new();
+        }
+    };
+    let mut files: Vec<(std::time::SystemTime, PathBuf)> = output
+        .split(|&byte| byte == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| PathBuf::from(String::from_utf8_lossy(path).into_owned()))
+        .map(|path| {
+            // Opening a file updates its access time, the nearest readily
+            // available stand-in for the last-used date.
+            let used = std::fs::metadata(&path)
+                .and_then(|meta| meta.accessed())
+                .unwrap_or(std::time::UNIX_EPOCH);
+            (used, path)
+        })
+        .collect();
+    files.sort_by(|(left, _), (right, _)| right.cmp(left));
+    files.into_iter().map(|(_, path)| path).collect()
+}
+
+#[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+fn recent_files() -> Vec<PathBuf> {
+    Vec::new()
+}
@@ -0,0 +1,273 @@
+use std::path::Path;
+
+use image::DynamicImage;
+use thumbnailer_core::{Generator, ImageGenerator};
+
+/// Generates with the Rust decoders and, for files they can't read, falls back
+/// to the operating system's thumbnail API so anything the OS can preview
+/// still gets a cached thumbnail.
+#[derive(Clone, Copy)]
+pub(crate) struct SystemGenerator {
+    pub(crate) image: ImageGenerator,
+}
+
+impl Generator for SystemGenerator {
+    fn generate(&self, path: &Path) -> Result<(Vec<u8>, String), String> {
+        let decode_error = match self.image.generate(path) {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+        match system_thumbnail(path, self.image.size) {
+            Some(Ok(image)) => self.image.thumbnail_image(&image, path),
+            Some(Err(err)) => Err(format!("{decode_error} ({err})")),
+            None => Err(decode_error),
+        }
+    }
+
+    fn size(&self) -> u32 {
+        self.image.size
+    }
+}
+
+/// `None` where the platform offers no thumbnail API.
+fn system_thumbnail(path: &Path, size: u32) -> Option<Result<DynamicImage, String>> {
+    #[cfg(windows)]
+    return Some(shell_thumbnail(path, size));
+    #[cfg(target_os = "macos")]
+    return Some(quick_look_thumbnail(path, size));
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+        let _ = (path, size);
+        None
+    }
+}
+
+/// Asks the Windows shell (IShellItemImageFactory) for a thumbnail, which
+/// covers formats with installed codecs such as HEIC or camera RAW. Icons are
+/// not accepted as thumbnails.
+#[cfg(windows)]
+fn shell_thumbnail(path: &Path, size: u32) -> Result<DynamicImage, String> {
+    use windows::{
+        core::HSTRING,
+        Win32::{
+            Foundation::SIZE,
+            Graphics::Gdi::DeleteObject,
+            System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED},
+            UI::Shell::{
+                IShellItemImageFactory, SHCreateItemFromParsingName, SIIGBF_BIGGERSIZEOK,
+                SIIGBF_RESIZETOFIT, SIIGBF_THUMBNAILONLY,
+            },
+        },
+    };
+
+    let edge = i32::try_from(size).unwrap_or(i32::MAX);
+    // SAFETY: COM is initialized for the duration of the call on this thread
+    // and the returned bitmap is released before returning.
+    unsafe {
+        // Rayon workers may already have COM initialized; only balance a
+        // successful call.
+        let initialized = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+        let result = (|| {
+            let factory: IShellItemImageFactory =
+                SHCreateItemFromParsingName(&HSTRING::from(path.as_os_str()), None)
+                    .map_err(|err| format!("Windows shell can't open the file: {err}"))?;
+            let bitmap = factory
+                .GetImage(
+                    SIZE { cx: edge, cy: edge },
+                    SIIGBF_RESIZETOFIT | SIIGBF_BIGGERSIZEOK | SIIGBF_THUMBNAILONLY,
+                )
+                .map_err(|err| format!("Windows shell has no thumbnail: {err}"))?;
+            let image = bitmap_to_image(bitmap);
+            let _ = DeleteObject(bitmap.into());
+            image
+        })();
+        if initialized {
+            CoUninitialize();
+        }
+        result
+    }
+}
+
+/// Copies a 32-bit top-down DIB out of the bitmap, swapping BGRA to RGBA.
+/// Opaque thumbnails often come back with every alpha byte zeroed, so an
+/// all-transparent result is treated as opaque.
+#[cfg(windows)]
+unsafe fn bitmap_to_image(
+    bitmap: windows::Win32::Graphics::Gdi::HBITMAP,
+) -> Result<DynamicImage, String> {
+    use windows::Win32::Graphics::Gdi::{
+        CreateCompatibleDC, DeleteDC, GetDIBits, GetObjectW, BITMAP, BITMAPINFO, BITMAPINFOHEADER,
+        BI_RGB, DIB_RGB_COLORS,
+    };
+
+    let mut info = BITMAP::default();
+    let read = GetObjectW(
+        bitmap.into(),
+        std::mem::size_of::<BITMAP>() as i32,
+        Some(&mut info as *mut BITMAP as *mut _),
+    );
+    if read == 0 || info.bmWidth <= 0 || info.bmHeight == 0 {
+        return Err("Windows shell returned an invalid bitmap.".to_string());
+    }
+    let width = info.bmWidth.unsigned_abs();
+    let height = info.bmHeight.unsigned_abs();
+
+    let mut header = BITMAPINFO {
+        bmiHeader: BITMAPINFOHEADER {
+            biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+            biWidth: info.bmWidth,
+            // Negative height asks for rows top to bottom.
+            biHeight: -(height as i32),
+            biPlanes: 1,
+            biBitCount: 32,
+            biCompression: BI_RGB.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    let dc = CreateCompatibleDC(None);
+    let lines = GetDIBits(
+        dc,
+        bitmap,
+        0,
+        height,
+        Some(pixels.as_mut_ptr().cast()),
+        &mut header,
+        DIB_RGB_COLORS,
+    );
+    let _ = DeleteDC(dc);
+    if lines == 0 {
+        return Err("Failed to read the Windows shell bitmap.".to_string());
+    }
+    let has_alpha = pixels.chunks_exact(4).any(|pixel| pixel[3] != 0);
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+        if !has_alpha {
+            pixel[3] = u8::MAX;
+        }
+    }
+    image::RgbaImage::from_raw(width, height, pixels)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| "Windows shell bitmap has an unexpected size.".to_string())
+}
+
+/// How long to wait for Quick Look before giving up on a file.
+#[cfg(target_os = "macos")]
+const QUICK_LOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Asks Quick Look (QLThumbnailGenerator) for a thumbnail, so anything Finder
+/// can preview shows up too. Icons are not accepted as thumbnails.
+#[cfg(target_os = "macos")]
+fn quick_look_thumbnail(path: &Path, size: u32) -> Result<DynamicImage, String> {
+    use std::sync::mpsc;
+
+    use block2::RcBlock;
+    use objc2::AllocAnyThread;
+    use objc2_core_foundation::CGSize;
+    use objc2_foundation::{NSError, NSString, NSURL};
+    use objc2_quick_look_thumbnailing::{
+        QLThumbnailGenerationRequest, QLThumbnailGenerationRequestRepresentationTypes,
+        QLThumbnailGenerator, QLThumbnailRepresentation,
+    };
+
+    let path = path
+        .to_str()
+        .ok_or_else(|| "Quick Look needs a UTF-8 path.".to_string())?;
+    let url = NSURL::fileURLWithPath(&NSString::from_str(path));
+    let edge = f64::from(size);
+    let (sender, receiver) = mpsc::channel();
+    let handler = RcBlock::new(
+        move |representation: *mut QLThumbnailRepresentation, error: *mut NSError| {
+            // SAFETY: Quick Look passes either a valid representation or a
+            // valid error, both alive for the duration of the callback.
+            let result = unsafe {
+                match representation.as_ref() {
+                    Some(representation) => cg_image_to_image(&representation.CGImage()),
+                    None => Err(error
+                        .as_ref()
+                        .map(|error| error.localizedDescription().to_string())
+                        .unwrap_or_else(|| "Quick Look has no thumbnail.".to_string())),
+                }
+            };
+            let _ = sender.send(result);
+        },
+    );
+    // SAFETY: the request and handler outlive the call, and the handler only
+    // touches what Quick Look hands it.
+    unsafe {
+        let request =
+            QLThumbnailGenerationRequest::initWithFileAtURL_size_scale_representationTypes(
+                QLThumbnailGenerationRequest::alloc(),
+                &url,
+                CGSize {
+                    width: edge,
+                    height: edge,
+                },
+                1.0,
+                QLThumbnailGenerationRequestRepresentationTypes::Thumbnail,
+            );
+        QLThumbnailGenerator::sharedGenerator()
+            .generateBestRepresentationForRequest_completionHandler(&request, &handler);
+    }
+    receiver
+        .recv_timeout(QUICK_LOOK_TIMEOUT)
+        .map_err(|_| "Quick Look timed out.".to_string())?
+        .map_err(|err| format!("Quick Look has no thumbnail: {err}"))
+}
+
+/// Draws the image into an RGBA bitmap and undoes the premultiplied alpha
+/// Core Graphics requires.
+#[cfg(target_os = "macos")]
+fn cg_image_to_image(image: &objc2_core_graphics::CGImage) -> Result<DynamicImage, String> {
+    use objc2_core_foundation::{CGPoint, CGRect, CGSize};
+    use objc2_core_graphics::{
+        CGBitmapContextCreate, CGColorSpace, CGContext, CGImage, CGImageAlphaInfo,
+        CGImageByteOrderInfo,
+    };
+
+    let width = CGImage::width(Some(image));
+    let height = CGImage::height(Some(image));
+    if width == 0 || height == 0 {
+        return Err("Quick Look returned an empty image.".to_string());
+    }
+    let bytes_per_row = width * 4;
+    let mut pixels = vec![0u8; bytes_per_row * height];
+    let color_space = CGColorSpace::new_device_rgb()
+        .ok_or_else(|| "Failed to create an RGB color space.".to_string())?;
+    // SAFETY: `pixels` holds `bytes_per_row * height` bytes and outlives the
+    // context, which is dropped at the end of this block.
+    unsafe {
+        let context = CGBitmapContextCreate(
+            pixels.as_mut_ptr().cast(),
+            width,
+            height,
+            8,
+            bytes_per_row,
+            Some(&color_space),
+            CGImageAlphaInfo::PremultipliedLast.0 | CGImageByteOrderInfo::Order32Big.0,
+        )
+        .ok_or_else(|| "Failed to create a bitmap context.".to_string())?;
+        let rect = CGRect {
+            origin: CGPoint { x: 0.0, y: 0.0 },
+            size: CGSize {
+                width: width as f64,
+                height: height as f64,
+            },
+        };
+        CGContext::draw_image(Some(&context), rect, Some(image));
+    }
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = u32::from(pixel[3]);
+        if alpha != 0 && alpha != 255 {
+            for channel in &mut pixel[..3] {
+                *channel = (u32::from(*channel) * 255 / alpha).min(255) as u8;
+            }
+        }
+    }
+    let width = u32::try_from(width).map_err(|_| "Quick Look image is too wide.".to_string())?;
+    let height = u32::try_from(height).map_err(|_| "Quick Look image is too tall.".to_string())?;
+    image::RgbaImage::from_raw(width, height, pixels)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| "Quick Look bitmap has an unexpected size.".to_string())
+}
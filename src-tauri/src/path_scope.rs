@@ -0,0 +1,95 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::RwLock,
+};
+
+use thumbnailer_core::cache_path;
+
+/// Folders the user opened this session, through the folder picker, the
+/// command line, a drop, a deep link, the tray or the recent folders.
+/// Commands only touch files under them, destinations included, so a
+/// compromised webview can't read or modify anything else.
+#[derive(Default)]
+pub(crate) struct PathScope {
+    /// Stored in their `cache_path` spelling so case and Unicode variants of
+    /// the same folder match.
+    roots: RwLock<Vec<PathBuf>>,
+    /// The same roots with symlinks resolved, which paths must also fall
+    /// under once theirs are.
+    resolved_roots: RwLock<Vec<PathBuf>>,
+}
+
+impl PathScope {
+    /// Allows `path` and everything under it. Usually a folder, but a single
+    /// image can be allowed on its own. Only call this for paths the user
+    /// chose outside the webview.
+    pub(crate) fn allow(&self, path: &Path) {
+        let root = PathBuf::from(cache_path(path));
+        let mut roots = self.roots.write().unwrap_or_else(|err| err.into_inner());
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+        if let Some(resolved) = resolve(path) {
+            let mut resolved_roots = self
+                .resolved_roots
+                .write()
+                .unwrap_or_else(|err| err.into_inner());
+            if !resolved_roots.contains(&resolved) {
+                resolved_roots.push(resolved);
+            }
+        }
+    }
+
+    /// Paths must be absolute and free of `..`, so nothing can climb out of a
+    /// root that it appears to be inside, and must stay inside one with
+    /// symlinks resolved. Paths that don't exist yet, such as an export's
+    /// output, are resolved through their parent.
+    pub(crate) fn check(&self, path: &Path) -> Result<(), String> {
+        let escapes = path
+            .components()
+            .any(|component| component == Component::ParentDir);
+        if path.is_absolute() && !escapes && self.contains(path) {
+            return Ok(());
+        }
+        Err(format!(
+            "{} is outside the folders opened in this session.",
+            path.display()
+        ))
+    }
+
+    pub(crate) fn check_all(&self, paths: &[String]) -> Result<(), String> {
+        paths
+            .iter()
+            .try_for_each(|path| self.check(Path::new(path)))
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        let candidate = PathBuf::from(cache_path(path));
+        let roots = self.roots.read().unwrap_or_else(|err| err.into_inner());
+        if !roots.iter().any(|root| candidate.starts_with(root)) {
+            return false;
+        }
+        let Some(resolved) = resolve(path) else {
+            // Neither it nor its parent exists, so there is nothing a link
+            // could redirect.
+            return true;
+        };
+        self.resolved_roots
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .any(|root| resolved.starts_with(root))
+    }
+}
+
+/// `path` with symlinks resolved, through its parent when it doesn't exist.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    let resolved = match fs::canonicalize(path) {
+        Ok(value) => value,
+        Err(_) => fs::canonicalize(path.parent()?)
+            .ok()?
+            .join(path.file_name()?),
+    };
+    Some(PathBuf::from(cache_path(&resolved)))
+}
@@ -0,0 +1,363 @@
+use std::{
+    fs::OpenOptions,
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+};
+
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, GenericImageView, Rgb, RgbImage,
+};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str};
+use rayon::prelude::*;
+use serde::Deserialize;
+use thumbnailer_core::decode_image;
+
+use crate::{
+    export::{emit_progress, validate_source},
+    file_ops::{failure, success, FileOperationSummary},
+    settings::Settings,
+    AppState, FULL_IMAGE_JPEG_QUALITY,
+};
+
+const POINTS_PER_INCH: f32 = 72.0;
+const MARGIN: f32 = 36.0;
+const CELL_GAP: f32 = 12.0;
+const CAPTION_FONT_SIZE: f32 = 8.0;
+const CAPTION_HEIGHT: f32 = CAPTION_FONT_SIZE * 1.75;
+/// Helvetica averages a bit over half an em per character.
+const CAPTION_CHAR_WIDTH: f32 = CAPTION_FONT_SIZE * 0.55;
+const FONT_NAME: Name<'static> = Name(b"F1");
+/// Images are decoded and encoded this many at a time before being written.
+const PDF_ENCODE_BATCH: usize = 8;
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum PdfMode {
+    #[default]
+    OnePerPage,
+    ContactSheet,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PageSize {
+    #[default]
+    A4,
+    Letter,
+}
+
+impl PageSize {
+    fn points(self, landscape: bool) -> (f32, f32) {
+        let (width, height) = match self {
+            Self::A4 => (595.28, 841.89),
+            Self::Letter => (612.0, 792.0),
+        };
+        if landscape {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct PdfLayout {
+    mode: PdfMode,
+    page_size: PageSize,
+    landscape: bool,
+    /// Grid used by the contact sheet mode.
+    columns: u32,
+    rows: u32,
+    /// Print the file name under each image.
+    captions: bool,
+    /// Images are downscaled to this print resolution, never upscaled.
+    dpi: u32,
+}
+
+impl Default for PdfLayout {
+    fn default() -> Self {
+        Self {
+            mode: PdfMode::OnePerPage,
+            page_size: PageSize::A4,
+            landscape: false,
+            columns: 4,
+            rows: 5,
+            captions: true,
+            dpi: 300,
+        }
+    }
+}
+
+/// Cell geometry in PDF points, with the origin at the bottom left.
+struct Cell {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+struct EncodedImage {
+    jpeg: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+/// Lays the selected images out into a PDF at `dest`, either one per page or
+/// as a contact sheet grid. Images are embedded as JPEG at the layout's print
+/// resolution; unreadable ones are skipped and reported.
+#[tauri::command]
+pub(crate) async fn export_pdf(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    paths: Vec<String>,
+    dest: String,
+    layout: Option<PdfLayout>,
+) -> Result<FileOperationSummary, String> {
+    state.path_scope.check_all(&paths)?;
+    state.path_scope.check(Path::new(&dest))?;
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        export_pdf_blocking(
+            &app,
+            &settings,
+            &paths,
+            PathBuf::from(dest),
+            &layout.unwrap_or_default(),
+        )
+    })
+    .await
+    .map_err(|err| format!("Failed to join PDF export task: {err}"))?
+}
+
+fn export_pdf_blocking(
+    app: &tauri::AppHandle,
+    settings: &Settings,
+    paths: &[String],
+    destination: PathBuf,
+    layout: &PdfLayout,
+) -> Result<FileOperationSummary, String> {
+    let (page_width, page_height) = layout.page_size.points(layout.landscape);
+    let (columns, rows) = match layout.mode {
+        PdfMode::OnePerPage => (1, 1),
+        PdfMode::ContactSheet => (layout.columns.clamp(1, 20), layout.rows.clamp(1, 20)),
+    };
+    let cells = grid_cells(page_width, page_height, columns, rows, layout.captions);
+    let cell_pixels = |cell: &Cell| {
+        let scale = layout.dpi.clamp(72, 1200) as f32 / POINTS_PER_INCH;
+        (
+            (cell.width * scale).ceil() as u32,
+            (cell.height * scale).ceil() as u32,
+        )
+    };
+    let (max_width, max_height) = cell_pixels(&cells[0]);
+
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let font_id = Ref::new(3);
+    let mut next_id = 4;
+    let mut alloc = || {
+        let id = Ref::new(next_id);
+        next_id += 1;
+        id
+    };
+
+    let mut pdf = Pdf::new();
+    let mut page_ids = Vec::new();
+    let mut results = Vec::with_capacity(paths.len());
+    // Images waiting to be placed on the current page: (xobject id, image, name).
+    let mut placed: Vec<(Ref, EncodedImage, String)> = Vec::new();
+    let mut current = 0;
+
+    let flush_page = |pdf: &mut Pdf,
+                      placed: &mut Vec<(Ref, EncodedImage, String)>,
+                      page_ids: &mut Vec<Ref>,
+                      page_id: Ref,
+                      content_id: Ref| {
+        let mut content = Content::new();
+        for (index, (image_id, image, name)) in placed.iter().enumerate() {
+            let cell = &cells[index];
+            let image_name = format!("Im{index}");
+            let fit = (cell.width / image.width as f32).min(cell.height / image.height as f32);
+            let (width, height) = (image.width as f32 * fit, image.height as f32 * fit);
+            let x = cell.x + (cell.width - width) / 2.0;
+            let y = cell.y + (cell.height - height) / 2.0;
+            content.save_state();
+            content.transform([width, 0.0, 0.0, height, x, y]);
+            content.x_object(Name(image_name.as_bytes()));
+            content.restore_state();
+            if layout.captions {
+                content.begin_text();
+                content.set_font(FONT_NAME, CAPTION_FONT_SIZE);
+                content.next_line(cell.x, cell.y - CAPTION_HEIGHT + CAPTION_FONT_SIZE * 0.5);
+                content.show(Str(&caption_bytes(name, cell.width)));
+                content.end_text();
+            }
+            let mut image_xobject = pdf.image_xobject(*image_id, &image.jpeg);
+            image_xobject.filter(Filter::DctDecode);
+            image_xobject.width(image.width as i32);
+            image_xobject.height(image.height as i32);
+            image_xobject.color_space().device_rgb();
+            image_xobject.bits_per_component(8);
+            image_xobject.finish();
+        }
+        pdf.stream(content_id, &content.finish());
+
+        let mut page = pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, page_width, page_height));
+        page.parent(page_tree_id);
+        page.contents(content_id);
+        let mut resources = page.resources();
+        if layout.captions {
+            resources.fonts().pair(FONT_NAME, font_id);
+        }
+        let mut x_objects = resources.x_objects();
+        for (index, (image_id, _, _)) in placed.iter().enumerate() {
+            x_objects.pair(Name(format!("Im{index}").as_bytes()), *image_id);
+        }
+        x_objects.finish();
+        resources.finish();
+        page.finish();
+        page_ids.push(page_id);
+        placed.clear();
+    };
+
+    for batch in paths.chunks(PDF_ENCODE_BATCH) {
+        let encoded: Vec<Result<EncodedImage, String>> = settings.install(|| {
+            batch
+                .par_iter()
+                .map(|path| {
+                    let source = Path::new(path);
+                    validate_source(source, settings)?;
+                    encode_for_pdf(source, max_width, max_height, settings)
+                })
+                .collect()
+        });
+        for (path, image) in batch.iter().zip(encoded) {
+            let source = Path::new(path);
+            match image {
+                Ok(image) => {
+                    let name = source
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    placed.push((alloc(), image, name));
+                    results.push(success(path.clone(), None));
+                }
+                Err(err) => results.push(failure(path.clone(), err)),
+            }
+            if placed.len() == cells.len() {
+                let (page_id, content_id) = (alloc(), alloc());
+                flush_page(&mut pdf, &mut placed, &mut page_ids, page_id, content_id);
+            }
+            current += 1;
+            emit_progress(app, current, paths.len(), source);
+        }
+    }
+    if !placed.is_empty() {
+        let (page_id, content_id) = (alloc(), alloc());
+        flush_page(&mut pdf, &mut placed, &mut page_ids, page_id, content_id);
+    }
+    if page_ids.is_empty() {
+        return Err("None of the selected images could be added to the PDF.".to_string());
+    }
+
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id)
+        .kids(page_ids.iter().copied())
+        .count(page_ids.len() as i32);
+    if layout.captions {
+        pdf.type1_font(font_id).base_font(Name(b"Helvetica"));
+    }
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&destination)
+        .map_err(|err| format!("Failed to create {}: {err}", destination.display()))?;
+    file.write_all(&pdf.finish())
+        .map_err(|err| format!("Failed to write {}: {err}", destination.display()))?;
+    Ok(FileOperationSummary::from_results(results))
+}
+
+/// Cells in reading order: left to right, top to bottom.
+fn grid_cells(
+    page_width: f32,
+    page_height: f32,
+    columns: u32,
+    rows: u32,
+    captions: bool,
+) -> Vec<Cell> {
+    let caption = if captions { CAPTION_HEIGHT } else { 0.0 };
+    let slot_width = (page_width - MARGIN * 2.0 - CELL_GAP * (columns - 1) as f32) / columns as f32;
+    let slot_height = (page_height - MARGIN * 2.0 - CELL_GAP * (rows - 1) as f32) / rows as f32;
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (row, column)))
+        .map(|(row, column)| {
+            let top = page_height - MARGIN - row as f32 * (slot_height + CELL_GAP);
+            Cell {
+                x: MARGIN + column as f32 * (slot_width + CELL_GAP),
+                y: top - slot_height + caption,
+                width: slot_width,
+                height: (slot_height - caption).max(1.0),
+            }
+        })
+        .collect()
+}
+
+fn encode_for_pdf(
+    source: &Path,
+    max_width: u32,
+    max_height: u32,
+    settings: &Settings,
+) -> Result<EncodedImage, String> {
+    let rgb = decode_image(source, &settings.decode_limits, |image| {
+        let (width, height) = image.dimensions();
+        let image = if width > max_width || height > max_height {
+            image.resize(max_width, max_height, FilterType::Lanczos3)
+        } else {
+            image
+        };
+        flatten_on_white(&image)
+    })?;
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut Cursor::new(&mut jpeg), FULL_IMAGE_JPEG_QUALITY)
+        .encode_image(&rgb)
+        .map_err(|err| format!("Failed to encode image {}: {err}", source.display()))?;
+    Ok(EncodedImage {
+        jpeg,
+        width: rgb.width(),
+        height: rgb.height(),
+    })
+}
+
+/// Paper is white, so transparent areas should print as white, not black.
+fn flatten_on_white(image: &DynamicImage) -> RgbImage {
+    if !image.color().has_alpha() {
+        return image.to_rgb8();
+    }
+    let rgba = image.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [red, green, blue, alpha] = rgba.get_pixel(x, y).0;
+        let blend = |channel: u8| {
+            let alpha = u16::from(alpha);
+            ((u16::from(channel) * alpha + 255 * (255 - alpha)) / 255) as u8
+        };
+        Rgb([blend(red), blend(green), blend(blue)])
+    })
+}
+
+/// The standard Helvetica font only covers Latin-1, so anything else becomes
+/// `?`. Long names are truncated to the cell width with an ellipsis.
+fn caption_bytes(name: &str, width: f32) -> Vec<u8> {
+    let max_chars = (width / CAPTION_CHAR_WIDTH).floor().max(1.0) as usize;
+    let mut bytes: Vec<u8> = name
+        .chars()
+        .map(|character| u8::try_from(u32::from(character)).unwrap_or(b'?'))
+        .collect();
+    if bytes.len() > max_chars {
+        bytes.truncate(max_chars.saturating_sub(3));
+        bytes.extend_from_slice(b"...");
+    }
+    bytes
+}
@@ -0,0 +1,283 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    thread,
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::Manager;
+use thumbnailer_core::cache_path;
+
+use crate::{
+    exif_info, open_cache_db, resolve_data_dir,
+    share_guard::{self, ShareGuard},
+};
+
+/// GeoNames exports bundled under the app's resources: a `cities*.txt`
+/// file of populated places and `countryInfo.txt` for country names.
+const CITIES_RESOURCE: &str = "geonames/cities15000.txt";
+const COUNTRIES_RESOURCE: &str = "geonames/countryInfo.txt";
+/// Photos further than this from every listed place get only a country.
+const MAX_CITY_DISTANCE_KM: f64 = 100.0;
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+static GEOCODER: OnceLock<Option<Geocoder>> = OnceLock::new();
+
+struct City {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+    country_code: String,
+}
+
+/// Nearest-place lookup over the bundled dataset, entirely offline.
+struct Geocoder {
+    cities: Vec<City>,
+    countries: HashMap<String, String>,
+}
+
+impl Geocoder {
+    fn load(resource_dir: &Path) -> Result<Geocoder, String> {
+        let read = |name: &str| {
+            let path = resource_dir.join(name);
+            fs::read_to_string(&path)
+                .map_err(|err| format!("Failed to read {}: {err}", path.display()))
+        };
+        // Tab-separated, with the name, latitude, longitude and country code
+        // in the 2nd, 5th, 6th and 9th columns.
+        let cities = read(CITIES_RESOURCE)?
+            .lines()
+            .filter_map(|line| {
+                let columns: Vec<&str> = line.split('\t').collect();
+                Some(City {
+                    name: columns.get(1)?.to_string(),
+                    latitude: columns.get(4)?.parse().ok()?,
+                    longitude: columns.get(5)?.parse().ok()?,
+                    country_code: columns.get(8)?.to_string(),
+                })
+            })
+            .collect();
+        // Comment lines start with `#`; the code and name are the 1st and
+        // 5th columns.
+        let countries = read(COUNTRIES_RESOURCE)
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| {
+                let columns: Vec<&str> = line.split('\t').collect();
+                Some((columns.first()?.to_string(), columns.get(4)?.to_string()))
+            })
+            .collect();
+        Ok(Geocoder { cities, countries })
+    }
+
+    /// "City, Country", or just the country far from any listed city.
+    fn place(&self, latitude: f64, longitude: f64) -> Option<String> {
+        let (city, distance_km) = self
+            .cities
+            .iter()
+            .map(|city| (city, distance_km(latitude, longitude, city)))
+            .min_by(|(_, left), (_, right)| left.total_cmp(right))?;
+        let country = self
+            .countries
+            .get(&city.country_code)
+            .cloned()
+            .unwrap_or_else(|| city.country_code.clone());
+        if distance_km <= MAX_CITY_DISTANCE_KM {
+            Some(format!("{}, {country}", city.name))
+        } else {
+            Some(country)
+        }
+    }
+}
+
+/// Haversine distance.
+fn distance_km(latitude: f64, longitude: f64, city: &City) -> f64 {
+    let (lat1, lat2) = (latitude.to_radians(), city.latitude.to_radians());
+    let half_lat = (lat2 - lat1) / 2.0;
+    let half_lon = (city.longitude - longitude).to_radians() / 2.0;
+    let a = half_lat.sin().powi(2) + lat1.cos() * lat2.cos() * half_lon.sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// Loads the dataset in the background, then names any cached positions
+/// seen before it was available. Without the dataset, positions are still
+/// cached but never named.
+pub(crate) fn init(app: &tauri::AppHandle) {
+    let resource_dir = app.path().resource_dir();
+    let data_dir = resolve_data_dir(app);
+    let spawned = thread::Builder::new()
+        .name("geocoder-load".to_string())
+        .spawn(move || {
+            let geocoder = resource_dir
+                .map_err(|err| format!("Failed to resolve resource path: {err}"))
+                .and_then(|resource_dir| Geocoder::load(&resource_dir));
+            let geocoder = GEOCODER.get_or_init(|| match geocoder {
+                Ok(value) => Some(value),
+                Err(err) => {
+                    log::warn!("Offline geocoding is unavailable: {}", err);
+                    None
+                }
+            });
+            if let Some(geocoder) = geocoder {
+                if let Err(err) = data_dir
+                    .and_then(|data_dir| open_cache_db(&data_dir))
+                    .and_then(|connection| name_pending(&connection, geocoder))
+                {
+                    log::warn!("Failed to name cached places: {}", err);
+                }
+            }
+        });
+    if let Err(err) = spawned {
+        log::warn!("Failed to start loading the geocoder: {}", err);
+    }
+}
+
+fn geocoder() -> Option<&'static Geocoder> {
+    GEOCODER.get().and_then(Option::as_ref)
+}
+
+/// Caches the GPS position of `path` from its EXIF, remembered per
+/// modification time like capture dates, and names it once the geocoder is
+/// loaded. `read` is false for online-only files.
+pub(crate) fn for_item(
+    connection: &Connection,
+    share_guard: &ShareGuard,
+    path: &Path,
+    modified_unix: i64,
+    read: bool,
+) -> Result<(), String> {
+    let cached: Option<i64> = connection
+        .query_row(
+            "SELECT 1 FROM image_places WHERE path = ?1 AND modified_unix = ?2",
+            params![cache_path(path), modified_unix],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read image place: {err}"))?;
+    if cached.is_some() || !read {
+        return Ok(());
+    }
+    let exif_path = PathBuf::from(path);
+    let position = share_guard.run_once(path, share_guard::METADATA_TIMEOUT, move || {
+        exif_info::read_exif(&exif_path)
+            .as_ref()
+            .and_then(exif_info::gps_position)
+    })?;
+    let (latitude, longitude) = position.unzip();
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO image_places (path, modified_unix, latitude, longitude, place)
+             VALUES (?1, ?2, ?3, ?4, NULL)",
+            params![cache_path(path), modified_unix, latitude, longitude],
+        )
+        .map_err(|err| format!("Failed to store image place: {err}"))?;
+    match (position, geocoder()) {
+        (Some((latitude, longitude)), Some(geocoder)) => {
+            let place = geocoder.place(latitude, longitude);
+            store_place(connection, &cache_path(path), place.as_deref())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn name_pending(connection: &Connection, geocoder: &Geocoder) -> Result<(), String> {
+    let mut statement = connection
+        .prepare(
+            "SELECT path, latitude, longitude FROM image_places
+             WHERE place IS NULL AND latitude IS NOT NULL AND longitude IS NOT NULL",
+        )
+        .map_err(|err| format!("Failed to read image places: {err}"))?;
+    let pending = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?))
+        })
+        .and_then(|rows| rows.collect::<Result<Vec<(String, f64, f64)>, _>>())
+        .map_err(|err| format!("Failed to read image places: {err}"))?;
+    for (path, latitude, longitude) in pending {
+        store_place(
+            connection,
+            &path,
+            geocoder.place(latitude, longitude).as_deref(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Keeps the search index in step with the table.
+fn store_place(connection: &Connection, path: &str, place: Option<&str>) -> Result<(), String> {
+    connection
+        .execute(
+            "UPDATE image_places SET place = ?2 WHERE path = ?1",
+            params![path, place],
+        )
+        .and_then(|_| connection.execute("DELETE FROM place_search WHERE path = ?1", params![path]))
+        .and_then(|_| {
+            connection.execute(
+                "INSERT INTO place_search (path, place) SELECT ?1, ?2 WHERE ?2 IS NOT NULL",
+                params![path, place],
+            )
+        })
+        .map(|_| ())
+        .map_err(|err| format!("Failed to store image place: {err}"))
+}
+
+pub(crate) fn relocate(
+    connection: &Connection,
+    source: &Path,
+    target: &Path,
+) -> Result<(), String> {
+    let (source, target) = (cache_path(source), cache_path(target));
+    connection
+        .execute(
+            "UPDATE OR REPLACE image_places SET path = ?1 WHERE path = ?2",
+            params![target, source],
+        )
+        .and_then(|_| {
+            connection.execute(
+                "UPDATE place_search SET path = ?1 WHERE path = ?2",
+                params![target, source],
+            )
+        })
+        .map(|_| ())
+        .map_err(|err| format!("Failed to move image place: {err}"))
+}
+
+/// Paths of cached images whose place matches every word of `query`, such
+/// as "Lisbon" or "porto portugal". Matching is offline and ignores case.
+#[tauri::command]
+pub(crate) async fn search_places(
+    app: tauri::AppHandle,
+    query: String,
+) -> Result<Vec<String>, String> {
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let Some(terms) = match_query(&query) else {
+            return Ok(Vec::new());
+        };
+        let connection = open_cache_db(&data_dir)?;
+        let mut statement = connection
+            .prepare("SELECT path FROM place_search WHERE place_search MATCH ?1 ORDER BY path")
+            .map_err(|err| format!("Failed to search places: {err}"))?;
+        let paths = statement
+            .query_map(params![terms], |row| row.get(0))
+            .and_then(|rows| rows.collect::<Result<Vec<String>, _>>())
+            .map_err(|err| format!("Failed to search places: {err}"));
+        paths
+    })
+    .await
+    .map_err(|err| format!("Failed to join place search task: {err}"))?
+}
+
+/// An FTS `MATCH` for rows containing every word of `query` as a prefix,
+/// or `None` for a blank query. Each word is quoted, so FTS syntax in the
+/// query is taken literally.
+pub(crate) fn match_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
@@ -0,0 +1,152 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use thumbnailer_core::{extended_path, PendingThumbnail, Scanner, ThumbnailCache};
+
+use crate::{open_cache_db, settings::Settings, PAUSE_POLL_INTERVAL};
+
+/// Subfolders of the open folder prefetched, in name order.
+const MAX_SUBFOLDERS: usize = 16;
+/// Images prefetched per subfolder: the first screenfuls, in the gallery's
+/// order.
+const MAX_IMAGES_PER_SUBFOLDER: usize = 200;
+
+/// Generates thumbnails for the open folder's immediate subfolders on one
+/// background thread, so stepping into one finds them cached. Only one
+/// prefetch runs at a time, and any gallery load stops it so the two never
+/// compete.
+#[derive(Default)]
+pub(crate) struct Prefetcher {
+    cancel_current: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+impl Prefetcher {
+    pub(crate) fn cancel(&self) {
+        if let Some(cancel) = self
+            .cancel_current
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+        {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Starts prefetching for `folder` unless the settings turn it off, or
+    /// scans are recursive and the gallery already covered its subfolders.
+    /// Thumbnails are made at the gallery's `thumbnail_size`.
+    pub(crate) fn start(
+        &self,
+        data_dir: PathBuf,
+        folder: PathBuf,
+        thumbnail_size: u32,
+        settings: Settings,
+        generation_paused: Arc<AtomicBool>,
+    ) {
+        self.cancel();
+        if !settings.prefetch_subfolders || settings.scan.recursive_scan {
+            return;
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        *self
+            .cancel_current
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = Some(cancel.clone());
+        let spawned = thread::Builder::new()
+            .name("subfolder-prefetch".to_string())
+            .spawn(move || {
+                let should_stop = || {
+                    while generation_paused.load(Ordering::Relaxed)
+                        && !cancel.load(Ordering::Relaxed)
+                    {
+                        thread::sleep(PAUSE_POLL_INTERVAL);
+                    }
+                    cancel.load(Ordering::Relaxed)
+                };
+                let prefetched =
+                    prefetch(&data_dir, &folder, thumbnail_size, &settings, should_stop);
+                if let Err(err) = prefetched {
+                    log::warn!("Failed to prefetch subfolders: {}", err);
+                }
+            });
+        if let Err(err) = spawned {
+            log::warn!("Failed to start subfolder prefetch: {}", err);
+        }
+    }
+}
+
+fn prefetch(
+    data_dir: &Path,
+    folder: &Path,
+    thumbnail_size: u32,
+    settings: &Settings,
+    should_stop: impl Fn() -> bool,
+) -> Result<(), String> {
+    let mut connection = open_cache_db(data_dir)?;
+    let generator = settings.generator(thumbnail_size);
+    let mut generated_any = false;
+    for subfolder in subfolders(folder, settings) {
+        let mut image_paths = settings.scan.scan(&subfolder)?;
+        settings.cloud_files.filter(&mut image_paths);
+        settings.raw_pairs.group(&mut image_paths);
+        image_paths.sort_unstable();
+        for image_path in image_paths.into_iter().take(MAX_IMAGES_PER_SUBFOLDER) {
+            if should_stop() {
+                return Ok(());
+            }
+            if !settings.cloud_files.allows_generation(&image_path) {
+                continue;
+            }
+            let pending = match PendingThumbnail::for_path(&image_path) {
+                Ok(value) => value,
+                Err(err) => {
+                    log::warn!("Skipping image during prefetch: {}", err);
+                    continue;
+                }
+            };
+            if connection.contains(&pending.cache_key, pending.modified_unix, thumbnail_size)? {
+                continue;
+            }
+            match pending.generate(&generator) {
+                Ok(generated) => {
+                    connection.store(std::slice::from_ref(&generated))?;
+                    generated_any = true;
+                }
+                Err(err) => log::warn!("Skipping image during prefetch: {}", err),
+            }
+        }
+    }
+    if generated_any {
+        connection.prune(settings.cache_max_bytes)?;
+    }
+    Ok(())
+}
+
+fn subfolders(folder: &Path, settings: &Settings) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(extended_path(folder)) {
+        Ok(value) => value,
+        Err(err) => {
+            log::warn!("Failed to list subfolders of {}: {}", folder.display(), err);
+            return Vec::new();
+        }
+    };
+    let mut subfolders: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            !settings.scan.is_excluded(&name) && !settings.scan.skips_folder(folder, &name)
+        })
+        .map(|entry| folder.join(entry.file_name()))
+        .filter(|path| extended_path(path).is_dir())
+        .collect();
+    subfolders.sort_unstable();
+    subfolders.truncate(MAX_SUBFOLDERS);
+    subfolders
+}
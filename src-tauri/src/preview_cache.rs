@@ -0,0 +1,126 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use rayon::prelude::*;
+use thumbnailer_core::last_modified_unix;
+
+use crate::{load_full_image_blocking, settings::Settings, AppState, FullImageBytes};
+
+/// Enough for the current image and a couple of neighbours on either side.
+const PREVIEW_CACHE_CAPACITY: usize = 6;
+
+struct CachedPreview {
+    path: PathBuf,
+    max_dimension: Option<u32>,
+    modified_unix: i64,
+    bytes: Arc<Vec<u8>>,
+}
+
+/// Small most-recently-used cache of viewer-ready image payloads.
+#[derive(Default)]
+pub(crate) struct PreviewCache {
+    entries: Mutex<VecDeque<CachedPreview>>,
+}
+
+impl PreviewCache {
+    pub(crate) fn get(&self, path: &Path, max_dimension: Option<u32>) -> Option<Arc<Vec<u8>>> {
+        let modified_unix = last_modified_unix(path).ok()?;
+        let mut entries = self.entries.lock().ok()?;
+        let index = entries.iter().position(|entry| {
+            entry.path == path
+                && entry.max_dimension == max_dimension
+                && entry.modified_unix == modified_unix
+        })?;
+        let entry = entries.remove(index)?;
+        let bytes = entry.bytes.clone();
+        entries.push_front(entry);
+        Some(bytes)
+    }
+
+    pub(crate) fn insert(&self, path: PathBuf, max_dimension: Option<u32>, bytes: Arc<Vec<u8>>) {
+        let Ok(modified_unix) = last_modified_unix(&path) else {
+            return;
+        };
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        entries.retain(|entry| !(entry.path == path && entry.max_dimension == max_dimension));
+        entries.push_front(CachedPreview {
+            path,
+            max_dimension,
+            modified_unix,
+            bytes,
+        });
+        entries.truncate(PREVIEW_CACHE_CAPACITY);
+    }
+
+    /// Drops every size of `path`, such as after it was edited.
+    pub(crate) fn remove(&self, path: &Path) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|entry| entry.path != path);
+        }
+    }
+
+    fn contains(&self, path: &Path, max_dimension: Option<u32>) -> bool {
+        self.entries.lock().is_ok_and(|entries| {
+            entries
+                .iter()
+                .any(|entry| entry.path == path && entry.max_dimension == max_dimension)
+        })
+    }
+}
+
+/// Decodes the given images (typically the previous and next ones in viewing
+/// order) into the preview cache so that navigating to them is instant.
+#[tauri::command]
+pub(crate) async fn preload_images(
+    state: tauri::State<'_, AppState>,
+    paths: Vec<String>,
+    max_dimension: Option<u32>,
+) -> Result<(), String> {
+    state.path_scope.check_all(&paths)?;
+    let cache = state.preview_cache.clone();
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || preload(&cache, paths, max_dimension, &settings))
+        .await
+        .map_err(|err| format!("Failed to join preload task: {err}"))
+}
+
+/// Decodes up to a cache's worth of `paths`, in order, at the viewer's
+/// `max_dimension`. Blocks until they are in.
+pub(crate) fn preload(
+    cache: &PreviewCache,
+    paths: Vec<String>,
+    max_dimension: Option<u32>,
+    settings: &Settings,
+) {
+    let max_dimension = Some(
+        max_dimension
+            .unwrap_or(crate::DEFAULT_FULL_IMAGE_MAX_DIMENSION)
+            .max(1),
+    );
+    let pending: Vec<PathBuf> = paths
+        .into_iter()
+        .take(PREVIEW_CACHE_CAPACITY - 1)
+        .map(PathBuf::from)
+        .filter(|path| !cache.contains(path, max_dimension))
+        .collect();
+    settings.install(|| {
+        pending.into_par_iter().for_each(|path| {
+            match load_full_image_blocking(
+                path.to_string_lossy().to_string(),
+                max_dimension,
+                settings,
+                true,
+            ) {
+                Ok(FullImageBytes::Owned(bytes)) => cache.insert(path, max_dimension, bytes),
+                // Too big to keep around; it is mapped again when viewed.
+                Ok(FullImageBytes::Mapped(_)) => {}
+                Err(err) => log::warn!("Failed to preload image: {}", err),
+            }
+        })
+    });
+}
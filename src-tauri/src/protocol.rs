@@ -0,0 +1,96 @@
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use tauri::{
+    http::{header, Request, Response, StatusCode},
+    UriSchemeResponder,
+};
+
+use crate::{resolve_data_dir, DB_FILE_NAME};
+
+pub(crate) const THUMBNAIL_SCHEME: &str = "thumb";
+
+/// URL under which the webview can fetch a cached thumbnail. Responses are
+/// cached as immutable, so the URL carries the blob's `content_hash`: a
+/// regenerated thumbnail gets a new URL even when its source's modified
+/// time didn't change.
+pub(crate) fn thumbnail_url(cache_key: &str, content_hash: &str) -> String {
+    // Windows and Android webviews only accept custom schemes through the
+    // `http://<scheme>.localhost` form.
+    if cfg!(any(windows, target_os = "android")) {
+        format!("http://{THUMBNAIL_SCHEME}.localhost/{cache_key}?v={content_hash}")
+    } else {
+        format!("{THUMBNAIL_SCHEME}://localhost/{cache_key}?v={content_hash}")
+    }
+}
+
+pub(crate) fn handle_thumbnail_request(
+    app: &tauri::AppHandle,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app = app.clone();
+    let cache_key = request.uri().path().trim_start_matches('/').to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        let response = match thumbnail_response(&app, &cache_key) {
+            Ok(value) => value,
+            Err((status, message)) => {
+                log::warn!("Thumbnail request for {} failed: {}", cache_key, message);
+                error_response(status, message)
+            }
+        };
+        responder.respond(response);
+    });
+}
+
+fn thumbnail_response(
+    app: &tauri::AppHandle,
+    cache_key: &str,
+) -> Result<Response<Vec<u8>>, (StatusCode, String)> {
+    let is_cache_key =
+        cache_key.len() == 64 && cache_key.bytes().all(|byte| byte.is_ascii_hexdigit());
+    if !is_cache_key {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Malformed thumbnail key.".to_string(),
+        ));
+    }
+
+    let data_dir = resolve_data_dir(app).map_err(internal_error)?;
+    let connection = Connection::open_with_flags(
+        data_dir.join(DB_FILE_NAME),
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|err| internal_error(format!("Failed to open cache database: {err}")))?;
+    let cached: Option<(Vec<u8>, String)> = connection
+        .query_row(
+            "SELECT thumbnail_blob, mime_type FROM thumbnails WHERE cache_key = ?1",
+            params![cache_key],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|err| internal_error(format!("Failed to read cache entry: {err}")))?;
+    let (blob, mime_type) =
+        cached.ok_or_else(|| (StatusCode::NOT_FOUND, "Thumbnail not cached.".to_string()))?;
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, mime_type)
+        .header(header::CONTENT_LENGTH, blob.len())
+        .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable")
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .body(blob)
+        .map_err(|err| internal_error(format!("Failed to build response: {err}")))
+}
+
+fn internal_error(message: String) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, message)
+}
+
+fn error_response(status: StatusCode, message: String) -> Response<Vec<u8>> {
+    let mut response = Response::new(message.into_bytes());
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/plain"),
+    );
+    response
+}
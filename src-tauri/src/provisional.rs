@@ -0,0 +1,61 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::Emitter;
+
+use crate::{
+    data_url_for_blob, exif_info,
+    share_guard::{self, ShareGuard},
+};
+
+/// A camera's own small preview from an image's EXIF, shown in the grid
+/// until the real thumbnail, which the gallery response carries, replaces
+/// it.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ProvisionalThumbnail {
+    path: String,
+    image: String,
+    /// Always set, so listeners can tell these from final thumbnails.
+    provisional: bool,
+}
+
+/// The `provisional-thumbnails` event, sent with each progress update
+/// during a gallery load, like `item-dimensions`.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProvisionalThumbnailBatch {
+    items: Vec<ProvisionalThumbnail>,
+}
+
+/// The embedded EXIF thumbnail of an image still waiting for generation,
+/// if it has one. Reading it touches only the file's header.
+pub(crate) fn for_item(
+    share_guard: &ShareGuard,
+    path: &Path,
+) -> Result<Option<ProvisionalThumbnail>, String> {
+    let exif_path = PathBuf::from(path);
+    let image = share_guard.run_once(path, share_guard::METADATA_TIMEOUT, move || {
+        let exif = exif_info::read_exif(&exif_path)?;
+        let thumbnail = exif_info::embedded_thumbnail(&exif)?;
+        Some(data_url_for_blob(thumbnail, "image/jpeg"))
+    })?;
+    Ok(image.map(|image| ProvisionalThumbnail {
+        path: path.to_string_lossy().to_string(),
+        image,
+        provisional: true,
+    }))
+}
+
+/// Sends the previews gathered since the last call, if any.
+pub(crate) fn emit(app: &tauri::AppHandle, items: &mut Vec<ProvisionalThumbnail>) {
+    if items.is_empty() {
+        return;
+    }
+    let batch = ProvisionalThumbnailBatch {
+        items: std::mem::take(items),
+    };
+    if let Err(err) = app.emit("provisional-thumbnails", &batch) {
+        log::warn!("Failed to emit provisional thumbnails: {}", err);
+    }
+}
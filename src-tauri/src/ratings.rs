@@ -0,0 +1,250 @@
+use std::{collections::HashMap, path::Path};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use thumbnailer_core::last_modified_unix;
+
+#[cfg(windows)]
+use windows::Win32::Storage::EnhancedStorage::PKEY_Rating;
+
+use crate::{open_cache_db, resolve_data_dir, AppState};
+
+/// Star ratings for `paths`, keyed by path; unrated images are left out.
+///
+/// A rating stored in the file itself, as Windows Explorer writes it, is
+/// picked up whenever the file has changed since it was last read, and
+/// replaces ours: a changed file means someone rated it elsewhere.
+#[tauri::command]
+pub(crate) async fn get_ratings(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    paths: Vec<String>,
+) -> Result<HashMap<String, u32>, String> {
+    state.path_scope.check_all(&paths)?;
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        let mut ratings = HashMap::new();
+        for path in paths {
+            if let Err(err) = sync_file_rating(&connection, Path::new(&path)) {
+                log::debug!("No file rating for {}: {}", path, err);
+            }
+            let rating: Option<u32> = connection
+                .query_row(
+                    "SELECT rating FROM image_ratings WHERE path = ?1",
+                    params![path],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|err| format!("Failed to read rating: {err}"))?;
+            if let Some(rating) = rating.filter(|rating| *rating > 0) {
+                ratings.insert(path, rating);
+            }
+        }
+        Ok(ratings)
+    })
+    .await
+    .map_err(|err| format!("Failed to join ratings task: {err}"))?
+}
+
+/// Rates `path` from 1 to 5 stars, or clears its rating with 0. With
+/// `write_file_ratings` set, the rating is also written into the file
+/// (through the Windows property system), where Explorer shows it.
+#[tauri::command]
+pub(crate) async fn set_rating(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+    rating: u32,
+) -> Result<(), String> {
+    state.path_scope.check(Path::new(&path))?;
+    if rating > 5 {
+        return Err(format!("{rating} is not a rating from 0 to 5 stars."));
+    }
+    let data_dir = resolve_data_dir(&app)?;
+    let write_file = state.settings.get().write_file_ratings;
+    tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO image_ratings (path, rating) VALUES (?1, ?2)",
+                params![path, rating],
+            )
+            .map_err(|err| format!("Failed to write rating: {err}"))?;
+        if write_file && write_file_rating(Path::new(&path), rating)? {
+            // Our own write changed the file; don't read it back as an
+            // outside edit.
+            mark_seen(&connection, Path::new(&path))?;
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|err| format!("Failed to join rating task: {err}"))?
+}
+
+/// Copies the file's own rating into `image_ratings` if the file changed
+/// since it was last read.
+fn sync_file_rating(connection: &Connection, path: &Path) -> Result<(), String> {
+    let modified_unix = last_modified_unix(path)?;
+    let seen: Option<i64> = connection
+        .query_row(
+            "SELECT modified_unix FROM rating_scans WHERE path = ?1",
+            params![path.to_string_lossy()],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read rating scan: {err}"))?;
+    if seen == Some(modified_unix) {
+        return Ok(());
+    }
+    if let Some(rating) = read_file_rating(path) {
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO image_ratings (path, rating) VALUES (?1, ?2)",
+                params![path.to_string_lossy(), rating],
+            )
+            .map_err(|err| format!("Failed to write rating: {err}"))?;
+    }
+    mark_seen(connection, path)
+}
+
+fn mark_seen(connection: &Connection, path: &Path) -> Result<(), String> {
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO rating_scans (path, modified_unix) VALUES (?1, ?2)",
+            params![path.to_string_lossy(), last_modified_unix(path)?],
+        )
+        .map(|_| ())
+        .map_err(|err| format!("Failed to record rating scan: {err}"))
+}
+
+/// Explorer stores ratings as 1, 25, 50, 75 or 99 percent; other tools
+/// write values in between, which round to the nearest star.
+#[cfg(windows)]
+fn stars_from_percent(percent: u32) -> Option<u32> {
+    match percent {
+        0 => None,
+        1..=12 => Some(1),
+        13..=37 => Some(2),
+        38..=62 => Some(3),
+        63..=87 => Some(4),
+        _ => Some(5),
+    }
+}
+
+#[cfg(windows)]
+fn percent_from_stars(stars: u32) -> u32 {
+    match stars {
+        0 => 0,
+        1 => 1,
+        2..=4 => (stars - 1) * 25,
+        _ => 99,
+    }
+}
+
+/// The shell's `System.Rating` property, which covers every format with a
+/// property handler: EXIF and XMP in JPEGs, TIFFs, HEICs and more.
+#[cfg(windows)]
+fn read_file_rating(path: &Path) -> Option<u32> {
+    use windows::Win32::System::Com::StructuredStorage::{PropVariantClear, PropVariantToUInt32};
+
+    with_property_store(path, false, |store| {
+        // SAFETY: the value is read and cleared before the store is released.
+        unsafe {
+            let mut value = store.GetValue(&PKEY_Rating)?;
+            let percent = PropVariantToUInt32(&value);
+            PropVariantClear(&mut value)?;
+            Ok(percent.ok().and_then(stars_from_percent))
+        }
+    })
+    .map_err(|err| log::debug!("No shell rating for {}: {}", path.display(), err))
+    .ok()
+    .flatten()
+}
+
+/// Returns whether the file was changed; formats without a writable
+/// property handler are left alone.
+#[cfg(windows)]
+fn write_file_rating(path: &Path, stars: u32) -> Result<bool, String> {
+    use windows::Win32::System::{
+        Com::StructuredStorage::PROPVARIANT,
+        Variant::{VT_EMPTY, VT_UI4},
+    };
+
+    with_property_store(path, true, |store| {
+        let mut value = PROPVARIANT::default();
+        // SAFETY: a `VT_UI4` holds no resources to free, and the store
+        // copies the value in `SetValue`.
+        unsafe {
+            let fields = &mut value.Anonymous.Anonymous;
+            if stars == 0 {
+                fields.vt = VT_EMPTY;
+            } else {
+                fields.vt = VT_UI4;
+                fields.Anonymous.ulVal = percent_from_stars(stars);
+            }
+            store.SetValue(&PKEY_Rating, &value)?;
+            store.Commit()?;
+        }
+        Ok(true)
+    })
+    .or_else(|err| {
+        log::warn!("Failed to write rating into {}: {}", path.display(), err);
+        Ok(false)
+    })
+}
+
+#[cfg(windows)]
+fn with_property_store<T>(
+    path: &Path,
+    writable: bool,
+    op: impl FnOnce(
+        &windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore,
+    ) -> windows::core::Result<T>,
+) -> Result<T, String> {
+    use windows::{
+        core::HSTRING,
+        Win32::{
+            System::Com::{CoInitializeEx, CoUninitialize, COINIT_MULTITHREADED},
+            UI::Shell::PropertiesSystem::{
+                IPropertyStore, SHGetPropertyStoreFromParsingName, GPS_DEFAULT, GPS_READWRITE,
+            },
+        },
+    };
+
+    let flags = if writable { GPS_READWRITE } else { GPS_DEFAULT };
+    // SAFETY: COM is initialized on this thread for the duration of the
+    // call, and the store is released before it is uninitialized.
+    unsafe {
+        // Blocking pool threads may already have COM initialized; only
+        // balance a successful call.
+        let initialized = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+        let result = SHGetPropertyStoreFromParsingName::<_, _, IPropertyStore>(
+            &HSTRING::from(path.as_os_str()),
+            None,
+            flags,
+        )
+        .and_then(|store| op(&store))
+        .map_err(|err| format!("Windows property system: {err}"));
+        if initialized {
+            CoUninitialize();
+        }
+        result
+    }
+}
+
+/// Elsewhere the EXIF `Rating` tag Explorer and most DAM tools write is
+/// read directly. Writing it back is only supported on Windows.
+#[cfg(not(windows))]
+fn read_file_rating(path: &Path) -> Option<u32> {
+    let exif = crate::exif_info::read_exif(path)?;
+    crate::exif_info::rating(&exif).filter(|stars| *stars > 0)
+}
+
+#[cfg(not(windows))]
+fn write_file_rating(path: &Path, _stars: u32) -> Result<bool, String> {
+    log::debug!(
+        "Writing ratings into files is only supported on Windows: {}",
+        path.display()
+    );
+    Ok(false)
+}
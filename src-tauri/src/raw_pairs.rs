@@ -0,0 +1,71 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Camera RAW extensions that can pair with a JPEG of the same name.
+const RAW_EXTENSIONS: &[&str] = &[
+    "3fr", "arw", "cr2", "cr3", "dng", "erf", "iiq", "kdc", "mrw", "nef", "nrw", "orf", "pef",
+    "raf", "rw2", "sr2", "srf", "srw", "x3f",
+];
+const JPEG_EXTENSIONS: &[&str] = &["jpg", "jpeg"];
+
+/// What galleries do with a RAW file and the JPEG the camera saved beside
+/// it, which share a name apart from the extension.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RawPairs {
+    /// List both files as separate items.
+    Separate,
+    /// List one item shown from the JPEG, with the RAW as its pair.
+    #[default]
+    Jpeg,
+    /// List one item shown from the RAW, with the JPEG as its pair.
+    Raw,
+}
+
+impl RawPairs {
+    /// Drops the half of each pair that isn't shown from `paths`, and
+    /// returns the dropped halves keyed by the path kept in their place.
+    pub(crate) fn group(self, paths: &mut Vec<PathBuf>) -> HashMap<PathBuf, PathBuf> {
+        let mut pairs = HashMap::new();
+        if self == RawPairs::Separate {
+            return pairs;
+        }
+        let jpegs: HashMap<PathBuf, &PathBuf> = paths
+            .iter()
+            .filter(|path| has_extension(path, JPEG_EXTENSIONS))
+            .filter_map(|path| Some((pair_key(path)?, path)))
+            .collect();
+        for raw in paths
+            .iter()
+            .filter(|path| has_extension(path, RAW_EXTENSIONS))
+        {
+            let Some(jpeg) = pair_key(raw).and_then(|key| jpegs.get(&key)) else {
+                continue;
+            };
+            let (kept, dropped) = match self {
+                RawPairs::Raw => (raw, *jpeg),
+                _ => (*jpeg, raw),
+            };
+            pairs.insert(kept.clone(), dropped.clone());
+        }
+        let dropped: HashSet<&PathBuf> = pairs.values().collect();
+        paths.retain(|path| !dropped.contains(path));
+        pairs
+    }
+}
+
+/// The folder and lowercased stem shared by both halves of a pair.
+fn pair_key(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_string_lossy().to_lowercase();
+    Some(path.with_file_name(stem))
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_some_and(|extension| extensions.contains(&extension.as_str()))
+}
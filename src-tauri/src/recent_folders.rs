@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::{now_unix, open_cache_db, path_scope::PathScope, resolve_data_dir, tray, AppState};
+
+/// Unpinned folders beyond this many are forgotten, oldest first.
+const MAX_RECENT_FOLDERS: i64 = 10;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RecentFolder {
+    pub(crate) path: String,
+    pub(crate) pinned: bool,
+    pub(crate) opened_unix: i64,
+}
+
+/// Pinned folders first, then the rest by when they were last opened.
+#[tauri::command]
+pub(crate) async fn get_recent_folders(app: tauri::AppHandle) -> Result<Vec<RecentFolder>, String> {
+    let data_dir = resolve_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || list(&open_cache_db(&data_dir)?))
+        .await
+        .map_err(|err| format!("Failed to join recent folders task: {err}"))?
+}
+
+#[tauri::command]
+pub(crate) async fn set_folder_pinned(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+    pinned: bool,
+) -> Result<Vec<RecentFolder>, String> {
+    let data_dir = resolve_data_dir(&app)?;
+    check_scope(data_dir.clone(), &state.path_scope, Path::new(&path)).await?;
+    let folders = tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        connection
+            .execute(
+                "INSERT INTO recent_folders (path, opened_unix, pinned) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(path) DO UPDATE SET pinned = excluded.pinned",
+                params![path, now_unix(), pinned],
+            )
+            .map_err(|err| format!("Failed to update pinned folder: {err}"))?;
+        trim(&connection)?;
+        list(&connection)
+    })
+    .await
+    .map_err(|err| format!("Failed to join pin folder task: {err}"))??;
+    tray::refresh(&app);
+    Ok(folders)
+}
+
+pub(crate) fn record(connection: &Connection, folder: &Path) -> Result<(), String> {
+    connection
+        .execute(
+            "INSERT INTO recent_folders (path, opened_unix) VALUES (?1, ?2)
+             ON CONFLICT(path) DO UPDATE SET opened_unix = excluded.opened_unix, trusted = 1",
+            params![folder.to_string_lossy(), now_unix()],
+        )
+        .map_err(|err| format!("Failed to record recent folder: {err}"))?;
+    trim(connection)
+}
+
+/// Checks `folder` against the path scope, allowing it first if it is a
+/// recent folder the user chose in an earlier session. Folders that only
+/// came from a restored backup aren't trusted until they are picked again.
+pub(crate) async fn check_scope(
+    data_dir: PathBuf,
+    scope: &PathScope,
+    folder: &Path,
+) -> Result<(), String> {
+    if scope.check(folder).is_ok() {
+        return Ok(());
+    }
+    let path = folder.to_string_lossy().to_string();
+    let recent =
+        tauri::async_runtime::spawn_blocking(move || contains(&open_cache_db(&data_dir)?, &path))
+            .await
+            .map_err(|err| format!("Failed to join recent folders task: {err}"))??;
+    if recent {
+        scope.allow(folder);
+    }
+    scope.check(folder)
+}
+
+fn contains(connection: &Connection, path: &str) -> Result<bool, String> {
+    connection
+        .query_row(
+            "SELECT 1 FROM recent_folders WHERE path = ?1 AND trusted = 1",
+            params![path],
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|row| row.is_some())
+        .map_err(|err| format!("Failed to query recent folders: {err}"))
+}
+
+pub(crate) fn list(connection: &Connection) -> Result<Vec<RecentFolder>, String> {
+    let mut statement = connection
+        .prepare(
+            "SELECT path, pinned, opened_unix FROM recent_folders
+             ORDER BY pinned DESC, opened_unix DESC",
+        )
+        .map_err(|err| format!("Failed to prepare recent folders query: {err}"))?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok(RecentFolder {
+                path: row.get(0)?,
+                pinned: row.get(1)?,
+                opened_unix: row.get(2)?,
+            })
+        })
+        .map_err(|err| format!("Failed to query recent folders: {err}"))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(|err| format!("Failed to read recent folders: {err}"))
+}
+
+pub(crate) fn last_opened(connection: &Connection) -> Result<Option<String>, String> {
+    connection
+        .query_row(
+            "SELECT path FROM recent_folders ORDER BY opened_unix DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to query last folder: {err}"))
+}
+
+fn trim(connection: &Connection) -> Result<(), String> {
+    connection
+        .execute(
+            "DELETE FROM recent_folders WHERE pinned = 0 AND path NOT IN (
+               SELECT path FROM recent_folders WHERE pinned = 0
+               ORDER BY opened_unix DESC LIMIT ?1
+             )",
+            params![MAX_RECENT_FOLDERS],
+        )
+        .map(|_| ())
+        .map_err(|err| format!("Failed to trim recent folders: {err}"))
+}
@@ -0,0 +1,126 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+
+use crate::{benchmark::BENCHMARK_DIR_PREFIX, now_unix, open_cache_db, resolve_data_dir, AppState};
+
+/// Present while the app runs and removed on a clean exit, so finding it at
+/// startup means the last session crashed or was killed.
+const SESSION_FILE_NAME: &str = "session.json";
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct SessionMarker {
+    started_unix: i64,
+    /// The folder a gallery load was in progress for, if any.
+    scanning_folder: Option<String>,
+}
+
+/// Runs at startup, before any gallery loads: settles a crashed session's
+/// database, clears what it left half done, and starts this session's
+/// marker. When the last session ended abnormally, it is kept for
+/// `get_abnormal_exit` and announced as `previous-session-crashed`.
+pub(crate) fn recover(app: &tauri::AppHandle) -> Result<(), String> {
+    let data_dir = resolve_data_dir(app)?;
+    let marker_path = data_dir.join(SESSION_FILE_NAME);
+    let previous: Option<SessionMarker> = fs::read(&marker_path)
+        .ok()
+        .map(|bytes| serde_json::from_slice(&bytes).unwrap_or_default());
+
+    // SQLite recovers from the write-ahead log on open; the checkpoint
+    // folds what the crashed session left in it back into the database and
+    // truncates it.
+    let connection = open_cache_db(&data_dir)?;
+    connection
+        .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+        .map_err(|err| format!("Failed to checkpoint cache database: {err}"))?;
+    if previous.is_some() {
+        remove_stale_benchmark_dirs();
+    }
+
+    let marker = SessionMarker {
+        started_unix: now_unix(),
+        scanning_folder: None,
+    };
+    write_marker(&data_dir, &marker)?;
+    if let Some(previous) = previous {
+        log::warn!("The previous session ended abnormally");
+        let state = app.state::<AppState>();
+        if let Ok(mut abnormal_exit) = state.abnormal_exit.lock() {
+            *abnormal_exit = Some(previous.clone());
+        }
+        if let Err(err) = app.emit("previous-session-crashed", &previous) {
+            log::warn!("Failed to emit previous session crash: {}", err);
+        }
+    }
+    Ok(())
+}
+
+/// Records the gallery load in progress (or that none is) in the marker.
+pub(crate) fn set_scanning_folder(data_dir: &Path, folder: Option<&Path>) {
+    let marker_path = data_dir.join(SESSION_FILE_NAME);
+    let mut marker: SessionMarker = fs::read(&marker_path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default();
+    marker.scanning_folder = folder.map(|folder| folder.to_string_lossy().to_string());
+    if let Err(err) = write_marker(data_dir, &marker) {
+        log::warn!("{}", err);
+    }
+}
+
+/// Removes the marker on a clean exit.
+pub(crate) fn end_session(app: &tauri::AppHandle) {
+    let Ok(data_dir) = resolve_data_dir(app) else {
+        return;
+    };
+    if let Err(err) = fs::remove_file(data_dir.join(SESSION_FILE_NAME)) {
+        log::warn!("Failed to remove session marker: {}", err);
+    }
+}
+
+/// How the last session ended abnormally, if it did; the UI offers to resume
+/// its scan. Returned once.
+#[tauri::command]
+pub(crate) fn get_abnormal_exit(state: tauri::State<'_, AppState>) -> Option<SessionMarker> {
+    state.abnormal_exit.lock().ok()?.take()
+}
+
+fn write_marker(data_dir: &Path, marker: &SessionMarker) -> Result<(), String> {
+    let json = serde_json::to_vec(marker)
+        .map_err(|err| format!("Failed to serialize session marker: {err}"))?;
+    // Written beside the marker and renamed over it, so a crash mid-write
+    // can't leave it unreadable.
+    let temp_path = data_dir.join(format!("{SESSION_FILE_NAME}.tmp"));
+    fs::write(&temp_path, json)
+        .and_then(|_| fs::rename(&temp_path, data_dir.join(SESSION_FILE_NAME)))
+        .map_err(|err| format!("Failed to write session marker: {err}"))
+}
+
+/// Benchmark images whose run was cut short. Only ever called after a crash,
+/// since a running benchmark in another instance would own one too.
+fn remove_stale_benchmark_dirs() {
+    let Ok(entries) = fs::read_dir(std::env::temp_dir()) else {
+        return;
+    };
+    let own = format!("{BENCHMARK_DIR_PREFIX}{}", std::process::id());
+    let stale: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name().is_some_and(|name| {
+                let name = name.to_string_lossy();
+                name.starts_with(BENCHMARK_DIR_PREFIX) && name != own
+            })
+        })
+        .collect();
+    for path in stale {
+        if let Err(err) = fs::remove_dir_all(&path) {
+            log::warn!("Failed to remove {}: {}", path.display(), err);
+        }
+    }
+}
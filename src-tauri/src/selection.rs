@@ -0,0 +1,152 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::Path,
+    sync::{Mutex, MutexGuard},
+};
+
+use rayon::prelude::*;
+use serde::Serialize;
+use thumbnailer_core::extended_path;
+
+use crate::{
+    file_ops::FileOperationSummary,
+    share_guard::{ShareGuard, METADATA_TIMEOUT},
+    AppState,
+};
+
+/// The images selected in the grid. Batch commands called without paths
+/// act on these.
+#[derive(Default)]
+pub(crate) struct Selection {
+    paths: Mutex<Vec<String>>,
+}
+
+impl Selection {
+    /// `paths` when given, otherwise the selection.
+    pub(crate) fn or_selected(&self, paths: Option<Vec<String>>) -> Result<Vec<String>, String> {
+        let paths = match paths {
+            Some(paths) => paths,
+            None => self.lock().clone(),
+        };
+        if paths.is_empty() {
+            return Err("Nothing is selected.".to_string());
+        }
+        Ok(paths)
+    }
+
+    /// Called when another folder is loaded.
+    pub(crate) fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Drops the files a delete, move or rename took away from where they
+    /// were selected, keeping the ones that moved at their new paths.
+    pub(crate) fn follow(&self, summary: &FileOperationSummary) {
+        let mut paths = self.lock();
+        let changes: Vec<(usize, Option<String>)> = {
+            let indices: HashMap<&str, usize> = paths
+                .iter()
+                .enumerate()
+                .map(|(index, path)| (path.as_str(), index))
+                .collect();
+            summary
+                .done()
+                .filter_map(|(path, new_path)| {
+                    let index = *indices.get(path)?;
+                    Some((index, new_path.map(str::to_string)))
+                })
+                .collect()
+        };
+        let mut removed = vec![false; paths.len()];
+        for (index, new_path) in changes {
+            match new_path {
+                Some(new_path) => paths[index] = new_path,
+                None => removed[index] = true,
+            }
+        }
+        let mut index = 0;
+        paths.retain(|_| {
+            index += 1;
+            !removed[index - 1]
+        });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<String>> {
+        self.paths.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SelectionSummary {
+    count: usize,
+    /// Of the selected files that still exist.
+    total_bytes: u64,
+    /// Selected count per lowercase file extension.
+    formats: BTreeMap<String, usize>,
+    /// Selected files that were moved or deleted since.
+    missing: usize,
+}
+
+/// Replaces the selection, keeping the first of any repeated paths.
+#[tauri::command]
+pub(crate) fn set_selection(
+    state: tauri::State<'_, AppState>,
+    paths: Vec<String>,
+) -> Result<(), String> {
+    state.path_scope.check_all(&paths)?;
+    let mut selected = Vec::with_capacity(paths.len());
+    let mut seen = HashSet::new();
+    for path in paths {
+        if seen.insert(path.clone()) {
+            selected.push(path);
+        }
+    }
+    *state.selection.lock() = selected;
+    Ok(())
+}
+
+/// Counts, sizes and formats of the selection for the status bar. Sizes are
+/// read in parallel every time, so they stay right after edits.
+#[tauri::command]
+pub(crate) async fn get_selection_summary(
+    state: tauri::State<'_, AppState>,
+) -> Result<SelectionSummary, String> {
+    let paths = state.selection.lock().clone();
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        // Selections can span an unresponsive share.
+        let share_guard = ShareGuard::default();
+        let sizes: Vec<Option<u64>> = settings.install(|| {
+            paths
+                .par_iter()
+                .map(|path| {
+                    let io_path = extended_path(Path::new(path)).into_owned();
+                    share_guard
+                        .run(Path::new(path), METADATA_TIMEOUT, move || {
+                            fs::metadata(&io_path)
+                        })
+                        .ok()
+                        .map(|metadata| metadata.len())
+                })
+                .collect()
+        });
+        let mut formats = BTreeMap::new();
+        for path in &paths {
+            let extension = Path::new(path)
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+                .unwrap_or_default();
+            *formats.entry(extension).or_insert(0) += 1;
+        }
+        SelectionSummary {
+            count: paths.len(),
+            total_bytes: sizes.iter().flatten().sum(),
+            formats,
+            missing: sizes.iter().filter(|size| size.is_none()).count(),
+        }
+    })
+    .await
+    .map_err(|err| format!("Failed to join selection summary task: {err}"))
+}
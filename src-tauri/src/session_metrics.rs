@@ -0,0 +1,68 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::Serialize;
+
+use crate::AppState;
+
+/// Thumbnail work since the app started, counted wherever galleries and
+/// single thumbnails consult the cache.
+#[derive(Default)]
+pub(crate) struct SessionMetrics {
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    decode_failures: AtomicU64,
+    bytes_generated: AtomicU64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SessionMetricsSnapshot {
+    cache_hits: u64,
+    cache_misses: u64,
+    /// Share of lookups served from the cache, from 0 to 1; 0 before any.
+    hit_rate: f64,
+    decode_failures: u64,
+    /// Encoded size of the thumbnails generated, as stored in the cache.
+    bytes_generated: u64,
+}
+
+impl SessionMetrics {
+    pub(crate) fn record_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_miss(&self) {
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_failure(&self) {
+        self.decode_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_generated(&self, bytes: usize) {
+        self.bytes_generated
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> SessionMetricsSnapshot {
+        let cache_hits = self.cache_hits.load(Ordering::Relaxed);
+        let cache_misses = self.cache_misses.load(Ordering::Relaxed);
+        let lookups = cache_hits + cache_misses;
+        SessionMetricsSnapshot {
+            cache_hits,
+            cache_misses,
+            hit_rate: if lookups == 0 {
+                0.0
+            } else {
+                cache_hits as f64 / lookups as f64
+            },
+            decode_failures: self.decode_failures.load(Ordering::Relaxed),
+            bytes_generated: self.bytes_generated.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[tauri::command]
+pub(crate) fn get_session_metrics(state: tauri::State<'_, AppState>) -> SessionMetricsSnapshot {
+    state.session_metrics.snapshot()
+}
@@ -0,0 +1,337 @@
+use std::{path::Path, sync::RwLock};
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use thumbnailer_core::{DecodeLimits, ImageGenerator, ScanOptions, ThumbnailCache, Transparency};
+
+use crate::{
+    auto_import::AutoImport, cloud_files::CloudFiles, export::ExportPreset, logging::LogLevel,
+    open_cache_db, os_thumbnail::SystemGenerator, raw_pairs::RawPairs, resolve_data_dir, AppState,
+};
+
+pub(crate) use thumbnailer_core::OutputFormat;
+
+const MIN_THUMBNAIL_SIZE: u32 = 16;
+pub(crate) const MAX_THUMBNAIL_SIZE: u32 = 2048;
+const MAX_THUMBNAIL_SHARPEN: f32 = 3.0;
+const MAX_DECODE_TIMEOUT_SECONDS: u64 = 600;
+
+/// Order of gallery items. Dates sort oldest first, sizes smallest first,
+/// and ties keep path order.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SortBy {
+    /// By path, as scanned.
+    #[default]
+    Name,
+    Modified,
+    /// EXIF capture time, or the modified time for images without one,
+    /// which copying and restoring from backups don't change.
+    Captured,
+    /// File size on disk. Images of unknown size, as in offline galleries
+    /// listed from the cache, come last.
+    Size,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct Settings {
+    /// Stored alongside the other settings rather than as a nested object.
+    #[serde(flatten)]
+    pub(crate) scan: ScanOptions,
+    pub(crate) thumbnail_size: u32,
+    pub(crate) thumbnail_format: OutputFormat,
+    pub(crate) thumbnail_quality: u8,
+    /// Radius in pixels of the unsharp mask run after downscaling, to
+    /// counter the softness scaling leaves; 0 turns it off and around 0.5
+    /// to 1 is a light touch.
+    pub(crate) thumbnail_sharpen: f32,
+    /// Whether transparent PNGs and WebPs keep their alpha in thumbnails
+    /// or are flattened, which also makes PNG thumbnails smaller.
+    pub(crate) thumbnail_transparency: Transparency,
+    /// Longest a gallery load waits for one image to decode. Slower files,
+    /// such as pathological TIFFs, are listed as broken instead.
+    pub(crate) decode_timeout_seconds: u64,
+    /// Worker threads used for decoding; 0 means one per CPU core.
+    pub(crate) concurrency: usize,
+    /// Upper bound for the thumbnail cache in bytes; 0 means unlimited.
+    pub(crate) cache_max_bytes: u64,
+    /// Accelerator such as `CommandOrControl+Shift+T` that brings the app
+    /// forward with the last folder from anywhere.
+    pub(crate) global_shortcut: Option<String>,
+    /// How galleries treat online-only files from sync clients.
+    pub(crate) cloud_files: CloudFiles,
+    /// Flattened like `scan`. The tile viewer, which exists for images too
+    /// big to view whole, isn't held to them.
+    #[serde(flatten)]
+    pub(crate) decode_limits: DecodeLimits,
+    /// Most verbose messages written to the log file.
+    pub(crate) log_level: LogLevel,
+    /// Whether the open folder's subfolders get thumbnails in the
+    /// background. Worth turning off on battery or a metered connection to
+    /// a network share.
+    pub(crate) prefetch_subfolders: bool,
+    /// Whether startup generates thumbnails for the images the OS lists as
+    /// recently used, and covers for their folders and the app's recent
+    /// ones, so the start screen opens from the cache.
+    pub(crate) warm_from_os_recents: bool,
+    /// Gallery order unless `load_gallery` asks for another.
+    pub(crate) sort_by: SortBy,
+    /// How RAW files shot alongside a JPEG are listed.
+    pub(crate) raw_pairs: RawPairs,
+    /// Applications `open_with` may launch: executable paths, or on macOS
+    /// application names. Only `pick_editor` adds to them; `set_settings`
+    /// keeps them as they are, so the webview can't choose what runs.
+    pub(crate) editors: Vec<String>,
+    /// Quality JPEGs are saved at after an edit that can't be lossless.
+    pub(crate) edit_jpeg_quality: u8,
+    /// Offered by name to `export_images`.
+    pub(crate) export_presets: Vec<ExportPreset>,
+    /// Whether opened folders are searched for faces in the background,
+    /// for the "photos with people" filter and face thumbnails. Off by
+    /// default; detection runs locally and nothing leaves the machine.
+    pub(crate) detect_faces: bool,
+    /// Whether `set_rating` also writes the rating into the file, where
+    /// Windows Explorer shows it. Only supported on Windows.
+    pub(crate) write_file_ratings: bool,
+    /// A folder, such as a memory card, whose new images are copied into a
+    /// library folder as they appear.
+    pub(crate) auto_import: AutoImport,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            scan: ScanOptions::default(),
+            thumbnail_size: 256,
+            thumbnail_format: OutputFormat::Png,
+            thumbnail_quality: 85,
+            thumbnail_sharpen: 0.0,
+            thumbnail_transparency: Transparency::default(),
+            decode_timeout_seconds: 30,
+            concurrency: 0,
+            cache_max_bytes: 0,
+            global_shortcut: None,
+            cloud_files: CloudFiles::default(),
+            decode_limits: DecodeLimits::default(),
+            log_level: LogLevel::default(),
+            prefetch_subfolders: true,
+            warm_from_os_recents: false,
+            sort_by: SortBy::default(),
+            raw_pairs: RawPairs::default(),
+            editors: Vec::new(),
+            edit_jpeg_quality: 90,
+            export_presets: ExportPreset::defaults(),
+            detect_faces: false,
+            write_file_ratings: false,
+            auto_import: AutoImport::default(),
+        }
+    }
+}
+
+impl Settings {
+    fn normalized(mut self) -> Self {
+        self.scan = self.scan.normalized();
+        self.thumbnail_size = self
+            .thumbnail_size
+            .clamp(MIN_THUMBNAIL_SIZE, MAX_THUMBNAIL_SIZE);
+        self.thumbnail_quality = self.thumbnail_quality.clamp(1, 100);
+        self.thumbnail_sharpen = if self.thumbnail_sharpen.is_finite() {
+            self.thumbnail_sharpen.clamp(0.0, MAX_THUMBNAIL_SHARPEN)
+        } else {
+            0.0
+        };
+        self.edit_jpeg_quality = self.edit_jpeg_quality.clamp(1, 100);
+        self.decode_timeout_seconds = self
+            .decode_timeout_seconds
+            .clamp(1, MAX_DECODE_TIMEOUT_SECONDS);
+        self.auto_import = self.auto_import.normalized();
+        for preset in &mut self.export_presets {
+            preset.name = preset.name.trim().to_string();
+        }
+        self.export_presets.retain(|preset| !preset.name.is_empty());
+        self.editors = self
+            .editors
+            .into_iter()
+            .map(|editor| editor.trim().to_string())
+            .filter(|editor| !editor.is_empty())
+            .collect();
+        self.global_shortcut = self
+            .global_shortcut
+            .map(|shortcut| shortcut.trim().to_string())
+            .filter(|shortcut| !shortcut.is_empty());
+        self
+    }
+
+    /// Whether cached thumbnails made under `other` look different. Size
+    /// isn't one of them: the cache already regenerates what is too small.
+    fn thumbnail_output_differs(&self, other: &Settings) -> bool {
+        self.thumbnail_format != other.thumbnail_format
+            || (self.thumbnail_format == OutputFormat::Jpeg
+                && self.thumbnail_quality != other.thumbnail_quality)
+            || self.thumbnail_sharpen != other.thumbnail_sharpen
+            || self.thumbnail_transparency != other.thumbnail_transparency
+    }
+
+    /// Renders thumbnails in the configured format at `size` pixels.
+    pub(crate) fn generator(&self, size: u32) -> SystemGenerator {
+        SystemGenerator {
+            image: ImageGenerator {
+                size,
+                format: self.thumbnail_format,
+                quality: self.thumbnail_quality,
+                limits: self.decode_limits,
+                sharpen: self.thumbnail_sharpen,
+                transparency: self.thumbnail_transparency,
+            },
+        }
+    }
+
+    /// Runs `op` on a pool sized by `concurrency`, or on rayon's global pool.
+    pub(crate) fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        if self.concurrency == 0 {
+            return op();
+        }
+        match rayon::ThreadPoolBuilder::new()
+            .num_threads(self.concurrency)
+            .build()
+        {
+            Ok(pool) => pool.install(op),
+            Err(err) => {
+                log::warn!("Failed to build worker pool: {}", err);
+                op()
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct SettingsStore {
+    current: RwLock<Settings>,
+}
+
+impl SettingsStore {
+    pub(crate) fn get(&self) -> Settings {
+        self.current
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    pub(crate) fn replace(&self, settings: Settings) {
+        *self.current.write().unwrap_or_else(|err| err.into_inner()) = settings;
+    }
+}
+
+/// Each setting is stored as its own JSON-encoded row, so settings added in
+/// later versions fall back to their defaults.
+pub(crate) fn load(connection: &Connection) -> Result<Settings, String> {
+    let mut statement = connection
+        .prepare("SELECT key, value FROM settings")
+        .map_err(|err| format!("Failed to read settings: {err}"))?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|err| format!("Failed to read settings: {err}"))?;
+    let mut values = serde_json::Map::new();
+    for row in rows {
+        let (key, value) = row.map_err(|err| format!("Failed to read settings: {err}"))?;
+        match serde_json::from_str(&value) {
+            Ok(value) => {
+                values.insert(key, value);
+            }
+            Err(err) => log::warn!("Ignoring malformed setting {}: {}", key, err),
+        }
+    }
+    serde_json::from_value::<Settings>(serde_json::Value::Object(values))
+        .map(Settings::normalized)
+        .map_err(|err| format!("Failed to parse settings: {err}"))
+}
+
+/// Adds `editor` to the applications `open_with` may launch and persists it.
+pub(crate) fn add_editor(
+    data_dir: &Path,
+    store: &SettingsStore,
+    editor: String,
+) -> Result<Settings, String> {
+    let mut settings = store.get();
+    if settings.editors.contains(&editor) {
+        return Ok(settings);
+    }
+    settings.editors.push(editor);
+    save(&mut open_cache_db(data_dir)?, &settings)?;
+    store.replace(settings.clone());
+    Ok(settings)
+}
+
+fn save(connection: &mut Connection, settings: &Settings) -> Result<(), String> {
+    let serde_json::Value::Object(values) = serde_json::to_value(settings)
+        .map_err(|err| format!("Failed to serialize settings: {err}"))?
+    else {
+        return Err("Settings did not serialize to an object.".to_string());
+    };
+    let tx = connection
+        .transaction()
+        .map_err(|err| format!("Failed to start settings transaction: {err}"))?;
+    for (key, value) in values {
+        tx.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value.to_string()],
+        )
+        .map_err(|err| format!("Failed to write setting {key}: {err}"))?;
+    }
+    tx.commit()
+        .map_err(|err| format!("Failed to commit settings transaction: {err}"))
+}
+
+#[tauri::command]
+pub(crate) fn get_settings(state: tauri::State<'_, AppState>) -> Settings {
+    state.settings.get()
+}
+
+/// Persists `settings` and applies them to subsequent work. Changing how
+/// thumbnails are rendered drops the thumbnail cache so it is rebuilt.
+#[tauri::command]
+pub(crate) async fn set_settings(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    settings: Settings,
+) -> Result<Settings, String> {
+    let data_dir = resolve_data_dir(&app)?;
+    let mut settings = settings.normalized();
+    let previous = state.settings.get();
+    settings.editors = previous.editors.clone();
+    if settings.auto_import != previous.auto_import {
+        let folders = [
+            &settings.auto_import.source,
+            &settings.auto_import.destination,
+        ];
+        for folder in folders.into_iter().flatten() {
+            state.path_scope.check(Path::new(folder))?;
+        }
+    }
+    #[cfg(desktop)]
+    if settings.global_shortcut != previous.global_shortcut {
+        crate::shortcut::replace(
+            &app,
+            previous.global_shortcut.as_deref(),
+            settings.global_shortcut.as_deref(),
+        )?;
+    }
+    let saved = settings.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut connection = open_cache_db(&data_dir)?;
+        save(&mut connection, &saved)?;
+        if saved.thumbnail_output_differs(&previous) {
+            connection.clear()?;
+        }
+        connection.prune(saved.cache_max_bytes)
+    })
+    .await
+    .map_err(|err| format!("Failed to join settings task: {err}"))??;
+    crate::logging::apply(settings.log_level);
+    state.settings.replace(settings.clone());
+    Ok(settings)
+}
@@ -0,0 +1,345 @@
+use std::{
+    fs, io,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex, OnceLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use thumbnailer_core::extended_path;
+
+/// Per-file limit for reading metadata, which is instant on healthy disks.
+pub(crate) const METADATA_TIMEOUT: Duration = Duration::from_secs(5);
+/// Per-file limit for reading and decoding an image into a thumbnail.
+pub(crate) const GENERATE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Attempts made for an operation failing with a transient error.
+const MAX_ATTEMPTS: u32 = 3;
+/// Multiplied by the attempt number before each retry.
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+/// Consecutive timeouts or transient failures after which the share is
+/// considered unreachable.
+const FAILURES_TO_TRIP: usize = 3;
+/// Fewest threads guarded work runs on; there are twice as many as cores
+/// when that is more.
+const MIN_WORKERS: usize = 8;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// Raw OS errors seen while a network share drops or reconnects.
+#[cfg(windows)]
+const TRANSIENT_OS_ERRORS: [i32; 6] = [
+    53,   // ERROR_BAD_NETPATH
+    54,   // ERROR_NETWORK_BUSY
+    59,   // ERROR_UNEXP_NET_ERR
+    64,   // ERROR_NETNAME_DELETED
+    121,  // ERROR_SEM_TIMEOUT
+    1231, // ERROR_NETWORK_UNREACHABLE
+];
+#[cfg(target_os = "linux")]
+const TRANSIENT_OS_ERRORS: [i32; 6] = [
+    5,   // EIO
+    100, // ENETDOWN
+    101, // ENETUNREACH
+    112, // EHOSTDOWN
+    113, // EHOSTUNREACH
+    116, // ESTALE
+];
+#[cfg(target_os = "macos")]
+const TRANSIENT_OS_ERRORS: [i32; 6] = [
+    5,  // EIO
+    50, // ENETDOWN
+    51, // ENETUNREACH
+    64, // EHOSTDOWN
+    65, // EHOSTUNREACH
+    70, // ESTALE
+];
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+const TRANSIENT_OS_ERRORS: [i32; 0] = [];
+
+/// Runs file IO for one gallery load so a stalled SMB/NFS share can't hang
+/// it. Each operation runs on a shared, fixed set of worker threads and is
+/// abandoned once its timeout passes; the call blocked in the kernel
+/// finishes or fails on its own later, keeping its worker until then. Once
+/// a dead share holds every worker, further work times out in the queue
+/// rather than starting more threads. After repeated failures the guard
+/// trips and skips the rest of the load without touching the share again.
+///
+/// Folder scanning itself isn't guarded; only the per-image work after it.
+#[derive(Default)]
+pub(crate) struct ShareGuard {
+    consecutive_failures: AtomicUsize,
+    tripped: AtomicBool,
+    skipped: AtomicUsize,
+}
+
+impl ShareGuard {
+    /// Runs `op` against `path`, retrying transient errors with backoff.
+    pub(crate) fn run<T: Send + 'static>(
+        &self,
+        path: &Path,
+        timeout: Duration,
+        op: impl Fn() -> io::Result<T> + Send + Sync + 'static,
+    ) -> Result<T, String> {
+        self.check(path)?;
+        let op = Arc::new(op);
+        let mut attempt = 1;
+        loop {
+            let attempt_op = op.clone();
+            match self.with_timeout(path, timeout, move || attempt_op())? {
+                Ok(value) => {
+                    self.consecutive_failures.store(0, Ordering::Relaxed);
+                    return Ok(value);
+                }
+                Err(err) if is_transient(&err) && attempt < MAX_ATTEMPTS => {
+                    thread::sleep(RETRY_BACKOFF * attempt);
+                    attempt += 1;
+                }
+                Err(err) => {
+                    if is_transient(&err) {
+                        self.record_failure();
+                    } else {
+                        // The share answered, even if only to say no.
+                        self.consecutive_failures.store(0, Ordering::Relaxed);
+                    }
+                    return Err(format!("Failed to read {}: {err}", path.display()));
+                }
+            }
+        }
+    }
+
+    /// Runs `op` once under `timeout`, for work whose errors can't be told
+    /// apart as transient.
+    pub(crate) fn run_once<T: Send + 'static>(
+        &self,
+        path: &Path,
+        timeout: Duration,
+        op: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, String> {
+        self.check(path)?;
+        let value = self.with_timeout(path, timeout, op)?;
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        Ok(value)
+    }
+
+    /// `run_once` for decoding an image on `decoders` within `timeout`,
+    /// counted from when the decode starts rather than from when it was
+    /// queued. Running out of time only counts against the share when the
+    /// share has stopped answering too; otherwise the file is just slow to
+    /// decode, and `Ok(None)` is returned. The decode can't be stopped and
+    /// finishes in the background, but no longer holds up its caller.
+    pub(crate) fn run_decode<T: Send + 'static>(
+        &self,
+        decoders: &DecodePool,
+        path: &Path,
+        timeout: Duration,
+        op: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<Option<T>, String> {
+        self.check(path)?;
+        let (started_sender, started) = mpsc::channel();
+        let (sender, receiver) = mpsc::channel();
+        decoders.run(move || {
+            let _ = started_sender.send(());
+            let _ = sender.send(op());
+        });
+        started
+            .recv()
+            .map_err(|_| format!("Failed to start decoding {}.", path.display()))?;
+        if let Ok(value) = receiver.recv_timeout(timeout) {
+            self.consecutive_failures.store(0, Ordering::Relaxed);
+            return Ok(Some(value));
+        }
+        decoders.replace_stuck();
+        let io_path = extended_path(path).into_owned();
+        self.run(path, METADATA_TIMEOUT, move || fs::metadata(&io_path))
+            .map(|_| None)
+    }
+
+    /// Whether `path` is definitely gone, as opposed to unreadable or on a
+    /// share that isn't answering.
+    pub(crate) fn is_missing(&self, path: &Path) -> bool {
+        let io_path = extended_path(path).into_owned();
+        self.run(path, METADATA_TIMEOUT, move || {
+            match fs::metadata(&io_path) {
+                Ok(_) => Ok(false),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(true),
+                Err(err) => Err(err),
+            }
+        })
+        .unwrap_or(false)
+    }
+
+    fn check(&self, path: &Path) -> Result<(), String> {
+        if self.tripped.load(Ordering::Relaxed) {
+            self.skipped.fetch_add(1, Ordering::Relaxed);
+            return Err(format!(
+                "Skipped {} because the share stopped responding.",
+                path.display()
+            ));
+        }
+        Ok(())
+    }
+
+    fn with_timeout<T: Send + 'static>(
+        &self,
+        path: &Path,
+        timeout: Duration,
+        op: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, String> {
+        let (sender, receiver) = mpsc::channel();
+        run_on_worker(move || {
+            let _ = sender.send(op());
+        });
+        receiver.recv_timeout(timeout).map_err(|_| {
+            self.record_failure();
+            format!(
+                "Timed out after {}s reading {}.",
+                timeout.as_secs(),
+                path.display()
+            )
+        })
+    }
+
+    fn record_failure(&self) {
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= FAILURES_TO_TRIP && !self.tripped.swap(true, Ordering::Relaxed) {
+            log::warn!("Share stopped responding; skipping the rest of the gallery");
+        }
+    }
+
+    /// An explanation for the response once the guard has tripped.
+    pub(crate) fn error(&self, folder: &Path) -> Option<String> {
+        if !self.tripped.load(Ordering::Relaxed) {
+            return None;
+        }
+        Some(format!(
+            "{} stopped responding; skipped the remaining {} image(s).",
+            folder.display(),
+            self.skipped.load(Ordering::Relaxed)
+        ))
+    }
+}
+
+/// Threads one gallery load decodes on, apart from the IO workers so slow
+/// decodes can't hold up the share's metadata checks. A decode that runs
+/// past its timeout keeps its thread, and another is started in its place.
+pub(crate) struct DecodePool {
+    sender: mpsc::Sender<Job>,
+    receiver: Arc<Mutex<mpsc::Receiver<Job>>>,
+    /// Threads to stop once their current decode ends, one for each
+    /// replacement started.
+    surplus: Arc<AtomicUsize>,
+}
+
+impl DecodePool {
+    /// Sized like the `concurrency` setting, so 0 means one per core.
+    pub(crate) fn new(concurrency: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let pool = Self {
+            sender,
+            receiver: Arc::new(Mutex::new(receiver)),
+            surplus: Arc::new(AtomicUsize::new(0)),
+        };
+        let threads = match concurrency {
+            0 => thread::available_parallelism().map_or(1, |cores| cores.get()),
+            threads => threads,
+        };
+        for _ in 0..threads {
+            pool.start_worker();
+        }
+        pool
+    }
+
+    fn run(&self, job: impl FnOnce() + Send + 'static) {
+        if self.sender.send(Box::new(job)).is_err() {
+            log::warn!("Decode workers are gone");
+        }
+    }
+
+    fn replace_stuck(&self) {
+        self.surplus.fetch_add(1, Ordering::Relaxed);
+        self.start_worker();
+    }
+
+    fn start_worker(&self) {
+        let receiver = self.receiver.clone();
+        let surplus = self.surplus.clone();
+        let spawned = thread::Builder::new()
+            .name("decode".to_string())
+            .spawn(move || loop {
+                let job = receiver
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => return,
+                }
+                let retired = surplus
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                        count.checked_sub(1)
+                    })
+                    .is_ok();
+                if retired {
+                    return;
+                }
+            });
+        if let Err(err) = spawned {
+            log::warn!("Failed to start decode worker: {}", err);
+        }
+    }
+}
+
+/// Queues `job` for the worker threads, starting them on first use.
+fn run_on_worker(job: impl FnOnce() + Send + 'static) {
+    static QUEUE: OnceLock<mpsc::Sender<Job>> = OnceLock::new();
+    let queue = QUEUE.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = thread::available_parallelism()
+            .map_or(MIN_WORKERS, |cores| cores.get() * 2)
+            .max(MIN_WORKERS);
+        for index in 0..workers {
+            let receiver = receiver.clone();
+            let spawned = thread::Builder::new()
+                .name(format!("share-guard-{index}"))
+                .spawn(move || loop {
+                    let job = receiver
+                        .lock()
+                        .unwrap_or_else(|err| err.into_inner())
+                        .recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => return,
+                    }
+                });
+            if let Err(err) = spawned {
+                log::warn!("Failed to start share guard worker: {}", err);
+            }
+        }
+        sender
+    });
+    if queue.send(Box::new(job)).is_err() {
+        log::warn!("Share guard workers are gone");
+    }
+}
+
+fn is_transient(err: &io::Error) -> bool {
+    use io::ErrorKind;
+
+    matches!(
+        err.kind(),
+        ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+    ) || err
+        .raw_os_error()
+        .is_some_and(|code| TRANSIENT_OS_ERRORS.contains(&code))
+}
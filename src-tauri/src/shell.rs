@@ -0,0 +1,362 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{resolve_data_dir, AppState};
+
+#[tauri::command]
+pub(crate) fn reveal_in_file_manager(
+    state: tauri::State<'_, AppState>,
+    path: String,
+) -> Result<(), String> {
+    let target = PathBuf::from(path);
+    state.path_scope.check(&target)?;
+    if !target.exists() {
+        return Err(format!("{} does not exist.", target.display()));
+    }
+    reveal_path(&target)
+}
+
+/// Opens `path` in `app`, which must be one of the `editors` in the
+/// settings, or the system default handler. With `watch` set, the thumbnail
+/// is regenerated whenever the editor saves the file.
+#[tauri::command]
+pub(crate) fn open_with(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+    app: Option<String>,
+    watch: Option<bool>,
+    thumbnail_size: Option<u32>,
+) -> Result<(), String> {
+    let target = PathBuf::from(path);
+    state.path_scope.check(&target)?;
+    if !target.is_file() {
+        return Err(format!("{} is not a file.", target.display()));
+    }
+
+    let settings = state.settings.get();
+    match app
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    {
+        Some(application) => {
+            if !settings.editors.iter().any(|editor| editor == application) {
+                return Err(format!("{application} is not a configured editor."));
+            }
+            editor_command(application)
+                .arg(&target)
+                .spawn()
+                .map(|_| ())
+                .map_err(|err| format!("Failed to open {}: {err}", target.display()))?
+        }
+        None => open_default(&target)?,
+    }
+
+    if watch.unwrap_or(false) {
+        let data_dir = resolve_data_dir(&app_handle)?;
+        state.edit_watchers.watch(
+            app_handle,
+            data_dir,
+            target,
+            thumbnail_size.unwrap_or(settings.thumbnail_size),
+            settings,
+        )?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn stop_watching_file(state: tauri::State<'_, AppState>, path: String) {
+    state.edit_watchers.unwatch(Path::new(&path));
+}
+
+#[cfg(target_os = "macos")]
+fn editor_command(application: &str) -> Command {
+    if Path::new(application).is_file() {
+        return Command::new(application);
+    }
+    // Application names such as "Pixelmator Pro" are resolved by LaunchServices.
+    let mut command = Command::new("open");
+    command.arg("-a").arg(application);
+    command
+}
+
+#[cfg(not(target_os = "macos"))]
+fn editor_command(application: &str) -> Command {
+    Command::new(application)
+}
+
+/// Through the shell API rather than `cmd /C start`, which would run
+/// anything after a `&` or `|` in the file name as another command.
+#[cfg(target_os = "windows")]
+fn open_default(path: &Path) -> Result<(), String> {
+    use std::{ffi::c_void, os::windows::ffi::OsStrExt};
+
+    const SW_SHOWNORMAL: i32 = 1;
+
+    #[link(name = "shell32")]
+    extern "system" {
+        fn ShellExecuteW(
+            window: *mut c_void,
+            operation: *const u16,
+            file: *const u16,
+            parameters: *const u16,
+            directory: *const u16,
+            show: i32,
+        ) -> isize;
+    }
+
+    let operation: Vec<u16> = "open".encode_utf16().chain(Some(0)).collect();
+    let file: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    // SAFETY: both buffers are NUL-terminated UTF-16 that outlive the call;
+    // the null pointers are optional arguments.
+    let result = unsafe {
+        ShellExecuteW(
+            std::ptr::null_mut(),
+            operation.as_ptr(),
+            file.as_ptr(),
+            std::ptr::null(),
+            std::ptr::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+    // Values up to 32 are error codes.
+    if result <= 32 {
+        return Err(format!("Failed to open {}: error {result}", path.display()));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn open_default(path: &Path) -> Result<(), String> {
+    spawn_opener("open", path)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn open_default(path: &Path) -> Result<(), String> {
+    spawn_opener("xdg-open", path)
+}
+
+#[cfg(unix)]
+fn spawn_opener(opener: &str, path: &Path) -> Result<(), String> {
+    Command::new(opener)
+        .arg(path)
+        .spawn()
+        .map(|_| ())
+        .map_err(|err| format!("Failed to open {}: {err}", path.display()))
+}
+
+#[tauri::command]
+pub(crate) async fn set_wallpaper(
+    state: tauri::State<'_, AppState>,
+    path: String,
+) -> Result<(), String> {
+    let target = PathBuf::from(path);
+    state.path_scope.check(&target)?;
+    if !target.is_file() {
+        return Err(format!("{} is not a file.", target.display()));
+    }
+    // The desktop shells want a stable absolute path, not one relative to our cwd.
+    let target = target
+        .canonicalize()
+        .map_err(|err| format!("Failed to resolve {}: {err}", target.display()))?;
+    tauri::async_runtime::spawn_blocking(move || set_wallpaper_blocking(&target))
+        .await
+        .map_err(|err| format!("Failed to join wallpaper task: {err}"))?
+}
+
+#[cfg(target_os = "windows")]
+fn set_wallpaper_blocking(path: &Path) -> Result<(), String> {
+    use std::{ffi::c_void, os::windows::ffi::OsStrExt};
+
+    const SPI_SETDESKWALLPAPER: u32 = 0x0014;
+    const SPIF_UPDATEINIFILE: u32 = 0x01;
+    const SPIF_SENDCHANGE: u32 = 0x02;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn SystemParametersInfoW(action: u32, param: u32, value: *mut c_void, flags: u32) -> i32;
+    }
+
+    let mut wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    // SAFETY: `wide` is a NUL-terminated UTF-16 buffer that outlives the call.
+    let ok = unsafe {
+        SystemParametersInfoW(
+            SPI_SETDESKWALLPAPER,
+            0,
+            wide.as_mut_ptr().cast(),
+            SPIF_UPDATEINIFILE | SPIF_SENDCHANGE,
+        )
+    };
+    if ok == 0 {
+        return Err(format!(
+            "Failed to set wallpaper: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn set_wallpaper_blocking(path: &Path) -> Result<(), String> {
+    // NSWorkspace through the JavaScript-for-Automation ObjC bridge, applied to
+    // every screen. The path is passed as argv so it never needs escaping.
+    const SCRIPT: &str = "ObjC.import('AppKit');
+function run(argv) {
+  const workspace = $.NSWorkspace.sharedWorkspace;
+  const url = $.NSURL.fileURLWithPath(argv[0]);
+  const screens = $.NSScreen.screens;
+  for (let i = 0; i < screens.count; i++) {
+    const ok = workspace.setDesktopImageURLForScreenOptionsError(url, screens.objectAtIndex(i), $({}), null);
+    if (!ok) { throw new Error('NSWorkspace refused the image'); }
+  }
+}";
+    let output = Command::new("osascript")
+        .args(["-l", "JavaScript", "-e", SCRIPT])
+        .arg(path)
+        .output()
+        .map_err(|err| format!("Failed to launch osascript: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to set wallpaper: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn set_wallpaper_blocking(path: &Path) -> Result<(), String> {
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let uri = file_uri_for_path(path);
+    let path_arg = path.to_string_lossy().to_string();
+
+    let commands: Vec<Vec<String>> = if desktop.contains("kde") {
+        vec![vec!["plasma-apply-wallpaperimage".into(), path_arg]]
+    } else if desktop.contains("cinnamon") {
+        vec![gsettings_set(
+            "org.cinnamon.desktop.background",
+            "picture-uri",
+            &uri,
+        )]
+    } else if desktop.contains("mate") {
+        vec![gsettings_set(
+            "org.mate.background",
+            "picture-filename",
+            &path_arg,
+        )]
+    } else if desktop.contains("xfce") {
+        return Err("Setting the wallpaper is not supported on Xfce.".to_string());
+    } else {
+        // GNOME and its derivatives keep separate keys for light and dark styles.
+        vec![
+            gsettings_set("org.gnome.desktop.background", "picture-uri", &uri),
+            gsettings_set("org.gnome.desktop.background", "picture-uri-dark", &uri),
+        ]
+    };
+
+    for (index, args) in commands.iter().enumerate() {
+        let output = Command::new(&args[0])
+            .args(&args[1..])
+            .output()
+            .map_err(|err| format!("Failed to launch {}: {err}", args[0]))?;
+        // `picture-uri-dark` only exists on GNOME 42+, so ignore it failing.
+        let optional = index > 0;
+        if !output.status.success() && !optional {
+            return Err(format!(
+                "Failed to set wallpaper: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn gsettings_set(schema: &str, key: &str, value: &str) -> Vec<String> {
+    vec![
+        "gsettings".into(),
+        "set".into(),
+        schema.into(),
+        key.into(),
+        value.into(),
+    ]
+}
+
+#[cfg(target_os = "windows")]
+fn reveal_path(path: &Path) -> Result<(), String> {
+    // Explorer expects `/select,<path>` as a single argument and exits with a
+    // non-zero status even on success, so only spawn failures are reported.
+    Command::new("explorer")
+        .arg(format!("/select,{}", path.display()))
+        .spawn()
+        .map(|_| ())
+        .map_err(|err| format!("Failed to launch Explorer: {err}"))
+}
+
+#[cfg(target_os = "macos")]
+fn reveal_path(path: &Path) -> Result<(), String> {
+    Command::new("open")
+        .arg("-R")
+        .arg(path)
+        .spawn()
+        .map(|_| ())
+        .map_err(|err| format!("Failed to launch Finder: {err}"))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn reveal_path(path: &Path) -> Result<(), String> {
+    // Most Linux file managers implement the FileManager1 D-Bus interface,
+    // which is the only portable way to get the item selected.
+    let show_items = Command::new("dbus-send")
+        .args([
+            "--session",
+            "--print-reply",
+            "--dest=org.freedesktop.FileManager1",
+            "--type=method_call",
+            "/org/freedesktop/FileManager1",
+            "org.freedesktop.FileManager1.ShowItems",
+        ])
+        .arg(format!("array:string:{}", file_uri_for_path(path)))
+        .arg("string:")
+        .output();
+    match show_items {
+        Ok(output) if output.status.success() => return Ok(()),
+        Ok(output) => log::warn!(
+            "FileManager1.ShowItems failed, falling back to xdg-open: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(err) => log::warn!("dbus-send unavailable, falling back to xdg-open: {}", err),
+    }
+
+    let folder = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    };
+    Command::new("xdg-open")
+        .arg(folder)
+        .spawn()
+        .map(|_| ())
+        .map_err(|err| format!("Failed to launch file manager: {err}"))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn file_uri_for_path(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
@@ -0,0 +1,67 @@
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+
+use crate::{
+    open_cache_db,
+    open_request::{self, focus_main_window, OpenRequest},
+    recent_folders, resolve_data_dir,
+};
+
+pub(crate) fn init(app: &tauri::AppHandle, shortcut: Option<&str>) -> Result<(), String> {
+    app.plugin(
+        tauri_plugin_global_shortcut::Builder::new()
+            .with_handler(|app, _shortcut, event| {
+                if event.state() == ShortcutState::Pressed {
+                    reopen_last_folder(app);
+                }
+            })
+            .build(),
+    )
+    .map_err(|err| format!("Failed to initialize global shortcuts: {err}"))?;
+    register(app, shortcut)
+}
+
+/// Swaps the registered shortcut, restoring `previous` if `next` is rejected
+/// (for example because another app already owns it).
+pub(crate) fn replace(
+    app: &tauri::AppHandle,
+    previous: Option<&str>,
+    next: Option<&str>,
+) -> Result<(), String> {
+    register(app, next).inspect_err(|_| {
+        if let Err(err) = register(app, previous) {
+            log::warn!("Failed to restore previous shortcut: {}", err);
+        }
+    })
+}
+
+fn register(app: &tauri::AppHandle, shortcut: Option<&str>) -> Result<(), String> {
+    let shortcuts = app.global_shortcut();
+    shortcuts
+        .unregister_all()
+        .map_err(|err| format!("Failed to clear global shortcuts: {err}"))?;
+    match shortcut {
+        Some(shortcut) => shortcuts
+            .register(shortcut)
+            .map_err(|err| format!("Failed to register shortcut {shortcut}: {err}")),
+        None => Ok(()),
+    }
+}
+
+/// Brings the main window forward and reloads the most recently opened folder.
+fn reopen_last_folder(app: &tauri::AppHandle) {
+    let last_folder = resolve_data_dir(app)
+        .and_then(|data_dir| open_cache_db(&data_dir))
+        .and_then(|connection| recent_folders::last_opened(&connection));
+    match last_folder {
+        Ok(Some(folder)) => {
+            let request = OpenRequest {
+                folder,
+                ..Default::default()
+            };
+            open_request::send(app, request);
+        }
+        Ok(None) => {}
+        Err(err) => log::warn!("Failed to look up the last folder: {}", err),
+    }
+    focus_main_window(app);
+}
@@ -0,0 +1,95 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Condvar, Mutex},
+};
+
+/// Runs one piece of work per key at a time: callers arriving while it runs
+/// wait for it and get a copy of its result instead of repeating it.
+pub(crate) struct SingleFlight<T> {
+    in_flight: Mutex<HashMap<String, Arc<Flight<T>>>>,
+}
+
+struct Flight<T> {
+    /// Set once the work ends; `None` inside if it panicked.
+    outcome: Mutex<Option<Option<T>>>,
+    finished: Condvar,
+}
+
+/// Publishes the leader's result, or its absence after a panic, and frees
+/// the key either way.
+struct Landing<'a, T> {
+    single_flight: &'a SingleFlight<T>,
+    key: &'a str,
+    flight: Arc<Flight<T>>,
+    result: Option<T>,
+}
+
+impl<T> Drop for Landing<'_, T> {
+    fn drop(&mut self) {
+        self.single_flight
+            .in_flight
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(self.key);
+        *self
+            .flight
+            .outcome
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = Some(self.result.take());
+        self.flight.finished.notify_all();
+    }
+}
+
+impl<T> Default for SingleFlight<T> {
+    fn default() -> Self {
+        Self {
+            in_flight: Mutex::default(),
+        }
+    }
+}
+
+impl<T: Clone> SingleFlight<T> {
+    pub(crate) fn run(&self, key: &str, work: impl FnOnce() -> T) -> T {
+        let (flight, leader) = {
+            let mut in_flight = self.in_flight.lock().unwrap_or_else(|err| err.into_inner());
+            match in_flight.get(key) {
+                Some(flight) => (flight.clone(), false),
+                None => {
+                    let flight = Arc::new(Flight {
+                        outcome: Mutex::new(None),
+                        finished: Condvar::new(),
+                    });
+                    in_flight.insert(key.to_string(), flight.clone());
+                    (flight, true)
+                }
+            }
+        };
+        if leader {
+            let mut landing = Landing {
+                single_flight: self,
+                key,
+                flight,
+                result: None,
+            };
+            let result = work();
+            landing.result = Some(result.clone());
+            return result;
+        }
+
+        let mut outcome = flight.outcome.lock().unwrap_or_else(|err| err.into_inner());
+        while outcome.is_none() {
+            outcome = flight
+                .finished
+                .wait(outcome)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+        match outcome.as_ref().and_then(|result| result.clone()) {
+            Some(result) => result,
+            // The leader panicked; try again rather than share nothing.
+            None => {
+                drop(outcome);
+                work()
+            }
+        }
+    }
+}
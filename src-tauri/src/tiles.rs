@@ -0,0 +1,525 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use image::{imageops::FilterType, DynamicImage, GenericImageView, ImageBuffer};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use thumbnailer_core::{
+    cache_key_for_path, decode_image, extended_path, last_modified_unix, reserve_decoded,
+    DecodeLimits, Reservation,
+};
+use tiff::{
+    decoder::{ChunkType, Decoder, DecodingResult},
+    ColorType as TiffColorType,
+};
+
+use crate::{encode_for_display, open_cache_db, resolve_data_dir, settings::Settings, AppState};
+
+pub(crate) const TILE_SIZE: u32 = 256;
+/// Tiling is for images too big to view whole, so the per-image decode
+/// limit is raised to this; the memory budget still applies.
+const TILE_MAX_DECODED_BYTES: u64 = 8 * 1024 * 1024 * 1024;
+
+/// Deep Zoom style descriptor: level `max_level` is the full resolution and
+/// every level below halves both dimensions, down to 1x1 at level 0.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TileInfo {
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    max_level: u32,
+}
+
+/// Source-pixel rectangle requested by the zoomed viewer.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImageRect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Decoded levels of the image currently being zoomed, so consecutive tile
+/// requests don't decode the source again.
+#[derive(Default)]
+pub(crate) struct TilePyramidCache {
+    pyramid: Mutex<Option<TilePyramid>>,
+    /// Held while a new source decodes, so two requests for it don't both
+    /// decode it, without blocking tiles of the pyramid already loaded.
+    decoding: Mutex<()>,
+}
+
+struct TilePyramid {
+    source: PathBuf,
+    modified_unix: i64,
+    max_level: u32,
+    levels: HashMap<u32, Arc<DynamicImage>>,
+}
+
+struct LoadedPyramid<'a> {
+    guard: MutexGuard<'a, Option<TilePyramid>>,
+    replaced: bool,
+}
+
+impl LoadedPyramid<'_> {
+    fn get(&mut self) -> &mut TilePyramid {
+        self.guard.as_mut().expect("pyramid is loaded")
+    }
+}
+
+impl TilePyramidCache {
+    /// Locks the cache with the pyramid for `path` loaded, decoding the
+    /// source if a different image (or an older version of it) is cached.
+    /// The cache is unlocked while decoding.
+    fn load(
+        &self,
+        path: &Path,
+        modified_unix: i64,
+        limits: &DecodeLimits,
+    ) -> Result<LoadedPyramid<'_>, String> {
+        let is_current = |pyramid: &Option<TilePyramid>| {
+            pyramid
+                .as_ref()
+                .is_some_and(|value| value.source == path && value.modified_unix == modified_unix)
+        };
+        let guard = self.lock()?;
+        if is_current(&guard) {
+            return Ok(LoadedPyramid {
+                guard,
+                replaced: false,
+            });
+        }
+        drop(guard);
+
+        let _decoding = self
+            .decoding
+            .lock()
+            .map_err(|_| "Tile cache is unavailable.".to_string())?;
+        // Another request may have decoded it while this one waited.
+        let guard = self.lock()?;
+        if is_current(&guard) {
+            return Ok(LoadedPyramid {
+                guard,
+                replaced: false,
+            });
+        }
+        drop(guard);
+        let limits = tile_limits(limits);
+        // The budget covers the decode itself. Holding it for as long as the
+        // pyramid is cached would starve thumbnail decodes while the viewer
+        // stays open, and only one source is cached at a time anyway.
+        let image = decode_image(path, &limits, Arc::new)?;
+        let (width, height) = image.dimensions();
+        let max_level = max_level_for(width, height);
+        let mut guard = self.lock()?;
+        *guard = Some(TilePyramid {
+            source: path.to_path_buf(),
+            modified_unix,
+            max_level,
+            levels: HashMap::from([(max_level, image)]),
+        });
+        Ok(LoadedPyramid {
+            guard,
+            replaced: true,
+        })
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Option<TilePyramid>>, String> {
+        self.pyramid
+            .lock()
+            .map_err(|_| "Tile cache is unavailable.".to_string())
+    }
+}
+
+impl TilePyramid {
+    fn level(&mut self, level: u32) -> Arc<DynamicImage> {
+        if let Some(image) = self.levels.get(&level) {
+            return image.clone();
+        }
+        // Only the full-resolution level is decoded from disk; lower levels
+        // are derived by halving the level above.
+        let above = self.level(level + 1);
+        let (width, height) = above.dimensions();
+        let image = Arc::new(above.resize_exact(
+            width.div_ceil(2).max(1),
+            height.div_ceil(2).max(1),
+            FilterType::Triangle,
+        ));
+        self.levels.insert(level, image.clone());
+        image
+    }
+}
+
+#[tauri::command]
+pub(crate) async fn get_image_tile_info(
+    state: tauri::State<'_, AppState>,
+    path: String,
+) -> Result<TileInfo, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        let image_path = validate_image_path(&path, &settings)?;
+        let (width, height) = image::ImageReader::open(extended_path(&image_path))
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(|err| format!("Failed to read image {}: {err}", image_path.display()))?
+            .into_dimensions()
+            .map_err(|err| format!("Failed to read image {}: {err}", image_path.display()))?;
+        Ok(TileInfo {
+            width,
+            height,
+            tile_size: TILE_SIZE,
+            max_level: max_level_for(width, height),
+        })
+    })
+    .await
+    .map_err(|err| format!("Failed to join tile info task: {err}"))?
+}
+
+/// Returns one encoded tile of the pyramid as a raw binary IPC payload.
+/// Tiles are cached in SQLite alongside the thumbnails.
+#[tauri::command]
+pub(crate) async fn get_image_tiles(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+    level: u32,
+    x: u32,
+    y: u32,
+) -> Result<tauri::ipc::Response, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let data_dir = resolve_data_dir(&app)?;
+    let tile_pyramid = state.tile_pyramid.clone();
+    let settings = state.settings.get();
+    let tile = tauri::async_runtime::spawn_blocking(move || {
+        let connection = open_cache_db(&data_dir)?;
+        let image_path = validate_image_path(&path, &settings)?;
+        let limits = &settings.decode_limits;
+        load_tile_blocking(&connection, &tile_pyramid, &image_path, limits, level, x, y)
+    })
+    .await
+    .map_err(|err| format!("Failed to join tile task: {err}"))??;
+    Ok(tauri::ipc::Response::new(tile))
+}
+
+fn load_tile_blocking(
+    connection: &Connection,
+    cache: &TilePyramidCache,
+    image_path: &Path,
+    limits: &DecodeLimits,
+    level: u32,
+    x: u32,
+    y: u32,
+) -> Result<Vec<u8>, String> {
+    let modified_unix = last_modified_unix(image_path)?;
+    let cache_key = cache_key_for_path(image_path);
+
+    let cached: Option<Vec<u8>> = connection
+        .query_row(
+            "SELECT tile_blob FROM image_tiles
+             WHERE cache_key = ?1 AND source_modified_unix = ?2
+               AND level = ?3 AND tile_x = ?4 AND tile_y = ?5",
+            params![cache_key, modified_unix, level, x, y],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read tile cache: {err}"))?;
+    if let Some(blob) = cached {
+        return Ok(blob);
+    }
+
+    let mut pyramid = cache.load(image_path, modified_unix, limits)?;
+    if pyramid.replaced {
+        // Tiles rendered from an older version of the file are useless now.
+        connection
+            .execute(
+                "DELETE FROM image_tiles WHERE cache_key = ?1 AND source_modified_unix != ?2",
+                params![cache_key, modified_unix],
+            )
+            .map_err(|err| format!("Failed to prune tile cache: {err}"))?;
+    }
+    let pyramid = pyramid.get();
+    if level > pyramid.max_level {
+        return Err(format!(
+            "Level {level} exceeds the maximum level {}.",
+            pyramid.max_level
+        ));
+    }
+
+    let level_image = pyramid.level(level);
+    let (level_width, level_height) = level_image.dimensions();
+    let (left, top) = (x.saturating_mul(TILE_SIZE), y.saturating_mul(TILE_SIZE));
+    if left >= level_width || top >= level_height {
+        return Err(format!("Tile {x},{y} is outside level {level}."));
+    }
+    let tile = level_image.crop_imm(
+        left,
+        top,
+        TILE_SIZE.min(level_width - left),
+        TILE_SIZE.min(level_height - top),
+    );
+    let blob = encode_for_display(&tile)
+        .map_err(|err| format!("Failed to encode tile for {}: {err}", image_path.display()))?;
+
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO image_tiles (
+               cache_key,
+               source_modified_unix,
+               level,
+               tile_x,
+               tile_y,
+               tile_blob
+             ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![cache_key, modified_unix, level, x, y, blob],
+        )
+        .map_err(|err| format!("Failed to write tile cache: {err}"))?;
+    Ok(blob)
+}
+
+/// Returns `rect` of the source scaled by `scale` (0 < scale <= 1) as a raw
+/// binary IPC payload. Striped and tiled TIFFs only decode the chunks that
+/// intersect the window; other formats are decoded once and kept in the tile
+/// pyramid cache for subsequent requests.
+#[tauri::command]
+pub(crate) async fn load_image_region(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    rect: ImageRect,
+    scale: Option<f32>,
+) -> Result<tauri::ipc::Response, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let tile_pyramid = state.tile_pyramid.clone();
+    let settings = state.settings.get();
+    let region = tauri::async_runtime::spawn_blocking(move || {
+        let image_path = validate_image_path(&path, &settings)?;
+        let scale = scale.unwrap_or(1.0);
+        load_image_region_blocking(
+            &tile_pyramid,
+            &image_path,
+            &settings.decode_limits,
+            rect,
+            scale,
+        )
+    })
+    .await
+    .map_err(|err| format!("Failed to join region task: {err}"))??;
+    Ok(tauri::ipc::Response::new(region))
+}
+
+fn load_image_region_blocking(
+    cache: &TilePyramidCache,
+    image_path: &Path,
+    limits: &DecodeLimits,
+    rect: ImageRect,
+    scale: f32,
+) -> Result<Vec<u8>, String> {
+    if !(scale > 0.0 && scale <= 1.0) {
+        return Err(format!("Scale {scale} must be within (0, 1]."));
+    }
+    let (width, height) = image::ImageReader::open(extended_path(image_path))
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|err| format!("Failed to read image {}: {err}", image_path.display()))?
+        .into_dimensions()
+        .map_err(|err| format!("Failed to read image {}: {err}", image_path.display()))?;
+    let rect = clamp_rect(rect, width, height)
+        .ok_or_else(|| "Requested region is outside the image.".to_string())?;
+
+    let is_tiff = image_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext.to_ascii_lowercase().as_str(), "tif" | "tiff"));
+    let partial = if is_tiff {
+        decode_tiff_region(image_path, rect, &tile_limits(limits))?
+    } else {
+        None
+    };
+    // Held until the region is encoded.
+    let (region, _reservation) = match partial {
+        Some((value, reservation)) => (value, Some(reservation)),
+        None => {
+            let modified_unix = last_modified_unix(image_path)?;
+            let mut pyramid = cache.load(image_path, modified_unix, limits)?;
+            let pyramid = pyramid.get();
+            let full = pyramid.level(pyramid.max_level);
+            (full.crop_imm(rect.x, rect.y, rect.width, rect.height), None)
+        }
+    };
+
+    let region = if scale < 1.0 {
+        let target_width = ((rect.width as f32 * scale).round() as u32).max(1);
+        let target_height = ((rect.height as f32 * scale).round() as u32).max(1);
+        region.resize_exact(target_width, target_height, FilterType::Triangle)
+    } else {
+        region
+    };
+    encode_for_display(&region)
+        .map_err(|err| format!("Failed to encode region of {}: {err}", image_path.display()))
+}
+
+fn tile_limits(limits: &DecodeLimits) -> DecodeLimits {
+    DecodeLimits {
+        max_file_bytes: 0,
+        max_decoded_bytes: limits.max_decoded_bytes.max(TILE_MAX_DECODED_BYTES),
+        memory_budget_bytes: limits.memory_budget_bytes,
+    }
+}
+
+fn clamp_rect(rect: ImageRect, width: u32, height: u32) -> Option<ImageRect> {
+    if rect.x >= width || rect.y >= height || rect.width == 0 || rect.height == 0 {
+        return None;
+    }
+    Some(ImageRect {
+        x: rect.x,
+        y: rect.y,
+        width: rect.width.min(width - rect.x),
+        height: rect.height.min(height - rect.y),
+    })
+}
+
+/// Assembles `rect` from the strips or tiles that intersect it, with its
+/// share of the memory budget. Returns `None` for layouts this doesn't
+/// handle (non-8-bit samples, planar data, palettes), in which case the
+/// caller decodes the whole image instead.
+fn decode_tiff_region(
+    path: &Path,
+    rect: ImageRect,
+    limits: &DecodeLimits,
+) -> Result<Option<(DynamicImage, Reservation)>, String> {
+    let Some(mut layout) = TiffLayout::open(path) else {
+        return Ok(None);
+    };
+    let buffer_bytes = u64::from(rect.width) * u64::from(rect.height) * layout.samples as u64;
+    let reservation = reserve_decoded(path, buffer_bytes, limits)?;
+    let allocation_error =
+        |err: String| format!("Failed to allocate a region of {}: {err}", path.display());
+    let buffer_bytes =
+        usize::try_from(buffer_bytes).map_err(|err| allocation_error(err.to_string()))?;
+    let mut buffer = Vec::new();
+    buffer
+        .try_reserve_exact(buffer_bytes)
+        .map_err(|err| allocation_error(err.to_string()))?;
+    buffer.resize(buffer_bytes, 0);
+    if layout.copy_region(rect, &mut buffer).is_none() {
+        return Ok(None);
+    }
+
+    let (width, height) = (rect.width, rect.height);
+    let image = match layout.samples {
+        1 => ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLuma8),
+        2 => ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageLumaA8),
+        3 => ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgb8),
+        _ => ImageBuffer::from_raw(width, height, buffer).map(DynamicImage::ImageRgba8),
+    };
+    Ok(image.map(|image| (image, reservation)))
+}
+
+/// An 8-bit, interleaved TIFF and how its pixels are chunked.
+struct TiffLayout {
+    decoder: Decoder<BufReader<File>>,
+    samples: usize,
+    image_width: u32,
+    chunk_width: u32,
+    chunk_height: u32,
+}
+
+impl TiffLayout {
+    fn open(path: &Path) -> Option<Self> {
+        let file = File::open(extended_path(path)).ok()?;
+        let mut decoder = Decoder::new(BufReader::new(file)).ok()?;
+        let (image_width, _) = decoder.dimensions().ok()?;
+        let samples = match decoder.colortype().ok()? {
+            TiffColorType::Gray(8) => 1,
+            TiffColorType::GrayA(8) => 2,
+            TiffColorType::RGB(8) => 3,
+            TiffColorType::RGBA(8) => 4,
+            _ => return None,
+        };
+        let (chunk_width, chunk_height) = match decoder.get_chunk_type() {
+            ChunkType::Tile => decoder.chunk_dimensions(),
+            ChunkType::Strip => (image_width, decoder.chunk_dimensions().1),
+        };
+        if chunk_width == 0 || chunk_height == 0 {
+            return None;
+        }
+        Some(Self {
+            decoder,
+            samples,
+            image_width,
+            chunk_width,
+            chunk_height,
+        })
+    }
+
+    /// Copies `rect` into `buffer`, rows packed tightly.
+    fn copy_region(&mut self, rect: ImageRect, buffer: &mut [u8]) -> Option<()> {
+        let Self {
+            decoder,
+            samples,
+            image_width,
+            chunk_width,
+            chunk_height,
+        } = self;
+        let (samples, chunk_width, chunk_height) = (*samples, *chunk_width, *chunk_height);
+        let chunks_across = image_width.div_ceil(chunk_width);
+        let row_bytes = rect.width as usize * samples;
+        let (right, bottom) = (rect.x + rect.width, rect.y + rect.height);
+        for chunk_y in rect.y / chunk_height..=(bottom - 1) / chunk_height {
+            for chunk_x in rect.x / chunk_width..=(right - 1) / chunk_width {
+                let index = chunk_y * chunks_across + chunk_x;
+                let DecodingResult::U8(data) = decoder.read_chunk(index).ok()? else {
+                    return None;
+                };
+                let (data_width, data_height) = decoder.chunk_data_dimensions(index);
+                if data.len() < data_width as usize * data_height as usize * samples {
+                    return None;
+                }
+
+                let (origin_x, origin_y) = (chunk_x * chunk_width, chunk_y * chunk_height);
+                let copy_left = rect.x.max(origin_x);
+                let copy_right = right.min(origin_x + data_width);
+                let copy_top = rect.y.max(origin_y);
+                let copy_bottom = bottom.min(origin_y + data_height);
+                if copy_left >= copy_right || copy_top >= copy_bottom {
+                    continue;
+                }
+                let span = (copy_right - copy_left) as usize * samples;
+                for row in copy_top..copy_bottom {
+                    let source_start = ((row - origin_y) as usize * data_width as usize
+                        + (copy_left - origin_x) as usize)
+                        * samples;
+                    let target_start = (row - rect.y) as usize * row_bytes
+                        + (copy_left - rect.x) as usize * samples;
+                    buffer[target_start..target_start + span]
+                        .copy_from_slice(&data[source_start..source_start + span]);
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+fn validate_image_path(path: &str, settings: &Settings) -> Result<PathBuf, String> {
+    let image_path = PathBuf::from(path);
+    if !extended_path(&image_path).is_file() {
+        return Err(format!("{} is not a file.", image_path.display()));
+    }
+    if !settings.scan.is_supported_image(&image_path) {
+        return Err(format!(
+            "Unsupported image format: {}",
+            image_path.display()
+        ));
+    }
+    Ok(image_path)
+}
+
+fn max_level_for(width: u32, height: u32) -> u32 {
+    let longest = width.max(height).max(1);
+    u32::BITS - (longest - 1).leading_zeros()
+}
@@ -0,0 +1,161 @@
+use std::{path::Path, sync::atomic::Ordering};
+
+use tauri::{
+    menu::{CheckMenuItem, IsMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu},
+    tray::TrayIconBuilder,
+    Manager,
+};
+use thumbnailer_core::ThumbnailCache;
+
+use crate::{
+    open_cache_db,
+    open_request::{self, focus_main_window, OpenRequest},
+    recent_folders::{self, RecentFolder},
+    resolve_data_dir, AppState,
+};
+
+const TRAY_ID: &str = "main";
+const SHOW_ID: &str = "show";
+const PAUSE_ID: &str = "pause-generation";
+const QUIT_ID: &str = "quit";
+/// Folder items carry their path after this prefix.
+const OPEN_FOLDER_PREFIX: &str = "open-folder:";
+
+pub(crate) fn create(app: &tauri::AppHandle) -> Result<(), String> {
+    let menu = build_menu(app)?;
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("thumbnailer")
+        .menu(&menu)
+        .show_menu_on_left_click(true)
+        .on_menu_event(handle_menu_event);
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder
+        .build(app)
+        .map(|_| ())
+        .map_err(|err| format!("Failed to create tray icon: {err}"))
+}
+
+/// Rebuilds the menu so recent folders and cache stats stay current.
+pub(crate) fn refresh(app: &tauri::AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let result = build_menu(app).and_then(|menu| {
+        tray.set_menu(Some(menu))
+            .map_err(|err| format!("Failed to update tray menu: {err}"))
+    });
+    if let Err(err) = result {
+        log::warn!("Failed to refresh tray menu: {}", err);
+    }
+}
+
+fn build_menu(app: &tauri::AppHandle) -> Result<Menu<tauri::Wry>, String> {
+    let connection = resolve_data_dir(app).and_then(|data_dir| open_cache_db(&data_dir))?;
+    let folders = recent_folders::list(&connection)?;
+    let (pinned, recent): (Vec<RecentFolder>, Vec<RecentFolder>) =
+        folders.into_iter().partition(|folder| folder.pinned);
+    let (thumbnail_count, cache_bytes) = connection.stats()?;
+    let paused = app
+        .state::<AppState>()
+        .generation_paused
+        .load(Ordering::Relaxed);
+
+    let menu_error = |err: tauri::Error| format!("Failed to build tray menu: {err}");
+    let show = MenuItem::with_id(app, SHOW_ID, "Show thumbnailer", true, None::<&str>)
+        .map_err(menu_error)?;
+    let pinned_menu = folder_submenu(app, "pinned", "Pinned folders", &pinned)?;
+    let recent_menu = folder_submenu(app, "recent", "Recent folders", &recent)?;
+    let pause = CheckMenuItem::with_id(
+        app,
+        PAUSE_ID,
+        "Pause thumbnail generation",
+        true,
+        paused,
+        None::<&str>,
+    )
+    .map_err(menu_error)?;
+    let stats = MenuItem::with_id(
+        app,
+        "cache-stats",
+        format!(
+            "Cache: {thumbnail_count} thumbnails, {:.1} MB",
+            cache_bytes as f64 / (1024.0 * 1024.0)
+        ),
+        false,
+        None::<&str>,
+    )
+    .map_err(menu_error)?;
+    let quit = MenuItem::with_id(app, QUIT_ID, "Quit", true, None::<&str>).map_err(menu_error)?;
+    let first_separator = PredefinedMenuItem::separator(app).map_err(menu_error)?;
+    let second_separator = PredefinedMenuItem::separator(app).map_err(menu_error)?;
+
+    Menu::with_items(
+        app,
+        &[
+            &show,
+            &pinned_menu,
+            &recent_menu,
+            &first_separator,
+            &pause,
+            &stats,
+            &second_separator,
+            &quit,
+        ],
+    )
+    .map_err(menu_error)
+}
+
+fn folder_submenu(
+    app: &tauri::AppHandle,
+    id: &str,
+    title: &str,
+    folders: &[RecentFolder],
+) -> Result<Submenu<tauri::Wry>, String> {
+    let items = folders
+        .iter()
+        .map(|folder| {
+            let label = Path::new(&folder.path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| folder.path.clone());
+            MenuItem::with_id(
+                app,
+                format!("{OPEN_FOLDER_PREFIX}{}", folder.path),
+                label,
+                true,
+                None::<&str>,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("Failed to build tray menu: {err}"))?;
+    let items: Vec<&dyn IsMenuItem<tauri::Wry>> = items
+        .iter()
+        .map(|item| item as &dyn IsMenuItem<tauri::Wry>)
+        .collect();
+    Submenu::with_id_and_items(app, id, title, !items.is_empty(), &items)
+        .map_err(|err| format!("Failed to build tray menu: {err}"))
+}
+
+fn handle_menu_event(app: &tauri::AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        SHOW_ID => focus_main_window(app),
+        PAUSE_ID => {
+            let paused = &app.state::<AppState>().generation_paused;
+            paused.fetch_xor(true, Ordering::Relaxed);
+        }
+        QUIT_ID => app.exit(0),
+        id => {
+            let Some(folder) = id.strip_prefix(OPEN_FOLDER_PREFIX) else {
+                return;
+            };
+            let request = OpenRequest {
+                folder: folder.to_string(),
+                ..Default::default()
+            };
+            open_request::send(app, request);
+            focus_main_window(app);
+        }
+    }
+}
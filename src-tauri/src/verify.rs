@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use rayon::prelude::*;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tauri::Emitter;
+use thumbnailer_core::{cache_path, extended_path, last_modified_unix, Scanner};
+
+use crate::{
+    manifest::sha256_file, now_unix, open_cache_db, resolve_data_dir, settings::Settings, AppState,
+};
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifyProgress {
+    current: usize,
+    total: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum VerifyStatus {
+    /// Hashed for the first time.
+    Added,
+    Unchanged,
+    /// Edited since the last run, as its size or modified time shows.
+    Modified,
+    /// Contents differ although size and modified time don't, which only
+    /// happens through corruption.
+    Corrupted,
+    Failed,
+}
+
+/// Only files needing attention are listed; the rest are just counted.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VerifyProblem {
+    path: String,
+    status: VerifyStatus,
+    expected_sha256: Option<String>,
+    actual_sha256: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VerifySummary {
+    added: usize,
+    unchanged: usize,
+    modified: usize,
+    corrupted: usize,
+    failed: usize,
+    problems: Vec<VerifyProblem>,
+}
+
+struct Stored {
+    size_bytes: i64,
+    modified_unix: i64,
+    sha256: String,
+}
+
+struct Checked {
+    path: PathBuf,
+    status: VerifyStatus,
+    size_bytes: i64,
+    modified_unix: i64,
+    sha256: Option<String>,
+    error: Option<String>,
+}
+
+/// Hashes every image under `path` (honoring the scan settings) and compares
+/// each with the hash from its previous run, emitting `verify-progress` as
+/// files finish. A corrupted file keeps its known-good hash, so it is
+/// reported again on every run until it is restored or re-saved.
+#[tauri::command]
+pub(crate) async fn verify_folder(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+) -> Result<VerifySummary, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let data_dir = resolve_data_dir(&app)?;
+    let settings = state.settings.get();
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut connection = open_cache_db(&data_dir)?;
+        verify_folder_blocking(&app, &mut connection, &settings, PathBuf::from(path))
+    })
+    .await
+    .map_err(|err| format!("Failed to join verify task: {err}"))?
+}
+
+fn verify_folder_blocking(
+    app: &tauri::AppHandle,
+    connection: &mut Connection,
+    settings: &Settings,
+    folder: PathBuf,
+) -> Result<VerifySummary, String> {
+    if !extended_path(&folder).is_dir() {
+        return Err(format!("{} is not a valid directory.", folder.display()));
+    }
+    let mut image_paths = settings.scan.scan(&folder)?;
+    image_paths.sort_unstable();
+
+    let mut stored = HashMap::new();
+    {
+        let mut statement = connection
+            .prepare("SELECT size_bytes, modified_unix, sha256 FROM checksums WHERE path = ?1")
+            .map_err(|err| format!("Failed to read checksums: {err}"))?;
+        for path in &image_paths {
+            let row = statement
+                .query_row(params![cache_path(path)], |row| {
+                    Ok(Stored {
+                        size_bytes: row.get(0)?,
+                        modified_unix: row.get(1)?,
+                        sha256: row.get(2)?,
+                    })
+                })
+                .optional()
+                .map_err(|err| format!("Failed to read checksums: {err}"))?;
+            if let Some(row) = row {
+                stored.insert(path.clone(), row);
+            }
+        }
+    }
+
+    let total = image_paths.len();
+    let completed = AtomicUsize::new(0);
+    let checked: Vec<Checked> = settings.install(|| {
+        image_paths
+            .into_par_iter()
+            .map(|path| {
+                let checked = check_file(path, &stored);
+                let current = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                if let Err(err) = app.emit("verify-progress", &VerifyProgress { current, total }) {
+                    log::warn!("Failed to emit verify progress: {}", err);
+                }
+                checked
+            })
+            .collect()
+    });
+
+    let verified_unix = now_unix();
+    let tx = connection
+        .transaction()
+        .map_err(|err| format!("Failed to start checksum transaction: {err}"))?;
+    for file in &checked {
+        let Some(sha256) = &file.sha256 else {
+            continue;
+        };
+        if file.status == VerifyStatus::Corrupted {
+            continue;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO checksums
+             (path, size_bytes, modified_unix, sha256, verified_unix)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                cache_path(&file.path),
+                file.size_bytes,
+                file.modified_unix,
+                sha256,
+                verified_unix
+            ],
+        )
+        .map_err(|err| format!("Failed to write checksum: {err}"))?;
+    }
+    tx.commit()
+        .map_err(|err| format!("Failed to commit checksum transaction: {err}"))?;
+
+    let count = |status| checked.iter().filter(|file| file.status == status).count();
+    Ok(VerifySummary {
+        added: count(VerifyStatus::Added),
+        unchanged: count(VerifyStatus::Unchanged),
+        modified: count(VerifyStatus::Modified),
+        corrupted: count(VerifyStatus::Corrupted),
+        failed: count(VerifyStatus::Failed),
+        problems: checked
+            .iter()
+            .filter(|file| matches!(file.status, VerifyStatus::Corrupted | VerifyStatus::Failed))
+            .map(|file| VerifyProblem {
+                path: file.path.to_string_lossy().to_string(),
+                status: file.status,
+                expected_sha256: stored.get(&file.path).map(|stored| stored.sha256.clone()),
+                actual_sha256: file.sha256.clone(),
+                error: file.error.clone(),
+            })
+            .collect(),
+    })
+}
+
+fn check_file(path: PathBuf, stored: &HashMap<PathBuf, Stored>) -> Checked {
+    let measured = fs::metadata(extended_path(&path))
+        .map_err(|err| format!("Failed to read metadata: {err}"))
+        .and_then(|metadata| Ok((metadata.len() as i64, last_modified_unix(&path)?)))
+        .and_then(|(size_bytes, modified_unix)| {
+            Ok((size_bytes, modified_unix, sha256_file(&path)?))
+        });
+    let (size_bytes, modified_unix, sha256) = match measured {
+        Ok(value) => value,
+        Err(error) => {
+            return Checked {
+                path,
+                status: VerifyStatus::Failed,
+                size_bytes: 0,
+                modified_unix: 0,
+                sha256: None,
+                error: Some(error),
+            }
+        }
+    };
+    let status = match stored.get(&path) {
+        None => VerifyStatus::Added,
+        Some(previous)
+            if previous.size_bytes != size_bytes || previous.modified_unix != modified_unix =>
+        {
+            VerifyStatus::Modified
+        }
+        Some(previous) if previous.sha256 != sha256 => VerifyStatus::Corrupted,
+        Some(_) => VerifyStatus::Unchanged,
+    };
+    Checked {
+        path,
+        status,
+        size_bytes,
+        modified_unix,
+        sha256: Some(sha256),
+        error: None,
+    }
+}
+
+/// Carries a file's known-good hash over to its new path.
+pub(crate) fn relocate(
+    connection: &Connection,
+    source: &Path,
+    target: &Path,
+) -> Result<(), String> {
+    connection
+        .execute(
+            "UPDATE OR REPLACE checksums SET path = ?1 WHERE path = ?2",
+            params![cache_path(target), cache_path(source)],
+        )
+        .map(|_| ())
+        .map_err(|err| format!("Failed to move checksum: {err}"))
+}
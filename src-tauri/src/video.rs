@@ -0,0 +1,278 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use thumbnailer_core::{cache_path, extended_path};
+
+use crate::{
+    share_guard::{self, ShareGuard},
+    AppState,
+};
+
+/// Extensions read through FFmpeg rather than the image decoders.
+const VIDEO_EXTENSIONS: &[&str] = &[
+    "mp4", "m4v", "mov", "mkv", "webm", "avi", "wmv", "mts", "m2ts", "3gp",
+];
+/// Longest side of a frame unless the caller asks for another.
+const DEFAULT_FRAME_MAX_DIMENSION: u32 = 1280;
+/// Codecs the webview plays natively. Safari's engine adds HEVC.
+#[cfg(not(target_os = "macos"))]
+const PLAYABLE_CODECS: &[&str] = &["h264", "vp8", "vp9", "av1"];
+#[cfg(target_os = "macos")]
+const PLAYABLE_CODECS: &[&str] = &["h264", "hevc", "vp8", "vp9", "av1"];
+
+/// What the grid shows on a video's tile.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VideoInfo {
+    duration_seconds: f64,
+    width: u32,
+    height: u32,
+    /// FFmpeg's name for the video codec, such as `h264` or `prores`.
+    codec: String,
+    /// False when the webview can't play the codec, so the UI can warn
+    /// before the viewer opens.
+    playable: bool,
+}
+
+impl VideoInfo {
+    fn new(duration_seconds: f64, width: u32, height: u32, codec: String) -> Self {
+        Self {
+            duration_seconds,
+            width,
+            height,
+            playable: PLAYABLE_CODECS.contains(&codec.as_str()),
+            codec,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Probe {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct ProbeFormat {
+    /// Seconds, as a decimal string.
+    duration: Option<String>,
+}
+
+pub(crate) fn is_video(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_some_and(|extension| VIDEO_EXTENSIONS.contains(&extension.as_str()))
+}
+
+/// A command-line tool from `PATH`, such as `ffmpeg`, without a console
+/// window flashing up on Windows.
+pub(crate) fn external_tool(name: &str) -> Command {
+    #[allow(unused_mut)]
+    let mut command = Command::new(name);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    command
+}
+
+/// The video's duration, size and codec, remembered per modification time
+/// so each file is probed once. Failed probes, such as when FFmpeg isn't
+/// installed, are not remembered. `probe` is false for online-only files.
+pub(crate) fn info_for_item(
+    connection: &Connection,
+    share_guard: &ShareGuard,
+    path: &Path,
+    modified_unix: i64,
+    probe: bool,
+) -> Result<Option<VideoInfo>, String> {
+    if !is_video(path) {
+        return Ok(None);
+    }
+    if let Some(info) = cached_info(connection, path, modified_unix)? {
+        return Ok(Some(info));
+    }
+    if !probe {
+        return Ok(None);
+    }
+    let probe_path = path.to_path_buf();
+    let info = share_guard
+        .run_once(path, share_guard::GENERATE_TIMEOUT, move || {
+            probe_info(&probe_path)
+        })
+        .and_then(|result| result)?;
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO video_info
+               (path, modified_unix, duration_seconds, width, height, codec)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                cache_path(path),
+                modified_unix,
+                info.duration_seconds,
+                info.width,
+                info.height,
+                info.codec
+            ],
+        )
+        .map_err(|err| format!("Failed to store video info: {err}"))?;
+    Ok(Some(info))
+}
+
+pub(crate) fn cached_info(
+    connection: &Connection,
+    path: &Path,
+    modified_unix: i64,
+) -> Result<Option<VideoInfo>, String> {
+    connection
+        .query_row(
+            "SELECT duration_seconds, width, height, codec FROM video_info
+             WHERE path = ?1 AND modified_unix = ?2",
+            params![cache_path(path), modified_unix],
+            |row| {
+                Ok(VideoInfo::new(
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read video info: {err}"))
+}
+
+pub(crate) fn relocate(
+    connection: &Connection,
+    source: &Path,
+    target: &Path,
+) -> Result<(), String> {
+    connection
+        .execute(
+            "UPDATE OR REPLACE video_info SET path = ?1 WHERE path = ?2",
+            params![cache_path(target), cache_path(source)],
+        )
+        .map(|_| ())
+        .map_err(|err| format!("Failed to move video info: {err}"))
+}
+
+fn probe_info(path: &Path) -> Result<VideoInfo, String> {
+    let output = external_tool("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args([
+            "-show_entries",
+            "stream=codec_name,width,height:format=duration",
+        ])
+        .args(["-of", "json"])
+        .arg(extended_path(path).as_os_str())
+        .output()
+        .map_err(|err| format!("Failed to launch ffprobe: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to probe {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let probe: Probe = serde_json::from_slice(&output.stdout)
+        .map_err(|err| format!("Failed to parse ffprobe output: {err}"))?;
+    let stream = probe
+        .streams
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("{} has no video stream.", path.display()))?;
+    let duration_seconds = probe
+        .format
+        .and_then(|format| format.duration)
+        .and_then(|duration| duration.parse().ok())
+        .unwrap_or(0.0);
+    Ok(VideoInfo::new(
+        duration_seconds,
+        stream.width.unwrap_or(0),
+        stream.height.unwrap_or(0),
+        stream.codec_name.unwrap_or_default(),
+    ))
+}
+
+fn validate_video_path(path: &str) -> Result<PathBuf, String> {
+    let video_path = PathBuf::from(path);
+    if !extended_path(&video_path).is_file() {
+        return Err(format!("{} is not a file.", video_path.display()));
+    }
+    if !is_video(&video_path) {
+        return Err(format!(
+            "Unsupported video format: {}",
+            video_path.display()
+        ));
+    }
+    Ok(video_path)
+}
+
+/// The frame shown `timestamp` seconds in, as JPEG, scaled down to fit
+/// `max_dimension`.
+fn extract_frame(path: &Path, timestamp: f64, max_dimension: u32) -> Result<Vec<u8>, String> {
+    if !timestamp.is_finite() || timestamp < 0.0 {
+        return Err(format!("Invalid video timestamp: {timestamp}"));
+    }
+    let scale = format!(
+        "scale='min(iw,{max_dimension})':'min(ih,{max_dimension})':force_original_aspect_ratio=decrease"
+    );
+    // Seeking before the input jumps to the nearest keyframe and decodes
+    // forward from there, which is exact and far quicker than reading from
+    // the start.
+    let output = external_tool("ffmpeg")
+        .args(["-v", "error", "-ss", &format!("{timestamp:.3}"), "-i"])
+        .arg(extended_path(path).as_os_str())
+        .args(["-frames:v", "1", "-vf", &scale])
+        .args(["-f", "image2pipe", "-c:v", "mjpeg", "-q:v", "3", "-"])
+        .output()
+        .map_err(|err| format!("Failed to launch ffmpeg: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to read a frame from {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    if output.stdout.is_empty() {
+        return Err(format!(
+            "{} has no frame at {timestamp} seconds.",
+            path.display()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Returns the frame at `timestamp` seconds as a raw JPEG IPC payload, for
+/// scrubbing previews and picking a poster frame. Needs FFmpeg on `PATH`.
+#[tauri::command]
+pub(crate) async fn get_video_frame(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    timestamp: f64,
+    max_dimension: Option<u32>,
+) -> Result<tauri::ipc::Response, String> {
+    state.path_scope.check(Path::new(&path))?;
+    let max_dimension = max_dimension.unwrap_or(DEFAULT_FRAME_MAX_DIMENSION).max(1);
+    let frame = tauri::async_runtime::spawn_blocking(move || {
+        let video_path = validate_video_path(&path)?;
+        extract_frame(&video_path, timestamp, max_dimension)
+    })
+    .await
+    .map_err(|err| format!("Failed to join video frame task: {err}"))??;
+    Ok(tauri::ipc::Response::new(frame))
+}
@@ -0,0 +1,95 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use tauri::{WebviewUrl, WebviewWindowBuilder};
+
+use crate::{export::validate_source, AppState};
+
+/// Labels of viewer windows start with this; the frontend routes on it and
+/// the capability file grants them IPC access.
+pub(crate) const VIEWER_LABEL_PREFIX: &str = "viewer-";
+
+/// The image shown by each open viewer window, keyed by window label.
+#[derive(Default)]
+pub(crate) struct ViewerWindows {
+    images: Mutex<HashMap<String, PathBuf>>,
+    next_id: AtomicUsize,
+}
+
+impl ViewerWindows {
+    pub(crate) fn forget(&self, label: &str) {
+        if let Ok(mut images) = self.images.lock() {
+            images.remove(label);
+        }
+    }
+}
+
+/// Opens `path` in a window of its own so it can be compared with the main
+/// window or moved to another monitor. Returns the new window's label.
+#[tauri::command]
+pub(crate) async fn open_in_new_window(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+) -> Result<String, String> {
+    // Async on purpose: building a window from a synchronous command
+    // deadlocks on Windows.
+    let image_path = PathBuf::from(path);
+    state.path_scope.check(&image_path)?;
+    validate_source(&image_path, &state.settings.get())?;
+    open_viewer(&app, &state.viewer_windows, image_path)
+}
+
+pub(crate) fn open_viewer(
+    app: &tauri::AppHandle,
+    viewers: &ViewerWindows,
+    image_path: PathBuf,
+) -> Result<String, String> {
+    let id = viewers.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    let label = format!("{VIEWER_LABEL_PREFIX}{id}");
+    let title = window_title(&image_path);
+    // Register first so the page can ask for its image as soon as it loads.
+    viewers
+        .images
+        .lock()
+        .map_err(|_| "Failed to lock viewer windows.".to_string())?
+        .insert(label.clone(), image_path);
+
+    let built = WebviewWindowBuilder::new(app, &label, WebviewUrl::App("index.html".into()))
+        .title(title)
+        .inner_size(1000.0, 750.0)
+        .min_inner_size(320.0, 240.0)
+        .build();
+    if let Err(err) = built {
+        viewers.forget(&label);
+        return Err(format!("Failed to open viewer window: {err}"));
+    }
+    Ok(label)
+}
+
+/// The image assigned to the calling viewer window, if any.
+#[tauri::command]
+pub(crate) fn get_viewer_image(
+    window: tauri::WebviewWindow,
+    state: tauri::State<'_, AppState>,
+) -> Option<String> {
+    state
+        .viewer_windows
+        .images
+        .lock()
+        .ok()?
+        .get(window.label())
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+fn window_title(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Viewer".to_string())
+}
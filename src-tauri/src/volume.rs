@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use rusqlite::Connection;
+use serde::Serialize;
+use tauri::Emitter;
+use thumbnailer_core::{cache_path, extended_path};
+
+use crate::{
+    broken, captions, capture_dates, protocol, settings::Settings, video, GalleryItem,
+    LoadGalleryResponse,
+};
+
+/// How often the open folder is checked for its drive coming and going.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VolumeEvent {
+    folder: String,
+}
+
+struct Watched {
+    folder: PathBuf,
+    online: bool,
+}
+
+/// Watches the open gallery folder and emits `volume-offline` when it
+/// disappears (an ejected drive or unplugged card) and `volume-online` when
+/// it comes back, so the gallery can switch to cached thumbnails and reload
+/// on its own.
+#[derive(Default)]
+pub(crate) struct VolumeMonitor {
+    watched: Arc<Mutex<Option<Watched>>>,
+    started: AtomicBool,
+}
+
+impl VolumeMonitor {
+    /// Replaces the watched folder. The polling thread starts on first use.
+    pub(crate) fn watch(&self, app: &tauri::AppHandle, folder: PathBuf, online: bool) {
+        *self.watched.lock().unwrap_or_else(|err| err.into_inner()) =
+            Some(Watched { folder, online });
+        if self.started.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let watched = self.watched.clone();
+        let app = app.clone();
+        thread::spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+            let Some(folder) = watched
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .as_ref()
+                .map(|watched| watched.folder.clone())
+            else {
+                continue;
+            };
+            // Checked without the lock held, since a dying drive can block.
+            let online = extended_path(&folder).is_dir();
+            let mut current = watched.lock().unwrap_or_else(|err| err.into_inner());
+            let Some(current) = current.as_mut() else {
+                continue;
+            };
+            if current.folder != folder || current.online == online {
+                continue;
+            }
+            current.online = online;
+            let event = if online {
+                "volume-online"
+            } else {
+                "volume-offline"
+            };
+            let payload = VolumeEvent {
+                folder: folder.to_string_lossy().to_string(),
+            };
+            if let Err(err) = app.emit(event, &payload) {
+                log::warn!("Failed to emit {}: {}", event, err);
+            }
+        });
+    }
+}
+
+/// The gallery of a folder that can't be reached, built from the thumbnails
+/// cached while it was. Read-only: only what was cached is listed, and
+/// nothing is generated. `None` when nothing under it was ever cached.
+pub(crate) fn offline_gallery(
+    connection: &Connection,
+    folder: &Path,
+    settings: &Settings,
+) -> Result<Option<LoadGalleryResponse>, String> {
+    // Cached paths may be spelled differently from `folder`, so they are
+    // compared in their normalized form.
+    let cached_folder = PathBuf::from(cache_path(folder));
+    let mut statement = connection
+        .prepare(
+            "SELECT cache_key, source_path, source_modified_unix, content_hash,
+               CASE display_path WHEN '' THEN source_path ELSE display_path END
+             FROM thumbnails
+             ORDER BY source_path",
+        )
+        .map_err(|err| format!("Failed to read cached gallery: {err}"))?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })
+        .map_err(|err| format!("Failed to read cached gallery: {err}"))?;
+
+    let mut items = Vec::new();
+    let mut thumbnails = HashMap::new();
+    for row in rows {
+        let (cache_key, source_path, modified_unix, hash, display_path) =
+            row.map_err(|err| format!("Failed to read cached gallery: {err}"))?;
+        let cached_path = PathBuf::from(cache_path(Path::new(&source_path)));
+        let path = Path::new(&display_path);
+        let in_folder = if settings.scan.recursive_scan {
+            cached_path.starts_with(&cached_folder) && cached_path != cached_folder
+        } else {
+            cached_path.parent() == Some(cached_folder.as_path())
+        };
+        if !in_folder || !settings.scan.is_supported_image(path) {
+            continue;
+        }
+        thumbnails.insert(
+            display_path.clone(),
+            protocol::thumbnail_url(&cache_key, &hash),
+        );
+        items.push(GalleryItem {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| "image".to_string()),
+            captured_unix: capture_dates::cached(connection, path, modified_unix)?.flatten(),
+            video: video::cached_info(connection, path, modified_unix)?,
+            error: broken::cached(connection, path, modified_unix)?,
+            path: display_path,
+            cloud: false,
+            modified_unix,
+            size_bytes: None,
+            has_motion: false,
+            pair_path: None,
+            caption: captions::cached(connection, &cache_key)?,
+        });
+    }
+    if items.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(LoadGalleryResponse {
+        total_items: items.len(),
+        items,
+        thumbnails,
+        cancelled: false,
+        error: Some(format!(
+            "{} is offline; showing cached thumbnails until it is reconnected.",
+            folder.display()
+        )),
+        offline: true,
+        session_id: None,
+        resume_token: None,
+    }))
+}
@@ -0,0 +1,291 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+use thumbnailer_core::{
+    cache_key_for_path, content_hash, extended_path, last_modified_unix, ThumbnailCache,
+};
+
+use crate::{
+    data_url_for_blob, load_thumbnail_blocking, open_cache_db, protocol, settings::Settings,
+    AppState,
+};
+
+/// Editors often write in several chunks; give them a moment before decoding.
+const EDIT_SETTLE_DELAY: Duration = Duration::from_millis(300);
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ThumbnailUpdated {
+    pub(crate) path: String,
+    pub(crate) thumbnail: String,
+}
+
+#[derive(Default)]
+pub(crate) struct EditWatchers {
+    watchers: Mutex<HashMap<PathBuf, RecommendedWatcher>>,
+}
+
+impl EditWatchers {
+    /// Regenerates the thumbnail of `path` and emits `thumbnail-updated` every
+    /// time its modified time changes. Watching the same file again replaces
+    /// the previous watcher.
+    pub(crate) fn watch(
+        &self,
+        app: tauri::AppHandle,
+        data_dir: PathBuf,
+        path: PathBuf,
+        thumbnail_size: u32,
+        settings: Settings,
+    ) -> Result<(), String> {
+        // Many editors save by writing a temp file and renaming it over the
+        // original, so watch the parent directory instead of the file itself.
+        let parent = path
+            .parent()
+            .ok_or_else(|| format!("{} has no parent directory.", path.display()))?
+            .to_path_buf();
+        let watched_path = path.clone();
+        let mut last_seen = last_modified_unix(&path).ok();
+        let changed = debounced("edit-watcher", move |path| {
+            let Ok(modified_unix) = last_modified_unix(&path) else {
+                return;
+            };
+            if last_seen == Some(modified_unix) {
+                return;
+            }
+            match invalidate_thumbnail(&app, &data_dir, &path, thumbnail_size, &settings) {
+                Ok(()) => last_seen = Some(modified_unix),
+                Err(err) => log::warn!("Failed to refresh edited thumbnail: {}", err),
+            }
+        })?;
+
+        let mut watcher =
+            notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+                let event = match result {
+                    Ok(value) => value,
+                    Err(err) => {
+                        log::warn!("File watcher error ({}): {}", watched_path.display(), err);
+                        return;
+                    }
+                };
+                if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    return;
+                }
+                if event.paths.iter().any(|changed| changed == &watched_path) {
+                    let _ = changed.send(watched_path.clone());
+                }
+            })
+            .map_err(|err| format!("Failed to create file watcher: {err}"))?;
+        watcher
+            .watch(&parent, RecursiveMode::NonRecursive)
+            .map_err(|err| format!("Failed to watch {}: {err}", parent.display()))?;
+
+        self.watchers
+            .lock()
+            .map_err(|_| "File watcher registry is unavailable.".to_string())?
+            .insert(path, watcher);
+        Ok(())
+    }
+
+    pub(crate) fn unwatch(&self, path: &Path) {
+        if let Ok(mut watchers) = self.watchers.lock() {
+            watchers.remove(path);
+        }
+    }
+
+    fn is_watching(&self, path: &Path) -> bool {
+        self.watchers
+            .lock()
+            .is_ok_and(|watchers| watchers.contains_key(path))
+    }
+}
+
+/// Watches the open gallery's folder for images edited in place.
+#[derive(Default)]
+pub(crate) struct GalleryWatcher {
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+impl GalleryWatcher {
+    /// Replaces the watched folder. Only images that already have a cached
+    /// thumbnail are refreshed; new files show up on the next load.
+    pub(crate) fn watch(
+        &self,
+        app: tauri::AppHandle,
+        data_dir: PathBuf,
+        folder: &Path,
+        thumbnail_size: u32,
+        settings: Settings,
+    ) -> Result<(), String> {
+        let recursive = if settings.scan.recursive_scan {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        let is_image = {
+            let settings = settings.clone();
+            move |path: &Path| settings.scan.is_supported_image(path)
+        };
+        // Modified time and length as of the last refresh, so the several
+        // events one save raises only refresh once.
+        let mut last_seen = HashMap::<PathBuf, (i64, u64)>::new();
+        let changed = debounced("gallery-watcher", move |path| {
+            // Files opened for editing are refreshed by their own watcher.
+            if app.state::<AppState>().edit_watchers.is_watching(&path) {
+                return;
+            }
+            match is_cached(&data_dir, &path) {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(err) => {
+                    log::warn!("Failed to check changed image: {}", err);
+                    return;
+                }
+            }
+            let Some(signature) = signature(&path) else {
+                return;
+            };
+            if last_seen.get(&path) == Some(&signature) {
+                return;
+            }
+            match invalidate_thumbnail(&app, &data_dir, &path, thumbnail_size, &settings) {
+                Ok(()) => {
+                    last_seen.insert(path, signature);
+                }
+                Err(err) => log::warn!("Failed to refresh changed thumbnail: {}", err),
+            }
+        })?;
+
+        let mut watcher =
+            notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+                let event = match result {
+                    Ok(value) => value,
+                    Err(err) => {
+                        log::warn!("Gallery watcher error: {}", err);
+                        return;
+                    }
+                };
+                if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    return;
+                }
+                for path in event.paths.into_iter().filter(|path| is_image(path)) {
+                    let _ = changed.send(path);
+                }
+            })
+            .map_err(|err| format!("Failed to create gallery watcher: {err}"))?;
+        watcher
+            .watch(folder, recursive)
+            .map_err(|err| format!("Failed to watch {}: {err}", folder.display()))?;
+        *self.watcher.lock().unwrap_or_else(|err| err.into_inner()) = Some(watcher);
+        Ok(())
+    }
+
+    pub(crate) fn stop(&self) {
+        self.watcher
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take();
+    }
+}
+
+/// Hands paths sent to the returned channel to `handle` on a thread of its
+/// own, once `EDIT_SETTLE_DELAY` has passed without another event, so
+/// notify's callback never waits on a decode. The thread ends when the
+/// sender, and so the watcher holding it, is dropped.
+fn debounced(
+    name: &str,
+    mut handle: impl FnMut(PathBuf) + Send + 'static,
+) -> Result<mpsc::Sender<PathBuf>, String> {
+    let (sender, receiver) = mpsc::channel::<PathBuf>();
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            while let Ok(path) = receiver.recv() {
+                let mut changed = vec![path];
+                loop {
+                    match receiver.recv_timeout(EDIT_SETTLE_DELAY) {
+                        Ok(path) => changed.push(path),
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                let mut seen = HashSet::new();
+                for path in changed {
+                    if seen.insert(path.clone()) {
+                        handle(path);
+                    }
+                }
+            }
+        })
+        .map_err(|err| format!("Failed to start {name} thread: {err}"))?;
+    Ok(sender)
+}
+
+/// Drops every copy of the thumbnail and viewer image of `path`, in the
+/// cache, the preview cache and gallery sessions, then regenerates the
+/// thumbnail and emits `thumbnail-updated`. Lookups already check modified
+/// times, but those can stay the same across quick saves.
+pub(crate) fn invalidate_thumbnail(
+    app: &tauri::AppHandle,
+    data_dir: &Path,
+    path: &Path,
+    thumbnail_size: u32,
+    settings: &Settings,
+) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let cache_key = cache_key_for_path(path);
+    open_cache_db(data_dir)?.remove(&cache_key)?;
+    state.preview_cache.remove(path);
+
+    let source_path = path.to_string_lossy().to_string();
+    let (blob, mime_type) = load_thumbnail_blocking(
+        data_dir.to_path_buf(),
+        source_path.clone(),
+        thumbnail_size,
+        settings,
+        &state.session_metrics,
+        &state.thumbnail_flights,
+    )?;
+    let modified_unix = last_modified_unix(path)?;
+    state.gallery_sessions.refresh(
+        &source_path,
+        modified_unix,
+        protocol::thumbnail_url(&cache_key, &content_hash(&blob)),
+    );
+    let payload = ThumbnailUpdated {
+        path: source_path,
+        thumbnail: data_url_for_blob(&blob, &mime_type),
+    };
+    app.emit("thumbnail-updated", &payload)
+        .map_err(|err| format!("Failed to emit thumbnail update: {err}"))
+}
+
+/// Whether the cache holds a thumbnail of `path`, current or not.
+fn is_cached(data_dir: &Path, path: &Path) -> Result<bool, String> {
+    open_cache_db(data_dir)?
+        .query_row(
+            "SELECT 1 FROM thumbnails WHERE cache_key = ?1",
+            params![cache_key_for_path(path)],
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|row| row.is_some())
+        .map_err(|err| format!("Failed to read cache entry: {err}"))
+}
+
+fn signature(path: &Path) -> Option<(i64, u64)> {
+    let modified_unix = last_modified_unix(path).ok()?;
+    let len = std::fs::metadata(extended_path(path)).ok()?.len();
+    Some((modified_unix, len))
+}
@@ -0,0 +1,203 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use image::{imageops, imageops::FilterType, DynamicImage, GenericImageView, Rgba, RgbaImage};
+use serde::Deserialize;
+
+/// Text is rasterized once at this height and scaled per image.
+const TEXT_RENDER_HEIGHT: f32 = 128.0;
+
+#[cfg(windows)]
+const FALLBACK_FONTS: &[&str] = &[
+    r"C:\Windows\Fonts\segoeui.ttf",
+    r"C:\Windows\Fonts\arial.ttf",
+];
+#[cfg(target_os = "macos")]
+const FALLBACK_FONTS: &[&str] = &[
+    "/System/Library/Fonts/Helvetica.ttc",
+    "/Library/Fonts/Arial.ttf",
+];
+#[cfg(not(any(windows, target_os = "macos")))]
+const FALLBACK_FONTS: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/noto/NotoSans-Regular.ttf",
+];
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WatermarkOptions {
+    /// PNG logo to overlay; takes precedence over `text`.
+    image_path: Option<String>,
+    text: Option<String>,
+    /// Font for `text`; a common system font is used when omitted.
+    font_path: Option<String>,
+    #[serde(default)]
+    corner: Corner,
+    /// 0.0 (invisible) to 1.0 (opaque).
+    opacity: Option<f32>,
+    /// Watermark width as a fraction of the image width.
+    scale: Option<f32>,
+}
+
+/// A watermark whose logo or text has been loaded once for the whole job.
+impl WatermarkOptions {
+    /// The logo and font files it reads.
+    pub(crate) fn files(&self) -> impl Iterator<Item = &Path> {
+        self.image_path.iter().chain(&self.font_path).map(Path::new)
+    }
+}
+
+pub(crate) struct Watermark {
+    overlay: RgbaImage,
+    corner: Corner,
+    opacity: f32,
+    scale: f32,
+}
+
+impl Watermark {
+    pub(crate) fn prepare(options: &WatermarkOptions) -> Result<Self, String> {
+        let overlay = match (&options.image_path, &options.text) {
+            (Some(image_path), _) => image::open(image_path)
+                .map_err(|err| format!("Failed to open watermark {image_path}: {err}"))?
+                .to_rgba8(),
+            (None, Some(text)) if !text.trim().is_empty() => {
+                render_text(text.trim(), &load_font(options.font_path.as_deref())?)?
+            }
+            _ => return Err("A watermark needs an image or some text.".to_string()),
+        };
+        Ok(Self {
+            overlay,
+            corner: options.corner,
+            opacity: options.opacity.unwrap_or(0.5).clamp(0.0, 1.0),
+            scale: options.scale.unwrap_or(0.2).clamp(0.01, 1.0),
+        })
+    }
+
+    pub(crate) fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let (width, height) = image.dimensions();
+        let (overlay_width, overlay_height) = self.overlay.dimensions();
+        let target_width = ((width as f32 * self.scale).round() as u32).max(1);
+        let target_height = ((overlay_height as f64 * target_width as f64
+            / overlay_width.max(1) as f64)
+            .round() as u32)
+            .clamp(1, height.max(1));
+        let mut overlay = imageops::resize(
+            &self.overlay,
+            target_width,
+            target_height,
+            FilterType::Triangle,
+        );
+        for pixel in overlay.pixels_mut() {
+            pixel[3] = (pixel[3] as f32 * self.opacity).round() as u8;
+        }
+
+        let margin = i64::from(width.min(height) / 50);
+        let right = i64::from(width) - i64::from(target_width) - margin;
+        let bottom = i64::from(height) - i64::from(target_height) - margin;
+        let (x, y) = match self.corner {
+            Corner::TopLeft => (margin, margin),
+            Corner::TopRight => (right, margin),
+            Corner::BottomLeft => (margin, bottom),
+            Corner::BottomRight => (right, bottom),
+        };
+        let has_alpha = image.color().has_alpha();
+        let mut canvas = image.into_rgba8();
+        imageops::overlay(&mut canvas, &overlay, x, y);
+        if has_alpha {
+            DynamicImage::ImageRgba8(canvas)
+        } else {
+            DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).into_rgb8())
+        }
+    }
+}
+
+fn load_font(font_path: Option<&str>) -> Result<FontVec, String> {
+    let candidates: Vec<PathBuf> = match font_path {
+        Some(path) => vec![PathBuf::from(path)],
+        None => FALLBACK_FONTS.iter().map(PathBuf::from).collect(),
+    };
+    for candidate in &candidates {
+        let Ok(data) = fs::read(candidate) else {
+            continue;
+        };
+        return FontVec::try_from_vec_and_index(data, 0)
+            .map_err(|err| format!("Failed to load font {}: {err}", candidate.display()));
+    }
+    Err("No font is available for the watermark text; choose a font file.".to_string())
+}
+
+/// White text with a soft dark outline so it reads on light and dark photos.
+fn render_text(text: &str, font: &FontVec) -> Result<RgbaImage, String> {
+    let scale = PxScale::from(TEXT_RENDER_HEIGHT);
+    let scaled = font.as_scaled(scale);
+    let padding = (TEXT_RENDER_HEIGHT / 16.0).ceil();
+
+    let mut glyphs = Vec::new();
+    let mut caret = padding;
+    let mut previous = None;
+    for character in text.chars() {
+        let id = scaled.glyph_id(character);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        glyphs.push(id.with_scale_and_position(scale, point(caret, padding + scaled.ascent())));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    let width = (caret + padding).ceil() as u32;
+    let height = (scaled.height() + padding * 2.0).ceil() as u32;
+    if width == 0 || height == 0 {
+        return Err("Watermark text has no visible glyphs.".to_string());
+    }
+
+    let mut coverage = vec![0f32; (width * height) as usize];
+    for glyph in glyphs {
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|x, y, value| {
+            let x = bounds.min.x as i64 + i64::from(x);
+            let y = bounds.min.y as i64 + i64::from(y);
+            if (0..i64::from(width)).contains(&x) && (0..i64::from(height)).contains(&y) {
+                let cell = &mut coverage[(y as u32 * width + x as u32) as usize];
+                *cell = cell.max(value);
+            }
+        });
+    }
+
+    let radius = (padding / 2.0).max(1.0) as i64;
+    let mut text_image = RgbaImage::new(width, height);
+    for (x, y, pixel) in text_image.enumerate_pixels_mut() {
+        let fill = coverage[(y * width + x) as usize];
+        let mut shadow = 0f32;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let (sx, sy) = (i64::from(x) + dx, i64::from(y) + dy);
+                if (0..i64::from(width)).contains(&sx) && (0..i64::from(height)).contains(&sy) {
+                    shadow = shadow.max(coverage[(sy as u32 * width + sx as u32) as usize]);
+                }
+            }
+        }
+        let alpha = fill.max(shadow * 0.6);
+        let level = if alpha > 0.0 { fill / alpha } else { 0.0 };
+        let channel = (255.0 * level).round() as u8;
+        *pixel = Rgba([channel, channel, channel, (255.0 * alpha).round() as u8]);
+    }
+    Ok(text_image)
+}
@@ -0,0 +1,103 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use thumbnailer_core::extended_path;
+
+/// The `name.xmp` sidecar next to an image, where Lightroom, darktable and
+/// others keep metadata for formats they won't write into.
+pub(crate) fn sidecar_path(path: &Path) -> PathBuf {
+    path.with_extension("xmp")
+}
+
+/// The sidecar's contents, or `None` when there is none.
+pub(crate) fn read_sidecar(path: &Path) -> Result<Option<String>, String> {
+    let sidecar = sidecar_path(path);
+    match fs::read_to_string(extended_path(&sidecar)) {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(format!("Failed to read {}: {err}", sidecar.display())),
+    }
+}
+
+/// A sidecar with `properties` (qualified names like `exif:GPSLatitude`, in
+/// the `prefix` namespace), written fresh or merged into `existing`: the
+/// `replaced` properties are dropped first and everything else is kept.
+pub(crate) fn set_properties(
+    existing: Option<&str>,
+    prefix: &str,
+    namespace: &str,
+    replaced: &[&str],
+    properties: &[(&str, String)],
+) -> Result<String, String> {
+    let attributes: String = properties
+        .iter()
+        .map(|(name, value)| format!(" {name}=\"{value}\""))
+        .collect();
+    let Some(existing) = existing else {
+        return Ok(format!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+             <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+             <rdf:Description rdf:about=\"\" xmlns:{prefix}=\"{namespace}\"{attributes}/>\n \
+             </rdf:RDF>\n\
+             </x:xmpmeta>\n\
+             <?xpacket end=\"w\"?>\n"
+        ));
+    };
+    let mut xmp = existing.to_string();
+    for property in replaced {
+        remove_property(&mut xmp, property);
+    }
+    if attributes.is_empty() {
+        return Ok(xmp);
+    }
+    let description = xmp
+        .find("<rdf:Description")
+        .ok_or_else(|| "The existing sidecar has no rdf:Description.".to_string())?
+        + "<rdf:Description".len();
+    let description_end = xmp[description..]
+        .find('>')
+        .map_or(xmp.len(), |end| description + end);
+    let mut inserted = attributes;
+    if !xmp[description..description_end].contains(&format!("xmlns:{prefix}="))
+        && !xmp.contains(namespace)
+    {
+        inserted.insert_str(0, &format!(" xmlns:{prefix}=\"{namespace}\""));
+    }
+    xmp.insert_str(description, &inserted);
+    Ok(xmp)
+}
+
+/// The value of `name`, written either as an attribute or as an element.
+pub(crate) fn property(xmp: &str, name: &str) -> Option<String> {
+    let attribute = format!(" {name}=\"");
+    if let Some(start) = xmp.find(&attribute) {
+        let value = &xmp[start + attribute.len()..];
+        return Some(value[..value.find('"')?].to_string());
+    }
+    let (open, close) = (format!("<{name}>"), format!("</{name}>"));
+    let start = xmp.find(&open)? + open.len();
+    let length = xmp[start..].find(&close)?;
+    Some(xmp[start..start + length].trim().to_string())
+}
+
+/// Drops `name` written either as an attribute or as an element.
+fn remove_property(xmp: &mut String, name: &str) {
+    let attribute = format!(" {name}=\"");
+    while let Some(start) = xmp.find(&attribute) {
+        let value_start = start + attribute.len();
+        let Some(value_length) = xmp[value_start..].find('"') else {
+            break;
+        };
+        xmp.replace_range(start..value_start + value_length + 1, "");
+    }
+    let (open, close) = (format!("<{name}>"), format!("</{name}>"));
+    while let Some(start) = xmp.find(&open) {
+        let Some(end) = xmp[start..].find(&close) else {
+            break;
+        };
+        xmp.replace_range(start..start + end + close.len(), "");
+    }
+}
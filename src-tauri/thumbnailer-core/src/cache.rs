@@ -0,0 +1,323 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cache_path, extended_path, retry_io,
+    timings::{self, Stage},
+    Generator,
+};
+
+/// Thumbnails keyed by source path, valid while the source's modified time
+/// matches. Each remembers the pixel size it was made at, and only serves
+/// requests for that size or smaller.
+pub trait ThumbnailCache {
+    fn contains(&self, cache_key: &str, modified_unix: i64, pixel_size: u32)
+        -> Result<bool, String>;
+    /// Like `contains`, but returns the cached blob's `content_hash`.
+    fn content_hash(
+        &self,
+        cache_key: &str,
+        modified_unix: i64,
+        pixel_size: u32,
+    ) -> Result<Option<String>, String>;
+    /// The cached blob and its MIME type.
+    fn get(
+        &self,
+        cache_key: &str,
+        modified_unix: i64,
+        pixel_size: u32,
+    ) -> Result<Option<(Vec<u8>, String)>, String>;
+    fn store(&mut self, generated: &[GeneratedThumbnail]) -> Result<(), String>;
+    /// Drops the entry for a source that no longer exists.
+    fn remove(&self, cache_key: &str) -> Result<(), String>;
+    /// Drops the oldest thumbnails until the cache fits in `max_bytes`. A
+    /// limit of 0 leaves the cache unbounded.
+    fn prune(&self, max_bytes: u64) -> Result<(), String>;
+    fn clear(&self) -> Result<(), String>;
+    /// Number of thumbnails and their total size in bytes.
+    fn stats(&self) -> Result<(i64, i64), String>;
+}
+
+/// An image whose thumbnail is missing or stale.
+pub struct PendingThumbnail {
+    pub image_path: PathBuf,
+    pub cache_key: String,
+    pub modified_unix: i64,
+}
+
+pub struct GeneratedThumbnail {
+    pub cache_key: String,
+    pub source_path: String,
+    /// The path as it was spelled, for showing; `source_path` is folded to
+    /// match the file's other spellings.
+    pub display_path: String,
+    pub modified_unix: i64,
+    /// The square the thumbnail was fitted in.
+    pub pixel_size: u32,
+    pub blob: Vec<u8>,
+    pub mime: String,
+}
+
+impl PendingThumbnail {
+    pub fn for_path(image_path: &Path) -> Result<Self, String> {
+        Ok(Self {
+            image_path: image_path.to_path_buf(),
+            cache_key: cache_key_for_path(image_path),
+            modified_unix: last_modified_unix(image_path)?,
+        })
+    }
+
+    pub fn generate(self, generator: &impl Generator) -> Result<GeneratedThumbnail, String> {
+        let (blob, mime) = generator.generate(&self.image_path)?;
+        Ok(GeneratedThumbnail {
+            cache_key: self.cache_key,
+            source_path: cache_path(&self.image_path),
+            display_path: self.image_path.to_string_lossy().to_string(),
+            modified_unix: self.modified_unix,
+            pixel_size: generator.size(),
+            blob,
+            mime,
+        })
+    }
+}
+
+/// Creates the `thumbnails` table if needed. Callers may keep their own
+/// tables in the same database.
+pub fn init_schema(connection: &Connection) -> Result<(), String> {
+    connection
+        .execute_batch(
+            "CREATE TABLE IF NOT EXISTS thumbnails (
+               cache_key TEXT PRIMARY KEY,
+               source_path TEXT NOT NULL,
+               source_modified_unix INTEGER NOT NULL,
+               thumbnail_blob BLOB NOT NULL,
+               mime_type TEXT NOT NULL,
+               pixel_size INTEGER NOT NULL DEFAULT 0,
+               content_hash TEXT NOT NULL DEFAULT '',
+               display_path TEXT NOT NULL DEFAULT ''
+             );",
+        )
+        .map_err(|err| format!("Failed to initialize thumbnail cache schema: {err}"))?;
+    // Caches from before sizes were recorded; their entries read as size 0
+    // and are regenerated on first use.
+    let has_pixel_size = connection
+        .prepare("SELECT 1 FROM pragma_table_info('thumbnails') WHERE name = 'pixel_size'")
+        .and_then(|mut statement| statement.exists([]))
+        .map_err(|err| format!("Failed to read thumbnail cache schema: {err}"))?;
+    if !has_pixel_size {
+        connection
+            .execute_batch(
+                "ALTER TABLE thumbnails ADD COLUMN pixel_size INTEGER NOT NULL DEFAULT 0;",
+            )
+            .map_err(|err| format!("Failed to upgrade thumbnail cache schema: {err}"))?;
+    }
+    // Entries from before hashes were recorded read as an empty hash until
+    // they are regenerated.
+    let has_content_hash = connection
+        .prepare("SELECT 1 FROM pragma_table_info('thumbnails') WHERE name = 'content_hash'")
+        .and_then(|mut statement| statement.exists([]))
+        .map_err(|err| format!("Failed to read thumbnail cache schema: {err}"))?;
+    if !has_content_hash {
+        connection
+            .execute_batch(
+                "ALTER TABLE thumbnails ADD COLUMN content_hash TEXT NOT NULL DEFAULT '';",
+            )
+            .map_err(|err| format!("Failed to upgrade thumbnail cache schema: {err}"))?;
+    }
+    // Entries from before display spellings were recorded read as empty;
+    // readers fall back to `source_path` until they are regenerated.
+    let has_display_path = connection
+        .prepare("SELECT 1 FROM pragma_table_info('thumbnails') WHERE name = 'display_path'")
+        .and_then(|mut statement| statement.exists([]))
+        .map_err(|err| format!("Failed to read thumbnail cache schema: {err}"))?;
+    if !has_display_path {
+        connection
+            .execute_batch(
+                "ALTER TABLE thumbnails ADD COLUMN display_path TEXT NOT NULL DEFAULT '';",
+            )
+            .map_err(|err| format!("Failed to upgrade thumbnail cache schema: {err}"))?;
+    }
+    Ok(())
+}
+
+impl ThumbnailCache for Connection {
+    fn contains(
+        &self,
+        cache_key: &str,
+        modified_unix: i64,
+        pixel_size: u32,
+    ) -> Result<bool, String> {
+        let _stage = timings::stage(Stage::CacheLookup);
+        self.query_row(
+            "SELECT 1
+             FROM thumbnails
+             WHERE cache_key = ?1 AND source_modified_unix = ?2 AND pixel_size >= ?3",
+            params![cache_key, modified_unix, pixel_size],
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|row| row.is_some())
+        .map_err(|err| format!("Failed to read cache entry: {err}"))
+    }
+
+    fn content_hash(
+        &self,
+        cache_key: &str,
+        modified_unix: i64,
+        pixel_size: u32,
+    ) -> Result<Option<String>, String> {
+        let _stage = timings::stage(Stage::CacheLookup);
+        self.query_row(
+            "SELECT content_hash
+             FROM thumbnails
+             WHERE cache_key = ?1 AND source_modified_unix = ?2 AND pixel_size >= ?3",
+            params![cache_key, modified_unix, pixel_size],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read cache entry: {err}"))
+    }
+
+    fn get(
+        &self,
+        cache_key: &str,
+        modified_unix: i64,
+        pixel_size: u32,
+    ) -> Result<Option<(Vec<u8>, String)>, String> {
+        let _stage = timings::stage(Stage::CacheLookup);
+        self.query_row(
+            "SELECT thumbnail_blob, mime_type
+             FROM thumbnails
+             WHERE cache_key = ?1 AND source_modified_unix = ?2 AND pixel_size >= ?3",
+            params![cache_key, modified_unix, pixel_size],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|err| format!("Failed to read cache entry: {err}"))
+    }
+
+    /// Writes all of `generated` in one transaction.
+    fn store(&mut self, generated: &[GeneratedThumbnail]) -> Result<(), String> {
+        let _stage = timings::stage(Stage::DbWrite);
+        let tx = self
+            .transaction()
+            .map_err(|err| format!("Failed to start cache transaction: {err}"))?;
+        for entry in generated {
+            tx.execute(
+                "INSERT INTO thumbnails (
+                   cache_key,
+                   source_path,
+                   source_modified_unix,
+                   thumbnail_blob,
+                   mime_type,
+                   pixel_size,
+                   content_hash,
+                   display_path
+                 ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                 ON CONFLICT(cache_key) DO UPDATE SET
+                   source_modified_unix = excluded.source_modified_unix,
+                   display_path = excluded.display_path,
+                   thumbnail_blob = excluded.thumbnail_blob,
+                   mime_type = excluded.mime_type,
+                   pixel_size = excluded.pixel_size,
+                   content_hash = excluded.content_hash",
+                params![
+                    entry.cache_key,
+                    entry.source_path,
+                    entry.modified_unix,
+                    entry.blob,
+                    entry.mime,
+                    entry.pixel_size,
+                    content_hash(&entry.blob),
+                    entry.display_path
+                ],
+            )
+            .map_err(|err| format!("Failed to write cache entry: {err}"))?;
+        }
+        tx.commit()
+            .map_err(|err| format!("Failed to commit cache transaction: {err}"))
+    }
+
+    fn remove(&self, cache_key: &str) -> Result<(), String> {
+        self.execute(
+            "DELETE FROM thumbnails WHERE cache_key = ?1",
+            params![cache_key],
+        )
+        .map(|_| ())
+        .map_err(|err| format!("Failed to remove cache entry: {err}"))
+    }
+
+    /// Oldest means earliest inserted, by rowid.
+    fn prune(&self, max_bytes: u64) -> Result<(), String> {
+        if max_bytes == 0 {
+            return Ok(());
+        }
+        let (_, total_bytes) = self.stats()?;
+        let excess_bytes = total_bytes.saturating_sub(i64::try_from(max_bytes).unwrap_or(i64::MAX));
+        if excess_bytes <= 0 {
+            return Ok(());
+        }
+        self.execute(
+            "DELETE FROM thumbnails WHERE rowid IN (
+               SELECT rowid FROM (
+                 SELECT rowid,
+                        SUM(LENGTH(thumbnail_blob)) OVER (ORDER BY rowid)
+                          - LENGTH(thumbnail_blob) AS preceding_bytes
+                 FROM thumbnails
+               )
+               WHERE preceding_bytes < ?1
+             )",
+            params![excess_bytes],
+        )
+        .map_err(|err| format!("Failed to prune thumbnail cache: {err}"))?;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        self.execute("DELETE FROM thumbnails", [])
+            .map(|_| ())
+            .map_err(|err| format!("Failed to clear thumbnail cache: {err}"))
+    }
+
+    fn stats(&self) -> Result<(i64, i64), String> {
+        self.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(LENGTH(thumbnail_blob)), 0) FROM thumbnails",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|err| format!("Failed to measure thumbnail cache: {err}"))
+    }
+}
+
+/// Short hash of a thumbnail blob, which changes whenever the thumbnail
+/// does, even when its source's modified time stays the same.
+pub fn content_hash(blob: &[u8]) -> String {
+    let digest = Sha256::digest(blob);
+    digest[..8].iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Hashes the path's `cache_path` spelling.
+pub fn cache_key_for_path(path: &Path) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(cache_path(path).as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+pub fn last_modified_unix(path: &Path) -> Result<i64, String> {
+    let io_path = extended_path(path);
+    let metadata = retry_io(|| fs::metadata(&io_path))
+        .map_err(|err| format!("Failed to read metadata for {}: {err}", path.display()))?;
+    let modified = metadata
+        .modified()
+        .map_err(|err| format!("Failed to read modified time for {}: {err}", path.display()))?;
+    let duration = modified
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| format!("Invalid modified time for {}: {err}", path.display()))?;
+    Ok(duration.as_secs() as i64)
+}
@@ -0,0 +1,169 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::Path,
+    sync::{Condvar, Mutex},
+};
+
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    extended_path, retry_io,
+    timings::{self, Stage},
+};
+
+const MIB: u64 = 1024 * 1024;
+
+/// How large an image may be before it is refused instead of decoded. Every
+/// limit is in bytes, and 0 turns it off.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DecodeLimits {
+    /// Size of the file on disk.
+    pub max_file_bytes: u64,
+    /// Pixel data of the decoded image, checked from its header.
+    pub max_decoded_bytes: u64,
+    /// Decoded pixel data held at once by all threads together. Decodes
+    /// wait for room rather than failing, and one larger than the whole
+    /// budget runs alone.
+    pub memory_budget_bytes: u64,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 512 * MIB,
+            max_decoded_bytes: 1024 * MIB,
+            memory_budget_bytes: 2048 * MIB,
+        }
+    }
+}
+
+/// Decoded bytes currently reserved across the process.
+struct InFlight {
+    used: Mutex<u64>,
+    released: Condvar,
+}
+
+static IN_FLIGHT: InFlight = InFlight {
+    used: Mutex::new(0),
+    released: Condvar::new(),
+};
+
+/// Part of the memory budget, given back when dropped.
+pub struct Reservation {
+    bytes: u64,
+}
+
+impl InFlight {
+    fn reserve(&'static self, bytes: u64, budget: u64) -> Reservation {
+        if budget == 0 {
+            return Reservation { bytes: 0 };
+        }
+        let mut used = self.used.lock().unwrap_or_else(|err| err.into_inner());
+        while *used > 0 && *used + bytes > budget {
+            used = self
+                .released
+                .wait(used)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+        *used += bytes;
+        Reservation { bytes }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if self.bytes == 0 {
+            return;
+        }
+        let mut used = IN_FLIGHT.used.lock().unwrap_or_else(|err| err.into_inner());
+        *used -= self.bytes;
+        IN_FLIGHT.released.notify_all();
+    }
+}
+
+/// Checks `bytes` of pixel data decoded from `path` against `limits` and
+/// reserves them from the memory budget, for decoders that allocate their
+/// own buffers.
+pub fn reserve_decoded(
+    path: &Path,
+    bytes: u64,
+    limits: &DecodeLimits,
+) -> Result<Reservation, String> {
+    if limits.max_decoded_bytes > 0 && bytes > limits.max_decoded_bytes {
+        return Err(format!(
+            "{} needs {} MB decoded, over the {} MB limit for one image.",
+            path.display(),
+            bytes / MIB,
+            limits.max_decoded_bytes / MIB
+        ));
+    }
+    Ok(IN_FLIGHT.reserve(bytes, limits.memory_budget_bytes))
+}
+
+/// Decodes `path` within `limits` and hands the image to `op`. The decoded
+/// size counts against the memory budget until `op` returns, so keep
+/// anything derived from the full image inside it.
+///
+/// Animated GIF and WebP files yield their first frame only: decoding
+/// through `ImageDecoder` stops there, where `AnimationDecoder` would
+/// composite every frame, so large animations stay as quick as stills.
+pub fn decode_image<T>(
+    path: &Path,
+    limits: &DecodeLimits,
+    op: impl FnOnce(DynamicImage) -> T,
+) -> Result<T, String> {
+    let open_error =
+        |err: image::ImageError| format!("Failed to open image {}: {err}", path.display());
+    let io_path = extended_path(path);
+    if limits.max_file_bytes > 0 {
+        let file_bytes = retry_io(|| fs::metadata(&io_path))
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))?
+            .len();
+        if file_bytes > limits.max_file_bytes {
+            return Err(format!(
+                "{} is {} MB, over the {} MB limit for one image.",
+                path.display(),
+                file_bytes / MIB,
+                limits.max_file_bytes / MIB
+            ));
+        }
+    }
+
+    let file = retry_io(|| File::open(&io_path))
+        .map_err(|err| format!("Failed to open image {}: {err}", path.display()))?;
+    // The same as `ImageReader::open`, with the extension as a fallback for
+    // contents that can't be sniffed.
+    let mut reader = ImageReader::new(BufReader::new(file));
+    if let Ok(format) = ImageFormat::from_path(path) {
+        reader.set_format(format);
+    }
+    let mut reader = reader
+        .with_guessed_format()
+        .map_err(|err| format!("Failed to open image {}: {err}", path.display()))?;
+    // Our own limit replaces the crate's fixed allocation cap.
+    let mut reader_limits = image::Limits::no_limits();
+    reader_limits.max_alloc = (limits.max_decoded_bytes > 0).then_some(limits.max_decoded_bytes);
+    reader.limits(reader_limits);
+    let decoder = reader.into_decoder().map_err(open_error)?;
+    let decoded_bytes = decoder.total_bytes();
+    if limits.max_decoded_bytes > 0 && decoded_bytes > limits.max_decoded_bytes {
+        let (width, height) = decoder.dimensions();
+        return Err(format!(
+            "{} is {width}x{height} and needs {} MB decoded, over the {} MB limit for one image.",
+            path.display(),
+            decoded_bytes / MIB,
+            limits.max_decoded_bytes / MIB
+        ));
+    }
+
+    let _reservation = IN_FLIGHT.reserve(decoded_bytes, limits.memory_budget_bytes);
+    let image = {
+        let _stage = timings::stage(Stage::Decode);
+        // The first frame of an animation; see above.
+        DynamicImage::from_decoder(decoder).map_err(open_error)?
+    };
+    Ok(op(image))
+}
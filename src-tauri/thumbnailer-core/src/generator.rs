@@ -0,0 +1,240 @@
+use std::{io::Cursor, path::Path};
+
+use image::{
+    codecs::{
+        jpeg::JpegEncoder,
+        png::{CompressionType, FilterType, PngEncoder},
+        webp::WebPEncoder,
+    },
+    ColorType, DynamicImage, GenericImageView, ImageEncoder, Rgb, RgbImage,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    decode_image,
+    timings::{self, Stage},
+    DecodeLimits,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    /// Always lossless; quality settings only apply to JPEG.
+    Webp,
+}
+
+impl OutputFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+        }
+    }
+}
+
+/// What becomes of transparent areas in thumbnails.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Transparency {
+    /// Left transparent, in formats that can hold it.
+    #[default]
+    Keep,
+    /// Composited over an opaque color, given as `[r, g, b]`.
+    Color { rgb: [u8; 3] },
+    /// Composited over a light gray checkerboard, as image editors show it.
+    Checkerboard,
+}
+
+/// Side of one checkerboard square, in thumbnail pixels.
+const CHECKER_SIZE: u32 = 8;
+const CHECKER_LIGHT: [u8; 3] = [255, 255, 255];
+const CHECKER_DARK: [u8; 3] = [204, 204, 204];
+
+impl Transparency {
+    /// The image composited over the background, dropping its alpha
+    /// channel; images that have none are returned as they are.
+    pub fn flatten(self, image: DynamicImage) -> DynamicImage {
+        if self == Transparency::Keep || !image.color().has_alpha() {
+            return image;
+        }
+        let rgba = image.to_rgba8();
+        let flattened = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let background = match self {
+                Transparency::Color { rgb } => rgb,
+                _ if (x / CHECKER_SIZE + y / CHECKER_SIZE) % 2 == 0 => CHECKER_LIGHT,
+                _ => CHECKER_DARK,
+            };
+            let [red, green, blue, alpha] = rgba.get_pixel(x, y).0;
+            let blend = |over: u8, under: u8| {
+                ((u32::from(over) * u32::from(alpha)
+                    + u32::from(under) * (255 - u32::from(alpha))
+                    + 127)
+                    / 255) as u8
+            };
+            Rgb([
+                blend(red, background[0]),
+                blend(green, background[1]),
+                blend(blue, background[2]),
+            ])
+        });
+        DynamicImage::ImageRgb8(flattened)
+    }
+}
+
+/// Turns a source image into an encoded thumbnail and its MIME type.
+pub trait Generator {
+    fn generate(&self, path: &Path) -> Result<(Vec<u8>, String), String>;
+    /// The square thumbnails are fitted in, recorded alongside them.
+    fn size(&self) -> u32;
+}
+
+/// Decodes with the `image` crate, within `limits`, and fits the result in a `size` square.
+#[derive(Clone, Copy)]
+pub struct ImageGenerator {
+    pub size: u32,
+    pub format: OutputFormat,
+    pub quality: u8,
+    pub limits: DecodeLimits,
+    /// Radius of an unsharp mask applied after scaling, in pixels; 0 skips
+    /// it.
+    pub sharpen: f32,
+    pub transparency: Transparency,
+}
+
+/// Differences smaller than this are left alone by the unsharp mask, so
+/// flat areas don't turn grainy.
+const SHARPEN_THRESHOLD: i32 = 2;
+
+impl ImageGenerator {
+    /// Scales and encodes an image that was decoded elsewhere, such as by an
+    /// OS thumbnail API; `path` is only used in error messages.
+    pub fn thumbnail_image(
+        &self,
+        image: &DynamicImage,
+        path: &Path,
+    ) -> Result<(Vec<u8>, String), String> {
+        let thumbnail = {
+            let _stage = timings::stage(Stage::Resize);
+            let thumbnail = image.thumbnail(self.size, self.size);
+            let thumbnail = if self.sharpen > 0.0 {
+                thumbnail.unsharpen(self.sharpen, SHARPEN_THRESHOLD)
+            } else {
+                thumbnail
+            };
+            self.transparency.flatten(thumbnail)
+        };
+        let _stage = timings::stage(Stage::Encode);
+        let bytes = encode_image(&thumbnail, self.format, self.quality)
+            .map_err(|err| format!("Failed to encode thumbnail {}: {err}", path.display()))?;
+        Ok((bytes, self.format.mime_type().to_string()))
+    }
+}
+
+impl Generator for ImageGenerator {
+    fn generate(&self, path: &Path) -> Result<(Vec<u8>, String), String> {
+        decode_image(path, &self.limits, |image| {
+            self.thumbnail_image(&image, path)
+        })?
+    }
+
+    fn size(&self) -> u32 {
+        self.size
+    }
+}
+
+/// `quality` only applies to JPEG; PNG keeps alpha and WebP is lossless.
+///
+/// PNGs use the fast deflate mode, which at thumbnail sizes encodes an order
+/// of magnitude quicker than the default for files within a few percent of
+/// its size, and drop the alpha channel from opaque images. The Sub filter
+/// encodes 256px photo thumbnails about 15% faster than Adaptive for files
+/// under 1% larger; no filter is faster still but nearly doubles them. They
+/// are tagged as sRGB, so color-managed webviews show them like the
+/// full-size image.
+pub fn encode_image(
+    image: &DynamicImage,
+    format: OutputFormat,
+    quality: u8,
+) -> Result<Vec<u8>, image::ImageError> {
+    let (width, height) = image.dimensions();
+    let mut bytes = Vec::new();
+    let mut cursor = Cursor::new(&mut bytes);
+    match format {
+        OutputFormat::Png => {
+            let encoder = PngEncoder::new_with_quality(
+                &mut cursor,
+                CompressionType::Fast,
+                FilterType::Sub,
+            );
+            if image.color().has_alpha() {
+                encoder.write_image(&image.to_rgba8(), width, height, ColorType::Rgba8.into())?
+            } else {
+                encoder.write_image(&image.to_rgb8(), width, height, ColorType::Rgb8.into())?
+            }
+            tag_srgb(&mut bytes);
+        }
+        OutputFormat::Jpeg => JpegEncoder::new_with_quality(&mut cursor, quality).write_image(
+            &image.to_rgb8(),
+            width,
+            height,
+            ColorType::Rgb8.into(),
+        )?,
+        OutputFormat::Webp => WebPEncoder::new_lossless(&mut cursor).write_image(
+            &image.to_rgba8(),
+            width,
+            height,
+            ColorType::Rgba8.into(),
+        )?,
+    }
+    Ok(bytes)
+}
+
+/// Adds an sRGB chunk with perceptual intent, and the matching gAMA chunk
+/// for decoders that predate sRGB, right after the IHDR chunk. Both are
+/// required to come before the image data.
+fn tag_srgb(png: &mut Vec<u8>) {
+    // The signature, then IHDR: length, type, 13 bytes of data and CRC.
+    const IHDR_END: usize = 8 + 4 + 4 + 13 + 4;
+    if png.len() < IHDR_END {
+        return;
+    }
+    let mut chunks = Vec::new();
+    push_chunk(&mut chunks, b"sRGB", &[0]);
+    // 1/2.2, in hundred-thousandths.
+    push_chunk(&mut chunks, b"gAMA", &45_455u32.to_be_bytes());
+    png.splice(IHDR_END..IHDR_END, chunks);
+}
+
+fn push_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc32(kind.iter().chain(data)).to_be_bytes());
+}
+
+/// The CRC-32 PNG chunks end with; chunks this small don't need a table.
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
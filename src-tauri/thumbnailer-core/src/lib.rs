@@ -0,0 +1,21 @@
+//! Folder scanning, thumbnail generation and the SQLite thumbnail cache used
+//! by thumbnailer. Nothing here depends on Tauri, so the desktop app, its
+//! headless `--generate` mode and other front ends share the same engine.
+
+pub mod cache;
+pub mod decode;
+pub mod generator;
+pub mod paths;
+pub mod retry;
+pub mod scanner;
+pub mod timings;
+
+pub use cache::{
+    cache_key_for_path, content_hash, last_modified_unix, GeneratedThumbnail, PendingThumbnail,
+    ThumbnailCache,
+};
+pub use decode::{decode_image, reserve_decoded, DecodeLimits, Reservation};
+pub use generator::{encode_image, Generator, ImageGenerator, OutputFormat, Transparency};
+pub use paths::{cache_path, extended_path};
+pub use retry::retry_io;
+pub use scanner::{ScanOptions, Scanner};
@@ -0,0 +1,99 @@
+use std::{borrow::Cow, path::Path};
+
+/// Windows refuses paths of `MAX_PATH` (260) characters or more unless they
+/// use the `\\?\` form; directories already fail 12 characters earlier.
+#[cfg(windows)]
+const LONG_PATH_THRESHOLD: usize = 248;
+
+/// The path to hand to file system calls. On Windows, long absolute paths
+/// get the `\\?\` extended-length prefix (`\\?\UNC\` for shares) so deep
+/// trees on NAS shares stay readable; everywhere else, and for short paths,
+/// `path` is returned as is.
+///
+/// Only use the result for IO. Cache keys and `source_path` values use the
+/// `cache_path` spelling, and everything shown to the user, cached
+/// `display_path` values included, keeps the plain path.
+pub fn extended_path(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        use std::{
+            ffi::OsString,
+            path::{Component, PathBuf, Prefix},
+        };
+
+        if path.as_os_str().len() < LONG_PATH_THRESHOLD {
+            return Cow::Borrowed(path);
+        }
+        let mut components = path.components();
+        let mut extended = match components.next() {
+            Some(Component::Prefix(prefix)) => match prefix.kind() {
+                Prefix::Disk(_) => {
+                    let mut extended = OsString::from(r"\\?\");
+                    extended.push(prefix.as_os_str());
+                    extended
+                }
+                Prefix::UNC(server, share) => {
+                    let mut extended = OsString::from(r"\\?\UNC\");
+                    extended.push(server);
+                    extended.push(r"\");
+                    extended.push(share);
+                    extended
+                }
+                // Already verbatim, or a device path that can't take it.
+                _ => return Cow::Borrowed(path),
+            },
+            _ => return Cow::Borrowed(path),
+        };
+        // Drive-relative paths such as `C:photos` aren't absolute.
+        if components.next() != Some(Component::RootDir) {
+            return Cow::Borrowed(path);
+        }
+        // Verbatim paths skip Win32 normalization, so resolve `.` and `..`
+        // and use backslashes here.
+        let mut names: Vec<&std::ffi::OsStr> = Vec::new();
+        for component in components {
+            match component {
+                Component::Normal(name) => names.push(name),
+                Component::ParentDir => {
+                    names.pop();
+                }
+                Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+            }
+        }
+        for name in names {
+            extended.push(r"\");
+            extended.push(name);
+        }
+        Cow::Owned(PathBuf::from(extended))
+    }
+    #[cfg(not(windows))]
+    Cow::Borrowed(path)
+}
+
+/// The spelling of `path` the cache identifies it by, and stores as
+/// `source_path`, so every spelling of the same file shares one entry.
+///
+/// - Windows paths are case-insensitive and accept either separator, so
+///   `D:\Photos` and `d:/photos/` are folded to `d:\photos`.
+/// - macOS file systems treat the NFC and NFD forms of a name as the same
+///   file, and names arrive in either form depending on where they came
+///   from, so they are NFC normalized.
+///
+/// Elsewhere names differing only in case or normalization are distinct
+/// files and are left as they are.
+pub fn cache_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    #[cfg(windows)]
+    {
+        let mut canonical = path.replace('/', r"\").to_lowercase();
+        // Keep the separator of a drive root such as `d:\`.
+        while canonical.len() > 3 && canonical.ends_with('\\') {
+            canonical.pop();
+        }
+        return canonical;
+    }
+    #[cfg(target_os = "macos")]
+    return unicode_normalization::UnicodeNormalization::nfc(path.as_ref()).collect();
+    #[cfg(not(any(windows, target_os = "macos")))]
+    path.into_owned()
+}
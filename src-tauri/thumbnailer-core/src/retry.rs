@@ -0,0 +1,47 @@
+use std::{io, thread, time::Duration};
+
+/// Attempts made for a file operation failing because the file is briefly
+/// locked.
+const MAX_ATTEMPTS: u32 = 4;
+/// Doubled after each retry, so a file is given about a third of a second.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(50);
+
+// Raw OS errors for a file another process holds open, as antivirus
+// scanners and sync clients do for a moment after a file changes.
+#[cfg(windows)]
+const LOCKED_OS_ERRORS: [i32; 2] = [
+    32, // ERROR_SHARING_VIOLATION
+    33, // ERROR_LOCK_VIOLATION
+];
+#[cfg(unix)]
+const LOCKED_OS_ERRORS: [i32; 1] = [
+    16, // EBUSY
+];
+#[cfg(not(any(windows, unix)))]
+const LOCKED_OS_ERRORS: [i32; 0] = [];
+
+/// Runs `op`, retrying with backoff while it fails because the file is
+/// locked or the call was interrupted. Other errors return at once.
+pub fn retry_io<T>(mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(err) if is_retryable(&err) && attempt < MAX_ATTEMPTS => {
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_retryable(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+    ) || err
+        .raw_os_error()
+        .is_some_and(|code| LOCKED_OS_ERRORS.contains(&code))
+}
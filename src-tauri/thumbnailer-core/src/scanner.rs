@@ -0,0 +1,278 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache_path, extended_path, retry_io,
+    timings::{self, Stage},
+};
+
+/// Finds the images a gallery should show.
+pub trait Scanner {
+    fn scan(&self, folder: &Path) -> Result<Vec<PathBuf>, String>;
+}
+
+/// Which files count as images and how far a scan descends.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScanOptions {
+    /// Lowercase file extensions, without the leading dot, shown in galleries.
+    pub extensions: Vec<String>,
+    pub recursive_scan: bool,
+    pub include_hidden: bool,
+    /// Wildcard patterns (`*` and `?`) matched against file and folder names.
+    pub excluded_patterns: Vec<String>,
+    /// Folder names, with the same wildcards, never descended into: version
+    /// control, dependency and build folders, and app bundles.
+    pub skipped_folders: Vec<String>,
+    /// Folders, and everything under them, scanned without
+    /// `skipped_folders`, such as a project whose `target` holds renders.
+    pub unskipped_roots: Vec<String>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            extensions: ["jpg", "jpeg", "png", "gif", "bmp", "webp", "tif", "tiff"]
+                .map(String::from)
+                .to_vec(),
+            recursive_scan: true,
+            include_hidden: false,
+            excluded_patterns: Vec::new(),
+            skipped_folders: [
+                ".git",
+                ".hg",
+                ".svn",
+                "node_modules",
+                "target",
+                "__pycache__",
+                ".venv",
+                "*.photoslibrary",
+                "*.app",
+            ]
+            .map(String::from)
+            .to_vec(),
+            unskipped_roots: Vec::new(),
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Lowercases and deduplicates extensions and drops blank patterns and
+    /// folders.
+    pub fn normalized(mut self) -> Self {
+        let mut extensions: Vec<String> = Vec::new();
+        for extension in self.extensions {
+            let extension = extension
+                .trim()
+                .trim_start_matches('.')
+                .to_ascii_lowercase();
+            if !extension.is_empty() && !extensions.contains(&extension) {
+                extensions.push(extension);
+            }
+        }
+        self.extensions = extensions;
+        let trimmed = |values: Vec<String>| -> Vec<String> {
+            values
+                .into_iter()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+                .collect()
+        };
+        self.excluded_patterns = trimmed(self.excluded_patterns);
+        self.skipped_folders = trimmed(self.skipped_folders);
+        self.unskipped_roots = trimmed(self.unskipped_roots);
+        self
+    }
+
+    pub fn supports_extension(&self, extension: &str) -> bool {
+        self.extensions
+            .iter()
+            .any(|configured| configured.eq_ignore_ascii_case(extension))
+    }
+
+    pub fn is_excluded(&self, name: &str) -> bool {
+        (!self.include_hidden && name.starts_with('.'))
+            || self
+                .excluded_patterns
+                .iter()
+                .any(|pattern| wildcard_match(pattern, name))
+    }
+
+    /// Whether a scan leaves out the subfolder `name` of `parent`, the
+    /// folder being listed rather than where the scan started.
+    pub fn skips_folder(&self, parent: &Path, name: &str) -> bool {
+        if !self
+            .skipped_folders
+            .iter()
+            .any(|pattern| wildcard_match(pattern, name))
+        {
+            return false;
+        }
+        let folder = PathBuf::from(cache_path(&parent.join(name)));
+        !self
+            .unskipped_roots
+            .iter()
+            .any(|unskipped| folder.starts_with(cache_path(Path::new(unskipped))))
+    }
+
+    pub fn is_supported_image(&self, path: &Path) -> bool {
+        self.mime_type_for_path(path).is_some()
+    }
+
+    /// MIME type for an enabled extension; `None` means the file is not
+    /// something the gallery handles.
+    pub fn mime_type_for_path(&self, path: &Path) -> Option<&'static str> {
+        let extension = path.extension()?.to_str()?;
+        if !self.supports_extension(extension) {
+            return None;
+        }
+        let mime_type = match image::ImageFormat::from_extension(extension) {
+            Some(format) => format.to_mime_type(),
+            None => match extension.to_ascii_lowercase().as_str() {
+                "heif" | "heic" => "image/heif",
+                "jxl" => "image/jxl",
+                _ => "application/octet-stream",
+            },
+        };
+        Some(mime_type)
+    }
+}
+
+impl Scanner for ScanOptions {
+    /// Unreadable directories and entries are logged and skipped rather than
+    /// failing the whole scan.
+    ///
+    /// A folder or file reached more than once, through a junction, symlink,
+    /// bind mount or hard link, is only listed the first time, which also
+    /// keeps links back up the tree from looping.
+    fn scan(&self, folder: &Path) -> Result<Vec<PathBuf>, String> {
+        let _stage = timings::stage(Stage::Scan);
+        let mut images = Vec::new();
+        let mut directories = vec![folder.to_path_buf()];
+        let mut seen = HashSet::new();
+        if let Ok(metadata) = fs::metadata(extended_path(folder)) {
+            seen.extend(directory_id(&extended_path(folder), &metadata));
+        }
+
+        while let Some(current_dir) = directories.pop() {
+            let io_dir = extended_path(&current_dir);
+            let entries = match retry_io(|| fs::read_dir(&io_dir)) {
+                Ok(value) => value,
+                Err(err) => {
+                    log::warn!(
+                        "Skipping unreadable directory while scanning ({}): {}",
+                        current_dir.display(),
+                        err
+                    );
+                    continue;
+                }
+            };
+
+            for entry in entries {
+                let entry = match entry {
+                    Ok(value) => value,
+                    Err(err) => {
+                        log::warn!("Skipping unreadable folder entry: {}", err);
+                        continue;
+                    }
+                };
+                if self.is_excluded(&entry.file_name().to_string_lossy()) {
+                    continue;
+                }
+                // Built from the plain directory path so results never carry
+                // the extended-length prefix used to read it.
+                let path = current_dir.join(entry.file_name());
+                let io_path = extended_path(&path);
+                // Follows links, so a linked folder is scanned as a folder.
+                let Ok(metadata) = fs::metadata(&io_path) else {
+                    continue;
+                };
+                if metadata.is_dir() {
+                    if self.skips_folder(&current_dir, &entry.file_name().to_string_lossy()) {
+                        continue;
+                    }
+                    let first_visit = directory_id(&io_path, &metadata)
+                        .map_or(true, |id| seen.insert(id));
+                    if self.recursive_scan && first_visit {
+                        directories.push(path);
+                    }
+                    continue;
+                }
+                if metadata.is_file()
+                    && self.is_supported_image(&path)
+                    && file_id(&metadata).map_or(true, |id| seen.insert(id))
+                {
+                    images.push(path);
+                }
+            }
+        }
+
+        Ok(images)
+    }
+}
+
+/// What a folder or file is, whichever path it was reached by: the device
+/// and inode on Unix. Windows has no stable equivalent short of opening the
+/// file, so folders go by their fully resolved path there instead.
+#[cfg(unix)]
+type FileId = (u64, u64);
+#[cfg(not(unix))]
+type FileId = PathBuf;
+
+#[cfg(unix)]
+fn directory_id(_io_path: &Path, metadata: &fs::Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn directory_id(io_path: &Path, _metadata: &fs::Metadata) -> Option<FileId> {
+    fs::canonicalize(io_path).ok()
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Files linked twice into the tree are listed twice on Windows; see
+/// `FileId`.
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<FileId> {
+    None
+}
+
+/// `*` matches any run of characters and `?` a single one, ignoring ASCII case.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&expected) if expected == '?' || expected.eq_ignore_ascii_case(&name[n]) => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
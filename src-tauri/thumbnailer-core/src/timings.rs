@@ -0,0 +1,129 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+use serde::Serialize;
+
+/// The steps of turning a folder into thumbnails, each traced with a span of
+/// the same name and timed.
+#[derive(Clone, Copy)]
+pub enum Stage {
+    Scan,
+    CacheLookup,
+    Decode,
+    Resize,
+    Encode,
+    DbWrite,
+}
+
+const STAGES: [Stage; 6] = [
+    Stage::Scan,
+    Stage::CacheLookup,
+    Stage::Decode,
+    Stage::Resize,
+    Stage::Encode,
+    Stage::DbWrite,
+];
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Scan => "scan",
+            Self::CacheLookup => "cache_lookup",
+            Self::Decode => "decode",
+            Self::Resize => "resize",
+            Self::Encode => "encode",
+            Self::DbWrite => "db_write",
+        }
+    }
+}
+
+struct Totals {
+    count: AtomicU64,
+    nanos: AtomicU64,
+}
+
+impl Totals {
+    const fn new() -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            nanos: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Process-wide, since stages run on whichever threads do the work.
+static TOTALS: [Totals; STAGES.len()] = [
+    Totals::new(),
+    Totals::new(),
+    Totals::new(),
+    Totals::new(),
+    Totals::new(),
+    Totals::new(),
+];
+
+/// Ends the stage's span and adds its time to the totals when dropped.
+pub struct StageGuard {
+    stage: Stage,
+    started: Instant,
+    _span: tracing::span::EnteredSpan,
+}
+
+impl Drop for StageGuard {
+    fn drop(&mut self) {
+        let totals = &TOTALS[self.stage as usize];
+        totals.count.fetch_add(1, Ordering::Relaxed);
+        let nanos = u64::try_from(self.started.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        totals.nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+}
+
+pub fn stage(stage: Stage) -> StageGuard {
+    let span = match stage {
+        Stage::Scan => tracing::info_span!("scan"),
+        Stage::CacheLookup => tracing::info_span!("cache_lookup"),
+        Stage::Decode => tracing::info_span!("decode"),
+        Stage::Resize => tracing::info_span!("resize"),
+        Stage::Encode => tracing::info_span!("encode"),
+        Stage::DbWrite => tracing::info_span!("db_write"),
+    };
+    StageGuard {
+        stage,
+        started: Instant::now(),
+        _span: span.entered(),
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageTiming {
+    pub stage: &'static str,
+    pub count: u64,
+    /// Summed over all threads, so parallel stages can exceed wall time.
+    pub total_ms: f64,
+}
+
+/// Clears the totals before a run to be measured.
+pub fn reset() {
+    for totals in &TOTALS {
+        totals.count.store(0, Ordering::Relaxed);
+        totals.nanos.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Totals since the last `reset`. They include any other work that ran in
+/// the meantime, such as an image opened in the viewer.
+pub fn snapshot() -> Vec<StageTiming> {
+    STAGES
+        .iter()
+        .map(|&stage| {
+            let totals = &TOTALS[stage as usize];
+            StageTiming {
+                stage: stage.name(),
+                count: totals.count.load(Ordering::Relaxed),
+                total_ms: totals.nanos.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+            }
+        })
+        .collect()
+}
@@ -1,6 +1,6 @@
-import { open } from '@tauri-apps/plugin-dialog'
+import { invoke } from '@tauri-apps/api/core'
 import './App.css'
-import { useMemo } from 'react'
+import { useEffect, useMemo } from 'react'
 import { hasTauriInvoke } from './utils/gallery'
 import {
   MAX_THUMBNAIL_SIZE,
@@ -9,6 +9,7 @@ import {
 import { useThumbnailSize } from './hooks/useThumbnailSize'
 import { useGallery } from './hooks/useGallery'
 import { usePreview } from './hooks/usePreview'
+import { useRecentFolders } from './hooks/useRecentFolders'
 import { Toolbar } from './components/Toolbar'
 import { StatusPanel } from './components/StatusPanel'
 import { GalleryGrid } from './components/GalleryGrid'
@@ -28,6 +29,8 @@ function App() {
     loadGallery,
     stopGalleryScan,
     thumbnailDataByPath,
+    pendingSelectPath,
+    clearPendingSelect,
   } = useGallery()
   const {
     previewItem,
@@ -40,6 +43,19 @@ function App() {
     closePreview,
     goToPreview,
   } = usePreview(items)
+  const { isFolderPinned, setFolderPinned } = useRecentFolders()
+  const folderPinned = isFolderPinned(selectedFolder)
+
+  useEffect(() => {
+    if (!pendingSelectPath) {
+      return
+    }
+    const item = items.find((entry) => entry.path === pendingSelectPath)
+    if (item) {
+      clearPendingSelect()
+      setPreviewItem(item)
+    }
+  }, [clearPendingSelect, items, pendingSelectPath, setPreviewItem])
 
   const hasItems = items.length > 0
   const columnWidthPx = useMemo(() => thumbnailSize, [thumbnailSize])
@@ -51,11 +67,7 @@ function App() {
       clearError('Folder picker requires Tauri runtime. Start with `npm run tauri dev`.')
       return
     }
-    const selected = await open({
-      directory: true,
-      multiple: false,
-      title: 'Pick an image folder',
-    })
+    const selected = await invoke('pick_folder', { title: 'Pick an image folder' })
     if (typeof selected !== 'string') {
       return
     }
@@ -63,6 +75,22 @@ function App() {
     await loadGallery(selected)
   }
 
+  async function toggleFolderPinned() {
+    try {
+      await setFolderPinned(selectedFolder, !folderPinned)
+    } catch (pinError) {
+      clearError(String(pinError))
+    }
+  }
+
+  async function openInNewWindow(item) {
+    try {
+      await invoke('open_in_new_window', { path: item.path })
+    } catch (openError) {
+      clearError(String(openError))
+    }
+  }
+
   return (
     <main className="app">
       <Toolbar
@@ -74,6 +102,8 @@ function App() {
         onPickFolder={pickFolder}
         onStop={stopGalleryScan}
         loading={loading}
+        folderPinned={folderPinned}
+        onTogglePin={hasTauriInvoke() ? toggleFolderPinned : undefined}
       />
 
       <StatusPanel status={status} selectedFolder={selectedFolder} error={error} />
@@ -98,6 +128,7 @@ function App() {
         onClose={closePreview}
         onGoLeft={() => goToPreview(-1)}
         onGoRight={() => goToPreview(1)}
+        onOpenInNewWindow={hasTauriInvoke() ? openInNewWindow : undefined}
       />
     </main>
   )
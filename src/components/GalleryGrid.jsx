@@ -51,6 +51,10 @@ const GalleryCell = memo(function GalleryCell({
         >
           {thumbnailDataByPath[item.path] ? (
             <img src={thumbnailDataByPath[item.path]} alt={item.name} loading="lazy" />
+          ) : item.cloud ? (
+            <div className="thumbPlaceholder cloud" title="Online-only file">
+              ☁
+            </div>
           ) : (
             <div className="thumbPlaceholder" />
           )}
@@ -8,6 +8,7 @@ export function PreviewModal({
   onClose,
   onGoLeft,
   onGoRight,
+  onOpenInNewWindow,
 }) {
   if (!previewItem) {
     return null
@@ -27,14 +28,21 @@ export function PreviewModal({
           </div>
           <p className="previewTitle">{previewItem.name}</p>
           <div className="previewNav">
+            {onOpenInNewWindow ? (
+              <button type="button" onClick={() => onOpenInNewWindow(previewItem)}>
+                New window
+              </button>
+            ) : null}
             <button type="button" className="previewClose" onClick={onClose}>
               Close
             </button>
           </div>
         </div>
         <div className="previewImageWrap">
-          {previewLoading && <p className="previewUnavailable">Loading preview...</p>}
-          {!previewLoading && previewImageSrc ? (
+          {previewLoading && !previewImageSrc ? (
+            <p className="previewUnavailable">Loading preview...</p>
+          ) : null}
+          {previewImageSrc ? (
             <img src={previewImageSrc} alt={previewItem.name} className="previewImage" />
           ) : null}
           {!previewLoading && !previewImageSrc ? (
@@ -7,6 +7,8 @@ export function Toolbar({
   onPickFolder,
   onStop,
   loading,
+  folderPinned,
+  onTogglePin,
 }) {
   return (
     <header className="toolbar">
@@ -27,6 +29,11 @@ export function Toolbar({
         <button type="button" onClick={onPickFolder} disabled={loading}>
           Pick Folder
         </button>
+        {selectedFolder && onTogglePin && (
+          <button type="button" onClick={onTogglePin}>
+            {folderPinned ? 'Unpin Folder' : 'Pin Folder'}
+          </button>
+        )}
         {loading && (
           <button type="button" onClick={onStop}>
             Stop
@@ -0,0 +1,64 @@
+import { useEffect, useState } from 'react'
+import { invoke } from '@tauri-apps/api/core'
+import { getCurrentWindow } from '@tauri-apps/api/window'
+import { usePreview } from '../hooks/usePreview'
+import { PreviewModal } from './PreviewModal'
+
+function fileName(path) {
+  return path.split(/[\\/]/).pop() || path
+}
+
+export function ViewerWindow() {
+  const [items, setItems] = useState([])
+  const [viewerError, setViewerError] = useState('')
+  const {
+    previewItem,
+    previewImageSrc,
+    previewLoading,
+    previewError,
+    setPreviewItem,
+  } = usePreview(items)
+
+  useEffect(() => {
+    invoke('get_viewer_image')
+      .then((path) => {
+        if (!path) {
+          setViewerError('This window has no image assigned.')
+          return
+        }
+        const item = { path, name: fileName(path) }
+        setItems([item])
+        setPreviewItem(item)
+      })
+      .catch((loadError) => setViewerError(String(loadError)))
+  }, [])
+
+  useEffect(() => {
+    // Escape clears the preview; in a viewer window that means closing it.
+    if (items.length > 0 && !previewItem) {
+      getCurrentWindow().close()
+    }
+  }, [items, previewItem])
+
+  if (viewerError) {
+    return (
+      <main className="app">
+        <p className="previewUnavailable">{viewerError}</p>
+      </main>
+    )
+  }
+
+  return (
+    <PreviewModal
+      previewItem={previewItem}
+      previewLoading={previewLoading}
+      previewImageSrc={previewImageSrc}
+      previewError={previewError}
+      hasPreviousPreview={false}
+      hasNextPreview={false}
+      onClose={() => getCurrentWindow().close()}
+      onGoLeft={() => {}}
+      onGoRight={() => {}}
+    />
+  )
+}
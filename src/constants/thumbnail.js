@@ -1,4 +1,3 @@
-export const THUMBNAIL_LOAD_SIZE = 256
 export const THUMBNAIL_SIZE_STORAGE_KEY = 'thumbnailer.thumbnailSize'
 export const DEFAULT_THUMBNAIL_SIZE = 200
 export const MIN_THUMBNAIL_SIZE = 100
@@ -1,8 +1,7 @@
 import { useCallback, useEffect, useRef, useState } from 'react'
 import { invoke } from '@tauri-apps/api/core'
 import { listen } from '@tauri-apps/api/event'
-import { THUMBNAIL_LOAD_SIZE } from '../constants/thumbnail'
-import { formatImageCount, getDroppedPaths, hasTauriInvoke } from '../utils/gallery'
+import { formatImageCount, hasTauriInvoke } from '../utils/gallery'
 
 export function useGallery() {
   const [selectedFolder, setSelectedFolder] = useState('')
@@ -13,7 +12,7 @@ export function useGallery() {
   const [error, setError] = useState('')
   const [thumbnailDataByPath, setThumbnailDataByPath] = useState({})
   const loadRunIdRef = useRef(0)
-  const lastDroppedFolderRef = useRef({ path: '', timestamp: 0 })
+  const [pendingSelectPath, setPendingSelectPath] = useState('')
 
   useEffect(() => {
     if (!hasTauriInvoke()) {
@@ -47,8 +46,62 @@ export function useGallery() {
     }
   }, [])
 
+  useEffect(() => {
+    if (!hasTauriInvoke()) {
+      return undefined
+    }
+    let unlisten
+    listen('thumbnail-updated', (event) => {
+      const payload = event.payload
+      if (!payload || typeof payload.path !== 'string' || typeof payload.thumbnail !== 'string') {
+        return
+      }
+      setThumbnailDataByPath((previous) => ({ ...previous, [payload.path]: payload.thumbnail }))
+    })
+      .then((unlistenFn) => {
+        unlisten = unlistenFn
+      })
+      .catch((eventError) => {
+        setError(String(eventError))
+      })
+    return () => {
+      if (unlisten) {
+        unlisten()
+      }
+    }
+  }, [])
+
+  useEffect(() => {
+    if (!hasTauriInvoke()) {
+      return undefined
+    }
+    let unlisten
+    listen('gallery-removed', (event) => {
+      const path = event.payload?.path
+      if (typeof path !== 'string') {
+        return
+      }
+      setItems((previous) => previous.filter((item) => item.path !== path))
+      setThumbnailDataByPath((previous) => {
+        const { [path]: _removed, ...rest } = previous
+        return rest
+      })
+    })
+      .then((unlistenFn) => {
+        unlisten = unlistenFn
+      })
+      .catch((eventError) => {
+        setError(String(eventError))
+      })
+    return () => {
+      if (unlisten) {
+        unlisten()
+      }
+    }
+  }, [])
+
   const loadGallery = useCallback(
-    async (folder) => {
+    async (folder, options = {}) => {
       if (!hasTauriInvoke()) {
         setError('Tauri API unavailable. Start with `npm run tauri dev`.')
         return
@@ -63,7 +116,8 @@ export function useGallery() {
       try {
         const response = await invoke('load_gallery', {
           folderPath: folder,
-          thumbnailSize: THUMBNAIL_LOAD_SIZE,
+          thumbnailSize: options.thumbnailSize ?? undefined,
+          recursive: options.recursive ?? undefined,
         })
         if (runId !== loadRunIdRef.current) {
           return
@@ -75,11 +129,16 @@ export function useGallery() {
             : {}
         setItems(galleryItems)
         setThumbnailDataByPath(thumbnails)
-        if (response?.cancelled) {
+        if (response?.offline) {
+          setStatus(`Offline. Showing ${formatImageCount(galleryItems.length)} from the cache.`)
+        } else if (response?.cancelled) {
           setStatus(`Stopped. Loaded ${formatImageCount(galleryItems.length)} before cancel.`)
         } else {
           setStatus(`Loaded ${formatImageCount(galleryItems.length)}.`)
         }
+        if (response?.error) {
+          setError(response.error)
+        }
       } catch (invokeError) {
         if (runId !== loadRunIdRef.current) {
           return
@@ -108,10 +167,17 @@ export function useGallery() {
           }
           return
         }
-        const initialFolder = await invoke('get_initial_folder')
-        if (!disposed && initialFolder) {
-          setSelectedFolder(initialFolder)
-          await loadGallery(initialFolder)
+        const [initialRequest, startupOptions] = await Promise.all([
+          invoke('get_initial_open_request'),
+          invoke('get_startup_options'),
+        ])
+        if (!disposed && initialRequest) {
+          setSelectedFolder(initialRequest.folder)
+          setPendingSelectPath(initialRequest.select || '')
+          await loadGallery(initialRequest.folder, {
+            thumbnailSize: startupOptions?.thumbnailSize,
+            recursive: startupOptions?.recursive || undefined,
+          })
         }
       } catch (invokeError) {
         if (!disposed) {
@@ -131,31 +197,28 @@ export function useGallery() {
     }
 
     let disposed = false
-    let unlistenFileDrop
-    let unlistenDragDrop
-
-    async function handleFolderDrop(event) {
-      const droppedPaths = getDroppedPaths(event.payload)
-      if (droppedPaths.length === 0) {
-        return
-      }
-
-      const nextFolder = droppedPaths[0]
-      const now = Date.now()
-      const lastDrop = lastDroppedFolderRef.current
-      if (lastDrop.path === nextFolder && now - lastDrop.timestamp < 500) {
-        return
-      }
-      lastDroppedFolderRef.current = { path: nextFolder, timestamp: now }
-
-      setSelectedFolder(nextFolder)
-      await loadGallery(nextFolder)
-    }
+    let unlistenOpenRequest
+    let unlistenDropRejected
+    let unlistenVolumeOffline
+    let unlistenVolumeOnline
 
-    async function registerDropListeners() {
+    async function registerOpenListeners() {
       try {
-        unlistenFileDrop = await listen('tauri://file-drop', handleFolderDrop)
-        unlistenDragDrop = await listen('tauri://drag-drop', handleFolderDrop)
+        unlistenOpenRequest = await listen('open-request', (event) => {
+          const { folder, select, thumbnailSize, recursive } = event.payload
+          setSelectedFolder(folder)
+          setPendingSelectPath(select || '')
+          loadGallery(folder, { thumbnailSize, recursive })
+        })
+        unlistenDropRejected = await listen('drop-rejected', (event) => {
+          setError(String(event.payload))
+        })
+        unlistenVolumeOffline = await listen('volume-offline', (event) => {
+          setError(`${event.payload.folder} went offline; showing cached thumbnails.`)
+        })
+        unlistenVolumeOnline = await listen('volume-online', (event) => {
+          loadGallery(event.payload.folder)
+        })
       } catch (eventError) {
         if (!disposed) {
           setError(String(eventError))
@@ -163,15 +226,21 @@ export function useGallery() {
       }
     }
 
-    registerDropListeners()
+    registerOpenListeners()
 
     return () => {
       disposed = true
-      if (unlistenFileDrop) {
-        unlistenFileDrop()
+      if (unlistenOpenRequest) {
+        unlistenOpenRequest()
+      }
+      if (unlistenDropRejected) {
+        unlistenDropRejected()
       }
-      if (unlistenDragDrop) {
-        unlistenDragDrop()
+      if (unlistenVolumeOffline) {
+        unlistenVolumeOffline()
+      }
+      if (unlistenVolumeOnline) {
+        unlistenVolumeOnline()
       }
     }
   }, [loadGallery])
@@ -192,6 +261,10 @@ export function useGallery() {
     setError(nextValue)
   }
 
+  function clearPendingSelect() {
+    setPendingSelectPath('')
+  }
+
   return {
     selectedFolder,
     setSelectedFolder,
@@ -204,5 +277,7 @@ export function useGallery() {
     loadGallery,
     stopGalleryScan,
     thumbnailDataByPath,
+    pendingSelectPath,
+    clearPendingSelect,
   }
 }
@@ -1,7 +1,13 @@
 import { useEffect, useMemo, useState } from 'react'
 import { convertFileSrc, invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
 import { hasTauriInvoke } from '../utils/gallery'
 
+function getPreviewMaxDimension() {
+  const scale = window.devicePixelRatio || 1
+  return Math.round(Math.max(window.screen.width, window.screen.height) * scale)
+}
+
 export function usePreview(items) {
   const [previewItem, setPreviewItem] = useState(null)
   const [previewImageSrc, setPreviewImageSrc] = useState('')
@@ -66,6 +72,22 @@ export function usePreview(items) {
     }
   }, [currentPreviewIndex, items, previewItem])
 
+  useEffect(() => {
+    if (currentPreviewIndex < 0 || !hasTauriInvoke()) {
+      return
+    }
+    const neighbourPaths = [items[currentPreviewIndex + 1], items[currentPreviewIndex - 1]]
+      .filter(Boolean)
+      .map((item) => item.path)
+    if (neighbourPaths.length === 0) {
+      return
+    }
+    invoke('preload_images', {
+      paths: neighbourPaths,
+      maxDimension: getPreviewMaxDimension(),
+    }).catch(() => {})
+  }, [currentPreviewIndex, items])
+
   useEffect(() => {
     if (!previewItem) {
       setPreviewImageSrc('')
@@ -75,6 +97,8 @@ export function usePreview(items) {
     }
 
     let cancelled = false
+    let objectUrl = ''
+    let unlistenPlaceholder = null
     async function loadPreview() {
       if (!hasTauriInvoke()) {
         setPreviewError('Preview unavailable outside Tauri runtime.')
@@ -98,15 +122,29 @@ export function usePreview(items) {
           return
         }
 
-        const dataUrl = await invoke('load_full_image', { path: previewItem.path })
+        unlistenPlaceholder = await listen('full-image-placeholder', (event) => {
+          if (!cancelled && !objectUrl && event.payload?.path === previewItem.path) {
+            setPreviewImageSrc(event.payload.image)
+          }
+        })
+        const imageBytes = await invoke('load_full_image', {
+          path: previewItem.path,
+          maxDimension: getPreviewMaxDimension(),
+          progressive: true,
+        })
         if (!cancelled) {
-          setPreviewImageSrc(String(dataUrl))
+          objectUrl = URL.createObjectURL(new Blob([imageBytes]))
+          setPreviewImageSrc(objectUrl)
         }
       } catch (previewLoadError) {
         if (!cancelled) {
           setPreviewError(String(previewLoadError))
         }
       } finally {
+        if (unlistenPlaceholder) {
+          unlistenPlaceholder()
+          unlistenPlaceholder = null
+        }
         if (!cancelled) {
           setPreviewLoading(false)
         }
@@ -115,6 +153,12 @@ export function usePreview(items) {
     loadPreview()
     return () => {
       cancelled = true
+      if (unlistenPlaceholder) {
+        unlistenPlaceholder()
+      }
+      if (objectUrl) {
+        URL.revokeObjectURL(objectUrl)
+      }
     }
   }, [previewItem])
 
@@ -0,0 +1,28 @@
+import { useCallback, useEffect, useState } from 'react'
+import { invoke } from '@tauri-apps/api/core'
+import { hasTauriInvoke } from '../utils/gallery'
+
+export function useRecentFolders() {
+  const [recentFolders, setRecentFolders] = useState([])
+
+  useEffect(() => {
+    if (!hasTauriInvoke()) {
+      return
+    }
+    invoke('get_recent_folders')
+      .then(setRecentFolders)
+      .catch(() => {})
+  }, [])
+
+  const isFolderPinned = useCallback(
+    (folder) => recentFolders.some((entry) => entry.path === folder && entry.pinned),
+    [recentFolders],
+  )
+
+  async function setFolderPinned(folder, pinned) {
+    const nextFolders = await invoke('set_folder_pinned', { path: folder, pinned })
+    setRecentFolders(nextFolders)
+  }
+
+  return { recentFolders, isFolderPinned, setFolderPinned }
+}
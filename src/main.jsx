@@ -1,10 +1,15 @@
 import { StrictMode } from 'react'
 import { createRoot } from 'react-dom/client'
+import { getCurrentWindow } from '@tauri-apps/api/window'
 import './index.css'
 import App from './App.jsx'
+import { ViewerWindow } from './components/ViewerWindow.jsx'
+import { hasTauriInvoke } from './utils/gallery'
+
+const isViewerWindow = hasTauriInvoke() && getCurrentWindow().label.startsWith('viewer-')
 
 createRoot(document.getElementById('root')).render(
   <StrictMode>
-    <App />
+    {isViewerWindow ? <ViewerWindow /> : <App />}
   </StrictMode>,
 )
@@ -12,16 +12,3 @@ export function formatImageCount(count) {
   const safeCount = Number.isFinite(count) ? Math.max(0, Math.floor(count)) : 0
   return `${safeCount} image${safeCount === 1 ? '' : 's'}`
 }
-
-export function getDroppedPaths(payload) {
-  if (Array.isArray(payload)) {
-    return payload.map((entry) => String(entry))
-  }
-  if (!payload || typeof payload !== 'object') {
-    return []
-  }
-  if (Array.isArray(payload.paths)) {
-    return payload.paths.map((entry) => String(entry))
-  }
-  return []
-}